@@ -0,0 +1,210 @@
+use crate::core::{
+    tenant::{Tenant, tenant_by_name},
+    utils::{PRESIGNED_URL_EXPIRY, get_env_var},
+};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// Current token envelope version, bumped whenever the payload changes.
+const TOKEN_VERSION: &str = "t1";
+
+#[derive(Serialize, Deserialize)]
+struct TokenPayload {
+    tenant: String,
+    dataitem_id: String,
+    expires_at: i64,
+}
+
+/// A `?token=` that is malformed, tampered with, expired, minted for another dataitem, or that
+/// the agent can't verify as `ACCESS_TOKEN_SECRET` is unset.
+#[derive(Debug)]
+pub(crate) struct InvalidAccessToken(pub String);
+
+impl std::fmt::Display for InvalidAccessToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid access token: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidAccessToken {}
+
+/// HMAC key of the access tokens, `ACCESS_TOKEN_SECRET`. Tokens are only minted with it set.
+fn secret() -> Option<String> {
+    get_env_var("ACCESS_TOKEN_SECRET").ok().filter(|secret| !secret.is_empty())
+}
+
+/// Whether the agent hands out its own token URLs instead of presigned storage URLs.
+pub(crate) fn enabled() -> bool {
+    secret().is_some()
+}
+
+/// Tokens expire after `ACCESS_TOKEN_TTL_SECS`, by default as long as presigned URLs.
+fn ttl_secs() -> i64 {
+    get_env_var("ACCESS_TOKEN_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|&secs: &i64| secs > 0)
+        .unwrap_or(PRESIGNED_URL_EXPIRY as i64)
+}
+
+fn token_mac(secret: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts keys of any size");
+    mac.update(TOKEN_VERSION.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// A `{version}.{payload}.{signature}` token granting access to the tenant's dataitem until it
+/// expires, `None` unless `ACCESS_TOKEN_SECRET` is set.
+pub(crate) fn mint(tenant: &Tenant, dataitem_id: &str) -> Option<(String, DateTime<Utc>)> {
+    let secret = secret()?;
+    let expires_at = Utc::now() + chrono::Duration::seconds(ttl_secs());
+    let token = sign(&secret, &tenant.name, dataitem_id, expires_at.timestamp())?;
+    Some((token, expires_at))
+}
+
+fn sign(secret: &str, tenant: &str, dataitem_id: &str, expires_at: i64) -> Option<String> {
+    let payload = TokenPayload {
+        tenant: tenant.to_string(),
+        dataitem_id: dataitem_id.to_string(),
+        expires_at,
+    };
+    let raw = serde_json::to_vec(&payload).ok()?;
+    let payload = general_purpose::URL_SAFE_NO_PAD.encode(raw);
+    let signature = general_purpose::URL_SAFE_NO_PAD
+        .encode(token_mac(secret, &payload).finalize().into_bytes());
+    Some(format!("{TOKEN_VERSION}.{payload}.{signature}"))
+}
+
+/// `{public_url}/{dataitem_id}?token=...` on this agent, `None` unless tokens are enabled.
+pub(crate) fn token_url(public_url: &str, tenant: &Tenant, dataitem_id: &str) -> Option<String> {
+    let (token, _) = mint(tenant, dataitem_id)?;
+    Some(format!("{public_url}/{dataitem_id}?token={token}"))
+}
+
+/// The tenant a token grants access to `dataitem_id` of, failing with `InvalidAccessToken`.
+pub(crate) fn verify(token: &str, dataitem_id: &str) -> Result<Tenant, InvalidAccessToken> {
+    let secret =
+        secret().ok_or_else(|| InvalidAccessToken("access tokens are disabled".to_string()))?;
+    let tenant = verified_tenant(&secret, token, dataitem_id, Utc::now().timestamp())?;
+    tenant_by_name(&tenant).map_err(|err| InvalidAccessToken(err.to_string()))
+}
+
+/// Name of the tenant a token signed with `secret` grants access to `dataitem_id` of at `now`.
+fn verified_tenant(
+    secret: &str,
+    token: &str,
+    dataitem_id: &str,
+    now: i64,
+) -> Result<String, InvalidAccessToken> {
+    let invalid = |reason: &str| InvalidAccessToken(reason.to_string());
+    let mut parts = token.split('.');
+    let (Some(version), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(invalid("expected version.payload.signature"));
+    };
+    if version != TOKEN_VERSION {
+        return Err(invalid("unsupported version"));
+    }
+
+    let signature = general_purpose::URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| invalid("invalid signature encoding"))?;
+    token_mac(secret, payload).verify_slice(&signature).map_err(|_| invalid("bad signature"))?;
+
+    let raw = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| invalid("invalid payload encoding"))?;
+    let payload: TokenPayload =
+        serde_json::from_slice(&raw).map_err(|_| invalid("invalid payload"))?;
+    if payload.dataitem_id != dataitem_id {
+        return Err(invalid("minted for another dataitem"));
+    }
+    if payload.expires_at < now {
+        return Err(invalid("expired"));
+    }
+    Ok(payload.tenant)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret";
+    const DATAITEM_ID: &str = "ZSobWuj5BXs9qPoIUdOzSRH5SE7UPFz-0i-a2nxr2sk";
+    const NOW: i64 = 1_700_000_000;
+
+    fn token(tenant: &str, expires_at: i64) -> String {
+        sign(SECRET, tenant, DATAITEM_ID, expires_at).unwrap()
+    }
+
+    fn reason(result: Result<String, InvalidAccessToken>) -> String {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn verifies_a_minted_token() {
+        let token = token("app1", NOW + 60);
+        assert!(token.starts_with("t1."));
+        assert_eq!(verified_tenant(SECRET, &token, DATAITEM_ID, NOW).unwrap(), "app1");
+        // still valid on its expiry second
+        assert_eq!(verified_tenant(SECRET, &token, DATAITEM_ID, NOW + 60).unwrap(), "app1");
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let token = token("", NOW - 1);
+        assert_eq!(reason(verified_tenant(SECRET, &token, DATAITEM_ID, NOW)), "expired");
+    }
+
+    #[test]
+    fn rejects_tokens_of_another_dataitem() {
+        let token = token("", NOW + 60);
+        let other = "eJMMKTp85cwH2JcavgA6xyMwa2oKEZCSD9DjCFJn17I";
+        assert_eq!(
+            reason(verified_tenant(SECRET, &token, other, NOW)),
+            "minted for another dataitem"
+        );
+    }
+
+    #[test]
+    fn rejects_tokens_signed_with_another_secret() {
+        let token = token("", NOW + 60);
+        assert_eq!(reason(verified_tenant("other", &token, DATAITEM_ID, NOW)), "bad signature");
+    }
+
+    #[test]
+    fn rejects_tampered_payloads() {
+        let token = token("app1", NOW + 60);
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let forged = TokenPayload {
+            tenant: "app2".to_string(),
+            dataitem_id: DATAITEM_ID.to_string(),
+            expires_at: NOW + 60,
+        };
+        let forged = general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        let tampered = format!("{TOKEN_VERSION}.{forged}.{signature}");
+        assert_eq!(reason(verified_tenant(SECRET, &tampered, DATAITEM_ID, NOW)), "bad signature");
+    }
+
+    #[test]
+    fn rejects_malformed_tokens() {
+        let token = token("", NOW + 60);
+        let cases = [
+            ("", "expected version.payload.signature"),
+            ("t1.payload", "expected version.payload.signature"),
+            (&format!("{token}.extra"), "expected version.payload.signature"),
+            (&token.replacen("t1.", "t0.", 1), "unsupported version"),
+            (&format!("{}!", token), "invalid signature encoding"),
+        ];
+        for (token, expected) in cases {
+            assert_eq!(reason(verified_tenant(SECRET, token, DATAITEM_ID, NOW)), expected);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use crate::core::{
+    bundler::post_dataitem,
+    gc::collect_garbage,
+    metadata::{
+        ExportFormat, TagQueryPage, TagQueryPagination, export_index, query_dataitems_by_tags,
+    },
+    models::{GcReport, ReindexReport},
+    reindex::reindex,
+    s3::{get_bucket_stats, get_dataitem, get_dataitem_url, store_dataitem, store_signed_dataitem},
+    server::{
+        OBJECT_SIZE_LIMIT, apply_cors_policy, dataitem_compression_layer, enforce_body_limits,
+        enforce_maintenance, enforce_memory_budget, enforce_upload_budget, handle_analytics_top,
+        handle_arns, handle_block, handle_bundler_balance, handle_commit_upload,
+        handle_compact_index, handle_complete_private_upload, handle_content_type_dataitems,
+        handle_create_feed, handle_create_private_bucket, handle_credits, handle_dataitem_id,
+        handle_dataitem_proof, handle_dataitem_receipt, handle_dataitem_stats,
+        handle_delete_dataitem, handle_delete_feed, handle_discard_upload, handle_exists,
+        handle_export_index, handle_feed_events, handle_gc_report, handle_get_bucket_registry,
+        handle_get_feed, handle_hash_dataitems, handle_healthz, handle_hls_playlist,
+        handle_hls_segment, handle_import, handle_index_compaction_report,
+        handle_invalidate_bucket_ownership, handle_items_get, handle_list_blocklist,
+        handle_list_feeds, handle_list_jobs, handle_list_reports, handle_maintenance,
+        handle_metrics, handle_name_history, handle_not_found, handle_openapi,
+        handle_owner_dataitems, handle_point_name, handle_post_dataitem, handle_post_estimate,
+        handle_post_status, handle_prefix_migration, handle_presign_private_upload,
+        handle_private_file, handle_provenance, handle_query_tags, handle_recent_dataitems,
+        handle_reload, handle_render_dataitem, handle_replication_status, handle_report_dataitem,
+        handle_request_log_settings, handle_resolve_name, handle_resolve_report,
+        handle_restore_dataitem, handle_retry_job, handle_route, handle_s3_get_object,
+        handle_s3_list_objects, handle_s3_put_object, handle_schedule, handle_selftest,
+        handle_stage_upload, handle_start_prefix_migration, handle_storage_stats,
+        handle_sync_dataitems, handle_test_vectors, handle_unblock, handle_update_maintenance,
+        handle_update_request_log, handle_upload_job, handle_upload_progress, limit_concurrency,
+        log_sampled_requests, record_provenance, serve_dataitem, tag_compressed_etag, upload_file,
+        upload_from_url, upload_raw_file,
+    },
+    tenant::Tenant,
+};
+use anyhow::Error;
+use axum::{
+    Router,
+    body::Bytes,
+    extract::DefaultBodyLimit,
+    middleware,
+    routing::{delete, get, post},
+};
+use bundles_rs::bundler::SendTransactionResponse;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use tower_http::limit::RequestBodyLimitLayer;
+
+/// Embeddable handle over the agent's storage pipeline, scoped to a tenant.
+#[derive(Debug, Clone, Default)]
+pub struct Agent {
+    tenant: Tenant,
+}
+
+impl Agent {
+    pub fn new() -> Self {
+        Agent::default()
+    }
+
+    /// Agent operating on the tenant's bucket, prefixes and tag index namespace.
+    pub fn for_tenant(tenant: Tenant) -> Self {
+        Agent { tenant }
+    }
+
+    /// Sign `data` as an agent ANS-104 dataitem, store it and index its tags.
+    pub async fn store(
+        &self,
+        data: Vec<u8>,
+        content_type: &str,
+        tags: &[(String, String)],
+    ) -> Result<String, Error> {
+        Ok(store_dataitem(data, content_type, tags, &[], &self.tenant).await?.id)
+    }
+
+    /// Store an already signed ANS-104 dataitem and index its tags.
+    pub async fn store_signed(&self, dataitem: Vec<u8>) -> Result<String, Error> {
+        Ok(store_signed_dataitem(dataitem, &self.tenant).await?.id)
+    }
+
+    /// Fetch the ANS-104 serialized dataitem.
+    pub async fn retrieve(&self, dataitem_id: &str) -> Result<Vec<u8>, Error> {
+        get_dataitem(dataitem_id, &self.tenant).await
+    }
+
+    /// Presigned URL of the dataitem's raw data.
+    pub async fn retrieve_url(&self, dataitem_id: &str) -> Result<String, Error> {
+        get_dataitem_url(dataitem_id, &self.tenant).await
+    }
+
+    /// Query indexed dataitems matching all the given tag KV pairs.
+    pub async fn query(
+        &self,
+        filters: &[(String, String)],
+        pagination: &TagQueryPagination,
+    ) -> Result<TagQueryPage, Error> {
+        let filters: Vec<(String, Vec<String>)> =
+            filters.iter().map(|(key, value)| (key.clone(), vec![value.clone()])).collect();
+        self.query_any_of(&filters, pagination).await
+    }
+
+    /// Query indexed dataitems matching all the given filters, each one a tag key and the
+    /// values it may have.
+    pub async fn query_any_of(
+        &self,
+        filters: &[(String, Vec<String>)],
+        pagination: &TagQueryPagination,
+    ) -> Result<TagQueryPage, Error> {
+        query_dataitems_by_tags(&self.tenant.name, filters, pagination).await
+    }
+
+    /// Post a stored dataitem to Arweave through the bundler.
+    pub async fn post(&self, dataitem_id: &str) -> Result<SendTransactionResponse, Error> {
+        post_dataitem(dataitem_id.to_string(), &self.tenant).await
+    }
+
+    /// Total dataitems count and size of the agent's bucket.
+    pub async fn stats(&self) -> Result<(u32, u64), Error> {
+        get_bucket_stats(&self.tenant).await
+    }
+
+    /// Reconcile the tenant's stored objects, see `GET /admin/gc`. With `dry_run` nothing is
+    /// changed.
+    pub async fn gc(&self, dry_run: bool) -> Result<GcReport, Error> {
+        collect_garbage(&self.tenant, dry_run).await
+    }
+
+    /// Index the stored dataitems missing from the tag index.
+    pub async fn reindex(&self) -> Result<ReindexReport, Error> {
+        reindex(&self.tenant).await
+    }
+
+    /// Stream the tenant's tag index rows, see `GET /export/index`.
+    pub async fn export_index(
+        &self,
+        format: ExportFormat,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+    ) -> Result<impl Stream<Item = reqwest::Result<Bytes>> + use<>, Error> {
+        export_index(&self.tenant.name, format, from, to).await
+    }
+
+    /// The agent's HTTP API, as served by the binary. Routes resolve their tenant from each
+    /// request, this handle's tenant doesn't scope them. The CORS policy is installed and the
+    /// background tasks spawned by the caller, see `install_cors_policy` and
+    /// `spawn_background_tasks`.
+    pub fn router(&self) -> Router {
+        // only proxied dataitem bodies are worth compressing
+        let serve_route = match dataitem_compression_layer() {
+            Some(compression) => get(serve_dataitem)
+                .layer(compression)
+                .layer(middleware::map_response(tag_compressed_etag)),
+            None => get(serve_dataitem),
+        };
+        let arns_route = match dataitem_compression_layer() {
+            Some(compression) => get(handle_arns)
+                .layer(compression)
+                .layer(middleware::map_response(tag_compressed_etag)),
+            None => get(handle_arns),
+        };
+
+        Router::new()
+            .route("/", get(handle_route))
+            .route("/healthz", get(handle_healthz))
+            .route("/stats", get(handle_storage_stats))
+            .route("/metrics", get(handle_metrics))
+            .route("/testvectors", get(handle_test_vectors))
+            .route("/id", post(handle_dataitem_id))
+            .route("/openapi.json", get(handle_openapi))
+            .route("/upload", post(upload_file))
+            .route("/upload/raw", post(upload_raw_file))
+            .route("/upload/from-url", post(upload_from_url))
+            .route("/upload/stage", post(handle_stage_upload))
+            .route("/upload/stage/{staging_id}", delete(handle_discard_upload))
+            .route("/upload/commit/{staging_id}", post(handle_commit_upload))
+            .route("/upload/{upload_id}/progress", get(handle_upload_progress))
+            .route("/upload/private", post(handle_private_file))
+            .route("/private/buckets", post(handle_create_private_bucket))
+            .route("/private/{bucket}/presign-upload", post(handle_presign_private_upload))
+            .route(
+                "/private/{bucket}/presign-upload/{upload_id}/complete",
+                post(handle_complete_private_upload),
+            )
+            .route("/jobs/{id}", get(handle_upload_job))
+            .route("/import", post(handle_import))
+            .route("/tags/query", post(handle_query_tags))
+            .route("/feeds", get(handle_list_feeds).post(handle_create_feed))
+            .route("/feeds/{name}", get(handle_get_feed).delete(handle_delete_feed))
+            .route("/feeds/{name}/events", get(handle_feed_events))
+            .route("/names/{name}", get(handle_resolve_name).put(handle_point_name))
+            .route("/names/{name}/history", get(handle_name_history))
+            .route("/arns/{name}", arns_route)
+            .route("/address/{owner}/dataitems", get(handle_owner_dataitems))
+            .route("/by-hash/{sha256}", get(handle_hash_dataitems))
+            .route("/recent", get(handle_recent_dataitems))
+            .route("/content-type/{mime}/dataitems", get(handle_content_type_dataitems))
+            .route("/content-type/{type}/{subtype}/dataitems", get(handle_content_type_dataitems))
+            .route("/exists", post(handle_exists))
+            .route("/items/get", post(handle_items_get))
+            .route("/post/{id}", post(handle_post_dataitem))
+            .route("/post/estimate/{id}", get(handle_post_estimate))
+            .route("/post/{id}/status", get(handle_post_status))
+            .route("/bundler/balance", get(handle_bundler_balance))
+            .route("/credits", get(handle_credits))
+            .route("/registry/{bucket_name}", get(handle_get_bucket_registry))
+            .route("/admin/gc", get(handle_gc_report))
+            .route(
+                "/admin/index/compaction",
+                get(handle_index_compaction_report).post(handle_compact_index),
+            )
+            .route("/admin/replication/status", get(handle_replication_status))
+            .route("/admin/jobs", get(handle_list_jobs))
+            .route("/admin/jobs/{id}/retry", post(handle_retry_job))
+            .route("/admin/migrations/prefix", post(handle_start_prefix_migration))
+            .route("/admin/reports", get(handle_list_reports))
+            .route("/admin/reports/{id}/resolve", post(handle_resolve_report))
+            .route("/admin/migrations/prefix/{id}", get(handle_prefix_migration))
+            .route("/admin/schedule", get(handle_schedule))
+            .route("/admin/provenance", get(handle_provenance))
+            .route("/admin/selftest", get(handle_selftest))
+            .route("/admin/reload", post(handle_reload))
+            .route(
+                "/admin/request-log",
+                get(handle_request_log_settings).put(handle_update_request_log),
+            )
+            .route("/admin/maintenance", get(handle_maintenance).put(handle_update_maintenance))
+            .route("/analytics/top", get(handle_analytics_top))
+            .route("/admin/blocklist", get(handle_list_blocklist).post(handle_block))
+            .route("/admin/blocklist/{kind}/{value}", delete(handle_unblock))
+            .route("/admin/bucket-ownership", delete(handle_invalidate_bucket_ownership))
+            .route("/export/index", get(handle_export_index))
+            .route("/sync/dataitems", get(handle_sync_dataitems))
+            .route("/s3/{bucket}", get(handle_s3_list_objects))
+            .route("/s3/{bucket}/", get(handle_s3_list_objects))
+            .route("/s3/{bucket}/{*key}", get(handle_s3_get_object).put(handle_s3_put_object))
+            .route("/{id}/render", get(handle_render_dataitem))
+            .route("/{id}/proof", get(handle_dataitem_proof))
+            .route("/{id}/receipt", get(handle_dataitem_receipt))
+            .route("/{id}/stats", get(handle_dataitem_stats))
+            .route("/{id}/hls", get(handle_hls_playlist))
+            .route("/{id}/hls/{segment}", get(handle_hls_segment))
+            .route("/{id}/restore", post(handle_restore_dataitem))
+            .route("/report/{id}", post(handle_report_dataitem))
+            .route("/{id}", serve_route.delete(handle_delete_dataitem))
+            .fallback(handle_not_found)
+            .route_layer(middleware::from_fn(enforce_body_limits))
+            .route_layer(middleware::from_fn(enforce_memory_budget))
+            .route_layer(middleware::from_fn(limit_concurrency))
+            .route_layer(middleware::from_fn(enforce_maintenance))
+            .layer(middleware::from_fn(enforce_upload_budget))
+            .layer(middleware::from_fn(log_sampled_requests))
+            .layer(middleware::from_fn(record_provenance))
+            .layer(DefaultBodyLimit::max(OBJECT_SIZE_LIMIT))
+            .layer(RequestBodyLimitLayer::new(OBJECT_SIZE_LIMIT))
+            .layer(middleware::from_fn(apply_cors_policy))
+    }
+}
@@ -1,24 +1,218 @@
-use anyhow::Error;
+use anyhow::{Error, anyhow};
+use base64::{Engine as _, engine::general_purpose};
 use bundles_rs::{
     ans104::{data_item::DataItem, tags::Tag},
     crypto::arweave::ArweaveSigner,
 };
 
-use crate::core::utils::{STORAGE_PROVIDER_NAME, get_env_var};
+use crate::core::{
+    models::UploadTag,
+    utils::{STORAGE_PROVIDER_NAME, get_env_var, sha256_hex},
+};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
 
 const RESERVED_TAGS: [&str; 2] = ["storage-provider", "agent-version"];
+/// Tag of the original file name, served as the `Content-Disposition` filename on download.
+pub(crate) const FILE_NAME_TAG: &str = "File-Name";
+/// Tag of the hex SHA-256 digest of the dataitem's data.
+pub(crate) const SHA256_TAG: &str = "SHA-256";
+/// Tag of the hex BLAKE3 digest of the dataitem's data.
+pub(crate) const BLAKE3_TAG: &str = "BLAKE3";
+const MAX_FILE_NAME_LEN: usize = 255;
+const DEFAULT_MAX_TAG_BYTES: usize = 4096;
+// decompressed size of a `tags.json.gz` part, well above any tag set within the byte limit
+const MAX_GZIP_TAGS_LEN: u64 = 1024 * 1024;
+
+/// Agent injected tags and the keys users may not set, configured by:
+/// - `STORAGE_PROVIDER_TAG_NAME` / `STORAGE_PROVIDER_TAG_VALUE` (`Storage-Provider: Load-S3`)
+/// - `AGENT_VERSION_TAG_NAME` (`Agent-Version`)
+/// - `DEPLOYMENT_ID` and `DEPLOYMENT_ID_TAG_NAME` (`Deployment-Id`), to tell fleet instances apart
+/// - `RESERVED_TAGS`, comma separated keys reserved on top of the injected ones
+/// - `CONTENT_HASH_TAGS=false`, to neither inject nor reserve the `SHA-256` and `BLAKE3` tags
+///
+/// Setting a tag name to an empty string disables that tag.
+#[derive(Debug, Clone)]
+pub(crate) struct TagPolicy {
+    pub injected: Vec<Tag>,
+    /// lowercased
+    pub reserved: Vec<String>,
+}
+
+impl TagPolicy {
+    pub fn from_env() -> Self {
+        let tag_name = |key: &str, default: &str| {
+            get_env_var(key).unwrap_or_else(|_| default.to_string()).trim().to_string()
+        };
+        let mut injected = Vec::new();
+
+        let provider_name = tag_name("STORAGE_PROVIDER_TAG_NAME", "Storage-Provider");
+        if !provider_name.is_empty() {
+            let value = get_env_var("STORAGE_PROVIDER_TAG_VALUE")
+                .unwrap_or_else(|_| STORAGE_PROVIDER_NAME.to_string());
+            injected.push(Tag::new(provider_name, value));
+        }
+        let version_name = tag_name("AGENT_VERSION_TAG_NAME", "Agent-Version");
+        if !version_name.is_empty() {
+            injected.push(Tag::new(version_name, format!("agent@{}", env!("CARGO_PKG_VERSION"))));
+        }
+        let deployment_id = get_env_var("DEPLOYMENT_ID").unwrap_or_default();
+        let deployment_name = tag_name("DEPLOYMENT_ID_TAG_NAME", "Deployment-Id");
+        if !deployment_id.trim().is_empty() && !deployment_name.is_empty() {
+            injected.push(Tag::new(deployment_name, deployment_id.trim()));
+        }
+
+        let mut reserved: Vec<String> = RESERVED_TAGS.iter().map(|key| key.to_string()).collect();
+        reserved.extend(injected.iter().map(|tag| tag.name.to_lowercase()));
+        reserved.extend(
+            get_env_var("RESERVED_TAGS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|key| key.trim().to_lowercase())
+                .filter(|key| !key.is_empty()),
+        );
+        if content_hash_tags() {
+            reserved.extend([SHA256_TAG, BLAKE3_TAG].map(str::to_lowercase));
+        }
+
+        TagPolicy { injected, reserved }
+    }
+
+    pub fn is_reserved(&self, key: &str) -> bool {
+        self.reserved.iter().any(|reserved| reserved.eq_ignore_ascii_case(key))
+    }
+}
+
+fn content_hash_tags() -> bool {
+    get_env_var("CONTENT_HASH_TAGS").map(|v| v != "false").unwrap_or(true)
+}
+
+/// Size and hex digests of a dataitem's data, indexed with it. The digests are tagged on the
+/// dataitems the agent signs, so consumers can verify a raw object against the signed dataitem.
+#[derive(Debug, Clone, Default)]
+pub struct ContentDigest {
+    pub size: usize,
+    pub sha256: String,
+    /// empty unless `CONTENT_HASH_BLAKE3=true`
+    pub blake3: String,
+}
+
+impl ContentDigest {
+    pub(crate) fn compute(data: &[u8]) -> Self {
+        let blake3 = match get_env_var("CONTENT_HASH_BLAKE3").is_ok_and(|v| v == "true") {
+            true => blake3::hash(data).to_hex().to_string(),
+            false => String::new(),
+        };
+        ContentDigest { size: data.len(), sha256: sha256_hex(data), blake3 }
+    }
+
+    fn tags(&self) -> Vec<Tag> {
+        if !content_hash_tags() {
+            return Vec::new();
+        }
+        let mut tags = vec![Tag::new(SHA256_TAG, &self.sha256)];
+        if !self.blake3.is_empty() {
+            tags.push(Tag::new(BLAKE3_TAG, &self.blake3));
+        }
+        tags
+    }
+}
+
+/// Tags attached to every dataitem the agent signs for one of `api_keys`.
+///
+/// Configured through the `DEFAULT_TAGS` env var as a JSON array, e.g.
+/// `[{"api_keys":["key1"],"tags":[{"key":"App-Name","value":"app1"}]}]`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct DefaultTagSet {
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+    pub tags: Vec<UploadTag>,
+}
+
+/// The default tags of the API key's `DEFAULT_TAGS` set, empty when it belongs to none.
+pub(crate) fn default_tags(api_key: Option<&str>) -> Result<Vec<(String, String)>, Error> {
+    let Some(api_key) = api_key else {
+        return Ok(Vec::new());
+    };
+    let sets: Vec<DefaultTagSet> = match get_env_var("DEFAULT_TAGS") {
+        Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)
+            .map_err(|err| anyhow!("invalid DEFAULT_TAGS config: {err}"))?,
+        _ => return Ok(Vec::new()),
+    };
+    let set = sets.into_iter().find(|set| set.api_keys.iter().any(|key| key == api_key));
+    Ok(set
+        .map(|set| set.tags.into_iter().map(|tag| (tag.key, tag.value)).collect())
+        .unwrap_or_default())
+}
+
+/// Key and value bytes a request's custom tags may add up to, `MAX_TAG_BYTES` (default 4096).
+fn max_tag_bytes() -> usize {
+    get_env_var("MAX_TAG_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(DEFAULT_MAX_TAG_BYTES)
+}
 
+/// Reject custom tags whose key and value bytes exceed `MAX_TAG_BYTES` altogether.
+pub(crate) fn check_tag_bytes(tags: &[UploadTag]) -> Result<(), Error> {
+    let bytes: usize = tags.iter().map(|tag| tag.key.len() + tag.value.len()).sum();
+    let max = max_tag_bytes();
+    if bytes > max {
+        return Err(anyhow!("custom tags take {bytes} bytes, more than the {max} allowed"));
+    }
+    Ok(())
+}
+
+/// A `key:value` tag of a repeated `tag` multipart field, split at the first colon.
+pub(crate) fn parse_tag_pair(field: &str) -> Result<UploadTag, Error> {
+    let (key, value) = field
+        .split_once(':')
+        .ok_or_else(|| anyhow!("invalid tag field {field:?}, expected key:value"))?;
+    Ok(UploadTag { key: key.to_string(), value: value.to_string() })
+}
+
+/// Tags of a `tags.json.gz` multipart part, the gzip compressed JSON array of the `tags` field.
+pub(crate) fn decode_gzip_tags(gzipped: &[u8]) -> Result<Vec<UploadTag>, Error> {
+    let mut json = Vec::new();
+    GzDecoder::new(gzipped)
+        .take(MAX_GZIP_TAGS_LEN + 1)
+        .read_to_end(&mut json)
+        .map_err(|err| anyhow!("invalid tags.json.gz part: {err}"))?;
+    if json.len() as u64 > MAX_GZIP_TAGS_LEN {
+        return Err(anyhow!("tags.json.gz part exceeds {MAX_GZIP_TAGS_LEN} bytes decompressed"));
+    }
+    serde_json::from_slice(&json).map_err(|err| anyhow!("invalid tags.json.gz part: {err}"))
+}
+
+/// The `File-Name` tag of an upload's `filename`, with any directory part dropped.
+pub(crate) fn file_name_tag(file_name: &str) -> Result<UploadTag, Error> {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(anyhow!("invalid filename {file_name:?}"));
+    }
+    if name.len() > MAX_FILE_NAME_LEN || name.chars().any(char::is_control) {
+        return Err(anyhow!(
+            "invalid filename, expected at most {MAX_FILE_NAME_LEN} bytes without control characters"
+        ));
+    }
+    Ok(UploadTag { key: FILE_NAME_TAG.to_string(), value: name.to_string() })
+}
+
+/// Sign `data`, `digest` being its `ContentDigest`, with the agent's key. Tags are applied by
+/// precedence: the `Content-Type`, agent injected and content hash tags, then the request's
+/// `extra_tags` and last the API key's `default_tags`, each skipped when a previous one already
+/// set its key.
 pub(crate) fn create_dataitem(
     data: Vec<u8>,
+    digest: &ContentDigest,
     content_type: &str,
     extra_tags: &[(String, String)],
+    default_tags: &[(String, String)],
 ) -> Result<DataItem, Error> {
     let jwk = get_env_var("UPLOADER_JWK")?;
-    let mut tags = vec![
-        Tag::new("Content-Type", content_type),
-        Tag::new("Storage-Provider", STORAGE_PROVIDER_NAME),
-        Tag::new("Agent-Version", format!("agent@{}", env!("CARGO_PKG_VERSION"))),
-    ];
+    let tag_policy = TagPolicy::from_env();
+    let mut tags = vec![Tag::new("Content-Type", content_type)];
+    tags.extend(tag_policy.injected.iter().cloned());
+    tags.extend(digest.tags());
     let signer = ArweaveSigner::from_jwk_str(&jwk)?;
 
     let mut seen: std::collections::HashSet<String> =
@@ -35,7 +229,7 @@ pub(crate) fn create_dataitem(
             continue;
         }
         let key_lower = key_trimmed.to_lowercase();
-        if RESERVED_TAGS.contains(&key_lower.as_str()) {
+        if tag_policy.is_reserved(&key_lower) {
             continue;
         }
         // the content-type tag is hardcoded at position at index 0
@@ -52,6 +246,21 @@ pub(crate) fn create_dataitem(
         }
     }
 
+    for (key, value) in default_tags {
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() || value.is_empty() || key.len() > 1024 || value.len() > 1024 {
+            continue;
+        }
+        // defaults never replace the content type, unlike a request's Content-Type tag
+        let key_lower = key.to_lowercase();
+        if key_lower == "content-type" || tag_policy.is_reserved(&key_lower) {
+            continue;
+        }
+        if seen.insert(key_lower) {
+            tags.push(Tag::new(key, value));
+        }
+    }
+
     DataItem::build_and_sign(&signer, None, None, tags, data)
 }
 
@@ -67,3 +276,55 @@ pub(crate) fn reconstruct_dataitem_data(dataitem: Vec<u8>) -> Result<(DataItem,
 
     Ok((dataitem, content_type_tag))
 }
+
+/// Arweave address of the agent's `UPLOADER_JWK` signer, the account paying for the
+/// dataitems it signs.
+pub(crate) fn agent_address() -> Result<String, Error> {
+    let jwk: serde_json::Value = serde_json::from_str(&get_env_var("UPLOADER_JWK")?)?;
+    let modulus = jwk["n"].as_str().ok_or_else(|| anyhow!("UPLOADER_JWK has no modulus"))?;
+    let owner = general_purpose::URL_SAFE_NO_PAD.decode(modulus.trim_end_matches('='))?;
+    Ok(general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(owner)))
+}
+
+/// Arweave style address of the dataitem signer: b64url(sha256(owner)).
+pub(crate) fn owner_address(dataitem: &DataItem) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(Sha256::digest(&dataitem.owner))
+}
+
+/// Split an ANS-104 bundle into its serialized dataitems.
+///
+/// Layout: a 32 byte little-endian item count, then a 64 byte header per item
+/// (32 byte little-endian size, 32 byte id) followed by the items back to back.
+pub(crate) fn unpack_bundle(bundle: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+    let count = read_u256_le(bundle, 0)?;
+    let headers_end = count
+        .checked_mul(64)
+        .and_then(|len| len.checked_add(32))
+        .filter(|end| *end <= bundle.len())
+        .ok_or_else(|| anyhow!("bundle header declares {count} items past the end of the data"))?;
+
+    let mut items = Vec::with_capacity(count);
+    let mut offset = headers_end;
+    for index in 0..count {
+        let size = read_u256_le(bundle, 32 + index * 64)?;
+        let end = offset
+            .checked_add(size)
+            .filter(|end| *end <= bundle.len())
+            .ok_or_else(|| anyhow!("bundle item {index} overruns the bundle data"))?;
+        items.push(bundle[offset..end].to_vec());
+        offset = end;
+    }
+    Ok(items)
+}
+
+fn read_u256_le(data: &[u8], offset: usize) -> Result<usize, Error> {
+    let bytes = data
+        .get(offset..offset + 32)
+        .ok_or_else(|| anyhow!("truncated bundle header at byte {offset}"))?;
+    let (low, high) = bytes.split_at(8);
+    if high.iter().any(|b| *b != 0) {
+        return Err(anyhow!("bundle header value at byte {offset} is out of range"));
+    }
+    let value = u64::from_le_bytes(low.try_into()?);
+    usize::try_from(value).map_err(|_| anyhow!("bundle header value {value} is out of range"))
+}
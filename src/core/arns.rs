@@ -0,0 +1,129 @@
+use crate::core::{
+    extract::is_arweave_id,
+    gateway::{GatewayFallback, arweave_gateway_url},
+    http::{http_client, send_with_retry},
+    metrics,
+    resilience::with_timeout,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::RwLock, time::Duration};
+
+const DEFAULT_REFRESH_SECS: u64 = 300;
+const DEFAULT_TTL_SECS: u64 = 3600;
+
+/// Where a served ArNS name currently points.
+#[derive(Debug, Clone)]
+pub(crate) struct ArnsRecord {
+    pub tx_id: String,
+    /// how long the registry lets the resolution be cached
+    pub ttl_secs: u64,
+}
+
+/// `GET /ar-io/resolver/{name}` answer of an ar.io gateway.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Resolution {
+    tx_id: String,
+    ttl_seconds: Option<u64>,
+}
+
+static RESOLVED: Lazy<RwLock<HashMap<String, ArnsRecord>>> = Lazy::new(Default::default);
+
+/// ArNS names and undernames (`docs_ardrive`) served under `GET /arns/{name}`, `ARNS_NAMES`
+/// (comma separated, none by default).
+pub(crate) fn served_names() -> Vec<String> {
+    get_env_var("ARNS_NAMES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+/// ar.io gateway resolving the names and serving their content, `ARNS_RESOLVER_URL` or the
+/// Arweave gateway.
+fn resolver_url() -> String {
+    get_env_var("ARNS_RESOLVER_URL")
+        .map(|url| url.trim_end_matches('/').to_string())
+        .unwrap_or_else(|_| arweave_gateway_url())
+}
+
+/// The gateway `GET /arns/{name}` reads resolved transactions from.
+pub(crate) fn content_gateway() -> GatewayFallback {
+    GatewayFallback { url: resolver_url(), rehydrate: false }
+}
+
+async fn resolve_remote(name: &str) -> Result<ArnsRecord, Error> {
+    let url = format!("{}/ar-io/resolver/{name}", resolver_url());
+    let resolution: Resolution = with_timeout("arns_resolver", 10, async {
+        let response = send_with_retry("arns_resolver", http_client()?.get(&url)).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("resolver returned {} for {name}", response.status()));
+        }
+        Ok(response.json().await?)
+    })
+    .await?;
+    if !is_arweave_id(&resolution.tx_id) {
+        return Err(anyhow!("resolver returned an invalid transaction id for {name}"));
+    }
+    Ok(ArnsRecord {
+        tx_id: resolution.tx_id,
+        ttl_secs: resolution.ttl_seconds.unwrap_or(DEFAULT_TTL_SECS),
+    })
+}
+
+/// Resolve `name` with the registry and keep the result, logging when it moved.
+async fn refresh(name: &str) -> Result<ArnsRecord, Error> {
+    let result = resolve_remote(name).await;
+    let outcome = if result.is_ok() { "ok" } else { "error" };
+    metrics::increment(&format!("arns_resolutions_total{{result=\"{outcome}\"}}"));
+    let record = result?;
+    let previous = RESOLVED.write().unwrap().insert(name.to_string(), record.clone());
+    if previous.is_none_or(|previous| previous.tx_id != record.tx_id) {
+        println!("ARNS: {name} -> {}", record.tx_id);
+    }
+    metrics::set_gauge("arns_names_resolved", RESOLVED.read().unwrap().len() as f64);
+    Ok(record)
+}
+
+/// Where the served ArNS `name` points, from the last poll or resolved now if it hasn't been
+/// yet. `None` unless the name is in `ARNS_NAMES`.
+pub(crate) async fn resolve(name: &str) -> Result<Option<ArnsRecord>, Error> {
+    let name = name.to_lowercase();
+    if !served_names().contains(&name) {
+        return Ok(None);
+    }
+    let cached = RESOLVED.read().unwrap().get(&name).cloned();
+    match cached {
+        Some(record) => Ok(Some(record)),
+        None => refresh(&name).await.map(Some),
+    }
+}
+
+/// Poll the registry for every name of `ARNS_NAMES` at startup, then every
+/// `ARNS_REFRESH_SECS` (default 300). A name that fails to resolve keeps its last resolution.
+pub(crate) fn spawn_arns_resolver() {
+    if served_names().is_empty() {
+        return;
+    }
+    let interval = get_env_var("ARNS_REFRESH_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_REFRESH_SECS);
+
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(interval));
+        loop {
+            ticker.tick().await;
+            for name in served_names() {
+                if let Err(err) = refresh(&name).await {
+                    println!("ARNS: failed to resolve {name}: {err}");
+                }
+            }
+        }
+    });
+}
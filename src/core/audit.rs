@@ -0,0 +1,29 @@
+use crate::core::utils::get_env_var;
+use serde_json::{Value, json};
+use tokio::io::AsyncWriteExt;
+
+/// Append an audit event as a JSON line to stdout and, when `AUDIT_LOG_PATH` is set, to that
+/// file. Failing to write the file never fails the request being audited.
+pub(crate) async fn record(event: &str, fields: Value) {
+    let mut entry = json!({
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+        "event": event,
+    });
+    if let (Some(entry), Value::Object(fields)) = (entry.as_object_mut(), fields) {
+        entry.extend(fields);
+    }
+    let line = entry.to_string();
+    println!("AUDIT {line}");
+
+    let Ok(path) = get_env_var("AUDIT_LOG_PATH") else {
+        return;
+    };
+    let file = tokio::fs::OpenOptions::new().create(true).append(true).open(&path).await;
+    let written = match file {
+        Ok(mut file) => file.write_all(format!("{line}\n").as_bytes()).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = written {
+        println!("AUDIT LOG WRITE FAILED: {path}: {err}");
+    }
+}
@@ -0,0 +1,141 @@
+use crate::core::{
+    metadata::{
+        BlockEntry, active_block_entries, dataitem_sha256, indexing_enabled, save_block_entry,
+    },
+    refreshed::Refreshed,
+    utils::{get_env_var, sha256_hex},
+};
+use anyhow::{Error, anyhow};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+pub(crate) const BLOCK_BY_ID: &str = "id";
+pub(crate) const BLOCK_BY_HASH: &str = "sha256";
+
+static BLOCKLIST: Lazy<Refreshed<Loaded>> = Lazy::new(|| Refreshed::new("BLOCKLIST"));
+
+#[derive(Clone, Default)]
+struct Loaded {
+    ids: HashSet<String>,
+    hashes: HashSet<String>,
+}
+
+/// A blocklisted dataitem or content, served and accepted by no route.
+#[derive(Debug)]
+pub(crate) struct Blocked(pub String);
+
+impl std::fmt::Display for Blocked {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is unavailable for legal reasons", self.0)
+    }
+}
+
+impl std::error::Error for Blocked {}
+
+/// Entries are re-read from ClickHouse every `BLOCKLIST_REFRESH_SECS` (default 30), so changes
+/// made on another agent apply within that delay.
+fn refresh_interval() -> Duration {
+    let secs = get_env_var("BLOCKLIST_REFRESH_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(30))
+}
+
+/// The current blocklist, reloaded when stale.
+async fn blocklist() -> Arc<Loaded> {
+    BLOCKLIST
+        .get(refresh_interval(), async {
+            // nothing can be blocklisted without ClickHouse
+            if !indexing_enabled() {
+                return Ok(Loaded::default());
+            }
+            let (ids, hashes): (Vec<_>, Vec<_>) =
+                active_block_entries().await?.into_iter().partition(|e| e.kind == BLOCK_BY_ID);
+            Ok(Loaded {
+                ids: ids.into_iter().map(|entry| entry.value).collect(),
+                hashes: hashes.into_iter().map(|entry| entry.value).collect(),
+            })
+        })
+        .await
+}
+
+/// Fail with `Blocked` when the dataitem ID is blocklisted.
+pub(crate) async fn check_id(dataitem_id: &str) -> Result<(), Blocked> {
+    match blocklist().await.ids.contains(dataitem_id) {
+        true => Err(Blocked(format!("dataitem {dataitem_id}"))),
+        false => Ok(()),
+    }
+}
+
+/// Fail with `Blocked` when the sha256 of `data` is blocklisted.
+pub(crate) async fn check_content(data: &[u8]) -> Result<(), Blocked> {
+    let loaded = blocklist().await;
+    if loaded.hashes.is_empty() {
+        return Ok(());
+    }
+    let hash = sha256_hex(data);
+    match loaded.hashes.contains(&hash) {
+        true => Err(Blocked(format!("content {hash}"))),
+        false => Ok(()),
+    }
+}
+
+/// Fail with `Blocked` when the hex sha256 `hash` is blocklisted.
+pub(crate) async fn check_hash(hash: &str) -> Result<(), Blocked> {
+    match blocklist().await.hashes.contains(hash) {
+        true => Err(Blocked(format!("content {hash}"))),
+        false => Ok(()),
+    }
+}
+
+/// Fail with `Blocked` when the indexed sha256 of the tenant's dataitem is blocklisted, for
+/// routes handing out its content without reading it (redirects, presigned URLs). `false` when
+/// the index can't tell (not indexed, indexed before content digests were recorded, ClickHouse
+/// unavailable) while hashes are blocklisted: the content itself has to be checked.
+pub(crate) async fn check_indexed_content(
+    tenant: &str,
+    dataitem_id: &str,
+) -> Result<bool, Blocked> {
+    let loaded = blocklist().await;
+    if loaded.hashes.is_empty() {
+        return Ok(true);
+    }
+    match dataitem_sha256(tenant, dataitem_id).await {
+        Ok(Some(hash)) if loaded.hashes.contains(&hash) => Err(Blocked(format!("content {hash}"))),
+        Ok(Some(_)) => Ok(true),
+        Ok(None) => Ok(false),
+        Err(err) => {
+            println!("BLOCKLIST: content hash of {dataitem_id} unavailable: {err}");
+            Ok(false)
+        }
+    }
+}
+
+/// Persist a blocklist change (`active` false lifts the entry), applied on this agent right
+/// away and on the others at their next reload.
+pub(crate) async fn set_blocked(
+    kind: &str,
+    value: &str,
+    reason: &str,
+    active: bool,
+) -> Result<BlockEntry, Error> {
+    if kind != BLOCK_BY_ID && kind != BLOCK_BY_HASH {
+        return Err(anyhow!("unknown blocklist kind {kind}, expected id or sha256"));
+    }
+    let entry = BlockEntry {
+        kind: kind.to_string(),
+        value: value.to_string(),
+        reason: reason.to_string(),
+        active,
+        updated_at: Utc::now(),
+    };
+    save_block_entry(&entry).await?;
+
+    BLOCKLIST.update(|loaded| {
+        let set = if kind == BLOCK_BY_ID { &mut loaded.ids } else { &mut loaded.hashes };
+        match active {
+            true => set.insert(entry.value.clone()),
+            false => set.remove(&entry.value),
+        };
+    });
+    Ok(entry)
+}
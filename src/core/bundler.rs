@@ -1,14 +1,192 @@
-use crate::core::s3::get_dataitem;
-use anyhow::Error;
+use crate::core::{
+    ans104::{agent_address, owner_address},
+    blocklist,
+    confirmations::track_post,
+    http::{http_client, send_with_retry},
+    metadata::record_arweave_post,
+    metrics,
+    models::{BundlerBalance, PostEstimate},
+    resilience::with_timeout,
+    s3::get_dataitem,
+    tenant::{Tenant, all_tenants},
+    utils::get_env_var,
+    webhooks,
+};
+use anyhow::{Error, anyhow};
 use bundles_rs::{
     ans104::data_item::DataItem,
     bundler::{BundlerClient, SendTransactionResponse},
 };
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub(crate) const DEFAULT_BUNDLER_PAYMENT_URL: &str = "https://payment.ardrive.io";
+// Turbo doesn't charge dataitems up to 105 KiB
+const DEFAULT_FREE_UPLOAD_BYTES: u64 = 107_520;
+pub(crate) const WINC_PER_AR: u128 = 1_000_000_000_000;
 
-pub(crate) async fn post_dataitem(id: String) -> Result<SendTransactionResponse, Error> {
-    let dataitem = get_dataitem(&id).await?;
+// whether the last balance check found the agent's account under the alert threshold
+static BALANCE_LOW: AtomicBool = AtomicBool::new(false);
+
+#[derive(Deserialize)]
+struct WincResponse {
+    winc: String,
+}
+
+pub(crate) async fn post_dataitem(
+    id: String,
+    tenant: &Tenant,
+) -> Result<SendTransactionResponse, Error> {
+    // anything on Arweave is permanent, never post blocklisted dataitems
+    blocklist::check_id(&id).await?;
+    let dataitem = get_dataitem(&id, tenant).await?;
     let signed_dataitem = DataItem::from_bytes(&dataitem)?;
     let client = BundlerClient::turbo().build()?;
-    let tx = client.send_transaction(signed_dataitem).await?;
+    let tx = with_timeout("bundler", 60, client.send_transaction(signed_dataitem)).await?;
+    // the post succeeded regardless, losing the record only disables the gateway fallback
+    if let Err(err) = record_arweave_post(&tenant.name, &id).await {
+        println!("RECORD ARWEAVE POST FAILED: {id}: {err}");
+    }
+    if let Err(err) = track_post(&tenant.name, &id).await {
+        println!("TRACK ARWEAVE POST FAILED: {id}: {err}");
+    }
     Ok(tx)
 }
+
+/// Payment service of the bundler, `BUNDLER_PAYMENT_URL` or Turbo's.
+fn payment_url() -> String {
+    get_env_var("BUNDLER_PAYMENT_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BUNDLER_PAYMENT_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// Largest dataitem the bundler posts for free, `BUNDLER_FREE_UPLOAD_BYTES` (default 105 KiB).
+pub(crate) fn free_upload_bytes() -> u64 {
+    get_env_var("BUNDLER_FREE_UPLOAD_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_FREE_UPLOAD_BYTES)
+}
+
+async fn payment_winc(path: &str) -> Result<u128, Error> {
+    with_timeout("bundler", 30, async {
+        let request = http_client()?.get(format!("{}{path}", payment_url()));
+        let response = send_with_retry("bundler", request).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "bundler payment service returned {} for {path}",
+                response.status()
+            ));
+        }
+        let body: WincResponse = response.json().await?;
+        body.winc.parse().map_err(|_| anyhow!("invalid winc amount {:?} for {path}", body.winc))
+    })
+    .await
+}
+
+/// Bundler price of posting `bytes`, in winston credits.
+pub(crate) async fn price_winc(bytes: u64) -> Result<u128, Error> {
+    payment_winc(&format!("/v1/price/bytes/{bytes}")).await
+}
+
+/// Winston credits held by the bundler account of `address`.
+pub(crate) async fn balance_winc(address: &str) -> Result<u128, Error> {
+    payment_winc(&format!("/v1/account/balance/arweave?address={address}")).await
+}
+
+/// Winston credits as a decimal AR amount.
+pub(crate) fn winc_to_ar(winc: u128) -> String {
+    let fraction = format!("{:012}", winc % WINC_PER_AR);
+    let fraction = fraction.trim_end_matches('0');
+    match fraction.is_empty() {
+        true => (winc / WINC_PER_AR).to_string(),
+        false => format!("{}.{fraction}", winc / WINC_PER_AR),
+    }
+}
+
+/// Balance of the agent's own bundler account, paying for the dataitems the agent signs.
+pub(crate) async fn agent_balance() -> Result<BundlerBalance, Error> {
+    let address = agent_address()?;
+    let winc = balance_winc(&address).await?;
+    Ok(BundlerBalance { address, winc: winc.to_string(), ar: winc_to_ar(winc) })
+}
+
+/// `bundler_balance_check` task: export the agent's balance as the `bundler_balance_winc` gauge
+/// and warn once it drops below `BUNDLER_BALANCE_ALERT_WINC`. The `bundler_balance_low` webhook
+/// fires when it crosses the threshold and `bundler_balance_recovered` once topped up again.
+pub(crate) async fn check_agent_balance() -> Result<String, Error> {
+    let address = agent_address()?;
+    let winc = balance_winc(&address).await?;
+    metrics::set_gauge("bundler_balance_winc", winc as f64);
+    let threshold: Option<u128> =
+        get_env_var("BUNDLER_BALANCE_ALERT_WINC").ok().and_then(|v| v.parse().ok());
+    let Some(threshold) = threshold else {
+        return Ok(format!("winc={winc}"));
+    };
+
+    let low = winc < threshold;
+    let was_low = BALANCE_LOW.swap(low, Ordering::Relaxed);
+    let fields = json!({
+        "address": address,
+        "winc": winc.to_string(),
+        "ar": winc_to_ar(winc),
+        "threshold_winc": threshold.to_string(),
+    });
+    if low {
+        println!(
+            "WARNING: bundler balance of {address} is {} AR, below the {} AR alert threshold",
+            winc_to_ar(winc),
+            winc_to_ar(threshold)
+        );
+        if !was_low {
+            webhooks::notify("bundler_balance_low", fields);
+        }
+    } else if was_low {
+        webhooks::notify("bundler_balance_recovered", fields);
+    }
+    Ok(format!("winc={winc} low={low}"))
+}
+
+/// What posting the signed `dataitem` costs its signer, and whether their balance covers it.
+pub(crate) async fn estimate_post(
+    dataitem_id: &str,
+    dataitem: &[u8],
+) -> Result<PostEstimate, Error> {
+    let payer = owner_address(&DataItem::from_bytes(dataitem)?);
+    let bytes = dataitem.len() as u64;
+    let free = bytes <= free_upload_bytes();
+    let (winc, balance) = match free {
+        true => (0, balance_winc(&payer).await?),
+        false => tokio::try_join!(price_winc(bytes), balance_winc(&payer))?,
+    };
+    Ok(PostEstimate {
+        dataitem_id: dataitem_id.to_string(),
+        bytes,
+        payer,
+        free,
+        winc: winc.to_string(),
+        ar: winc_to_ar(winc),
+        balance_winc: balance.to_string(),
+        affordable: balance >= winc,
+    })
+}
+
+/// `post_dataitem` job of `core::jobs`, payload `{"dataitem_id": "..."}`.
+pub(crate) async fn run_post_dataitem_job(
+    tenant: &str,
+    payload: &serde_json::Value,
+) -> Result<(), Error> {
+    let dataitem_id = payload["dataitem_id"]
+        .as_str()
+        .ok_or_else(|| anyhow!("post_dataitem job without a dataitem_id"))?;
+    let tenant = all_tenants()?
+        .into_iter()
+        .find(|t| t.name == tenant)
+        .ok_or_else(|| anyhow!("unknown tenant {tenant:?}"))?;
+    post_dataitem(dataitem_id.to_string(), &tenant).await?;
+    Ok(())
+}
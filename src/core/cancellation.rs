@@ -0,0 +1,185 @@
+use crate::core::{progress, provenance, utils::get_env_var};
+use anyhow::Error;
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicU8, Ordering},
+    },
+};
+use tokio::time::{Instant, sleep_until, timeout_at};
+use tokio_util::sync::CancellationToken;
+
+tokio::task_local! {
+    static CURRENT: UploadBudget;
+}
+
+/// Why an upload's writes were aborted.
+#[derive(Debug)]
+pub(crate) enum UploadAborted {
+    /// the request was dropped, e.g. the client disconnected
+    Cancelled,
+    /// `UPLOAD_DEADLINE_SECS` elapsed
+    DeadlineExceeded(u64),
+}
+
+impl std::fmt::Display for UploadAborted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadAborted::Cancelled => write!(f, "upload cancelled, the request was dropped"),
+            UploadAborted::DeadlineExceeded(secs) => {
+                write!(f, "upload exceeded its time budget of {secs}s")
+            }
+        }
+    }
+}
+
+impl std::error::Error for UploadAborted {}
+
+// outcome of an upload, settled once by whichever of its writes and its request is first
+const OPEN: u8 = 0;
+const COMMITTED: u8 = 1;
+const ABANDONED: u8 = 2;
+
+/// Cancellation and deadline of the request an upload is written for. Outside of one (e.g.
+/// deferred upload jobs) it is never cancelled and has no deadline.
+#[derive(Clone, Default)]
+pub(crate) struct UploadBudget {
+    token: CancellationToken,
+    deadline: Option<(Instant, u64)>,
+    outcome: Arc<AtomicU8>,
+}
+
+impl UploadBudget {
+    /// Requests have `UPLOAD_DEADLINE_SECS` (unset, no deadline) to complete.
+    fn for_request() -> Self {
+        let secs = get_env_var("UPLOAD_DEADLINE_SECS").ok().and_then(|v| v.parse().ok());
+        let deadline = secs
+            .filter(|&secs| secs > 0)
+            .map(|secs| (Instant::now() + std::time::Duration::from_secs(secs), secs));
+        UploadBudget { token: CancellationToken::new(), deadline, outcome: Default::default() }
+    }
+
+    /// Fails once the request was dropped or ran out of time.
+    pub(crate) fn check(&self) -> Result<(), UploadAborted> {
+        if self.token.is_cancelled() {
+            return Err(UploadAborted::Cancelled);
+        }
+        match self.deadline {
+            Some((deadline, secs)) if Instant::now() >= deadline => {
+                Err(UploadAborted::DeadlineExceeded(secs))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Mark the upload's writes complete, kept even if the request is dropped from now on. Fails
+    /// when the request was dropped, ran out of time or was `abandon`ed first.
+    pub(crate) fn commit(&self) -> Result<(), UploadAborted> {
+        self.check()?;
+        match self.outcome.compare_exchange(OPEN, COMMITTED, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) | Err(COMMITTED) => Ok(()),
+            Err(_) => Err(UploadAborted::Cancelled),
+        }
+    }
+
+    /// Mark the upload abandoned by its request, so its writes can't be committed anymore.
+    /// `false` when they already were and are kept.
+    pub(crate) fn abandon(&self) -> bool {
+        match self.outcome.compare_exchange(OPEN, ABANDONED, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) | Err(ABANDONED) => true,
+            Err(_) => false,
+        }
+    }
+
+    /// Run `step`, dropping it halfway when the request is dropped or runs out of time.
+    pub(crate) async fn run<T>(
+        &self,
+        step: impl Future<Output = Result<T, Error>>,
+    ) -> Result<T, Error> {
+        self.check()?;
+        let expired = async {
+            match self.deadline {
+                Some((deadline, secs)) => {
+                    sleep_until(deadline).await;
+                    UploadAborted::DeadlineExceeded(secs)
+                }
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            biased;
+            _ = self.token.cancelled() => Err(UploadAborted::Cancelled.into()),
+            aborted = expired => Err(aborted.into()),
+            result = step => result,
+        }
+    }
+}
+
+/// Budget of the current request.
+pub(crate) fn current() -> UploadBudget {
+    CURRENT.try_with(Clone::clone).unwrap_or_default()
+}
+
+/// Run the request `handled` with an `UploadBudget`, cancelled when it is dropped or exceeds
+/// `UPLOAD_DEADLINE_SECS`.
+pub(crate) async fn scope<F: Future>(handled: F) -> Result<F::Output, UploadAborted> {
+    let budget = UploadBudget::for_request();
+    let cancel_on_drop = budget.token.clone().drop_guard();
+    let output = match budget.deadline {
+        Some((deadline, secs)) => timeout_at(deadline, CURRENT.scope(budget.clone(), handled))
+            .await
+            .map_err(|_| UploadAborted::DeadlineExceeded(secs))?,
+        None => CURRENT.scope(budget, handled).await,
+    };
+    cancel_on_drop.disarm();
+    Ok(output)
+}
+
+/// Run the writes of an upload in a task of their own with the request's budget, provenance and
+/// progress, so a dropped request can't interrupt them at an arbitrary point: they see the
+/// budget cancelled instead and roll back.
+pub(crate) async fn detached<T: Send + 'static>(
+    writes: impl Future<Output = Result<T, Error>> + Send + 'static,
+) -> Result<T, Error> {
+    let writes = CURRENT.scope(current(), provenance::scope(provenance::current(), writes));
+    let handle = match progress::current() {
+        Some(tracker) => tokio::spawn(progress::track(tracker, writes)),
+        None => tokio::spawn(writes),
+    };
+    handle.await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn commit_and_abandon_settle_once() {
+        let committed = UploadBudget::default();
+        assert!(committed.commit().is_ok());
+        assert!(committed.commit().is_ok());
+        assert!(!committed.abandon());
+
+        let abandoned = UploadBudget::default();
+        assert!(abandoned.abandon());
+        assert!(abandoned.abandon());
+        assert!(matches!(abandoned.commit(), Err(UploadAborted::Cancelled)));
+    }
+
+    #[tokio::test]
+    async fn dropped_requests_cancel_their_budget() {
+        let (budget_tx, budget_rx) = tokio::sync::oneshot::channel();
+        let request = scope(async move {
+            budget_tx.send(current()).ok();
+            std::future::pending::<()>().await
+        });
+        let mut request = Box::pin(request);
+        // poll the request once so it hands out its budget, then drop it
+        assert!(futures::poll!(&mut request).is_pending());
+        drop(request);
+        let budget = budget_rx.await.unwrap();
+        assert!(matches!(budget.check(), Err(UploadAborted::Cancelled)));
+        assert!(matches!(budget.commit(), Err(UploadAborted::Cancelled)));
+    }
+}
@@ -0,0 +1,402 @@
+use crate::core::{
+    storage::{ListPage, ObjectBody, StorageBackend},
+    utils::{get_env_var, sha256_hex},
+};
+use anyhow::{Error, anyhow};
+use async_trait::async_trait;
+use futures::{StreamExt, TryStreamExt, stream};
+use reed_solomon_erasure::galois_8::ReedSolomon;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+// first bytes of a manifest object, followed by its JSON
+const MANIFEST_MAGIC: &[u8] = b"LOAD-S3-AGENT-CHUNKS/1\n";
+const DEFAULT_CHUNK_BYTES: usize = 32 * 1024 * 1024;
+const DEFAULT_PARITY_CHUNKS: usize = 2;
+const DEFAULT_CONCURRENCY: usize = 4;
+// galois field of 8 bits
+const MAX_CHUNKS: usize = 256;
+
+/// A body split by `ChunkedBackend`, stored at its key in place of the body.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkManifest {
+    size: usize,
+    chunk_size: usize,
+    data_chunks: usize,
+    parity_chunks: usize,
+    /// of each chunk, data chunks first
+    chunk_sha256: Vec<String>,
+    sha256: String,
+    content_type: String,
+    #[serde(default)]
+    content_encoding: Option<String>,
+}
+
+/// A chunked object has no single URL.
+#[derive(Debug)]
+pub(crate) struct ChunkedObject(pub String);
+
+impl std::fmt::Display for ChunkedObject {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is stored in chunks and can't be presigned", self.0)
+    }
+}
+
+impl std::error::Error for ChunkedObject {}
+
+fn chunk_key(key: &str, index: usize) -> String {
+    format!("{key}.chunks/{index}")
+}
+
+fn env_usize(key: &str) -> Option<usize> {
+    get_env_var(key).ok().and_then(|v| v.parse().ok())
+}
+
+/// Split bodies larger than `CHUNKED_STORAGE_THRESHOLD_BYTES` (unset, never) into
+/// `CHUNKED_STORAGE_CHUNK_BYTES` (default 32 MiB) data chunks plus
+/// `CHUNKED_STORAGE_PARITY_CHUNKS` (default 2) Reed-Solomon parity chunks, stored as
+/// `{key}.chunks/{i}` objects with a manifest at `{key}`. Reads reassemble them, fetching
+/// `CHUNKED_STORAGE_CONCURRENCY` (default 4) chunks at once, and rebuild up to that many
+/// missing or corrupt chunks from the parity ones.
+pub(crate) fn with_chunking(inner: Box<dyn StorageBackend>) -> Box<dyn StorageBackend> {
+    Box::new(ChunkedBackend { inner })
+}
+
+struct ChunkedBackend {
+    inner: Box<dyn StorageBackend>,
+}
+
+impl ChunkedBackend {
+    fn threshold() -> Option<usize> {
+        env_usize("CHUNKED_STORAGE_THRESHOLD_BYTES").filter(|&threshold| threshold > 0)
+    }
+
+    fn concurrency() -> usize {
+        env_usize("CHUNKED_STORAGE_CONCURRENCY").unwrap_or(DEFAULT_CONCURRENCY).max(1)
+    }
+
+    async fn put_chunked(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        content_encoding: Option<&str>,
+    ) -> Result<(), Error> {
+        let parity_chunks = env_usize("CHUNKED_STORAGE_PARITY_CHUNKS")
+            .unwrap_or(DEFAULT_PARITY_CHUNKS)
+            .min(MAX_CHUNKS / 2);
+        // grown until every chunk fits in the field
+        let max_data_chunks = MAX_CHUNKS - parity_chunks;
+        let chunk_size = env_usize("CHUNKED_STORAGE_CHUNK_BYTES")
+            .unwrap_or(DEFAULT_CHUNK_BYTES)
+            .max(body.len().div_ceil(max_data_chunks))
+            .max(1);
+        let data_chunks = body.len().div_ceil(chunk_size).max(1);
+        let (size, sha256) = (body.len(), sha256_hex(&body));
+
+        let chunks = tokio::task::spawn_blocking(move || {
+            let mut chunks: Vec<Vec<u8>> = body
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    let mut chunk = chunk.to_vec();
+                    chunk.resize(chunk_size, 0);
+                    chunk
+                })
+                .collect();
+            chunks.resize(data_chunks + parity_chunks, vec![0; chunk_size]);
+            if parity_chunks > 0 {
+                ReedSolomon::new(data_chunks, parity_chunks)?.encode(&mut chunks)?;
+            }
+            Ok::<_, Error>(chunks)
+        })
+        .await??;
+        let manifest = ChunkManifest {
+            size,
+            chunk_size,
+            data_chunks,
+            parity_chunks,
+            chunk_sha256: chunks.iter().map(|chunk| sha256_hex(chunk)).collect(),
+            sha256,
+            content_type: content_type.to_string(),
+            content_encoding: content_encoding.map(String::from),
+        };
+
+        let written = stream::iter(chunks.into_iter().enumerate())
+            .map(|(index, chunk)| async move {
+                let chunk_key = chunk_key(key, index);
+                self.inner.put(bucket, &chunk_key, chunk, "application/octet-stream", None).await
+            })
+            .buffer_unordered(Self::concurrency())
+            .try_collect::<Vec<()>>()
+            .await;
+        let total = manifest.data_chunks + manifest.parity_chunks;
+        if let Err(err) = written {
+            self.delete_chunks(bucket, key, total).await;
+            return Err(err.context(format!("failed to store the chunks of {key}")));
+        }
+
+        let mut body = MANIFEST_MAGIC.to_vec();
+        body.extend(serde_json::to_vec(&manifest)?);
+        if let Err(err) = self.inner.put(bucket, key, body, content_type, None).await {
+            self.delete_chunks(bucket, key, total).await;
+            return Err(err);
+        }
+        println!("CHUNKED STORAGE: {key} stored in {data_chunks}+{parity_chunks} chunks");
+        Ok(())
+    }
+
+    /// Reassemble the body of `manifest`, rebuilding missing or corrupt chunks from parity.
+    async fn reassemble(
+        &self,
+        bucket: &str,
+        key: &str,
+        manifest: ChunkManifest,
+    ) -> Result<ObjectBody, Error> {
+        let fetch = |range: std::ops::Range<usize>| {
+            let manifest = &manifest;
+            stream::iter(range)
+                .map(move |index| async move {
+                    let chunk = self.inner.get(bucket, &chunk_key(key, index)).await.ok();
+                    // a corrupt chunk is as good as a missing one
+                    chunk.filter(|chunk| {
+                        manifest
+                            .chunk_sha256
+                            .get(index)
+                            .is_some_and(|sha| *sha == sha256_hex(chunk))
+                    })
+                })
+                .buffered(Self::concurrency())
+                .collect::<Vec<Option<Vec<u8>>>>()
+        };
+
+        let total = manifest.data_chunks + manifest.parity_chunks;
+        let mut chunks = fetch(0..manifest.data_chunks).await;
+        let missing = chunks.iter().filter(|chunk| chunk.is_none()).count();
+        if missing > 0 {
+            chunks.extend(fetch(manifest.data_chunks..total).await);
+            let lost = chunks.iter().filter(|chunk| chunk.is_none()).count();
+            if lost > manifest.parity_chunks {
+                return Err(anyhow!(
+                    "{lost} chunks of {key} are missing or corrupt, more than its {} parity chunks",
+                    manifest.parity_chunks
+                ));
+            }
+            println!("CHUNKED STORAGE: rebuilding {missing} chunks of {key}");
+            let (data_chunks, parity_chunks) = (manifest.data_chunks, manifest.parity_chunks);
+            chunks = tokio::task::spawn_blocking(move || {
+                ReedSolomon::new(data_chunks, parity_chunks)?.reconstruct_data(&mut chunks)?;
+                Ok::<_, Error>(chunks)
+            })
+            .await??;
+        }
+
+        let mut data: Vec<u8> = chunks
+            .into_iter()
+            .take(manifest.data_chunks)
+            .flat_map(|chunk| chunk.unwrap_or_default())
+            .collect();
+        data.truncate(manifest.size);
+        if sha256_hex(&data) != manifest.sha256 {
+            return Err(anyhow!("reassembled body of {key} doesn't match its manifest"));
+        }
+        Ok(ObjectBody {
+            data,
+            content_type: Some(manifest.content_type),
+            content_encoding: manifest.content_encoding,
+        })
+    }
+
+    /// The manifest stored at `key`, if the object there is one.
+    async fn manifest(&self, bucket: &str, key: &str) -> Result<Option<ChunkManifest>, Error> {
+        if !self.inner.exists(bucket, &chunk_key(key, 0)).await? {
+            return Ok(None);
+        }
+        parse_manifest(&self.inner.get(bucket, key).await?)
+    }
+
+    async fn delete_chunks(&self, bucket: &str, key: &str, total: usize) {
+        for index in 0..total {
+            let chunk_key = chunk_key(key, index);
+            if let Err(err) = self.inner.delete(bucket, &chunk_key).await {
+                println!("CHUNKED STORAGE: orphaned {chunk_key}: {err}");
+            }
+        }
+    }
+}
+
+/// The manifest `data` holds, checked against the chunk layout `put_chunked` writes so a
+/// crafted one can't make reads fetch or allocate more than `MAX_CHUNKS` chunks.
+fn parse_manifest(data: &[u8]) -> Result<Option<ChunkManifest>, Error> {
+    let Some(json) = data.strip_prefix(MANIFEST_MAGIC) else {
+        return Ok(None);
+    };
+    let manifest: ChunkManifest = serde_json::from_slice(json)?;
+    let total = manifest.data_chunks.saturating_add(manifest.parity_chunks);
+    if manifest.data_chunks == 0 || total > MAX_CHUNKS {
+        return Err(anyhow!("invalid chunk manifest: {total} chunks"));
+    }
+    if manifest.chunk_sha256.len() != total {
+        return Err(anyhow!(
+            "invalid chunk manifest: {} chunk hashes for {total} chunks",
+            manifest.chunk_sha256.len()
+        ));
+    }
+    if manifest.size > manifest.data_chunks.saturating_mul(manifest.chunk_size) {
+        return Err(anyhow!(
+            "invalid chunk manifest: {} bytes don't fit its chunks",
+            manifest.size
+        ));
+    }
+    Ok(Some(manifest))
+}
+
+#[async_trait]
+impl StorageBackend for ChunkedBackend {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        tagging: Option<&str>,
+    ) -> Result<(), Error> {
+        match Self::threshold() {
+            Some(threshold) if body.len() > threshold => {
+                self.put_chunked(bucket, key, body, content_type, None).await
+            }
+            _ => self.inner.put(bucket, key, body, content_type, tagging).await,
+        }
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(self.get_object(bucket, key).await?.data)
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        let object = self.inner.get_object(bucket, key).await?;
+        // a stored body may start like a manifest, only the ones written with their chunks are
+        if !object.data.starts_with(MANIFEST_MAGIC)
+            || !self.inner.exists(bucket, &chunk_key(key, 0)).await?
+        {
+            return Ok(object);
+        }
+        match parse_manifest(&object.data)? {
+            Some(manifest) => self.reassemble(bucket, key, manifest).await,
+            None => Ok(object),
+        }
+    }
+
+    fn keeps_content_encoding(&self) -> bool {
+        self.inner.keeps_content_encoding()
+    }
+
+    async fn put_encoded(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        content_encoding: &str,
+    ) -> Result<(), Error> {
+        match Self::threshold() {
+            Some(threshold) if body.len() > threshold => {
+                self.put_chunked(bucket, key, body, content_type, Some(content_encoding)).await
+            }
+            _ => self.inner.put_encoded(bucket, key, body, content_type, content_encoding).await,
+        }
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        self.inner.exists(bucket, key).await
+    }
+
+    /// Chunked objects have the size their manifest records.
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        match self.manifest(bucket, key).await? {
+            Some(manifest) => Ok(Some(manifest.size as u64)),
+            None => self.inner.size(bucket, key).await,
+        }
+    }
+
+    /// Chunked objects fail with `ChunkedObject`, checked only while chunking is enabled.
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        if Self::threshold().is_some() && self.inner.exists(bucket, &chunk_key(key, 0)).await? {
+            return Err(ChunkedObject(key.to_string()).into());
+        }
+        self.inner.presign(bucket, key, expires_in).await
+    }
+
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        content_length: u64,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        self.inner.presign_put(bucket, key, content_type, content_length, expires_in).await
+    }
+
+    /// Chunks aren't listed, they are under a `{key}.chunks/` prefix of their own. Chunked
+    /// objects are listed with the size of their manifest.
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListPage, Error> {
+        self.inner.list(bucket, prefix, continuation_token).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        if let Some(manifest) = self.manifest(bucket, key).await? {
+            self.inner.delete(bucket, key).await?;
+            self.delete_chunks(bucket, key, manifest.data_chunks + manifest.parity_chunks).await;
+            return Ok(());
+        }
+        self.inner.delete(bucket, key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(data_chunks: usize, parity_chunks: usize, hashes: usize) -> Vec<u8> {
+        let manifest = ChunkManifest {
+            size: 10,
+            chunk_size: 10,
+            data_chunks,
+            parity_chunks,
+            chunk_sha256: vec![sha256_hex(b""); hashes],
+            sha256: sha256_hex(b""),
+            content_type: "video/mp4".to_string(),
+            content_encoding: None,
+        };
+        let mut body = MANIFEST_MAGIC.to_vec();
+        body.extend(serde_json::to_vec(&manifest).unwrap());
+        body
+    }
+
+    #[test]
+    fn parses_manifests_only() {
+        assert!(parse_manifest(b"hello world").unwrap().is_none());
+        let parsed = parse_manifest(&manifest(1, 2, 3)).unwrap().unwrap();
+        assert_eq!((parsed.data_chunks, parsed.parity_chunks), (1, 2));
+    }
+
+    #[test]
+    fn rejects_manifests_outside_the_chunk_layout() {
+        assert!(parse_manifest(&manifest(0, 0, 0)).is_err());
+        assert!(parse_manifest(&manifest(MAX_CHUNKS, 1, MAX_CHUNKS + 1)).is_err());
+        assert!(parse_manifest(&manifest(usize::MAX, 1, 0)).is_err());
+        assert!(parse_manifest(&manifest(2, 2, 3)).is_err());
+        assert!(parse_manifest(b"LOAD-S3-AGENT-CHUNKS/1\nnot json").is_err());
+    }
+}
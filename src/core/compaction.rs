@@ -0,0 +1,59 @@
+use crate::core::{
+    metadata::{TagIndexStats, optimize_tag_index, tag_index_stats},
+    metrics,
+    models::IndexCompactionReport,
+};
+use anyhow::Error;
+use std::time::Instant;
+
+fn record_gauges(stats: &TagIndexStats) {
+    metrics::set_gauge("tag_index_rows", stats.rows as f64);
+    metrics::set_gauge(
+        "tag_index_duplicate_rows",
+        stats.rows.saturating_sub(stats.unique_rows) as f64,
+    );
+}
+
+/// Merge the tag index with `OPTIMIZE ... FINAL`, so the rows re-indexing a dataitem left behind
+/// are dropped. `dry_run` only counts them.
+pub(crate) async fn compact_tag_index(dry_run: bool) -> Result<IndexCompactionReport, Error> {
+    let started = Instant::now();
+    let before = tag_index_stats().await?;
+    record_gauges(&before);
+    let after = if dry_run {
+        before
+    } else {
+        optimize_tag_index().await?;
+        let after = tag_index_stats().await?;
+        record_gauges(&after);
+        after
+    };
+
+    Ok(IndexCompactionReport {
+        dry_run,
+        rows_before: before.rows,
+        unique_rows: before.unique_rows,
+        duplicate_rows: before.rows.saturating_sub(before.unique_rows),
+        rows_after: after.rows,
+        removed: before.rows.saturating_sub(after.rows),
+        dataitems: after.dataitems,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Scheduled compaction of the tag index.
+pub(crate) async fn compact_tag_index_scheduled() -> Result<String, Error> {
+    let report = compact_tag_index(false).await?;
+    println!(
+        "INDEX COMPACTION: rows_before={} duplicates={} rows_after={} removed={} duration_ms={}",
+        report.rows_before,
+        report.duplicate_rows,
+        report.rows_after,
+        report.removed,
+        report.duration_ms
+    );
+    Ok(format!(
+        "rows_before={} rows_after={} removed={}",
+        report.rows_before, report.rows_after, report.removed
+    ))
+}
@@ -0,0 +1,180 @@
+use crate::core::{metrics, utils::get_env_var};
+use anyhow::{Error, anyhow};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::{
+        Arc, Mutex, RwLock,
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const DEFAULT_RETRY_AFTER_SECS: u64 = 1;
+// copies of an upload body held at once by the pipeline: the body, the dataitem signed over it
+// and the dataitem's serialization
+const PIPELINE_COPIES: u64 = 3;
+
+tokio::task_local! {
+    static RESERVED: Arc<UploadReservation>;
+}
+
+/// Global (`MAX_CONCURRENT_REQUESTS`) and per route (`ROUTE_CONCURRENCY_LIMITS`, e.g.
+/// `{"/upload":8}` keyed by the route pattern) limits of the requests handled at once.
+#[derive(Default)]
+pub(crate) struct ConcurrencyLimits {
+    global: Option<Arc<Semaphore>>,
+    routes: HashMap<String, Arc<Semaphore>>,
+}
+
+impl ConcurrencyLimits {
+    fn load() -> Result<Self, Error> {
+        let global = match get_env_var("MAX_CONCURRENT_REQUESTS") {
+            Ok(raw) if !raw.trim().is_empty() => raw
+                .trim()
+                .parse::<usize>()
+                .map_err(|err| anyhow!("invalid MAX_CONCURRENT_REQUESTS: {err}"))?,
+            _ => 0,
+        };
+        let routes: BTreeMap<String, usize> = match get_env_var("ROUTE_CONCURRENCY_LIMITS") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)
+                .map_err(|err| anyhow!("invalid ROUTE_CONCURRENCY_LIMITS config: {err}"))?,
+            _ => BTreeMap::new(),
+        };
+        Ok(ConcurrencyLimits {
+            global: (global > 0).then(|| Arc::new(Semaphore::new(global))),
+            routes: routes
+                .into_iter()
+                .filter(|(_, limit)| *limit > 0)
+                .map(|(route, limit)| (route, Arc::new(Semaphore::new(limit))))
+                .collect(),
+        })
+    }
+}
+
+static LIMITS: Lazy<RwLock<Arc<ConcurrencyLimits>>> = Lazy::new(|| {
+    let limits = ConcurrencyLimits::load().unwrap_or_else(|err| {
+        println!("CONCURRENCY LIMITS disabled: {err}");
+        ConcurrencyLimits::default()
+    });
+    RwLock::new(Arc::new(limits))
+});
+
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+static UPLOAD_BYTES_RESERVED: AtomicU64 = AtomicU64::new(0);
+static ROUTE_IN_FLIGHT: Lazy<Mutex<HashMap<String, usize>>> = Lazy::new(Default::default);
+
+/// Rebuild the limits from the environment. Requests already in flight count against the
+/// previous ones until they complete.
+pub(crate) fn reload() -> Result<(), Error> {
+    let limits = ConcurrencyLimits::load()?;
+    *LIMITS.write().unwrap() = Arc::new(limits);
+    Ok(())
+}
+
+/// Seconds a shed client is told to wait, `CONCURRENCY_RETRY_AFTER_SECS` (default 1).
+pub(crate) fn retry_after_secs() -> u64 {
+    get_env_var("CONCURRENCY_RETRY_AFTER_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RETRY_AFTER_SECS)
+}
+
+/// A request being handled, counted in `requests_in_flight` until it is dropped.
+pub(crate) struct InFlight {
+    route: String,
+    _permits: Vec<OwnedSemaphorePermit>,
+}
+
+fn record_in_flight(route: &str, delta: isize) {
+    let total = match delta {
+        1 => IN_FLIGHT.fetch_add(1, Ordering::Relaxed) + 1,
+        _ => IN_FLIGHT.fetch_sub(1, Ordering::Relaxed) - 1,
+    };
+    metrics::set_gauge("requests_in_flight", total as f64);
+    let mut routes = ROUTE_IN_FLIGHT.lock().unwrap();
+    let count = routes.entry(route.to_string()).or_default();
+    *count = count.saturating_add_signed(delta);
+    metrics::set_gauge(&format!("route_requests_in_flight{{route=\"{route}\"}}"), *count as f64);
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        record_in_flight(&self.route, -1);
+    }
+}
+
+/// Admit a request to `route` within the global and route limits, `None` when either is
+/// reached and the request must be shed.
+pub(crate) fn admit(route: &str) -> Option<InFlight> {
+    let limits = LIMITS.read().unwrap().clone();
+    let mut permits = Vec::with_capacity(2);
+    for semaphore in [limits.global.as_ref(), limits.routes.get(route)].into_iter().flatten() {
+        match semaphore.clone().try_acquire_owned() {
+            Ok(permit) => permits.push(permit),
+            Err(_) => {
+                metrics::increment(&format!("requests_shed_total{{route=\"{route}\"}}"));
+                return None;
+            }
+        }
+    }
+    record_in_flight(route, 1);
+    Some(InFlight { route: route.to_string(), _permits: permits })
+}
+
+/// Bytes the upload bodies in flight may buffer at once, `UPLOAD_MEMORY_BUDGET_BYTES` (default
+/// 0, unbounded).
+fn upload_memory_budget() -> u64 {
+    get_env_var("UPLOAD_MEMORY_BUDGET_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(0)
+}
+
+/// Bytes of an upload body counted against the memory budget until it is dropped.
+pub(crate) struct UploadReservation {
+    bytes: u64,
+}
+
+impl Drop for UploadReservation {
+    fn drop(&mut self) {
+        let reserved = UPLOAD_BYTES_RESERVED.fetch_sub(self.bytes, Ordering::AcqRel) - self.bytes;
+        metrics::set_gauge("upload_buffer_bytes_in_flight", reserved as f64);
+    }
+}
+
+/// Reserve the memory an upload body of `bytes` takes through the pipeline against the upload
+/// memory budget, `None` when it would exceed it and the upload must be rejected. An upload
+/// larger than the whole budget is only admitted alone.
+pub(crate) fn reserve_upload_bytes(bytes: u64) -> Option<UploadReservation> {
+    let budget = upload_memory_budget();
+    if budget == 0 {
+        return Some(UploadReservation { bytes: 0 });
+    }
+    let bytes = bytes.saturating_mul(PIPELINE_COPIES);
+    let admitted =
+        UPLOAD_BYTES_RESERVED.fetch_update(Ordering::AcqRel, Ordering::Acquire, |reserved| {
+            let fits = reserved.saturating_add(bytes) <= budget || reserved == 0;
+            fits.then(|| reserved + bytes)
+        });
+    match admitted {
+        Ok(reserved) => {
+            metrics::set_gauge("upload_buffer_bytes_in_flight", (reserved + bytes) as f64);
+            Some(UploadReservation { bytes })
+        }
+        Err(_) => {
+            metrics::increment("uploads_rejected_memory_total");
+            None
+        }
+    }
+}
+
+/// Handle a request holding its upload `reservation`, released once the request and the
+/// background work it `held` it for are done.
+pub(crate) async fn holding<F: Future>(reservation: UploadReservation, handled: F) -> F::Output {
+    RESERVED.scope(Arc::new(reservation), handled).await
+}
+
+/// The upload reservation of the current request, for background work still holding its body
+/// after the response (e.g. async uploads) to keep until it is done.
+pub(crate) fn held() -> Option<Arc<UploadReservation>> {
+    RESERVED.try_with(Arc::clone).ok()
+}
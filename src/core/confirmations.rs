@@ -0,0 +1,131 @@
+use crate::core::{
+    http::{http_client, send_with_retry},
+    metadata::{UnsettledPost, indexing_enabled, record_post_status, unsettled_posts},
+    resilience::with_timeout,
+    utils::get_env_var,
+    webhooks,
+};
+use anyhow::{Error, anyhow};
+use chrono::Utc;
+use serde::Deserialize;
+use serde_json::json;
+
+pub(crate) const POST_PENDING: &str = "pending";
+pub(crate) const POST_SEEDED: &str = "seeded";
+pub(crate) const POST_CONFIRMED: &str = "confirmed";
+pub(crate) const POST_FAILED: &str = "failed";
+
+pub(crate) const DEFAULT_BUNDLER_UPLOAD_URL: &str = "https://upload.ardrive.io";
+const POLL_BATCH: usize = 500;
+const DEFAULT_TIMEOUT_SECS: i64 = 24 * 60 * 60;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundlerStatus {
+    status: String,
+    #[serde(default)]
+    bundle_id: Option<String>,
+}
+
+/// Upload service of the bundler, `BUNDLER_UPLOAD_URL` or Turbo's.
+fn upload_url() -> String {
+    get_env_var("BUNDLER_UPLOAD_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_BUNDLER_UPLOAD_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+/// A post still unknown to the bundler after `POST_CONFIRMATION_TIMEOUT_SECS` (default a day)
+/// is considered failed.
+fn timeout() -> chrono::Duration {
+    let secs = get_env_var("POST_CONFIRMATION_TIMEOUT_SECS").ok().and_then(|v| v.parse().ok());
+    chrono::Duration::seconds(secs.unwrap_or(DEFAULT_TIMEOUT_SECS))
+}
+
+/// The bundler's status of a dataitem mapped to ours with its bundle, `None` when unknown.
+async fn bundler_status(dataitem_id: &str) -> Result<Option<(&'static str, String)>, Error> {
+    with_timeout("bundler", 30, async {
+        let request = http_client()?.get(format!("{}/v1/tx/{dataitem_id}/status", upload_url()));
+        let response = send_with_retry("bundler", request).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!("bundler returned {} for {dataitem_id}", response.status()));
+        }
+        let body: BundlerStatus = response.json().await?;
+        let status = match body.status.to_ascii_uppercase().as_str() {
+            "CONFIRMED" => POST_CONFIRMED,
+            "FINALIZED" => POST_SEEDED,
+            "FAILED" => POST_FAILED,
+            _ => POST_PENDING,
+        };
+        let detail = body.bundle_id.map(|id| format!("bundle {id}")).unwrap_or_default();
+        Ok(Some((status, detail)))
+    })
+    .await
+}
+
+async fn transition(
+    tenant: &str,
+    dataitem_id: &str,
+    previous: Option<&str>,
+    status: &str,
+    detail: &str,
+) -> Result<(), Error> {
+    record_post_status(tenant, dataitem_id, status, detail).await?;
+    webhooks::notify(
+        "post_status",
+        json!({
+            "tenant": tenant,
+            "dataitem_id": dataitem_id,
+            "status": status,
+            "previous": previous,
+            "detail": detail,
+        }),
+    );
+    Ok(())
+}
+
+/// Start tracking a dataitem just handed to the bundler as `pending`, unless indexing is off.
+pub(crate) async fn track_post(tenant: &str, dataitem_id: &str) -> Result<(), Error> {
+    if !indexing_enabled() {
+        return Ok(());
+    }
+    transition(tenant, dataitem_id, None, POST_PENDING, "").await
+}
+
+async fn poll(post: &UnsettledPost) -> Result<bool, Error> {
+    let (status, detail) = match bundler_status(&post.dataitem_id).await? {
+        Some(found) => found,
+        None if Utc::now() - post.posted_at > timeout() => {
+            (POST_FAILED, "unknown to the bundler".to_string())
+        }
+        None => return Ok(false),
+    };
+    if status == post.status {
+        return Ok(false);
+    }
+    transition(&post.tenant, &post.dataitem_id, Some(&post.status), status, &detail).await?;
+    Ok(true)
+}
+
+/// `post_confirmations` task: poll the bundler for every pending or seeded post and record
+/// their transitions.
+pub(crate) async fn poll_confirmations() -> Result<String, Error> {
+    let posts = unsettled_posts(&[POST_PENDING, POST_SEEDED], POLL_BATCH).await?;
+    let (mut changed, mut failed) = (0, 0);
+    for post in &posts {
+        match poll(post).await {
+            Ok(true) => changed += 1,
+            Ok(false) => {}
+            Err(err) => {
+                println!("CONFIRMATIONS: {} failed: {err}", post.dataitem_id);
+                failed += 1;
+            }
+        }
+    }
+    Ok(format!("polled={} changed={changed} failed={failed}", posts.len()))
+}
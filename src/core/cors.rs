@@ -0,0 +1,92 @@
+use crate::core::utils::get_env_var;
+use anyhow::{Error, anyhow};
+use axum::http::{HeaderName, HeaderValue, Method};
+use std::time::Duration;
+use tower_http::cors::{AllowHeaders, AllowMethods, AllowOrigin, Any, CorsLayer, ExposeHeaders};
+
+/// Browser access policy, defaulting to any origin, method and header.
+///
+/// `CORS_ALLOWED_ORIGINS`, `CORS_ALLOWED_METHODS`, `CORS_ALLOWED_HEADERS` and
+/// `CORS_EXPOSE_HEADERS` take comma separated lists (`*` for any), `CORS_MAX_AGE_SECS` caches
+/// preflight responses and `CORS_ALLOW_CREDENTIALS=true` allows cookies and auth headers,
+/// which requires explicit origins, methods and headers.
+pub(crate) fn cors_layer() -> Result<CorsLayer, Error> {
+    let origins = env_list("CORS_ALLOWED_ORIGINS");
+    let methods = env_list("CORS_ALLOWED_METHODS");
+    let headers = env_list("CORS_ALLOWED_HEADERS");
+    let credentials = get_env_var("CORS_ALLOW_CREDENTIALS").map(|v| v == "true").unwrap_or(false);
+
+    if credentials && [&origins, &methods, &headers].iter().any(|list| list.is_none()) {
+        return Err(anyhow!(
+            "CORS_ALLOW_CREDENTIALS requires explicit CORS_ALLOWED_ORIGINS, CORS_ALLOWED_METHODS and CORS_ALLOWED_HEADERS"
+        ));
+    }
+
+    let allow_origin = match origins {
+        None => AllowOrigin::from(Any),
+        Some(origins) => AllowOrigin::list(
+            origins
+                .iter()
+                .map(|origin| {
+                    HeaderValue::from_str(origin)
+                        .map_err(|_| anyhow!("invalid CORS_ALLOWED_ORIGINS entry: {origin}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+    let allow_methods = match methods {
+        None => AllowMethods::from(Any),
+        Some(methods) => AllowMethods::list(
+            methods
+                .iter()
+                .map(|method| {
+                    Method::from_bytes(method.to_uppercase().as_bytes())
+                        .map_err(|_| anyhow!("invalid CORS_ALLOWED_METHODS entry: {method}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        ),
+    };
+    let allow_headers = match headers {
+        None => AllowHeaders::from(Any),
+        Some(headers) => AllowHeaders::list(header_names("CORS_ALLOWED_HEADERS", &headers)?),
+    };
+
+    let mut layer = CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods(allow_methods)
+        .allow_headers(allow_headers)
+        .allow_credentials(credentials);
+    if let Some(expose) = env_list("CORS_EXPOSE_HEADERS") {
+        layer = layer
+            .expose_headers(ExposeHeaders::list(header_names("CORS_EXPOSE_HEADERS", &expose)?));
+    }
+    if let Ok(max_age) = get_env_var("CORS_MAX_AGE_SECS") {
+        let secs = max_age.parse().map_err(|_| anyhow!("invalid CORS_MAX_AGE_SECS: {max_age}"))?;
+        layer = layer.max_age(Duration::from_secs(secs));
+    }
+    Ok(layer)
+}
+
+/// Comma separated values of `key`, `None` when unset, empty or `*`.
+fn env_list(key: &str) -> Option<Vec<String>> {
+    let raw = get_env_var(key).ok()?;
+    let values: Vec<String> = raw
+        .split(',')
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+        .collect();
+    if values.is_empty() || values.iter().any(|value| value == "*") {
+        return None;
+    }
+    Some(values)
+}
+
+fn header_names(key: &str, names: &[String]) -> Result<Vec<HeaderName>, Error> {
+    names
+        .iter()
+        .map(|name| {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| anyhow!("invalid {key} entry: {name}"))
+        })
+        .collect()
+}
@@ -0,0 +1,381 @@
+use crate::core::{
+    cancellation::{self, UploadBudget},
+    http::{http_client, send_with_retry},
+    progress::new_job_id,
+    resilience::with_timeout,
+    s3::lcp_api_url,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+
+const DEFAULT_CREDITS_PER_BYTE: u128 = 1;
+
+#[derive(Deserialize)]
+struct BalanceResponse {
+    balance: String,
+}
+
+/// A load_acc key's credits don't cover an upload.
+#[derive(Debug)]
+pub(crate) struct InsufficientCredits {
+    pub required: u128,
+    pub balance: Option<u128>,
+}
+
+impl std::fmt::Display for InsufficientCredits {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.balance {
+            Some(balance) => write!(
+                f,
+                "insufficient credits: the upload costs {} and the balance is {balance}",
+                self.required
+            ),
+            None => write!(f, "insufficient credits: the upload costs {}", self.required),
+        }
+    }
+}
+
+impl std::error::Error for InsufficientCredits {}
+
+/// How uploads are metered, read from the environment by `load`.
+#[derive(Debug, Clone)]
+pub(crate) struct CreditsConfig {
+    /// uploads of load_acc keys debit their LCP credits, `UPLOAD_CREDITS_ENABLED=true`
+    pub enabled: bool,
+    /// credits debited per uploaded byte, `CREDITS_PER_BYTE` (default 1)
+    pub per_byte: u128,
+    /// keys uploading for free, `SERVER_API_KEYS`
+    pub operator_keys: Vec<String>,
+    /// credits API of the LCP account system, `CREDITS_API_URL` or `LCP_API_URL`
+    pub api_url: Option<String>,
+    /// token the agent authenticates to the credits API with, `AUTH_SERVER_KEY`
+    pub server_auth: Option<String>,
+}
+
+impl CreditsConfig {
+    pub(crate) fn load() -> Self {
+        let operator_keys = get_env_var("SERVER_API_KEYS").unwrap_or_default();
+        CreditsConfig {
+            enabled: get_env_var("UPLOAD_CREDITS_ENABLED").is_ok_and(|v| v == "true"),
+            per_byte: get_env_var("CREDITS_PER_BYTE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(DEFAULT_CREDITS_PER_BYTE),
+            operator_keys: operator_keys
+                .split(',')
+                .map(|key| key.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .collect(),
+            api_url: get_env_var("CREDITS_API_URL")
+                .ok()
+                .filter(|url| !url.trim().is_empty())
+                .or_else(lcp_api_url)
+                .map(|url| url.trim().trim_end_matches('/').to_string()),
+            server_auth: get_env_var("AUTH_SERVER_KEY").ok(),
+        }
+    }
+
+    /// Operator keys upload for free, only load_acc keys are metered.
+    pub(crate) fn is_metered(&self, api_key: &str) -> bool {
+        self.enabled && !self.operator_keys.iter().any(|key| key == api_key)
+    }
+
+    fn api(&self) -> Result<(&str, &str), Error> {
+        let api_url = self.api_url.as_deref().ok_or_else(|| {
+            anyhow!("CREDITS_API_URL or LCP_API_URL must be set for upload credits")
+        })?;
+        let server_auth = self
+            .server_auth
+            .as_deref()
+            .ok_or_else(|| anyhow!("AUTH_SERVER_KEY must be set for upload credits"))?;
+        Ok((api_url, server_auth))
+    }
+}
+
+fn parse_credits(raw: &str) -> Result<u128, Error> {
+    raw.parse().map_err(|_| anyhow!("invalid credits amount {raw:?}"))
+}
+
+/// Credits held by a load_acc key.
+pub(crate) async fn balance(config: &CreditsConfig, load_acc: &str) -> Result<u128, Error> {
+    let (api_url, server_auth) = config.api()?;
+    let url = format!("{api_url}/credits/{load_acc}");
+    with_timeout("lcp", 10, async {
+        let request = http_client()?.get(&url).header("X-Load-Auth-Token", server_auth);
+        let response = send_with_retry("lcp", request).await?;
+        if !response.status().is_success() {
+            return Err(anyhow!("credits API returned {} for the balance", response.status()));
+        }
+        let body: BalanceResponse = response.json().await?;
+        parse_credits(&body.balance)
+    })
+    .await
+}
+
+/// Credits taken from a load_acc key for one upload, given back with `refund` when the upload
+/// fails afterwards. A debit dropped along with its request (the client disconnected,
+/// `UPLOAD_DEADLINE_SECS` elapsed) is refunded in the background, unless it was `settle`d or
+/// the upload's writes were committed.
+pub(crate) struct Debit {
+    config: CreditsConfig,
+    load_acc: String,
+    amount: u128,
+    // lets the credits API recognize a retried call instead of charging twice
+    reference: String,
+    upload: UploadBudget,
+    settled: bool,
+}
+
+impl Drop for Debit {
+    fn drop(&mut self) {
+        if self.settled || !self.upload.abandon() {
+            return;
+        }
+        let debit = Debit {
+            config: self.config.clone(),
+            load_acc: std::mem::take(&mut self.load_acc),
+            amount: self.amount,
+            reference: std::mem::take(&mut self.reference),
+            upload: self.upload.clone(),
+            settled: false,
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => drop(runtime.spawn(give_back(debit))),
+            Err(_) => {
+                let debit = debit.into_settled();
+                println!("CREDITS: debit {} dropped outside of the runtime", debit.reference);
+            }
+        }
+    }
+}
+
+impl Debit {
+    fn into_settled(mut self) -> Self {
+        self.settled = true;
+        self
+    }
+}
+
+async fn transfer(
+    config: &CreditsConfig,
+    load_acc: &str,
+    action: &str,
+    amount: u128,
+    reference: &str,
+) -> Result<(), Error> {
+    let (api_url, server_auth) = config.api()?;
+    let url = format!("{api_url}/credits/{load_acc}/{action}");
+    let body = json!({ "amount": amount.to_string(), "reference": reference });
+    with_timeout("lcp", 10, async {
+        let request =
+            http_client()?.post(&url).header("X-Load-Auth-Token", server_auth).json(&body);
+        let response = send_with_retry("lcp", request).await?;
+        if response.status() == StatusCode::PAYMENT_REQUIRED {
+            let balance = response.json::<BalanceResponse>().await.ok();
+            let balance = balance.and_then(|body| parse_credits(&body.balance).ok());
+            return Err(InsufficientCredits { required: amount, balance }.into());
+        }
+        if !response.status().is_success() {
+            return Err(anyhow!("credits API returned {} for the {action}", response.status()));
+        }
+        Ok(())
+    })
+    .await
+}
+
+/// Debit an upload of `bytes` from `api_key`'s credits, failing with `InsufficientCredits` when
+/// they don't cover it. `None` for keys that aren't metered.
+pub(crate) async fn debit(
+    config: &CreditsConfig,
+    api_key: Option<&str>,
+    bytes: usize,
+) -> Result<Option<Debit>, Error> {
+    let Some(load_acc) = api_key.filter(|key| config.is_metered(key)) else {
+        return Ok(None);
+    };
+    let amount = bytes as u128 * config.per_byte;
+    let reference = new_job_id();
+    transfer(config, load_acc, "debit", amount, &reference).await?;
+    Ok(Some(Debit {
+        config: config.clone(),
+        load_acc: load_acc.to_string(),
+        amount,
+        reference,
+        upload: cancellation::current(),
+        settled: false,
+    }))
+}
+
+/// Keep the credits of an upload that was stored.
+pub(crate) fn settle(debit: Option<Debit>) {
+    if let Some(debit) = debit {
+        debit.into_settled();
+    }
+}
+
+async fn give_back(debit: Debit) {
+    let debit = debit.into_settled();
+    let refunded =
+        transfer(&debit.config, &debit.load_acc, "refund", debit.amount, &debit.reference).await;
+    if let Err(err) = refunded {
+        println!("CREDITS: failed to refund {} of debit {}: {err}", debit.amount, debit.reference);
+    }
+}
+
+/// Give back the credits of an upload that failed after its debit.
+pub(crate) async fn refund(debit: Option<Debit>) {
+    if let Some(debit) = debit {
+        give_back(debit).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Json, Router, extract::Path, http::header, response::IntoResponse, routing::post};
+    use once_cell::sync::Lazy;
+    use serde_json::Value;
+    use std::sync::Mutex;
+
+    // (load_acc, action, amount, reference) of every call the credits API received
+    type Calls = Mutex<Vec<(String, String, String, String)>>;
+
+    const OPERATOR_KEY: &str = "operator-key";
+    const POOR_KEY: &str = "poor-key";
+
+    static CALLS: Calls = Mutex::new(Vec::new());
+
+    /// A credits API on its own runtime, shared by the tests.
+    static CREDITS_API: Lazy<String> = Lazy::new(|| {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let app = Router::new().route("/credits/{load_acc}/{action}", post(credits_api));
+        std::thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                axum::serve(listener, app).await.unwrap();
+            });
+        });
+        url
+    });
+
+    fn config() -> CreditsConfig {
+        CreditsConfig {
+            enabled: true,
+            per_byte: 1,
+            operator_keys: vec![OPERATOR_KEY.to_string()],
+            api_url: Some(CREDITS_API.clone()),
+            server_auth: Some("server-key".to_string()),
+        }
+    }
+
+    async fn credits_api(
+        Path((load_acc, action)): Path<(String, String)>,
+        Json(body): Json<Value>,
+    ) -> impl IntoResponse {
+        let amount = body["amount"].as_str().unwrap_or_default().to_string();
+        let reference = body["reference"].as_str().unwrap_or_default().to_string();
+        CALLS.lock().unwrap().push((load_acc.clone(), action, amount, reference));
+        // each test's runtime is gone after it, its pooled connections with it
+        let close = [(header::CONNECTION, "close")];
+        match load_acc == POOR_KEY {
+            true => (StatusCode::PAYMENT_REQUIRED, close, Json(json!({ "balance": "5" }))),
+            false => (StatusCode::OK, close, Json(json!({}))),
+        }
+    }
+
+    fn calls(load_acc: &str) -> Vec<(String, String, String)> {
+        let calls = CALLS.lock().unwrap();
+        calls
+            .iter()
+            .filter(|(key, ..)| key == load_acc)
+            .map(|(_, action, amount, reference)| {
+                (action.clone(), amount.clone(), reference.clone())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn debits_metered_keys_only() {
+        let config = config();
+        assert!(debit(&config, None, 10).await.unwrap().is_none());
+        assert!(debit(&config, Some(OPERATOR_KEY), 10).await.unwrap().is_none());
+        assert!(calls(OPERATOR_KEY).is_empty());
+
+        let debited = debit(&config, Some("metered-key"), 10).await.unwrap().unwrap();
+        let reference = debited.reference.clone();
+        settle(Some(debited));
+        assert_eq!(calls("metered-key"), [("debit".to_string(), "10".to_string(), reference)]);
+    }
+
+    #[tokio::test]
+    async fn refunds_failed_uploads() {
+        let config = config();
+        let debited = debit(&config, Some("refunded-key"), 7).await.unwrap();
+        let reference = debited.as_ref().unwrap().reference.clone();
+        refund(debited).await;
+        assert_eq!(
+            calls("refunded-key"),
+            [
+                ("debit".to_string(), "7".to_string(), reference.clone()),
+                ("refund".to_string(), "7".to_string(), reference),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_uploads_the_balance_doesnt_cover() {
+        let config = config();
+        let err = debit(&config, Some(POOR_KEY), 10).await.err().unwrap();
+        let insufficient = err.downcast_ref::<InsufficientCredits>().unwrap();
+        assert_eq!(insufficient.required, 10);
+        assert_eq!(insufficient.balance, Some(5));
+    }
+
+    /// Calls to `load_acc`'s credits once the background refunds, if any, had time to land.
+    async fn settled_calls(load_acc: &str) -> Vec<(String, String, String)> {
+        for _ in 0..50 {
+            if calls(load_acc).iter().any(|(action, ..)| action == "refund") {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+        }
+        calls(load_acc)
+    }
+
+    #[tokio::test]
+    async fn refunds_debits_dropped_with_their_request() {
+        let config = config();
+        let debited = debit(&config, Some("dropped-key"), 3).await.unwrap();
+        let reference = debited.as_ref().unwrap().reference.clone();
+        drop(debited);
+        assert_eq!(
+            settled_calls("dropped-key").await,
+            [
+                ("debit".to_string(), "3".to_string(), reference.clone()),
+                ("refund".to_string(), "3".to_string(), reference),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn keeps_debits_of_settled_and_committed_uploads() {
+        let config = config();
+        settle(debit(&config, Some("settled-key"), 3).await.unwrap());
+        assert_eq!(settled_calls("settled-key").await.len(), 1);
+
+        // the request is dropped after the writes were committed, but before the debit settled
+        let committed = cancellation::scope(async {
+            let debited = debit(&config, Some("committed-key"), 3).await.unwrap();
+            cancellation::current().commit().unwrap();
+            drop(debited);
+        });
+        committed.await.unwrap();
+        assert_eq!(settled_calls("committed-key").await.len(), 1);
+    }
+}
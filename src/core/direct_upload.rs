@@ -0,0 +1,186 @@
+use crate::core::{
+    lcp::validate_bucket_ownership,
+    progress::new_job_id,
+    provenance,
+    storage::{StorageBackend, storage_backend},
+    utils::get_env_var,
+};
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+const DEFAULT_DIR: &str = "direct-uploads";
+const DEFAULT_EXPIRY_SECS: u64 = 15 * 60;
+// a PUT started right before the URL expired still has to finish
+const COMPLETION_GRACE_SECS: i64 = 60 * 60;
+const MANIFEST_SUFFIX: &str = ".json";
+
+/// What the completion needs besides the uploaded body, written next to it.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    content_type: String,
+    folder_name: String,
+    dataitem_name: String,
+    is_signed: bool,
+    principal: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A presigned upload URL, the body being published by `complete`.
+pub(crate) struct PresignedUpload {
+    pub upload_id: String,
+    pub url: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// A completed direct upload, ready to be signed and stored like `POST /upload/private` ones.
+pub(crate) struct DirectUpload {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    pub folder_name: String,
+    pub dataitem_name: String,
+    pub is_signed: bool,
+}
+
+/// An upload ID that is unknown, expired, presigned for another key, or not uploaded yet.
+#[derive(Debug)]
+pub(crate) struct DirectUploadNotFound(pub String);
+
+impl std::fmt::Display for DirectUploadNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "direct upload {} not found", self.0)
+    }
+}
+
+impl std::error::Error for DirectUploadNotFound {}
+
+/// The load_acc key presigning an upload doesn't own its bucket.
+#[derive(Debug)]
+pub(crate) struct NotBucketOwner(pub String);
+
+impl std::fmt::Display for NotBucketOwner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "the load_acc key doesn't own bucket {}", self.0)
+    }
+}
+
+impl std::error::Error for NotBucketOwner {}
+
+/// Uploads land under `PRIVATE_DIRECT_UPLOAD_DIR` (default `direct-uploads`) of the private
+/// bucket until completed.
+fn upload_dir() -> String {
+    get_env_var("PRIVATE_DIRECT_UPLOAD_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_DIR.to_string())
+}
+
+/// Presigned URLs expire after `PRIVATE_DIRECT_UPLOAD_EXPIRY_SECS` (default 15 minutes).
+fn expiry() -> Duration {
+    let secs = get_env_var("PRIVATE_DIRECT_UPLOAD_EXPIRY_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.filter(|&secs| secs > 0).unwrap_or(DEFAULT_EXPIRY_SECS))
+}
+
+fn keys(upload_id: &str) -> (String, String) {
+    let body = format!("{}/{upload_id}", upload_dir());
+    let manifest = format!("{body}{MANIFEST_SUFFIX}");
+    (body, manifest)
+}
+
+// upload IDs are generated by `new_job_id`, anything else would address other objects
+fn is_upload_id(upload_id: &str) -> bool {
+    upload_id.len() == 32 && upload_id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Presign a `PUT` of a `content_type` body of `size` bytes into the private bucket
+/// `bucket_name` owned by `load_acc`, failing with `NotBucketOwner`.
+pub(crate) async fn presign(
+    bucket_name: &str,
+    load_acc: &str,
+    content_type: &str,
+    size: u64,
+    folder_name: &str,
+    dataitem_name: &str,
+    is_signed: bool,
+) -> Result<PresignedUpload, Error> {
+    if !validate_bucket_ownership(bucket_name, load_acc).await? {
+        return Err(NotBucketOwner(bucket_name.to_string()).into());
+    }
+    let storage = storage_backend().await?;
+    let upload_id = new_job_id();
+    let (key_body, key_manifest) = keys(&upload_id);
+    let expiry = expiry();
+    let manifest = Manifest {
+        content_type: content_type.to_string(),
+        folder_name: folder_name.to_string(),
+        dataitem_name: dataitem_name.to_string(),
+        is_signed,
+        principal: provenance::current().principal,
+        expires_at: Utc::now() + chrono::Duration::from_std(expiry)?,
+    };
+
+    let url = storage.presign_put(bucket_name, &key_body, content_type, size, expiry).await?;
+    let manifest_json = serde_json::to_vec(&manifest)?;
+    storage.put(bucket_name, &key_manifest, manifest_json, "application/json", None).await?;
+    Ok(PresignedUpload { upload_id, url, expires_at: manifest.expires_at })
+}
+
+async fn manifest(
+    storage: &dyn StorageBackend,
+    bucket_name: &str,
+    upload_id: &str,
+) -> Result<Manifest, Error> {
+    let not_found = || DirectUploadNotFound(upload_id.to_string());
+    if !is_upload_id(upload_id) {
+        return Err(not_found().into());
+    }
+    let (_, key_manifest) = keys(upload_id);
+    if !storage.exists(bucket_name, &key_manifest).await? {
+        return Err(not_found().into());
+    }
+    let manifest: Manifest =
+        serde_json::from_slice(&storage.get(bucket_name, &key_manifest).await?)?;
+    let expired =
+        manifest.expires_at + chrono::Duration::seconds(COMPLETION_GRACE_SECS) < Utc::now();
+    if expired || manifest.principal != provenance::current().principal {
+        return Err(not_found().into());
+    }
+    Ok(manifest)
+}
+
+/// Size of the body uploaded with the current request's key, to check and reserve memory for
+/// before it is loaded, failing with `DirectUploadNotFound`.
+pub(crate) async fn uploaded_size(bucket_name: &str, upload_id: &str) -> Result<u64, Error> {
+    let storage = storage_backend().await?;
+    manifest(storage.as_ref(), bucket_name, upload_id).await?;
+    let (key_body, _) = keys(upload_id);
+    let size = storage.size(bucket_name, &key_body).await?;
+    size.ok_or_else(|| DirectUploadNotFound(upload_id.to_string()).into())
+}
+
+/// The body uploaded with the current request's key, failing with `DirectUploadNotFound`.
+/// Ownership of the bucket is checked again when it is stored.
+pub(crate) async fn load(bucket_name: &str, upload_id: &str) -> Result<DirectUpload, Error> {
+    let storage = storage_backend().await?;
+    let manifest = manifest(storage.as_ref(), bucket_name, upload_id).await?;
+    let (key_body, _) = keys(upload_id);
+    if !storage.exists(bucket_name, &key_body).await? {
+        return Err(DirectUploadNotFound(upload_id.to_string()).into());
+    }
+    Ok(DirectUpload {
+        data: storage.get(bucket_name, &key_body).await?,
+        content_type: manifest.content_type,
+        folder_name: manifest.folder_name,
+        dataitem_name: manifest.dataitem_name,
+        is_signed: manifest.is_signed,
+    })
+}
+
+/// Drop a direct upload and its manifest, once completed or rejected.
+pub(crate) async fn remove(bucket_name: &str, upload_id: &str) -> Result<(), Error> {
+    let storage = storage_backend().await?;
+    let (key_body, key_manifest) = keys(upload_id);
+    storage.delete(bucket_name, &key_body).await?;
+    storage.delete(bucket_name, &key_manifest).await
+}
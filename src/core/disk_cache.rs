@@ -0,0 +1,137 @@
+use crate::core::utils::sha256_hex;
+use std::{collections::HashMap, path::PathBuf, sync::Mutex, time::UNIX_EPOCH};
+
+struct CacheEntry {
+    size: u64,
+    last_access: u64,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<String, CacheEntry>,
+    total_bytes: u64,
+    tick: u64,
+}
+
+/// Size-bounded LRU cache of blobs on local disk. Keys are hashed into file names, the
+/// recency index lives in memory and is rebuilt from the directory on startup.
+pub(crate) struct DiskCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    index: Mutex<CacheIndex>,
+}
+
+impl DiskCache {
+    pub fn new(dir: impl Into<PathBuf>, max_bytes: u64) -> Self {
+        let dir = dir.into();
+        let mut index = CacheIndex::default();
+
+        if let Ok(read_dir) = std::fs::read_dir(&dir) {
+            let mut existing: Vec<(String, u64, u64)> = read_dir
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                    let modified = metadata
+                        .modified()
+                        .ok()
+                        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                        .map(|d| d.as_secs())
+                        .unwrap_or_default();
+                    Some((
+                        entry.file_name().to_string_lossy().into_owned(),
+                        metadata.len(),
+                        modified,
+                    ))
+                })
+                .collect();
+            // oldest first, so ticks follow the files' modification order
+            existing.sort_by_key(|(_, _, modified)| *modified);
+            for (file_name, size, _) in existing {
+                index.tick += 1;
+                index.total_bytes += size;
+                index.entries.insert(file_name, CacheEntry { size, last_access: index.tick });
+            }
+        }
+
+        DiskCache { dir, max_bytes, index: Mutex::new(index) }
+    }
+
+    fn file_name(key: &str) -> String {
+        sha256_hex(key.as_bytes())
+    }
+
+    pub async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let file_name = Self::file_name(key);
+        {
+            let mut index = self.index.lock().unwrap();
+            index.tick += 1;
+            let tick = index.tick;
+            index.entries.get_mut(&file_name)?.last_access = tick;
+        }
+        match tokio::fs::read(self.dir.join(&file_name)).await {
+            Ok(data) => Some(data),
+            Err(_) => {
+                self.forget(&file_name);
+                None
+            }
+        }
+    }
+
+    pub async fn put(&self, key: &str, data: &[u8]) {
+        let size = data.len() as u64;
+        if size > self.max_bytes {
+            return;
+        }
+        let file_name = Self::file_name(key);
+        if tokio::fs::create_dir_all(&self.dir).await.is_err()
+            || tokio::fs::write(self.dir.join(&file_name), data).await.is_err()
+        {
+            return;
+        }
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            index.tick += 1;
+            let tick = index.tick;
+            if let Some(previous) =
+                index.entries.insert(file_name.clone(), CacheEntry { size, last_access: tick })
+            {
+                index.total_bytes -= previous.size;
+            }
+            index.total_bytes += size;
+
+            let mut evicted = Vec::new();
+            while index.total_bytes > self.max_bytes {
+                let Some(lru) = index
+                    .entries
+                    .iter()
+                    .filter(|(name, _)| **name != file_name)
+                    .min_by_key(|(_, entry)| entry.last_access)
+                    .map(|(name, _)| name.clone())
+                else {
+                    break;
+                };
+                if let Some(entry) = index.entries.remove(&lru) {
+                    index.total_bytes -= entry.size;
+                }
+                evicted.push(lru);
+            }
+            evicted
+        };
+
+        for file_name in evicted {
+            let _ = tokio::fs::remove_file(self.dir.join(file_name)).await;
+        }
+    }
+
+    pub fn size_bytes(&self) -> u64 {
+        self.index.lock().unwrap().total_bytes
+    }
+
+    fn forget(&self, file_name: &str) {
+        let mut index = self.index.lock().unwrap();
+        if let Some(entry) = index.entries.remove(file_name) {
+            index.total_bytes -= entry.size;
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use crate::core::models::AgentError;
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+
+/// Dataitem IDs and owner addresses: 32 bytes of sha256, b64url without padding.
+pub(crate) fn is_arweave_id(id: &str) -> bool {
+    id.len() == 43 && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// The dataitem ID of a route's single path parameter, rejected with `400` unless it is a
+/// well-formed 43 characters b64url ID, so it can't address other objects once put in a key.
+pub struct DataitemId(pub String);
+
+impl<S: Send + Sync> FromRequestParts<S> for DataitemId {
+    type Rejection = AgentError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(id) = Path::<String>::from_request_parts(parts, state)
+            .await
+            .map_err(|rejection| AgentError::BadRequest(rejection.body_text()))?;
+        if !is_arweave_id(&id) {
+            return Err(AgentError::BadRequest(format!(
+                "invalid dataitem id {id:?}, expected 43 base64url characters"
+            )));
+        }
+        Ok(DataitemId(id))
+    }
+}
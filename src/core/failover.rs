@@ -0,0 +1,284 @@
+use crate::core::{
+    metrics,
+    models::ReadEndpointHealth,
+    resilience::{BreakerOpen, CircuitBreaker, DependencyTimeout, RetryPolicy, with_timeout},
+    s3::{S3Backend, s3_client_for},
+    storage::{ListPage, ObjectBody, StorageBackend},
+    utils::get_env_var,
+};
+use anyhow::Error;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::OnceCell;
+
+const DEFAULT_READ_TIMEOUT_SECS: u64 = 30;
+
+static PRIMARY: Lazy<ReadEndpoint> = Lazy::new(|| ReadEndpoint {
+    name: "primary".to_string(),
+    breaker: Box::leak(Box::new(CircuitBreaker::from_env("s3_read_primary", "S3_READ"))),
+    is_primary: true,
+    stats: Mutex::default(),
+});
+static READ_REPLICAS: OnceCell<Vec<Arc<ReadReplica>>> = OnceCell::const_new();
+
+#[derive(Default)]
+struct EndpointStats {
+    served_total: u64,
+    failures_total: u64,
+    last_error: Option<String>,
+    last_failure_at: Option<DateTime<Utc>>,
+}
+
+/// Health of a storage endpoint reads are served from, its breaker skips it while unhealthy.
+struct ReadEndpoint {
+    name: String,
+    breaker: &'static CircuitBreaker,
+    // replica backends record their transient errors on the breaker themselves
+    is_primary: bool,
+    stats: Mutex<EndpointStats>,
+}
+
+impl ReadEndpoint {
+    fn served(&self) {
+        if self.is_primary {
+            self.breaker.record_success();
+        }
+        self.stats.lock().unwrap().served_total += 1;
+    }
+
+    fn failed(&self, err: &Error) {
+        if self.is_primary || err.is::<DependencyTimeout>() {
+            self.breaker.record_failure();
+        }
+        println!("READ FAILOVER: {} failed: {err}", self.name);
+        let mut stats = self.stats.lock().unwrap();
+        stats.failures_total += 1;
+        stats.last_error = Some(err.to_string());
+        stats.last_failure_at = Some(Utc::now());
+    }
+
+    fn health(&self) -> ReadEndpointHealth {
+        let stats = self.stats.lock().unwrap();
+        ReadEndpointHealth {
+            endpoint: self.name.clone(),
+            state: self.breaker.state().as_str().to_string(),
+            served_total: stats.served_total,
+            failures_total: stats.failures_total,
+            last_error: stats.last_error.clone(),
+            last_failure_at: stats.last_failure_at.map(|at| at.to_rfc3339()),
+        }
+    }
+}
+
+/// Read-only S3-compatible endpoint holding copies of the primary's buckets, e.g. in another
+/// region.
+struct ReadReplica {
+    endpoint: ReadEndpoint,
+    backend: S3Backend,
+}
+
+/// `READ_REPLICA_S3_ENDPOINT_URLS`, comma separated and tried in order, with
+/// `READ_REPLICA_S3_REGION`, `READ_REPLICA_S3_ACCESS_KEY_ID` and
+/// `READ_REPLICA_S3_SECRET_ACCESS_KEY` defaulting to the primary's `AWS_*` settings.
+async fn read_replicas() -> &'static [Arc<ReadReplica>] {
+    READ_REPLICAS
+        .get_or_init(|| async {
+            let Ok(urls) = get_env_var("READ_REPLICA_S3_ENDPOINT_URLS") else {
+                return Vec::new();
+            };
+            let setting = |key: &str, fallback: &str, default: &str| {
+                get_env_var(key)
+                    .or_else(|_| get_env_var(fallback))
+                    .unwrap_or_else(|_| default.to_string())
+            };
+            let region = setting("READ_REPLICA_S3_REGION", "AWS_REGION", "auto");
+            let access_key_id = setting("READ_REPLICA_S3_ACCESS_KEY_ID", "AWS_ACCESS_KEY_ID", "");
+            let secret_access_key =
+                setting("READ_REPLICA_S3_SECRET_ACCESS_KEY", "AWS_SECRET_ACCESS_KEY", "");
+
+            let mut replicas = Vec::new();
+            for url in urls.split(',').map(str::trim).filter(|url| !url.is_empty()) {
+                let name = format!("s3_read_replica_{}", replicas.len());
+                let breaker: &'static CircuitBreaker =
+                    Box::leak(Box::new(CircuitBreaker::from_env(&name, "READ_REPLICA_S3")));
+                let client = s3_client_for(url, &region, &access_key_id, &secret_access_key).await;
+                let backend = S3Backend::with_client(
+                    client,
+                    RetryPolicy::from_env("READ_REPLICA_S3"),
+                    breaker,
+                );
+                let endpoint = ReadEndpoint {
+                    name: url.to_string(),
+                    breaker,
+                    is_primary: false,
+                    stats: Mutex::default(),
+                };
+                replicas.push(Arc::new(ReadReplica { endpoint, backend }));
+            }
+            println!("READ FAILOVER: {} read replica endpoints", replicas.len());
+            replicas
+        })
+        .await
+}
+
+/// Wrap `primary` in a `FailoverBackend` when read replica endpoints are configured.
+pub(crate) async fn with_read_failover(
+    primary: Box<dyn StorageBackend>,
+) -> Box<dyn StorageBackend> {
+    let replicas = read_replicas().await;
+    if replicas.is_empty() {
+        return primary;
+    }
+    Box::new(FailoverBackend { primary, replicas })
+}
+
+/// Reads go to the primary backend, then to each read replica in order when it errors or takes
+/// longer than `S3_READ_TIMEOUT_SECS` (default 30). Writes only ever go to the primary.
+struct FailoverBackend {
+    primary: Box<dyn StorageBackend>,
+    replicas: &'static [Arc<ReadReplica>],
+}
+
+#[async_trait]
+impl StorageBackend for FailoverBackend {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        tagging: Option<&str>,
+    ) -> Result<(), Error> {
+        self.primary.put(bucket, key, body, content_type, tagging).await
+    }
+
+    fn keeps_content_encoding(&self) -> bool {
+        self.primary.keeps_content_encoding()
+    }
+
+    async fn put_encoded(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        content_encoding: &str,
+    ) -> Result<(), Error> {
+        self.primary.put_encoded(bucket, key, body, content_type, content_encoding).await
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(self.get_object(bucket, key).await?.data)
+    }
+
+    /// An endpoint counts as failed when it errored on an object a later one served, or timed
+    /// out. When every endpoint errors (e.g. a missing key) the first error is returned.
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        let mut endpoints: Vec<(&ReadEndpoint, &dyn StorageBackend)> = Vec::new();
+        // an unhealthy primary is skipped until its breaker lets a probe through
+        if PRIMARY.breaker.allow() {
+            endpoints.push((&PRIMARY, self.primary.as_ref()));
+        }
+        endpoints.extend(self.replicas.iter().map(|r| (&r.endpoint, &r.backend as _)));
+
+        let mut errors: Vec<(&ReadEndpoint, Error)> = Vec::new();
+        for (endpoint, backend) in endpoints {
+            let read = backend.get_object(bucket, key);
+            match with_timeout("s3_read", DEFAULT_READ_TIMEOUT_SECS, read).await {
+                Ok(object) => {
+                    endpoint.served();
+                    if !errors.is_empty() {
+                        metrics::increment("s3_read_failovers_total");
+                    }
+                    for (failed, err) in &errors {
+                        if !err.is::<DependencyTimeout>() {
+                            failed.failed(err);
+                        }
+                    }
+                    return Ok(object);
+                }
+                Err(err) => {
+                    if err.is::<DependencyTimeout>() {
+                        endpoint.failed(&err);
+                    }
+                    errors.push((endpoint, err));
+                }
+            }
+        }
+
+        // the primary answered like every replica did, it is healthy
+        if let Some((endpoint, err)) = errors.first() {
+            if endpoint.is_primary && !err.is::<DependencyTimeout>() {
+                PRIMARY.breaker.record_success();
+            }
+        }
+        let first = errors.into_iter().next().map(|(_, err)| err);
+        Err(first
+            .unwrap_or_else(|| BreakerOpen { dependency: PRIMARY.breaker.name().into() }.into()))
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        self.primary.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        self.primary.size(bucket, key).await
+    }
+
+    /// Presigned on the first endpoint whose breaker isn't open, so clients are sent to one
+    /// likely to answer.
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        if PRIMARY.breaker.rejecting() {
+            if let Some(replica) = self.replicas.iter().find(|r| !r.endpoint.breaker.rejecting()) {
+                return replica.backend.presign(bucket, key, expires_in).await;
+            }
+        }
+        self.primary.presign(bucket, key, expires_in).await
+    }
+
+    // uploads are never made to a read replica
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        content_length: u64,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        self.primary.presign_put(bucket, key, content_type, content_length, expires_in).await
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListPage, Error> {
+        self.primary.list(bucket, prefix, continuation_token).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        self.primary.delete(bucket, key).await
+    }
+}
+
+/// Health of the primary and of each read replica, empty without read replicas.
+pub(crate) async fn read_endpoints_health() -> Vec<ReadEndpointHealth> {
+    let replicas = read_replicas().await;
+    if replicas.is_empty() {
+        return Vec::new();
+    }
+    std::iter::once(PRIMARY.health())
+        .chain(replicas.iter().map(|replica| replica.endpoint.health()))
+        .collect()
+}
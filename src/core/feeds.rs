@@ -0,0 +1,198 @@
+use crate::core::{
+    metadata::{
+        FeedRecord, get_feed, indexing_enabled, list_feeds, matches_filters, normalize_filters,
+        normalize_tags, save_feed,
+    },
+    metrics,
+    models::{FeedItemEvent, UploadTag},
+    provenance, webhooks,
+};
+use anyhow::Error;
+use chrono::{SubsecRound, Utc};
+use futures::{Stream, StreamExt, stream};
+use once_cell::sync::Lazy;
+use serde_json::json;
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+const MAX_NAME_LEN: usize = 64;
+// other agents sharing ClickHouse pick up saved or removed feeds after at most this long
+const FEED_CACHE_TTL: Duration = Duration::from_secs(30);
+// events a slow `GET /feeds/{name}/events` client may fall behind before missing some
+const EVENT_BUFFER: usize = 1024;
+
+/// A dataitem the agent just indexed.
+struct IndexedItem {
+    tenant: String,
+    dataitem_id: String,
+    content_type: String,
+    tags: Vec<(String, String)>,
+}
+
+/// Indexed dataitems matching a feed, for its `GET /feeds/{name}/events` subscribers.
+static EVENTS: Lazy<broadcast::Sender<(String, Arc<FeedItemEvent>)>> =
+    Lazy::new(|| broadcast::channel(EVENT_BUFFER).0);
+
+/// A tenant's feeds and when they were loaded.
+type CachedFeeds = (Instant, Arc<Vec<FeedRecord>>);
+
+static FEED_CACHE: Lazy<Mutex<HashMap<String, CachedFeeds>>> = Lazy::new(Default::default);
+
+/// A feed name or filters `save` refuses.
+#[derive(Debug)]
+pub(crate) struct InvalidFeed(pub String);
+
+impl std::fmt::Display for InvalidFeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidFeed {}
+
+fn check_name(name: &str) -> Result<(), InvalidFeed> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_');
+    match valid {
+        true => Ok(()),
+        false => Err(InvalidFeed(format!(
+            "invalid feed name {name:?}, expected 1 to {MAX_NAME_LEN} lowercase letters, digits, '-' or '_'"
+        ))),
+    }
+}
+
+/// Save the tenant's feed `name` over the tag filters `filters`, replacing the feed of that
+/// name. Fails with `InvalidFeed`.
+pub(crate) async fn save(
+    tenant: &str,
+    name: &str,
+    filters: &[(String, Vec<String>)],
+) -> Result<FeedRecord, Error> {
+    check_name(name)?;
+    let filters = normalize_filters(filters);
+    if filters.is_empty() {
+        return Err(InvalidFeed("a feed needs at least one filter with a value".into()).into());
+    }
+    let now = Utc::now().trunc_subsecs(3);
+    let created_at = get_feed(tenant, name).await?.map(|feed| feed.created_at).unwrap_or(now);
+    let feed = FeedRecord {
+        tenant: tenant.to_string(),
+        name: name.to_string(),
+        filters,
+        created_by: provenance::current().principal,
+        active: true,
+        created_at,
+        updated_at: now,
+    };
+    save_feed(&feed).await?;
+    FEED_CACHE.lock().unwrap().remove(tenant);
+    Ok(feed)
+}
+
+/// Remove the tenant's feed `name`, `None` when it doesn't exist.
+pub(crate) async fn remove(tenant: &str, name: &str) -> Result<Option<FeedRecord>, Error> {
+    let Some(mut feed) = get_feed(tenant, name).await? else {
+        return Ok(None);
+    };
+    feed.active = false;
+    feed.updated_at = Utc::now().trunc_subsecs(3);
+    save_feed(&feed).await?;
+    FEED_CACHE.lock().unwrap().remove(tenant);
+    Ok(Some(feed))
+}
+
+async fn tenant_feeds(tenant: &str) -> Result<Arc<Vec<FeedRecord>>, Error> {
+    let cached = FEED_CACHE.lock().unwrap().get(tenant).cloned();
+    if let Some((loaded_at, feeds)) = cached {
+        if loaded_at.elapsed() < FEED_CACHE_TTL {
+            return Ok(feeds);
+        }
+    }
+    let feeds = Arc::new(list_feeds(tenant).await?);
+    FEED_CACHE.lock().unwrap().insert(tenant.to_string(), (Instant::now(), feeds.clone()));
+    Ok(feeds)
+}
+
+async fn match_feeds(item: IndexedItem) -> Result<(), Error> {
+    let feeds = tenant_feeds(&item.tenant).await?;
+    for feed in feeds.iter().filter(|feed| matches_filters(&feed.filters, &item.tags)) {
+        webhooks::notify(
+            "feed_item",
+            json!({
+                "tenant": item.tenant,
+                "feed": feed.name,
+                "dataitem_id": item.dataitem_id,
+                "content_type": item.content_type,
+            }),
+        );
+        let event = FeedItemEvent {
+            feed: feed.name.clone(),
+            dataitem_id: item.dataitem_id.clone(),
+            content_type: item.content_type.clone(),
+            created_at: Utc::now().to_rfc3339(),
+            tags: item
+                .tags
+                .iter()
+                .map(|(key, value)| UploadTag { key: key.clone(), value: value.clone() })
+                .collect(),
+        };
+        // no subscriber is not an error
+        let _ = EVENTS.send((item.tenant.clone(), Arc::new(event)));
+        metrics::increment("feed_items_total");
+    }
+    Ok(())
+}
+
+/// Notify the tenant's feeds a dataitem the agent just indexed matches, in the background: a
+/// `feed_item` webhook and an event to the feed's subscribers each. Failures are logged, never
+/// failing the upload.
+pub(crate) fn publish(
+    tenant: &str,
+    dataitem_id: &str,
+    content_type: &str,
+    tags: &[(String, String)],
+) {
+    if !indexing_enabled() {
+        return;
+    }
+    let item = IndexedItem {
+        tenant: tenant.to_string(),
+        dataitem_id: dataitem_id.to_string(),
+        content_type: content_type.to_string(),
+        tags: normalize_tags(tags),
+    };
+    tokio::spawn(async move {
+        let dataitem_id = item.dataitem_id.clone();
+        if let Err(err) = match_feeds(item).await {
+            println!("FEEDS: failed to match {dataitem_id} against the feeds: {err}");
+        }
+    });
+}
+
+/// The dataitems the agent indexes from now on matching the tenant's feed `name`. A subscriber
+/// falling more than 1024 events behind misses the oldest ones.
+pub(crate) fn subscribe(tenant: &str, name: &str) -> impl Stream<Item = FeedItemEvent> + use<> {
+    let (tenant, name) = (tenant.to_string(), name.to_string());
+    stream::unfold(EVENTS.subscribe(), |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => return Some((event, receiver)),
+                Err(RecvError::Lagged(missed)) => {
+                    metrics::add("feed_events_dropped_total", missed as f64);
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    })
+    .filter_map(move |(event_tenant, event)| {
+        let matched = event_tenant == tenant && event.feed == name;
+        async move { matched.then(|| event.as_ref().clone()) }
+    })
+}
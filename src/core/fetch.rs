@@ -0,0 +1,235 @@
+use crate::core::{progress, resilience::with_timeout, utils::get_env_var};
+use anyhow::{Error, anyhow};
+use reqwest::{Client, StatusCode, Url, header, redirect::Policy};
+use std::{
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60;
+const MAX_REDIRECTS: usize = 5;
+
+/// A downloaded source of `POST /upload/from-url`.
+pub(crate) struct FetchedObject {
+    pub data: Vec<u8>,
+    pub content_type: Option<String>,
+}
+
+/// A source URL refused before any request is sent to it.
+#[derive(Debug)]
+pub(crate) struct UrlNotAllowed(pub String);
+
+impl std::fmt::Display for UrlNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "source URL not allowed: {}", self.0)
+    }
+}
+
+impl std::error::Error for UrlNotAllowed {}
+
+/// A source larger than the upload size limit.
+#[derive(Debug)]
+pub(crate) struct SourceTooLarge(pub usize);
+
+impl std::fmt::Display for SourceTooLarge {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "source exceeds the size limit of {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for SourceTooLarge {}
+
+/// Addresses routable on the public internet, so a source URL can't reach the agent's own
+/// network: loopback, private, link-local (cloud metadata), CGNAT and reserved ranges are not.
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (b == 18 || b == 19))
+        || a >= 240)
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let [first, second, ..] = ip.segments();
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || ip.is_unique_local()
+        || ip.is_unicast_link_local()
+        // documentation and NAT64, the latter embedding IPv4 addresses of any range
+        || (first == 0x2001 && second == 0xdb8)
+        || (first == 0x64 && second == 0xff9b))
+}
+
+/// `UPLOAD_URL_ALLOWLIST`, comma separated hosts or `*.domain` patterns sources must be on.
+/// Unset, any host with public addresses is allowed.
+fn is_allowed_host(host: &str) -> bool {
+    match get_env_var("UPLOAD_URL_ALLOWLIST") {
+        Ok(allowlist) => is_listed_host(host, &allowlist),
+        Err(_) => true,
+    }
+}
+
+fn is_listed_host(host: &str, allowlist: &str) -> bool {
+    let host = host.to_lowercase();
+    let mut entries =
+        allowlist.split(',').map(|e| e.trim().to_lowercase()).filter(|e| !e.is_empty());
+    entries.any(|entry| match entry.strip_prefix("*.") {
+        Some(domain) => host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.')),
+        None => host == entry,
+    })
+}
+
+/// Check `url` and resolve its host, every address it resolves to must be public. The
+/// returned address is the one connected to, so the host can't be re-resolved elsewhere.
+async fn resolve_allowed(url: &Url) -> Result<SocketAddr, Error> {
+    let not_allowed = |reason: String| Error::from(UrlNotAllowed(reason));
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(not_allowed(format!("unsupported scheme {}", url.scheme())));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err(not_allowed("credentials in the URL".to_string()));
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+    let host = url.host_str().ok_or_else(|| not_allowed("no host".to_string()))?;
+    // IPv6 literals keep their brackets in the URL
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    // checked before resolving, so hosts outside the allowlist aren't even looked up
+    if !is_allowed_host(host) {
+        return Err(not_allowed(format!("{host} is not in UPLOAD_URL_ALLOWLIST")));
+    }
+    let addrs: Vec<SocketAddr> = match host.parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|err| not_allowed(format!("{host} does not resolve: {err}")))?
+            .collect(),
+    };
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(not_allowed(format!(
+            "{host} resolves to the non-public address {}",
+            addr.ip()
+        )));
+    }
+    addrs.into_iter().next().ok_or_else(|| not_allowed(format!("{host} has no address")))
+}
+
+/// Download `url` for an upload of at most `max_bytes`. Each hop of up to 5 redirects is checked
+/// like the URL itself, the whole download is bounded by `UPLOAD_URL_TIMEOUT_SECS` (default
+/// 60).
+pub(crate) async fn fetch_url(url: Url, max_bytes: usize) -> Result<FetchedObject, Error> {
+    let mut url = url;
+    with_timeout("upload_url", DEFAULT_TIMEOUT_SECS, async move {
+        for _ in 0..=MAX_REDIRECTS {
+            let addr = resolve_allowed(&url).await?;
+            let host = url.host_str().unwrap_or_default();
+            // pinned to the checked address, without proxies that would resolve it again
+            let client = Client::builder()
+                .redirect(Policy::none())
+                .no_proxy()
+                .resolve(host, addr)
+                .connect_timeout(Duration::from_secs(10))
+                .build()?;
+            let mut response = client.get(url.clone()).send().await?;
+
+            if response.status().is_redirection() {
+                let location = response
+                    .headers()
+                    .get(header::LOCATION)
+                    .and_then(|h| h.to_str().ok())
+                    .ok_or_else(|| anyhow!("source redirected without a Location"))?;
+                url = url.join(location)?;
+                continue;
+            }
+            if response.status() != StatusCode::OK {
+                return Err(anyhow!("source answered {}", response.status()));
+            }
+            if response.content_length().is_some_and(|len| len > max_bytes as u64) {
+                return Err(SourceTooLarge(max_bytes).into());
+            }
+            let content_type = response
+                .headers()
+                .get(header::CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .map(String::from);
+
+            let mut data = Vec::new();
+            while let Some(chunk) = response.chunk().await? {
+                if data.len() + chunk.len() > max_bytes {
+                    return Err(SourceTooLarge(max_bytes).into());
+                }
+                progress::add_received(chunk.len());
+                data.extend_from_slice(&chunk);
+            }
+            return Ok(FetchedObject { data, content_type });
+        }
+        Err(anyhow!("source redirected more than {MAX_REDIRECTS} times"))
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn public_ipv4() {
+        for ip in ["10.0.0.1", "172.16.5.4", "192.168.1.1", "127.0.0.1", "169.254.169.254"] {
+            assert!(!is_public_ipv4(ip.parse().unwrap()), "{ip}");
+        }
+        // CGNAT is 100.64.0.0/10 only
+        assert!(!is_public_ipv4("100.64.0.1".parse().unwrap()));
+        assert!(!is_public_ipv4("100.127.255.255".parse().unwrap()));
+        assert!(is_public_ipv4("100.128.0.1".parse().unwrap()));
+        assert!(is_public_ipv4("8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn public_ipv6() {
+        for ip in ["::1", "::", "fd00::1", "fe80::1", "2001:db8::1"] {
+            assert!(!is_public_ipv6(ip.parse().unwrap()), "{ip}");
+        }
+        // NAT64 embeds an IPv4 address, here 10.0.0.1
+        assert!(!is_public_ipv6("64:ff9b::a00:1".parse().unwrap()));
+        assert!(is_public_ipv6("2606:4700::1111".parse().unwrap()));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_are_checked_as_ipv4() {
+        assert!(!is_public_ip("::ffff:10.0.0.1".parse().unwrap()));
+        assert!(!is_public_ip("::ffff:127.0.0.1".parse().unwrap()));
+        assert!(is_public_ip("::ffff:8.8.8.8".parse().unwrap()));
+    }
+
+    #[test]
+    fn listed_hosts() {
+        let allowlist = "cdn.example.com, *.example.org";
+        assert!(is_listed_host("cdn.example.com", allowlist));
+        assert!(is_listed_host("CDN.Example.com", allowlist));
+        assert!(!is_listed_host("other.example.com", allowlist));
+        assert!(is_listed_host("a.example.org", allowlist));
+        assert!(is_listed_host("a.b.example.org", allowlist));
+        // the wildcard needs a subdomain and a label boundary
+        assert!(!is_listed_host("example.org", allowlist));
+        assert!(!is_listed_host("evilexample.org", allowlist));
+        assert!(!is_listed_host("cdn.example.com", ""));
+    }
+}
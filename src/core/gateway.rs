@@ -0,0 +1,78 @@
+use crate::core::{
+    http::{http_client, send_with_retry},
+    metadata::is_posted_to_arweave,
+    resilience::with_timeout,
+    storage::{ObjectBody, StorageBackend},
+    tenant::Tenant,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+
+pub(crate) const DEFAULT_ARWEAVE_GATEWAY_URL: &str = "https://arweave.net";
+
+/// Gateway serving dataitems that were posted to Arweave and later evicted from S3.
+#[derive(Debug, Clone)]
+pub(crate) struct GatewayFallback {
+    pub url: String,
+    /// write fetched bodies back to S3 (`ARWEAVE_GATEWAY_REHYDRATE=true`)
+    pub rehydrate: bool,
+}
+
+/// Arweave gateway serving posted dataitems, `ARWEAVE_GATEWAY_URL` or arweave.net.
+pub(crate) fn arweave_gateway_url() -> String {
+    get_env_var("ARWEAVE_GATEWAY_URL")
+        .unwrap_or_else(|_| DEFAULT_ARWEAVE_GATEWAY_URL.to_string())
+        .trim_end_matches('/')
+        .to_string()
+}
+
+impl GatewayFallback {
+    /// Enabled unless `ARWEAVE_GATEWAY_FALLBACK=false`, gateway set by `ARWEAVE_GATEWAY_URL`.
+    pub fn load() -> Option<Self> {
+        if get_env_var("ARWEAVE_GATEWAY_FALLBACK").map(|v| v == "false").unwrap_or(false) {
+            return None;
+        }
+        let rehydrate =
+            get_env_var("ARWEAVE_GATEWAY_REHYDRATE").map(|v| v == "true").unwrap_or(false);
+        Some(GatewayFallback { url: arweave_gateway_url(), rehydrate })
+    }
+
+    pub fn dataitem_url(&self, dataitem_id: &str) -> String {
+        format!("{}/{dataitem_id}", self.url)
+    }
+
+    /// Whether the raw body at `key` is gone from storage but the dataitem was posted to Arweave.
+    /// Lookup failures count as "no", so the caller's original error surfaces instead.
+    pub async fn applies(
+        &self,
+        storage: &dyn StorageBackend,
+        bucket: &str,
+        key: &str,
+        dataitem_id: &str,
+        tenant: &Tenant,
+    ) -> bool {
+        let missing = matches!(storage.exists(bucket, key).await, Ok(false));
+        missing && is_posted_to_arweave(&tenant.name, dataitem_id).await.unwrap_or(false)
+    }
+
+    pub async fn fetch(&self, dataitem_id: &str) -> Result<ObjectBody, Error> {
+        with_timeout("arweave_gateway", 30, async {
+            let request = http_client()?.get(self.dataitem_url(dataitem_id));
+            let response = send_with_retry("arweave_gateway", request).await?;
+            if !response.status().is_success() {
+                return Err(anyhow!(
+                    "gateway returned {} for dataitem {dataitem_id}",
+                    response.status()
+                ));
+            }
+            let content_type = response
+                .headers()
+                .get(reqwest::header::CONTENT_TYPE)
+                .and_then(|h| h.to_str().ok())
+                .map(|ct| ct.to_string());
+            let data = response.bytes().await?.to_vec();
+            Ok(ObjectBody { data, content_type, content_encoding: None })
+        })
+        .await
+    }
+}
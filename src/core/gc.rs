@@ -0,0 +1,131 @@
+use crate::core::{
+    ans104::reconstruct_dataitem_data,
+    models::GcReport,
+    raw_compression::put_raw,
+    s3::{AgentConfig, list_all_objects},
+    staging::purge_expired_stages,
+    storage::{StorageBackend, storage_backend},
+    tenant::{Tenant, all_tenants},
+    trash::purge_expired,
+    utils::get_env_var,
+};
+use anyhow::Error;
+use std::collections::HashSet;
+
+const ANS104_SUFFIX: &str = ".ans104";
+
+/// Reconcile a tenant's `.ans104` and raw prefixes. Raw bodies whose signed dataitem is gone
+/// can't be re-signed and are deleted, dataitems missing their raw body get it re-extracted,
+/// soft-deleted ones past their retention are purged and expired staged uploads removed. With
+/// `dry_run` the report only lists what would be done.
+pub(crate) async fn collect_garbage(tenant: &Tenant, dry_run: bool) -> Result<GcReport, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    let bucket = &agent_config.s3_bucket_name;
+    let dataitems_prefix = format!("{}/", agent_config.s3_dir_name);
+    let raw_prefix = format!("{}/", agent_config.s3_raw_dir_name);
+
+    // both prefixes may be the same directory, the suffix tells the objects apart
+    let dataitem_ids: HashSet<String> =
+        list_all_objects(storage.as_ref(), bucket, &dataitems_prefix)
+            .await?
+            .into_iter()
+            .filter_map(|obj| {
+                obj.key
+                    .strip_prefix(&dataitems_prefix)?
+                    .strip_suffix(ANS104_SUFFIX)
+                    .map(String::from)
+            })
+            .collect();
+    let raw_ids: HashSet<String> = list_all_objects(storage.as_ref(), bucket, &raw_prefix)
+        .await?
+        .into_iter()
+        .filter(|obj| !obj.key.ends_with(ANS104_SUFFIX))
+        .filter_map(|obj| obj.key.strip_prefix(&raw_prefix).map(String::from))
+        .collect();
+
+    let mut report = GcReport {
+        tenant: tenant.name.clone(),
+        dry_run,
+        dataitems_scanned: dataitem_ids.len(),
+        raw_objects_scanned: raw_ids.len(),
+        orphaned_raw: raw_ids.difference(&dataitem_ids).cloned().collect(),
+        missing_raw: dataitem_ids.difference(&raw_ids).cloned().collect(),
+        ..Default::default()
+    };
+    report.orphaned_raw.sort();
+    report.missing_raw.sort();
+    if !dry_run {
+        for dataitem_id in &report.orphaned_raw {
+            let key = format!("{raw_prefix}{dataitem_id}");
+            match storage.delete(bucket, &key).await {
+                Ok(()) => report.deleted += 1,
+                Err(err) => report.errors.push(format!("delete {key}: {err}")),
+            }
+        }
+        for dataitem_id in &report.missing_raw {
+            match repair_raw(storage.as_ref(), &agent_config, dataitem_id).await {
+                Ok(()) => report.repaired += 1,
+                Err(err) => report.errors.push(format!("repair {dataitem_id}: {err}")),
+            }
+        }
+    }
+    // last, the listings above still include the objects being purged
+    match purge_expired(storage.as_ref(), tenant, dry_run, &mut report.errors).await {
+        Ok(purged) => report.purged = purged,
+        Err(err) => report.errors.push(format!("list expired deletions: {err}")),
+    }
+    match purge_expired_stages(storage.as_ref(), tenant, dry_run, &mut report.errors).await {
+        Ok(expired) => report.expired_stages = expired,
+        Err(err) => report.errors.push(format!("list staged uploads: {err}")),
+    }
+
+    Ok(report)
+}
+
+async fn repair_raw(
+    storage: &dyn StorageBackend,
+    agent_config: &AgentConfig,
+    dataitem_id: &str,
+) -> Result<(), Error> {
+    let bucket = &agent_config.s3_bucket_name;
+    let key_dataitem = format!("{}/{dataitem_id}{ANS104_SUFFIX}", agent_config.s3_dir_name);
+    let key_raw = format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name);
+
+    let (dataitem, content_type) =
+        reconstruct_dataitem_data(storage.get(bucket, &key_dataitem).await?)?;
+    put_raw(storage, bucket, &key_raw, dataitem.data, &content_type).await
+}
+
+/// Run the GC over every tenant, `GC_DRY_RUN=true` only logs the reports.
+pub(crate) async fn collect_garbage_all() -> Result<String, Error> {
+    let dry_run = get_env_var("GC_DRY_RUN").map(|v| v == "true").unwrap_or(false);
+    let (mut deleted, mut repaired, mut purged, mut failed) = (0, 0, 0, 0);
+    for tenant in all_tenants()? {
+        match collect_garbage(&tenant, dry_run).await {
+            Ok(report) => {
+                println!(
+                    "GC tenant={:?} dry_run={dry_run} orphaned_raw={} missing_raw={} purged={} expired_stages={} deleted={} repaired={} errors={}",
+                    report.tenant,
+                    report.orphaned_raw.len(),
+                    report.missing_raw.len(),
+                    report.purged.len(),
+                    report.expired_stages.len(),
+                    report.deleted,
+                    report.repaired,
+                    report.errors.len()
+                );
+                deleted += report.deleted;
+                repaired += report.repaired;
+                if !dry_run {
+                    purged += report.purged.len();
+                }
+            }
+            Err(err) => {
+                println!("GC tenant={:?} failed: {err}", tenant.name);
+                failed += 1;
+            }
+        }
+    }
+    Ok(format!("deleted={deleted} repaired={repaired} purged={purged} failed_tenants={failed}"))
+}
@@ -0,0 +1,90 @@
+use crate::core::{
+    metadata::{DataitemHits, indexing_enabled, record_dataitem_hits},
+    utils::get_env_var,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+const DEFAULT_FLUSH_SECS: u64 = 10;
+
+/// Serves of a dataitem on this agent since the last flush.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct PendingHits {
+    pub hits: u64,
+    pub last_access: DateTime<Utc>,
+}
+
+// keyed by (tenant, dataitem id)
+static PENDING: Lazy<Mutex<HashMap<(String, String), PendingHits>>> = Lazy::new(Default::default);
+
+/// Count a serve of `dataitem_id`, flushed to ClickHouse with the next batch.
+pub(crate) fn record_hit(tenant: &str, dataitem_id: &str) {
+    if !indexing_enabled() {
+        return;
+    }
+    let now = Utc::now();
+    let mut pending = PENDING.lock().unwrap();
+    let entry = pending
+        .entry((tenant.to_string(), dataitem_id.to_string()))
+        .or_insert(PendingHits { hits: 0, last_access: now });
+    entry.hits += 1;
+    entry.last_access = now;
+}
+
+/// Hits of `dataitem_id` on this agent not flushed yet.
+pub(crate) fn pending_hits(tenant: &str, dataitem_id: &str) -> Option<PendingHits> {
+    PENDING.lock().unwrap().get(&(tenant.to_string(), dataitem_id.to_string())).copied()
+}
+
+fn take_pending() -> Vec<DataitemHits> {
+    let pending = std::mem::take(&mut *PENDING.lock().unwrap());
+    pending
+        .into_iter()
+        .map(|((tenant, dataitem_id), pending)| DataitemHits {
+            tenant,
+            dataitem_id,
+            hits: pending.hits,
+            last_access: pending.last_access,
+        })
+        .collect()
+}
+
+// a failed flush is retried with the next one instead of losing its hits
+fn restore_pending(batch: Vec<DataitemHits>) {
+    let mut pending = PENDING.lock().unwrap();
+    for entry in batch {
+        let merged = pending
+            .entry((entry.tenant, entry.dataitem_id))
+            .or_insert(PendingHits { hits: 0, last_access: entry.last_access });
+        merged.hits += entry.hits;
+        merged.last_access = merged.last_access.max(entry.last_access);
+    }
+}
+
+/// Flush the buffered hits every `HIT_STATS_FLUSH_SECS` (default 10), unless indexing is
+/// disabled.
+pub(crate) fn spawn_hit_flusher() {
+    if !indexing_enabled() {
+        return;
+    }
+    let secs = get_env_var("HIT_STATS_FLUSH_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_FLUSH_SECS);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(secs));
+        loop {
+            interval.tick().await;
+            let batch = take_pending();
+            if batch.is_empty() {
+                continue;
+            }
+            if let Err(err) = record_dataitem_hits(&batch).await {
+                println!("HIT STATS: flush of {} dataitems failed: {err}", batch.len());
+                restore_pending(batch);
+            }
+        }
+    });
+}
@@ -0,0 +1,334 @@
+use crate::core::{
+    extract::is_arweave_id,
+    jobs::{self, HLS_REMUX_JOB},
+    metadata::get_job,
+    progress::new_job_id,
+    s3::{AgentConfig, get_dataitem_raw},
+    storage::storage_backend,
+    tenant::{Tenant, all_tenants},
+    utils::get_env_var,
+};
+use anyhow::{Context, Error, anyhow};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{path::Path, time::Duration};
+use tokio::process::Command;
+
+pub(crate) const PLAYLIST_CONTENT_TYPE: &str = "application/vnd.apple.mpegurl";
+pub(crate) const SEGMENT_CONTENT_TYPE: &str = "video/mp2t";
+const DEFAULT_HLS_DIR: &str = "hls";
+const DEFAULT_SEGMENT_SECS: u64 = 6;
+const DEFAULT_REMUX_TIMEOUT_SECS: u64 = 300;
+const PLAYLIST_NAME: &str = "index.m3u8";
+const STATUS_NAME: &str = "status.json";
+const SEGMENT_PREFIX: &str = "seg_";
+const SEGMENT_SUFFIX: &str = ".ts";
+// ffmpeg's stderr kept in a failed remux status
+const MAX_ERROR_LEN: usize = 1024;
+// demuxers of the video containers remuxed. Playlist demuxers (hls, concat, ...) would open the
+// URLs and paths an uploaded source lists, so they aren't allowed, nor any protocol but the
+// local source file.
+const INPUT_FORMATS: &str = "mov,mp4,m4a,3gp,3g2,mj2,matroska,webm,mpegts";
+const INPUT_PROTOCOLS: &str = "file";
+
+const STATE_QUEUED: &str = "queued";
+const STATE_FAILED: &str = "failed";
+const STATE_UNSUPPORTED: &str = "unsupported";
+
+/// Remux progress of a dataitem, next to its segments until the playlist is written.
+#[derive(Serialize, Deserialize)]
+struct RemuxStatus {
+    state: String,
+    job_id: String,
+    #[serde(default)]
+    error: String,
+    updated_at: DateTime<Utc>,
+}
+
+/// The HLS playlist of a video dataitem, or the job still generating it.
+pub(crate) enum HlsPlaylist {
+    Ready(Vec<u8>),
+    Pending { job_id: String },
+}
+
+/// A dataitem whose content type is not a video.
+#[derive(Debug)]
+pub(crate) struct NotAVideo(pub String);
+
+impl std::fmt::Display for NotAVideo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for NotAVideo {}
+
+/// A video ffmpeg could not remux into HLS segments.
+#[derive(Debug)]
+pub(crate) struct RemuxFailed(pub String);
+
+impl std::fmt::Display for RemuxFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HLS remux failed: {}", self.0)
+    }
+}
+
+impl std::error::Error for RemuxFailed {}
+
+/// A segment name that the remux didn't produce.
+#[derive(Debug)]
+pub(crate) struct SegmentNotFound(pub String);
+
+impl std::fmt::Display for SegmentNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "HLS segment {} not found", self.0)
+    }
+}
+
+impl std::error::Error for SegmentNotFound {}
+
+/// Playlists and segments are kept under `HLS_DIR_NAME` (default `hls`) of the tenant's bucket.
+fn hls_dir() -> String {
+    get_env_var("HLS_DIR_NAME")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_HLS_DIR.to_string())
+}
+
+fn key(dataitem_id: &str, name: &str) -> String {
+    format!("{}/{dataitem_id}/{name}", hls_dir())
+}
+
+fn env_secs(key: &str, default: u64) -> u64 {
+    get_env_var(key).ok().and_then(|v| v.parse().ok()).filter(|secs| *secs > 0).unwrap_or(default)
+}
+
+// segments are named by ffmpeg's `seg_%05d.ts` pattern, anything else would address other objects
+pub(crate) fn is_segment_name(name: &str) -> bool {
+    name.strip_prefix(SEGMENT_PREFIX)
+        .and_then(|name| name.strip_suffix(SEGMENT_SUFFIX))
+        .is_some_and(|index| !index.is_empty() && index.bytes().all(|b| b.is_ascii_digit()))
+}
+
+async fn read_status(tenant: &Tenant, dataitem_id: &str) -> Result<Option<RemuxStatus>, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    let key = key(dataitem_id, STATUS_NAME);
+    if !storage.exists(bucket, &key).await? {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::from_slice(&storage.get(bucket, &key).await?)?))
+}
+
+async fn save_status(
+    tenant: &Tenant,
+    dataitem_id: &str,
+    state: &str,
+    job_id: &str,
+    error: String,
+) -> Result<(), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let status = RemuxStatus {
+        state: state.to_string(),
+        job_id: job_id.to_string(),
+        error,
+        updated_at: Utc::now(),
+    };
+    storage_backend()
+        .await?
+        .put(
+            &agent_config.s3_bucket_name,
+            &key(dataitem_id, STATUS_NAME),
+            serde_json::to_vec(&status)?,
+            "application/json",
+            None,
+        )
+        .await
+}
+
+/// The dataitem's HLS playlist, queueing a remux job the first time it is asked for. Fails with
+/// `NotAVideo` or `RemuxFailed` once the job gave up on the dataitem.
+pub(crate) async fn playlist(tenant: &Tenant, dataitem_id: &str) -> Result<HlsPlaylist, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    let playlist_key = key(dataitem_id, PLAYLIST_NAME);
+    if storage.exists(bucket, &playlist_key).await? {
+        return Ok(HlsPlaylist::Ready(storage.get(bucket, &playlist_key).await?));
+    }
+
+    if let Some(status) = read_status(tenant, dataitem_id).await? {
+        match status.state.as_str() {
+            STATE_UNSUPPORTED => return Err(NotAVideo(status.error).into()),
+            STATE_FAILED => return Err(RemuxFailed(status.error).into()),
+            _ => {}
+        }
+        match get_job(&status.job_id).await? {
+            Some(job) if job.state == "dead" => return Err(RemuxFailed(job.last_error).into()),
+            Some(_) => return Ok(HlsPlaylist::Pending { job_id: status.job_id }),
+            // lost queue entry, e.g. a ClickHouse reset, queue it again
+            None => {}
+        }
+    }
+
+    let payload = json!({ "dataitem_id": dataitem_id });
+    let job_id = jobs::enqueue(HLS_REMUX_JOB, &tenant.name, payload).await?;
+    save_status(tenant, dataitem_id, STATE_QUEUED, &job_id, String::new()).await?;
+    Ok(HlsPlaylist::Pending { job_id })
+}
+
+/// A segment of the dataitem's HLS playlist, failing with `SegmentNotFound`.
+pub(crate) async fn segment(
+    tenant: &Tenant,
+    dataitem_id: &str,
+    name: &str,
+) -> Result<Vec<u8>, Error> {
+    if !is_segment_name(name) {
+        return Err(SegmentNotFound(name.to_string()).into());
+    }
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    let key = key(dataitem_id, name);
+    if !storage.exists(bucket, &key).await? {
+        return Err(SegmentNotFound(name.to_string()).into());
+    }
+    storage.get(bucket, &key).await
+}
+
+/// Remux a stored video into HLS segments with ffmpeg, payload `{"dataitem_id": "..."}`.
+/// Videos ffmpeg can't remux are recorded as failed instead of retried.
+pub(crate) async fn run_hls_remux_job(
+    tenant: &str,
+    payload: &serde_json::Value,
+) -> Result<(), Error> {
+    let dataitem_id = payload["dataitem_id"]
+        .as_str()
+        .filter(|id| is_arweave_id(id))
+        .ok_or_else(|| anyhow!("hls_remux job without a valid dataitem_id"))?;
+    let tenant = all_tenants()?
+        .into_iter()
+        .find(|t| t.name == tenant)
+        .ok_or_else(|| anyhow!("unknown tenant {tenant:?}"))?;
+    let status_job_id =
+        read_status(&tenant, dataitem_id).await?.map(|status| status.job_id).unwrap_or_default();
+
+    let source = get_dataitem_raw(dataitem_id, &tenant).await?;
+    let content_type = source.content_type.unwrap_or_default();
+    // content type is not stored by every backend, let ffmpeg probe those
+    if !content_type.is_empty() && !content_type.starts_with("video/") {
+        let error = format!("dataitem content type {content_type} is not a video");
+        return save_status(&tenant, dataitem_id, STATE_UNSUPPORTED, &status_job_id, error).await;
+    }
+
+    let work_dir = std::env::temp_dir().join(format!("load-s3-agent-hls-{}", new_job_id()));
+    let remuxed = remux(&work_dir, source.data).await;
+    let result = match remuxed {
+        Ok(Ok(())) => store_output(&tenant, dataitem_id, &work_dir).await,
+        // the source can't be remuxed, retrying wouldn't change that
+        Ok(Err(error)) => {
+            save_status(&tenant, dataitem_id, STATE_FAILED, &status_job_id, error).await
+        }
+        Err(err) => Err(err),
+    };
+    if let Err(err) = tokio::fs::remove_dir_all(&work_dir).await {
+        println!("HLS: failed to remove {}: {err}", work_dir.display());
+    }
+    result
+}
+
+/// Run ffmpeg on `data` in `work_dir`, the inner error being ffmpeg's own when it rejected the
+/// source.
+async fn remux(work_dir: &Path, data: Vec<u8>) -> Result<Result<(), String>, Error> {
+    tokio::fs::create_dir_all(work_dir).await?;
+    let input = work_dir.join("source");
+    tokio::fs::write(&input, data).await?;
+
+    let ffmpeg = get_env_var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+    let segment_secs = env_secs("HLS_SEGMENT_SECS", DEFAULT_SEGMENT_SECS);
+    let timeout_secs = env_secs("HLS_REMUX_TIMEOUT_SECS", DEFAULT_REMUX_TIMEOUT_SECS);
+    let mut command = Command::new(&ffmpeg);
+    command
+        .args(["-hide_banner", "-loglevel", "error", "-nostdin"])
+        .args(["-protocol_whitelist", INPUT_PROTOCOLS, "-format_whitelist", INPUT_FORMATS])
+        .arg("-i")
+        .arg(&input)
+        .args(["-c", "copy", "-f", "hls", "-hls_playlist_type", "vod"])
+        .args(["-hls_time", &segment_secs.to_string()])
+        // segments are served from `/{id}/hls/{segment}`, relative to the playlist's `/{id}/hls`
+        .args(["-hls_base_url", "hls/", "-hls_segment_filename"])
+        .arg(work_dir.join(format!("{SEGMENT_PREFIX}%05d{SEGMENT_SUFFIX}")))
+        .arg(work_dir.join(PLAYLIST_NAME))
+        .kill_on_drop(true);
+
+    let output = tokio::time::timeout(Duration::from_secs(timeout_secs), command.output())
+        .await
+        .map_err(|_| anyhow!("ffmpeg did not finish within {timeout_secs}s"))?
+        .with_context(|| format!("failed to run {ffmpeg}, set FFMPEG_PATH"))?;
+    if output.status.success() {
+        return Ok(Ok(()));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let stderr = stderr.trim();
+    let tail = stderr.char_indices().rev().nth(MAX_ERROR_LEN).map_or(0, |(at, _)| at);
+    Ok(Err(format!("ffmpeg exited with {}: {}", output.status, &stderr[tail..])))
+}
+
+/// Upload the segments, then the playlist, so a served playlist never points to a missing
+/// segment.
+async fn store_output(tenant: &Tenant, dataitem_id: &str, work_dir: &Path) -> Result<(), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+
+    let mut segments = Vec::new();
+    let mut entries = tokio::fs::read_dir(work_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if is_segment_name(&name) {
+            segments.push(name);
+        }
+    }
+    segments.sort();
+    for name in &segments {
+        let data = tokio::fs::read(work_dir.join(name)).await?;
+        storage.put(bucket, &key(dataitem_id, name), data, SEGMENT_CONTENT_TYPE, None).await?;
+    }
+    let playlist = tokio::fs::read(work_dir.join(PLAYLIST_NAME)).await?;
+    storage
+        .put(bucket, &key(dataitem_id, PLAYLIST_NAME), playlist, PLAYLIST_CONTENT_TYPE, None)
+        .await?;
+
+    if let Err(err) = storage.delete(bucket, &key(dataitem_id, STATUS_NAME)).await {
+        println!("HLS: failed to remove the remux status of {dataitem_id}: {err}");
+    }
+    println!("HLS: remuxed {dataitem_id} into {} segments", segments.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_ffmpeg_segment_names_only() {
+        for name in ["seg_00000.ts", "seg_1.ts", "seg_123456.ts"] {
+            assert!(is_segment_name(name), "{name}");
+        }
+        for name in [
+            "seg_.ts",
+            "seg_00000.mp4",
+            "seg_0a.ts",
+            "index.m3u8",
+            "status.json",
+            "seg_00000.ts/../status.json",
+            "../seg_00000.ts",
+            "seg_-1.ts",
+            "",
+        ] {
+            assert!(!is_segment_name(name), "{name}");
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use crate::core::{
+    resilience::{RetryPolicy, retry},
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use once_cell::sync::OnceCell;
+use reqwest::{Client, Proxy, RequestBuilder, Response};
+use std::time::Duration;
+
+static CLIENT: OnceCell<Client> = OnceCell::new();
+
+/// Process-wide pooled HTTP client shared by the auth, ClickHouse and gateway calls, so
+/// connections and TLS sessions are reused across requests.
+///
+/// Configured by `HTTP_CONNECT_TIMEOUT_SECS` (default 10) and `HTTP_POOL_MAX_IDLE_PER_HOST`
+/// (default 32). The usual `HTTP_PROXY`/`HTTPS_PROXY`/`NO_PROXY` variables are honored and
+/// `OUTBOUND_PROXY_URL` routes every call through the given proxy.
+pub(crate) fn http_client() -> Result<&'static Client, Error> {
+    CLIENT.get_or_try_init(|| {
+        let mut builder = Client::builder()
+            .connect_timeout(Duration::from_secs(env_or("HTTP_CONNECT_TIMEOUT_SECS", 10)))
+            .pool_max_idle_per_host(env_or("HTTP_POOL_MAX_IDLE_PER_HOST", 32) as usize)
+            .pool_idle_timeout(Duration::from_secs(90))
+            .tcp_keepalive(Duration::from_secs(60));
+        if let Ok(proxy) = get_env_var("OUTBOUND_PROXY_URL") {
+            builder = builder.proxy(Proxy::all(&proxy)?);
+        }
+        builder.build().map_err(|err| anyhow!("failed to build the HTTP client: {err}"))
+    })
+}
+
+fn env_or(key: &str, default: u64) -> u64 {
+    get_env_var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+enum AttemptError {
+    Request(reqwest::Error),
+    Status(Response),
+}
+
+fn is_transient(err: &AttemptError) -> bool {
+    match err {
+        AttemptError::Request(err) => err.is_connect() || err.is_timeout(),
+        AttemptError::Status(response) => {
+            let status = response.status().as_u16();
+            status == 429 || status >= 500
+        }
+    }
+}
+
+/// Send `request`, retrying connection failures, timeouts, 429 and 5xx responses with the
+/// `HTTP_RETRY_*` policy. The last response is returned as is once attempts run out, so
+/// callers still handle error statuses themselves. Streaming bodies are sent only once.
+pub(crate) async fn send_with_retry(
+    dependency: &str,
+    request: RequestBuilder,
+) -> Result<Response, Error> {
+    if request.try_clone().is_none() {
+        return Ok(request.send().await?);
+    }
+
+    let policy = RetryPolicy::from_env("HTTP");
+    let result = retry(&policy, dependency, "request", is_transient, || {
+        let attempt = request.try_clone().expect("request body is cloneable");
+        async move {
+            let response = attempt.send().await.map_err(AttemptError::Request)?;
+            let status = response.status().as_u16();
+            if status == 429 || status >= 500 {
+                return Err(AttemptError::Status(response));
+            }
+            Ok(response)
+        }
+    })
+    .await;
+
+    match result {
+        Ok(response) | Err(AttemptError::Status(response)) => Ok(response),
+        Err(AttemptError::Request(err)) => Err(err.into()),
+    }
+}
@@ -0,0 +1,86 @@
+use crate::core::{
+    http::{http_client, send_with_retry},
+    metrics,
+    resilience::with_timeout,
+    tenant::Tenant,
+    utils::{get_env_var, hyperbeam_node_url},
+};
+use anyhow::{Error, anyhow};
+use once_cell::sync::Lazy;
+use serde_json::{Value, json};
+use tokio::sync::Semaphore;
+
+const DEFAULT_ANNOUNCE_PATH: &str = "/~s3@1.0/announce";
+
+// bounds the announcements in flight, `HYPERBEAM_ANNOUNCE_CONCURRENCY` (default 4)
+static PERMITS: Lazy<Semaphore> = Lazy::new(|| {
+    let permits = get_env_var("HYPERBEAM_ANNOUNCE_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(4)
+        .max(1);
+    Semaphore::new(permits)
+});
+
+/// Where stored dataitems are announced, `None` unless `HYPERBEAM_ANNOUNCE=true`.
+/// `HYPERBEAM_ANNOUNCE_PATH` (default `/~s3@1.0/announce`) on `HYPERBEAM_NODE_URL`.
+fn announce_url() -> Option<String> {
+    if !get_env_var("HYPERBEAM_ANNOUNCE").is_ok_and(|v| v == "true") {
+        return None;
+    }
+    let path = get_env_var("HYPERBEAM_ANNOUNCE_PATH")
+        .unwrap_or_else(|_| DEFAULT_ANNOUNCE_PATH.to_string());
+    Some(format!("{}/{}", hyperbeam_node_url(), path.trim_start_matches('/')))
+}
+
+/// Announce a newly stored dataitem to the HyperBEAM node in the background, so its `~s3@1.0`
+/// device discovers it without polling the bucket. Failures are logged and counted in the
+/// `hyperbeam_announcements_total` metric, never failing the upload.
+pub(crate) fn announce(
+    tenant: &Tenant,
+    bucket: &str,
+    dataitem_id: &str,
+    size: usize,
+    content_type: &str,
+) {
+    let Some(url) = announce_url() else {
+        return;
+    };
+    let body = json!({
+        "id": dataitem_id,
+        "size": size,
+        "content_type": content_type,
+        "bucket": bucket,
+        "tenant": tenant.name,
+    });
+    let dataitem_id = dataitem_id.to_string();
+    tokio::spawn(async move {
+        let result = match PERMITS.acquire().await {
+            Ok(_permit) => send(&url, &body).await,
+            Err(err) => Err(err.into()),
+        };
+        let result = match result {
+            Ok(()) => "ok",
+            Err(err) => {
+                println!("HYPERBEAM ANNOUNCE FAILED: {dataitem_id}: {err}");
+                "failed"
+            }
+        };
+        metrics::increment(&format!("hyperbeam_announcements_total{{result=\"{result}\"}}"));
+    });
+}
+
+async fn send(url: &str, body: &Value) -> Result<(), Error> {
+    with_timeout("hyperbeam", 10, async {
+        let mut request = http_client()?.post(url).json(body);
+        if let Ok(token) = get_env_var("HYPERBEAM_ANNOUNCE_TOKEN") {
+            request = request.bearer_auth(token);
+        }
+        let response = send_with_retry("hyperbeam", request).await?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(anyhow!("node returned {}", response.status())),
+        }
+    })
+    .await
+}
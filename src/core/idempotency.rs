@@ -0,0 +1,166 @@
+use crate::core::{
+    metadata::{IdempotentResponse, get_idempotent_response, save_idempotent_response},
+    models::{AgentError, api_error, upstream_error},
+    provenance,
+    utils::{get_env_var, sha256_hex},
+};
+use axum::{
+    body::{Body, to_bytes},
+    http::{HeaderMap, HeaderName, StatusCode, header},
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::{collections::HashSet, future::Future, sync::Mutex};
+
+const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+const MAX_KEY_LEN: usize = 255;
+const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+// `principal/route/key` of the idempotent requests running on this agent
+static IN_FLIGHT: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+/// A request sent with an `Idempotency-Key`, scoped to its principal and route. It is in flight
+/// on this agent until dropped, a concurrent request with the same key is refused meanwhile.
+pub(crate) struct IdempotencyKey {
+    principal: String,
+    route: String,
+    key: String,
+    request_hash: String,
+}
+
+impl IdempotencyKey {
+    /// The request's `Idempotency-Key`, `None` without one. `fingerprint` tells requests to the
+    /// same route apart, so a key reused for a different one is refused instead of replayed.
+    pub(crate) fn from_headers(
+        headers: &HeaderMap,
+        route: &str,
+        fingerprint: &[&str],
+    ) -> Result<Option<Self>, AgentError> {
+        let Some(value) = headers.get("idempotency-key") else {
+            return Ok(None);
+        };
+        let key = value
+            .to_str()
+            .ok()
+            .filter(|key| !key.is_empty() && key.len() <= MAX_KEY_LEN)
+            .filter(|key| key.bytes().all(|byte| byte.is_ascii_graphic()))
+            .ok_or_else(|| {
+                api_error(
+                    StatusCode::BAD_REQUEST,
+                    format!("Idempotency-Key must be 1 to {MAX_KEY_LEN} visible ASCII characters"),
+                )
+            })?;
+
+        let idempotency = IdempotencyKey {
+            principal: provenance::current().principal,
+            route: route.to_string(),
+            key: key.to_string(),
+            request_hash: sha256_hex(fingerprint.join("\n").as_bytes()),
+        };
+        if !IN_FLIGHT.lock().unwrap().insert(idempotency.in_flight_key()) {
+            return Err(api_error(
+                StatusCode::CONFLICT,
+                "a request with this Idempotency-Key is still in progress",
+            ));
+        }
+        Ok(Some(idempotency))
+    }
+
+    /// Add `part` to the fingerprint, for requests only told apart once their body is read.
+    pub(crate) fn including(mut self, part: &str) -> Self {
+        self.request_hash = sha256_hex(format!("{}\n{part}", self.request_hash).as_bytes());
+        self
+    }
+
+    /// `saved` if it was the response to the same request, `422` otherwise.
+    pub(crate) fn replay(self, saved: IdempotentResponse) -> Result<Response, AgentError> {
+        if saved.request_hash != self.request_hash {
+            return Err(api_error(
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "Idempotency-Key was already used for a different request",
+            ));
+        }
+        Ok(replay(saved))
+    }
+
+    /// Save a successful `response` to be replayed for repeated requests.
+    pub(crate) async fn save(self, response: Response) -> Result<Response, AgentError> {
+        if !response.status().is_success() {
+            return Ok(response);
+        }
+        let (parts, body) = response.into_parts();
+        let body = to_bytes(body, usize::MAX).await.map_err(|e| {
+            api_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                format!("failed to read the response: {e}"),
+            )
+        })?;
+        let now = Utc::now();
+        let saved = IdempotentResponse {
+            principal: self.principal.clone(),
+            route: self.route.clone(),
+            idempotency_key: self.key.clone(),
+            request_hash: self.request_hash.clone(),
+            status: parts.status.as_u16(),
+            body: String::from_utf8_lossy(&body).into_owned(),
+            created_at: now,
+            expires_at: now + ttl(),
+        };
+        // the request succeeded regardless, losing the response only disables its replay
+        if let Err(err) = save_idempotent_response(&saved).await {
+            println!("IDEMPOTENCY: failed to save the response of {}: {err}", self.route);
+        }
+        Ok(Response::from_parts(parts, Body::from(body)))
+    }
+
+    fn in_flight_key(&self) -> String {
+        format!("{}/{}/{}", self.principal, self.route, self.key)
+    }
+}
+
+impl Drop for IdempotencyKey {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap().remove(&self.in_flight_key());
+    }
+}
+
+/// Saved responses are replayed for `IDEMPOTENCY_TTL_SECS` (default a day).
+fn ttl() -> chrono::Duration {
+    let secs = get_env_var("IDEMPOTENCY_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    chrono::Duration::seconds(secs.unwrap_or(DEFAULT_TTL_SECS))
+}
+
+fn replay(saved: IdempotentResponse) -> Response {
+    let status = StatusCode::from_u16(saved.status).unwrap_or(StatusCode::OK);
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/json"), (IDEMPOTENT_REPLAYED, "true")],
+        saved.body,
+    )
+        .into_response()
+}
+
+/// Run `request` at most once per idempotency key: a repeated request gets the saved response
+/// of the first successful one, marked with `Idempotent-Replayed: true`. Failed responses are
+/// not saved, so the request can be retried with the same key.
+pub(crate) async fn once(
+    key: Option<IdempotencyKey>,
+    request: impl Future<Output = Result<Response, AgentError>>,
+) -> Result<Response, AgentError> {
+    let Some(key) = key else {
+        return request.await;
+    };
+    if let Some(saved) = saved(&key).await? {
+        return key.replay(saved);
+    }
+    let response = request.await?;
+    key.save(response).await
+}
+
+/// The saved response of the first request with `key`, `None` if there is none yet.
+pub(crate) async fn saved(key: &IdempotencyKey) -> Result<Option<IdempotentResponse>, AgentError> {
+    get_idempotent_response(&key.principal, &key.route, &key.key).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check the Idempotency-Key", &e)
+    })
+}
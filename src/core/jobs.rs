@@ -0,0 +1,203 @@
+use crate::core::{
+    bundler::run_post_dataitem_job,
+    hls::run_hls_remux_job,
+    maintenance,
+    metadata::{JobRecord, due_jobs, get_job, indexing_enabled, list_jobs, save_job},
+    metrics,
+    migration::run_prefix_migration_job,
+    replication::run_replicate_object_job,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use std::time::Duration;
+use tokio::task::JoinSet;
+
+/// Post a stored dataitem to Arweave, payload `{"dataitem_id": "..."}`.
+pub(crate) const POST_DATAITEM_JOB: &str = "post_dataitem";
+/// Remux a stored video into HLS segments, payload `{"dataitem_id": "..."}`.
+pub(crate) const HLS_REMUX_JOB: &str = "hls_remux";
+/// Copy a tenant's objects from an old prefix layout to the current one, payload a
+/// `PrefixMigration` the job checkpoints its progress into.
+pub(crate) const PREFIX_MIGRATION_JOB: &str = "prefix_migration";
+/// Mirror an object the replica failed to take, payload
+/// `{"bucket": "...", "key": "...", "tagging": ...}`.
+pub(crate) const REPLICATE_OBJECT_JOB: &str = "replicate_object";
+
+const MAX_BACKOFF_SECS: i64 = 60 * 60;
+
+fn env_or(key: &str, default: u64) -> u64 {
+    get_env_var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// Run one job of `kind`. Every job kind is dispatched here, payloads are the kind's JSON
+/// arguments. Long running kinds may checkpoint their progress into the job's payload.
+async fn run(job: &mut JobRecord) -> Result<(), Error> {
+    let payload: serde_json::Value = serde_json::from_str(&job.payload)?;
+    match job.kind.as_str() {
+        POST_DATAITEM_JOB => run_post_dataitem_job(&job.tenant, &payload).await,
+        HLS_REMUX_JOB => run_hls_remux_job(&job.tenant, &payload).await,
+        PREFIX_MIGRATION_JOB => run_prefix_migration_job(job).await,
+        REPLICATE_OBJECT_JOB => run_replicate_object_job(&payload).await,
+        kind => Err(anyhow!("unknown job kind {kind}")),
+    }
+}
+
+/// Queue a job of `kind` for `tenant`, returning its ID.
+pub(crate) async fn enqueue(
+    kind: &str,
+    tenant: &str,
+    payload: serde_json::Value,
+) -> Result<String, Error> {
+    enqueue_at(kind, tenant, payload, Utc::now()).await
+}
+
+/// Queue a job of `kind` for `tenant` that isn't picked up before `run_at`, returning its ID.
+pub(crate) async fn enqueue_at(
+    kind: &str,
+    tenant: &str,
+    payload: serde_json::Value,
+    run_at: DateTime<Utc>,
+) -> Result<String, Error> {
+    let id: String = rand::random::<[u8; 16]>().iter().map(|b| format!("{b:02x}")).collect();
+    let now = Utc::now();
+    save_job(&JobRecord {
+        id: id.clone(),
+        kind: kind.to_string(),
+        tenant: tenant.to_string(),
+        payload: payload.to_string(),
+        state: "pending".to_string(),
+        attempts: 0,
+        last_error: String::new(),
+        run_at,
+        created_at: now,
+        updated_at: now,
+    })
+    .await?;
+    metrics::increment(&format!("jobs_enqueued_total{{kind=\"{kind}\"}}"));
+    Ok(id)
+}
+
+#[derive(Debug)]
+pub(crate) struct JobNotDead {
+    pub id: String,
+    pub state: String,
+}
+
+impl std::fmt::Display for JobNotDead {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job {} is {}, only dead jobs can be retried", self.id, self.state)
+    }
+}
+
+impl std::error::Error for JobNotDead {}
+
+/// Move a dead-lettered job back to the queue with a fresh attempt budget.
+pub(crate) async fn retry_job(id: &str) -> Result<Option<JobRecord>, Error> {
+    let Some(mut job) = get_job(id).await? else {
+        return Ok(None);
+    };
+    if job.state != "dead" {
+        return Err(JobNotDead { id: job.id, state: job.state }.into());
+    }
+    requeue(&mut job).await?;
+    Ok(Some(job))
+}
+
+async fn requeue(job: &mut JobRecord) -> Result<(), Error> {
+    job.state = "pending".to_string();
+    job.attempts = 0;
+    job.run_at = Utc::now();
+    job.updated_at = job.run_at;
+    save_job(job).await
+}
+
+/// Requeue up to `limit` dead-lettered Arweave posts, e.g. after a bundler outage.
+pub(crate) async fn requeue_dead_posts(limit: usize) -> Result<usize, Error> {
+    let mut dead = list_jobs(Some("dead"), Some(POST_DATAITEM_JOB), limit).await?;
+    for job in &mut dead {
+        requeue(job).await?;
+    }
+    Ok(dead.len())
+}
+
+async fn process(mut job: JobRecord, max_attempts: u32, retry_base_secs: u64) {
+    // claim it, counting the attempt upfront so a job that crashes the agent still runs out
+    job.state = "running".to_string();
+    job.attempts += 1;
+    job.updated_at = Utc::now();
+    if let Err(err) = save_job(&job).await {
+        println!("JOBS: failed to claim {} {}: {err}", job.kind, job.id);
+        return;
+    }
+
+    let result = run(&mut job).await;
+    job.updated_at = Utc::now();
+    let outcome = match result {
+        Ok(()) => {
+            job.state = "done".to_string();
+            job.last_error.clear();
+            "done"
+        }
+        Err(err) if job.attempts >= max_attempts => {
+            println!("JOBS: {} {} dead after {} attempts: {err}", job.kind, job.id, job.attempts);
+            job.state = "dead".to_string();
+            job.last_error = err.to_string();
+            "dead"
+        }
+        Err(err) => {
+            let exp = retry_base_secs.saturating_mul(1 << (job.attempts - 1).min(16));
+            let backoff = (exp as i64).min(MAX_BACKOFF_SECS);
+            job.state = "pending".to_string();
+            job.last_error = err.to_string();
+            job.run_at = job.updated_at + ChronoDuration::seconds(backoff);
+            "retry"
+        }
+    };
+    metrics::increment(&format!("jobs_total{{kind=\"{}\",result=\"{outcome}\"}}", job.kind));
+    if let Err(err) = save_job(&job).await {
+        println!("JOBS: failed to record {} {} as {}: {err}", job.kind, job.id, job.state);
+    }
+}
+
+/// Start the job workers: every `JOBS_POLL_INTERVAL_SECS` (default 5) up to `JOB_WORKERS`
+/// (default 4, 0 disables) due jobs run concurrently. Failed jobs are retried with exponential
+/// backoff from `JOB_RETRY_BASE_SECS` (default 10s, capped at an hour) and dead-lettered after
+/// `JOB_MAX_ATTEMPTS` (default 5). Running jobs not finished within `JOB_LEASE_SECS` (default
+/// 600) are picked up again.
+pub(crate) fn spawn_job_workers() {
+    let workers = env_or("JOB_WORKERS", 4) as usize;
+    // jobs are queued in ClickHouse
+    if workers == 0 || !indexing_enabled() {
+        return;
+    }
+    let poll_interval = Duration::from_secs(env_or("JOBS_POLL_INTERVAL_SECS", 5).max(1));
+    let max_attempts = env_or("JOB_MAX_ATTEMPTS", 5).max(1) as u32;
+    let retry_base_secs = env_or("JOB_RETRY_BASE_SECS", 10);
+    let lease_secs = env_or("JOB_LEASE_SECS", 600);
+
+    tokio::spawn(async move {
+        loop {
+            // queued jobs write to storage or post, they wait for the end of maintenance
+            maintenance::wait_until_off().await;
+            let jobs = match due_jobs(lease_secs, workers).await {
+                Ok(jobs) => jobs,
+                Err(err) => {
+                    println!("JOBS: {err}");
+                    tokio::time::sleep(poll_interval).await;
+                    continue;
+                }
+            };
+            let full_batch = jobs.len() == workers;
+            let mut batch = JoinSet::new();
+            for job in jobs {
+                batch.spawn(process(job, max_attempts, retry_base_secs));
+            }
+            batch.join_all().await;
+            // keep draining a backlog without waiting for the next poll
+            if !full_batch {
+                tokio::time::sleep(poll_interval).await;
+            }
+        }
+    });
+}
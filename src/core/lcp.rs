@@ -1,10 +1,164 @@
-use crate::core::s3::get_bucket_tags;
+use crate::core::{
+    metrics,
+    s3::{get_bucket_tags, get_storage_bucket_tags, is_lcp_unreachable},
+    utils::{get_env_var, sha256_hex},
+};
 use anyhow::Error;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
+const DEFAULT_OWNERSHIP_CACHE_TTL_SECS: u64 = 60;
+
+// (bucket, sha256 of the load_acc key) -> when its ownership was validated
+static OWNERSHIP_CACHE: Lazy<Mutex<HashMap<(String, String), Instant>>> =
+    Lazy::new(Default::default);
+
+/// A private upload's bucket name breaking the S3 naming rules.
+#[derive(Debug)]
+pub(crate) struct InvalidBucketName(pub String);
+
+impl std::fmt::Display for InvalidBucketName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid bucket name {:?}, expected 3 to 63 lowercase letters, digits, dots or hyphens",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidBucketName {}
+
+/// A private upload's bucket missing from `PRIVATE_BUCKET_ALLOWLIST`.
+#[derive(Debug)]
+pub(crate) struct BucketNotAllowed(pub String);
+
+impl std::fmt::Display for BucketNotAllowed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bucket {} is not allowed for private uploads on this agent", self.0)
+    }
+}
+
+impl std::error::Error for BucketNotAllowed {}
+
+/// A bucket to create whose name is already taken, by the caller or anyone else.
+#[derive(Debug)]
+pub(crate) struct BucketExists(pub String);
+
+impl std::fmt::Display for BucketExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bucket {} already exists", self.0)
+    }
+}
+
+impl std::error::Error for BucketExists {}
+
+/// S3 bucket naming rules: 3 to 63 lowercase letters, digits, dots and hyphens, starting and
+/// ending with a letter or digit, without adjacent dots, IP address forms or reserved affixes.
+fn is_valid_bucket_name(name: &str) -> bool {
+    let allowed = |b: u8| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'.' || b == b'-';
+    let edge = |b: Option<&u8>| b.is_some_and(|b| b.is_ascii_lowercase() || b.is_ascii_digit());
+    (3..=63).contains(&name.len())
+        && name.bytes().all(allowed)
+        && edge(name.as_bytes().first())
+        && edge(name.as_bytes().last())
+        && !name.contains("..")
+        && !name.contains(".-")
+        && !name.contains("-.")
+        && name.parse::<Ipv4Addr>().is_err()
+        && !name.starts_with("xn--")
+        && !name.starts_with("sthree-")
+        && !name.ends_with("-s3alias")
+        && !name.ends_with("--ol-s3")
+}
+
+/// `PRIVATE_BUCKET_ALLOWLIST`, comma separated bucket names or `prefix*` patterns the private
+/// uploads are restricted to. Unset, any bucket owned by the caller is accepted.
+fn is_allowed_bucket(name: &str) -> bool {
+    let Ok(allowlist) = get_env_var("PRIVATE_BUCKET_ALLOWLIST") else {
+        return true;
+    };
+    let mut entries = allowlist.split(',').map(str::trim).filter(|entry| !entry.is_empty());
+    entries.any(|entry| match entry.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == entry,
+    })
+}
+
+/// Reject a caller supplied private bucket before any storage or LCP call is made with it.
+pub(crate) fn check_private_bucket(bucket_name: &str) -> Result<(), Error> {
+    if !is_valid_bucket_name(bucket_name) {
+        return Err(InvalidBucketName(bucket_name.to_string()).into());
+    }
+    if !is_allowed_bucket(bucket_name) {
+        return Err(BucketNotAllowed(bucket_name.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Validated bucket owners are trusted for `BUCKET_OWNERSHIP_CACHE_TTL_SECS` (default 60, 0
+/// disables the cache).
+fn ownership_cache_ttl() -> Duration {
+    let secs = get_env_var("BUCKET_OWNERSHIP_CACHE_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(DEFAULT_OWNERSHIP_CACHE_TTL_SECS))
+}
+
+/// `BUCKET_OWNERSHIP_STRICT=false` checks ownership on the storage bucket's own `load_acc_*`
+/// tags while the LCP API is unreachable, instead of failing the private upload.
+fn strict_ownership() -> bool {
+    get_env_var("BUCKET_OWNERSHIP_STRICT").map(|v| v != "false").unwrap_or(true)
+}
+
+/// Forget the validated owners of `bucket_name`, or of every bucket, returning how many were
+/// forgotten.
+pub(crate) fn invalidate_bucket_ownership(bucket_name: Option<&str>) -> usize {
+    let mut cache = OWNERSHIP_CACHE.lock().unwrap();
+    let before = cache.len();
+    match bucket_name {
+        Some(bucket_name) => cache.retain(|(bucket, _), _| bucket != bucket_name),
+        None => cache.clear(),
+    }
+    before - cache.len()
+}
+
+/// Whether `load_acc` owns `bucket_name` per its LCP bucket tags. Only positive answers are
+/// cached, a key granted ownership is recognized right away.
 pub(crate) async fn validate_bucket_ownership(
     bucket_name: &str,
     load_acc: &str,
 ) -> Result<bool, Error> {
-    let bucket_load_tags = get_bucket_tags(bucket_name).await?;
-    Ok(bucket_load_tags.contains(&load_acc.to_string()))
+    check_private_bucket(bucket_name)?;
+    let ttl = ownership_cache_ttl();
+    let cache_key = (bucket_name.to_string(), sha256_hex(load_acc.as_bytes()));
+    let cached =
+        OWNERSHIP_CACHE.lock().unwrap().get(&cache_key).is_some_and(|at| at.elapsed() < ttl);
+    if cached {
+        return Ok(true);
+    }
+
+    let bucket_load_tags = match get_bucket_tags(bucket_name).await {
+        Ok(tags) => tags,
+        Err(err) if is_lcp_unreachable(&err) && !strict_ownership() => {
+            let Some(tags) = get_storage_bucket_tags(bucket_name).await? else {
+                return Err(err);
+            };
+            // not cached, the LCP API is asked again once it is back
+            println!("OWNERSHIP FALLBACK: {bucket_name} checked on the storage tags: {err}");
+            metrics::increment("bucket_ownership_fallback_total");
+            return Ok(tags.contains(&load_acc.to_string()));
+        }
+        Err(err) => return Err(err),
+    };
+    let owned = bucket_load_tags.contains(&load_acc.to_string());
+    if owned && !ttl.is_zero() {
+        let mut cache = OWNERSHIP_CACHE.lock().unwrap();
+        cache.retain(|_, validated_at| validated_at.elapsed() < ttl);
+        cache.insert(cache_key, Instant::now());
+    }
+    Ok(owned)
 }
@@ -0,0 +1,155 @@
+use crate::core::{
+    bundler::post_dataitem,
+    maintenance,
+    metadata::{dataitems_pending_post, mark_raw_evicted, posts_with_raw_body},
+    metrics,
+    s3::AgentConfig,
+    storage::storage_backend,
+    tenant::{Tenant, all_tenants},
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use chrono::Utc;
+use serde::Deserialize;
+use std::time::Duration;
+
+const DEFAULT_LIFECYCLE_INTERVAL_SECS: u64 = 3600;
+const DEFAULT_LIFECYCLE_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum LifecycleAction {
+    /// post dataitems to Arweave `days` after they were stored
+    Post,
+    /// delete the raw body from S3 `days` after a successful Arweave post, keeping the `.ans104`
+    EvictRaw,
+}
+
+impl LifecycleAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            LifecycleAction::Post => "post",
+            LifecycleAction::EvictRaw => "evict_raw",
+        }
+    }
+}
+
+/// A rule of `LIFECYCLE_RULES`, e.g.
+/// `[{"action":"post","days":1},{"action":"evict_raw","days":30,"tenant":"app1"}]`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct LifecycleRule {
+    pub action: LifecycleAction,
+    pub days: u64,
+    /// restrict the rule to a tenant, all tenants when unset (`""` is the default tenant)
+    #[serde(default)]
+    pub tenant: Option<String>,
+}
+
+pub(crate) fn load_rules() -> Result<Vec<LifecycleRule>, Error> {
+    match get_env_var("LIFECYCLE_RULES") {
+        Ok(raw) if !raw.trim().is_empty() => {
+            serde_json::from_str(&raw).map_err(|err| anyhow!("invalid LIFECYCLE_RULES: {err}"))
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Apply a rule to a tenant, handling at most `batch_size` dataitems. Returns how many were.
+async fn apply_rule(
+    rule: &LifecycleRule,
+    tenant: &Tenant,
+    batch_size: usize,
+) -> Result<usize, Error> {
+    let cutoff = Utc::now() - chrono::Duration::days(rule.days as i64);
+    let mut applied = 0;
+
+    match rule.action {
+        LifecycleAction::Post => {
+            for dataitem_id in dataitems_pending_post(&tenant.name, cutoff, batch_size).await? {
+                match post_dataitem(dataitem_id.clone(), tenant).await {
+                    Ok(_) => applied += 1,
+                    Err(err) => println!("LIFECYCLE post {dataitem_id} failed: {err}"),
+                }
+            }
+        }
+        LifecycleAction::EvictRaw => {
+            let agent_config = AgentConfig::for_tenant(tenant);
+            let storage = storage_backend().await?;
+            for post in posts_with_raw_body(&tenant.name, cutoff, batch_size).await? {
+                let key_raw = format!("{}/{}", agent_config.s3_raw_dir_name, post.dataitem_id);
+                if let Err(err) = storage.delete(&agent_config.s3_bucket_name, &key_raw).await {
+                    println!("LIFECYCLE evict {} failed: {err}", post.dataitem_id);
+                    continue;
+                }
+                mark_raw_evicted(&tenant.name, &post).await?;
+                applied += 1;
+            }
+        }
+    }
+
+    metrics::add(
+        &format!("lifecycle_actions_total{{action=\"{}\"}}", rule.action.as_str()),
+        applied as f64,
+    );
+    Ok(applied)
+}
+
+/// Run `LIFECYCLE_RULES` every `LIFECYCLE_INTERVAL_SECS` (default 1h), `LIFECYCLE_BATCH_SIZE`
+/// dataitems per rule and tenant at a time.
+pub(crate) fn spawn_lifecycle_task() {
+    let rules = match load_rules() {
+        Ok(rules) if !rules.is_empty() => rules,
+        Ok(_) => return,
+        Err(err) => {
+            println!("LIFECYCLE disabled: {err}");
+            return;
+        }
+    };
+    let interval = get_env_var("LIFECYCLE_INTERVAL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .filter(|secs| *secs > 0)
+        .unwrap_or(DEFAULT_LIFECYCLE_INTERVAL_SECS);
+    let batch_size = get_env_var("LIFECYCLE_BATCH_SIZE")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_LIFECYCLE_BATCH_SIZE);
+
+    tokio::spawn(async move {
+        let period = Duration::from_secs(interval);
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        loop {
+            ticker.tick().await;
+            if maintenance::enabled() {
+                println!("LIFECYCLE: skipped, the agent is in maintenance");
+                continue;
+            }
+            let tenants = match all_tenants() {
+                Ok(tenants) => tenants,
+                Err(err) => {
+                    println!("LIFECYCLE: {err}");
+                    continue;
+                }
+            };
+            for rule in &rules {
+                let matching = tenants
+                    .iter()
+                    .filter(|tenant| rule.tenant.as_deref().is_none_or(|name| name == tenant.name));
+                for tenant in matching {
+                    match apply_rule(rule, tenant, batch_size).await {
+                        Ok(applied) => println!(
+                            "LIFECYCLE {} tenant={:?}: {applied} dataitems",
+                            rule.action.as_str(),
+                            tenant.name
+                        ),
+                        Err(err) => println!(
+                            "LIFECYCLE {} tenant={:?} failed: {err}",
+                            rule.action.as_str(),
+                            tenant.name
+                        ),
+                    }
+                }
+            }
+        }
+    });
+}
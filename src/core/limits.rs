@@ -0,0 +1,51 @@
+use crate::core::utils::{OBJECT_SIZE_LIMIT, get_env_var};
+use anyhow::{Error, anyhow};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+/// Request body limit applied to the API keys of a tier.
+///
+/// Tiers are configured through the `SIZE_LIMIT_TIERS` env var as a JSON array, e.g.
+/// `[{"name":"free","max_bytes":10485760,"api_keys":["key1"]}]`.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct SizeLimitTier {
+    pub name: String,
+    pub max_bytes: usize,
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+}
+
+/// Per route (`ROUTE_SIZE_LIMITS`, e.g. `{"/upload/private":52428800}` keyed by the route
+/// pattern) and per key tier body limits. Neither can raise the global `OBJECT_SIZE_LIMIT`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BodyLimits {
+    pub routes: BTreeMap<String, usize>,
+    pub tiers: Vec<SizeLimitTier>,
+}
+
+impl BodyLimits {
+    pub fn load() -> Result<Self, Error> {
+        let routes = match get_env_var("ROUTE_SIZE_LIMITS") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)
+                .map_err(|err| anyhow!("invalid ROUTE_SIZE_LIMITS config: {err}"))?,
+            _ => BTreeMap::new(),
+        };
+        let tiers = match get_env_var("SIZE_LIMIT_TIERS") {
+            Ok(raw) if !raw.trim().is_empty() => serde_json::from_str(&raw)
+                .map_err(|err| anyhow!("invalid SIZE_LIMIT_TIERS config: {err}"))?,
+            _ => Vec::new(),
+        };
+        Ok(BodyLimits { routes, tiers })
+    }
+
+    pub fn key_tier(&self, token: &str) -> Option<&SizeLimitTier> {
+        self.tiers.iter().find(|tier| tier.api_keys.iter().any(|key| key == token))
+    }
+
+    /// Effective limit of a request: the smallest of the global, route and key tier limits.
+    pub fn limit_for(&self, route: Option<&str>, token: Option<&str>) -> usize {
+        let route_limit = route.and_then(|route| self.routes.get(route)).copied();
+        let key_limit = token.and_then(|token| self.key_tier(token)).map(|tier| tier.max_bytes);
+        [Some(OBJECT_SIZE_LIMIT), route_limit, key_limit].into_iter().flatten().min().unwrap()
+    }
+}
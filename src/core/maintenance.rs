@@ -0,0 +1,86 @@
+use crate::core::{
+    metrics,
+    models::{MaintenanceSettings, MaintenanceUpdate},
+    utils::get_env_var,
+};
+use axum::http::Method;
+use std::{sync::RwLock, time::Duration};
+
+const DEFAULT_MESSAGE: &str = "the agent is in maintenance, uploads are paused";
+const DEFAULT_RETRY_AFTER_SECS: u64 = 300;
+// routes taking a POST body without writing anything, still served in maintenance
+const READ_ONLY_POSTS: [&str; 4] = ["/id", "/tags/query", "/exists", "/items/get"];
+const RESUME_POLL: Duration = Duration::from_secs(5);
+
+/// Mode switched with `PUT /admin/maintenance`, overriding the `MAINTENANCE_*` variables until
+/// restart.
+static OVERRIDE: RwLock<Option<MaintenanceSettings>> = RwLock::new(None);
+
+fn env_settings() -> MaintenanceSettings {
+    MaintenanceSettings {
+        enabled: get_env_var("MAINTENANCE_MODE").is_ok_and(|v| v == "true"),
+        message: get_env_var("MAINTENANCE_MESSAGE")
+            .ok()
+            .filter(|message| !message.trim().is_empty())
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string()),
+        retry_after_secs: get_env_var("MAINTENANCE_RETRY_AFTER_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_RETRY_AFTER_SECS),
+        overridden: false,
+    }
+}
+
+/// The mode in effect, from the admin API or else `MAINTENANCE_MODE` (default off),
+/// `MAINTENANCE_MESSAGE` and `MAINTENANCE_RETRY_AFTER_SECS` (default 300).
+pub(crate) fn settings() -> MaintenanceSettings {
+    OVERRIDE.read().unwrap().clone().unwrap_or_else(env_settings)
+}
+
+/// Whether the agent is in maintenance, background writers skip or defer their work meanwhile.
+pub(crate) fn enabled() -> bool {
+    settings().enabled
+}
+
+/// Hold a background write until the agent is out of maintenance.
+pub(crate) async fn wait_until_off() {
+    while enabled() {
+        tokio::time::sleep(RESUME_POLL).await;
+    }
+}
+
+/// Apply an admin update on top of the mode in effect, `reset` going back to the environment
+/// first.
+pub(crate) fn update(change: MaintenanceUpdate) -> MaintenanceSettings {
+    let unchanged =
+        change.enabled.is_none() && change.message.is_none() && change.retry_after_secs.is_none();
+    let settings = if change.reset && unchanged {
+        *OVERRIDE.write().unwrap() = None;
+        env_settings()
+    } else {
+        let mut settings = if change.reset { env_settings() } else { settings() };
+        if let Some(enabled) = change.enabled {
+            settings.enabled = enabled;
+        }
+        if let Some(message) = change.message.filter(|message| !message.trim().is_empty()) {
+            settings.message = message;
+        }
+        if let Some(retry_after_secs) = change.retry_after_secs {
+            settings.retry_after_secs = retry_after_secs;
+        }
+        settings.overridden = true;
+        *OVERRIDE.write().unwrap() = Some(settings.clone());
+        settings
+    };
+    metrics::set_gauge("maintenance_mode", if settings.enabled { 1.0 } else { 0.0 });
+    println!("MAINTENANCE: {}", if settings.enabled { "on" } else { "off" });
+    settings
+}
+
+/// Whether a `method` request to `route` is still served in maintenance: reads, read-only
+/// `POST`s and the admin API, so the mode can be switched back off.
+pub(crate) fn allows(method: &Method, route: &str) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+        || route.starts_with("/admin/")
+        || READ_ONLY_POSTS.contains(&route)
+}
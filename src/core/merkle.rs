@@ -0,0 +1,185 @@
+use crate::core::{
+    gateway::arweave_gateway_url,
+    jobs::{self, POST_DATAITEM_JOB},
+    metadata::{
+        MerkleBatch, dataitems_indexed_between, latest_merkle_batch, merkle_batch_of,
+        merkle_leaves, save_merkle_batch,
+    },
+    models::{MerkleProof, MerkleStep},
+    s3::store_dataitem,
+    tenant::{Tenant, all_tenants},
+};
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, SubsecRound, Utc};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+
+/// `version` of the published batches, bumped if their hashing ever changes.
+const BATCH_VERSION: &str = "load-s3-agent/merkle/1";
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+// a dataitem is indexed a moment after its `created_at`, recent ones are left to the next batch
+const SETTLE_SECS: i64 = 60;
+
+type Hash = [u8; 32];
+
+fn leaf_hash(dataitem_id: &str) -> Hash {
+    Sha256::new().chain_update([LEAF_PREFIX]).chain_update(dataitem_id.as_bytes()).finalize().into()
+}
+
+fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+fn hex(hash: &Hash) -> String {
+    hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Every level of the tree over `dataitem_ids`, the leaves first and the root last. The odd last
+/// node of a level is carried up as is.
+fn levels(dataitem_ids: &[String]) -> Vec<Vec<Hash>> {
+    let mut levels = vec![dataitem_ids.iter().map(|id| leaf_hash(id)).collect::<Vec<_>>()];
+    while let Some(level) = levels.last().filter(|level| level.len() > 1) {
+        let next = level
+            .chunks(2)
+            .map(|pair| match pair {
+                [left, right] => node_hash(left, right),
+                _ => pair[0],
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+fn root(levels: &[Vec<Hash>]) -> String {
+    levels.last().and_then(|level| level.first()).map(hex).unwrap_or_default()
+}
+
+/// Siblings of the leaf at `index`, from the leaf up.
+fn path(levels: &[Vec<Hash>], mut index: usize) -> Vec<MerkleStep> {
+    let mut path = Vec::new();
+    for level in &levels[..levels.len() - 1] {
+        let sibling = index ^ 1;
+        if let Some(hash) = level.get(sibling) {
+            let side = if sibling < index { "left" } else { "right" };
+            path.push(MerkleStep { side: side.to_string(), hash: hex(hash) });
+        }
+        index /= 2;
+    }
+    path
+}
+
+/// Build a Merkle batch over the dataitems the tenant indexed since its last batch and publish
+/// the root as an agent signed dataitem, chained to the previous batch and posted to Arweave by
+/// a `post_dataitem` job. `None` when nothing was indexed.
+async fn anchor(tenant: &Tenant) -> Result<Option<MerkleBatch>, Error> {
+    let previous = latest_merkle_batch(&tenant.name).await?;
+    let start = previous.as_ref().map(|batch| batch.end_at).unwrap_or(DateTime::UNIX_EPOCH);
+    // ClickHouse keeps milliseconds, the next batch starts exactly where this one ends
+    let end = (Utc::now() - chrono::Duration::seconds(SETTLE_SECS)).trunc_subsecs(3);
+    if end <= start {
+        return Ok(None);
+    }
+    let dataitem_ids = dataitems_indexed_between(&tenant.name, &start, &end).await?;
+    if dataitem_ids.is_empty() {
+        return Ok(None);
+    }
+
+    let root = root(&levels(&dataitem_ids));
+    let previous_batch_id = previous.map(|batch| batch.batch_id).unwrap_or_default();
+    let payload = json!({
+        "version": BATCH_VERSION,
+        "root": root,
+        "leaf_count": dataitem_ids.len(),
+        "start": start.to_rfc3339(),
+        "end": end.to_rfc3339(),
+        "previous_batch": (!previous_batch_id.is_empty()).then_some(&previous_batch_id),
+    });
+    let mut tags = vec![
+        ("Merkle-Root".to_string(), root.clone()),
+        ("Leaf-Count".to_string(), dataitem_ids.len().to_string()),
+    ];
+    if !previous_batch_id.is_empty() {
+        tags.push(("Previous-Batch".to_string(), previous_batch_id.clone()));
+    }
+    let stored =
+        store_dataitem(serde_json::to_vec(&payload)?, "application/json", &tags, &[], tenant)
+            .await?;
+
+    let batch = MerkleBatch {
+        tenant: tenant.name.clone(),
+        batch_id: stored.id,
+        root,
+        leaf_count: dataitem_ids.len() as u64,
+        start_at: start,
+        end_at: end,
+        previous_batch_id,
+    };
+    save_merkle_batch(&batch, &dataitem_ids).await?;
+    let post = json!({ "dataitem_id": batch.batch_id });
+    if let Err(err) = jobs::enqueue(POST_DATAITEM_JOB, &tenant.name, post).await {
+        println!("MERKLE: failed to queue the post of batch {}: {err}", batch.batch_id);
+    }
+    Ok(Some(batch))
+}
+
+/// Anchor the dataitems every tenant indexed since its last Merkle batch.
+pub(crate) async fn anchor_all() -> Result<String, Error> {
+    let (mut batches, mut leaves, mut failed) = (0, 0, 0);
+    for tenant in all_tenants()? {
+        match anchor(&tenant).await {
+            Ok(Some(batch)) => {
+                println!(
+                    "MERKLE: tenant={:?} batch={} root={} leaves={}",
+                    tenant.name, batch.batch_id, batch.root, batch.leaf_count
+                );
+                batches += 1;
+                leaves += batch.leaf_count;
+            }
+            Ok(None) => {}
+            Err(err) => {
+                println!("MERKLE: tenant {:?} failed: {err}", tenant.name);
+                failed += 1;
+            }
+        }
+    }
+    Ok(format!("batches={batches} leaves={leaves} failed={failed}"))
+}
+
+/// Inclusion proof of a tenant's dataitem in the first Merkle batch holding it, `None` until
+/// it is anchored.
+pub(crate) async fn proof(
+    tenant: &Tenant,
+    dataitem_id: &str,
+) -> Result<Option<MerkleProof>, Error> {
+    let Some(batch) = merkle_batch_of(&tenant.name, dataitem_id).await? else {
+        return Ok(None);
+    };
+    let leaves = merkle_leaves(&tenant.name, &batch.batch_id).await?;
+    let leaf_index = leaves.iter().position(|id| id == dataitem_id).ok_or_else(|| {
+        anyhow!("{dataitem_id} is missing from the leaves of batch {}", batch.batch_id)
+    })?;
+    let levels = levels(&leaves);
+    if root(&levels) != batch.root {
+        return Err(anyhow!("the leaves of batch {} don't hash to its root", batch.batch_id));
+    }
+
+    Ok(Some(MerkleProof {
+        dataitem_id: dataitem_id.to_string(),
+        batch_arweave_url: format!("{}/{}", arweave_gateway_url(), batch.batch_id),
+        batch_id: batch.batch_id,
+        root: batch.root,
+        leaf_index: leaf_index as u64,
+        leaf_count: batch.leaf_count,
+        leaf: hex(&levels[0][leaf_index]),
+        path: path(&levels, leaf_index),
+        batch_start: batch.start_at.to_rfc3339(),
+        batch_end: batch.end_at.to_rfc3339(),
+    }))
+}
@@ -1,28 +1,316 @@
+use crate::core::{
+    ans104::{ContentDigest, FILE_NAME_TAG},
+    http::{http_client, send_with_retry},
+    provenance::Provenance,
+    query_cache,
+    resilience::with_timeout,
+};
 use anyhow::{Context, Result, anyhow};
+use axum::body::Bytes;
 use base64::{Engine as _, engine::general_purpose};
-use chrono::{DateTime, NaiveDateTime, Utc};
-use clickhouse::Client;
-use once_cell::sync::OnceCell;
-use reqwest::Client as HttpClient;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, DurationRound, NaiveDateTime, Utc};
+use clickhouse::{Client, query::Query};
+use futures::Stream;
+use hmac::{Hmac, Mac};
+use once_cell::sync::{Lazy, OnceCell};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use sha2::Sha256;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeSet, HashMap};
 
+const DATAITEM_TAGS: &str = "dataitem_tags";
+const ARWEAVE_POSTS: &str = "arweave_posts";
+const POST_STATUS: &str = "post_status";
+const BLOCKLIST: &str = "blocklist";
+const DELETIONS: &str = "deletions";
+const JOBS: &str = "jobs";
+const IDEMPOTENCY_KEYS: &str = "idempotency_keys";
+const DATAITEM_HITS: &str = "dataitem_hits";
+const DATAITEM_HITS_HOURLY: &str = "dataitem_hits_hourly";
+const RECEIPTS: &str = "receipts";
+const MERKLE_BATCHES: &str = "merkle_batches";
+const MERKLE_LEAVES: &str = "merkle_leaves";
+const FEEDS: &str = "feeds";
+const NAMES: &str = "names";
+const ABUSE_REPORTS: &str = "abuse_reports";
+
+// `{table}` is replaced with the prefixed table name, see `ensure_schema`
 const TABLE_DDL: &str = r#"
-CREATE TABLE IF NOT EXISTS dataitem_tags
+CREATE TABLE IF NOT EXISTS {table}
 (
     dataitem_id String,
     content_type String,
     created_at   DateTime64(3, 'UTC'),
     tag_key      String,
-    tag_value    String
+    tag_value    String,
+    tenant       String DEFAULT ''
+)
+ENGINE = ReplacingMergeTree(created_at)
+ORDER BY (tenant, tag_key, tag_value, dataitem_id);
+"#;
+
+// tenant namespace, '' for the default tenant (rows indexed before multi-tenancy). Tables created
+// before it was part of the sorting key dedup rows across tenants, see `check_tag_index_key`.
+const TENANT_COLUMN_DDL: &str =
+    "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS tenant String DEFAULT ''";
+
+// ANS-104 owner address (b64url sha256 of the owner key), '' for rows indexed before it
+const OWNER_COLUMN_DDL: &str =
+    "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS owner String DEFAULT ''";
+const OWNER_INDEX_DDL: &str = "ALTER TABLE {table} ADD INDEX IF NOT EXISTS owner_idx owner \
+     TYPE bloom_filter GRANULARITY 4";
+// upload provenance (`core::provenance`), for operators only and never part of the dataitem
+const PROVENANCE_COLUMNS_DDL: &str = "ALTER TABLE {table} \
+     ADD COLUMN IF NOT EXISTS principal String DEFAULT '', \
+     ADD COLUMN IF NOT EXISTS source_ip String DEFAULT '', \
+     ADD COLUMN IF NOT EXISTS user_agent String DEFAULT ''";
+const CONTENT_TYPE_INDEX_DDL: &str = "ALTER TABLE {table} ADD INDEX IF NOT EXISTS \
+     content_type_idx content_type TYPE bloom_filter GRANULARITY 4";
+// value of the dataitem's `File-Name` tag, served as its `Content-Disposition` filename
+const FILE_NAME_COLUMN_DDL: &str =
+    "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS file_name String DEFAULT ''";
+// bytes of the dataitem's data, 0 for rows indexed before it
+const DATA_SIZE_COLUMN_DDL: &str =
+    "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS data_size UInt64 DEFAULT 0";
+// hex digests of the dataitem's data (`ans104::ContentDigest`), '' for rows indexed before them
+const CONTENT_HASH_COLUMNS_DDL: &str = "ALTER TABLE {table} \
+     ADD COLUMN IF NOT EXISTS content_sha256 String DEFAULT '', \
+     ADD COLUMN IF NOT EXISTS content_blake3 String DEFAULT ''";
+const CONTENT_SHA256_INDEX_DDL: &str = "ALTER TABLE {table} ADD INDEX IF NOT EXISTS \
+     content_sha256_idx content_sha256 TYPE bloom_filter GRANULARITY 4";
+
+// dataitems posted to Arweave, so reads can fall back to a gateway once evicted from S3
+const ARWEAVE_POSTS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    dataitem_id String,
+    posted_at   DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(posted_at)
+ORDER BY (tenant, dataitem_id);
+"#;
+
+// set once lifecycle rules evicted the raw body, rows are re-inserted with the same version
+const RAW_EVICTED_COLUMN_DDL: &str = "ALTER TABLE {table} ADD COLUMN IF NOT EXISTS \
+     raw_evicted_at Nullable(DateTime64(3, 'UTC'))";
+
+// Arweave confirmation status of posted dataitems (`core::confirmations`), a row per transition
+const POST_STATUS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    dataitem_id String,
+    status      LowCardinality(String),
+    detail      String,
+    at          DateTime64(3, 'UTC')
+)
+ENGINE = MergeTree
+ORDER BY (tenant, dataitem_id, at);
+"#;
+
+// moderation blocklist (`core::blocklist`) of dataitem IDs and content sha256 hashes, an entry
+// is lifted by inserting it again with `active = 0`
+const BLOCKLIST_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    kind       LowCardinality(String),
+    value      String,
+    reason     String,
+    active     UInt8,
+    updated_at DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (kind, value);
+"#;
+
+// soft-deleted dataitems (`core::trash`), each state change inserts a new row version: `deleted`
+// hides the dataitem, `restored` undoes it and `purged` records that the GC removed its objects
+const DELETIONS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    dataitem_id String,
+    state       LowCardinality(String),
+    deleted_at  DateTime64(3, 'UTC'),
+    updated_at  DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (tenant, dataitem_id);
+"#;
+
+// saved responses of requests sent with an `Idempotency-Key` (`core::idempotency`), dropped
+// by ClickHouse once expired
+const IDEMPOTENCY_KEYS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    principal       String,
+    route           String,
+    idempotency_key String,
+    request_hash    String,
+    status          UInt16,
+    body            String,
+    created_at      DateTime64(3, 'UTC'),
+    expires_at      DateTime64(3, 'UTC')
 )
 ENGINE = ReplacingMergeTree(created_at)
-ORDER BY (tag_key, tag_value, dataitem_id);
+ORDER BY (principal, route, idempotency_key)
+TTL toDateTime(expires_at);
+"#;
+
+// serve hits per dataitem (`core::hits`), each flush inserts the hits counted since the last one
+// and merges fold them into a total
+const DATAITEM_HITS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    dataitem_id String,
+    hits        SimpleAggregateFunction(sum, UInt64),
+    last_access SimpleAggregateFunction(max, DateTime64(3, 'UTC'))
+)
+ENGINE = AggregatingMergeTree
+ORDER BY (tenant, dataitem_id);
+"#;
+
+// serve hits per dataitem and hour for `GET /analytics/top`, kept for a year
+const DATAITEM_HITS_HOURLY_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    hour        DateTime('UTC'),
+    dataitem_id String,
+    hits        UInt64
+)
+ENGINE = SummingMergeTree(hits)
+ORDER BY (tenant, hour, dataitem_id)
+TTL hour + INTERVAL 1 YEAR;
+"#;
+
+// background job queue (`core::jobs`), every state change inserts a new row version
+const JOBS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    id         String,
+    kind       LowCardinality(String),
+    tenant     String,
+    payload    String,
+    state      LowCardinality(String),
+    attempts   UInt32,
+    last_error String,
+    run_at     DateTime64(3, 'UTC'),
+    created_at DateTime64(3, 'UTC'),
+    updated_at DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY id;
+"#;
+
+// upload receipts signed by the agent (`core::receipts`), the signer's key is kept with each one
+// as the agent's key may be rotated
+const RECEIPTS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    dataitem_id String,
+    size        UInt64,
+    sha256      String,
+    issued_at   DateTime64(3, 'UTC'),
+    signer      String,
+    owner       String,
+    signature   String
+)
+ENGINE = MergeTree
+ORDER BY (tenant, dataitem_id, issued_at);
+"#;
+
+// Merkle batches anchoring the catalog (`core::merkle`), `batch_id` being the agent signed
+// dataitem publishing the root
+const MERKLE_BATCHES_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant            String,
+    batch_id          String,
+    root              String,
+    leaf_count        UInt64,
+    start_at          DateTime64(3, 'UTC'),
+    end_at            DateTime64(3, 'UTC'),
+    previous_batch_id String
+)
+ENGINE = MergeTree
+ORDER BY (tenant, end_at);
+"#;
+
+// the dataitem IDs of each Merkle batch in leaf order
+const MERKLE_LEAVES_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    batch_id    String,
+    leaf_index  UInt64,
+    dataitem_id String,
+    INDEX dataitem_idx dataitem_id TYPE bloom_filter GRANULARITY 4
+)
+ENGINE = MergeTree
+ORDER BY (tenant, batch_id, leaf_index);
+"#;
+
+// named tag queries (`core::feeds`), `filters` being the JSON `[[key, [values]]]` of the query
+const FEEDS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant     String,
+    name       String,
+    filters    String,
+    created_by String,
+    active     UInt8,
+    created_at DateTime64(3, 'UTC'),
+    updated_at DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY (tenant, name);
+"#;
+
+// every version of the mutable names (`core::names`), the latest one being the highest
+const NAMES_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    tenant      String,
+    name        String,
+    version     UInt64,
+    dataitem_id String,
+    updated_by  String,
+    created_at  DateTime64(3, 'UTC')
+)
+ENGINE = MergeTree
+ORDER BY (tenant, name, version);
+"#;
+
+// abuse reports (`core::reports`), each state change inserts a new row version: `open` until an
+// operator dismisses it, blocklists or deletes the dataitem
+const ABUSE_REPORTS_DDL: &str = r#"
+CREATE TABLE IF NOT EXISTS {table}
+(
+    id              String,
+    tenant          String,
+    dataitem_id     String,
+    reason          String,
+    details         String,
+    reporter_name   String,
+    reporter_email  String,
+    source_ip       String,
+    state           LowCardinality(String),
+    resolution_note String,
+    resolved_by     String,
+    created_at      DateTime64(3, 'UTC'),
+    updated_at      DateTime64(3, 'UTC')
+)
+ENGINE = ReplacingMergeTree(updated_at)
+ORDER BY id;
 "#;
 
 static CLIENT: OnceCell<Client> = OnceCell::new();
-static HTTP_CLIENT: OnceCell<HttpClient> = OnceCell::new();
+static TABLE_PREFIX: Lazy<String> =
+    Lazy::new(|| std::env::var("CLICKHOUSE_TABLE_PREFIX").unwrap_or_default());
 
 #[derive(Debug, Clone)]
 struct ClickhouseConfig {
@@ -35,9 +323,16 @@ struct ClickhouseConfig {
 impl ClickhouseConfig {
     fn load() -> Result<Self> {
         let url = std::env::var("CLICKHOUSE_URL").context("CLICKHOUSE_URL env var not set")?;
-        let database = std::env::var("CLICKHOUSE_DATABASE").unwrap();
+        let database =
+            std::env::var("CLICKHOUSE_DATABASE").context("CLICKHOUSE_DATABASE env var not set")?;
         let user = std::env::var("CLICKHOUSE_USER").ok().filter(|v| !v.is_empty());
         let password = std::env::var("CLICKHOUSE_PASSWORD").ok().filter(|v| !v.is_empty());
+        // spliced into every statement, unlike the values which are bound or escaped
+        if !TABLE_PREFIX.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            return Err(anyhow!(
+                "CLICKHOUSE_TABLE_PREFIX may only contain ASCII letters, digits and underscores"
+            ));
+        }
 
         Ok(Self { url, database, user, password })
     }
@@ -57,16 +352,128 @@ fn client() -> Result<&'static Client> {
     })
 }
 
-fn http_client() -> Result<&'static HttpClient> {
-    HTTP_CLIENT.get_or_try_init(|| HttpClient::builder().build().map_err(|err| anyhow!(err)))
+/// Tag indexing and everything else kept in ClickHouse, on unless `INDEXING_ENABLED=false`.
+pub fn indexing_enabled() -> bool {
+    !std::env::var("INDEXING_ENABLED").is_ok_and(|v| v == "false")
+}
+
+/// A ClickHouse read or write on an agent running with `INDEXING_ENABLED=false`.
+#[derive(Debug)]
+pub struct IndexingDisabled;
+
+impl std::fmt::Display for IndexingDisabled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "indexing is disabled on this agent, set INDEXING_ENABLED=true and the CLICKHOUSE_* \
+             env vars to enable it"
+        )
+    }
+}
+
+impl std::error::Error for IndexingDisabled {}
+
+/// `name` with `CLICKHOUSE_TABLE_PREFIX`, letting several agents share one database.
+fn prefixed(name: &str) -> String {
+    format!("{}{name}", *TABLE_PREFIX)
+}
+
+const CLICKHOUSE_TIMEOUT_SECS: u64 = 30;
+const CLICKHOUSE_OPTIMIZE_TIMEOUT_SECS: u64 = 60 * 60;
+
+/// `Query::execute` bounded by `CLICKHOUSE_TIMEOUT_SECS`.
+trait ExecuteBounded {
+    async fn execute_bounded(self) -> Result<()>;
+}
+
+impl ExecuteBounded for Query {
+    async fn execute_bounded(self) -> Result<()> {
+        with_timeout("clickhouse", CLICKHOUSE_TIMEOUT_SECS, self.execute()).await
+    }
 }
 
 async fn ensure_schema() -> Result<()> {
+    if !indexing_enabled() {
+        return Err(IndexingDisabled.into());
+    }
     let client = client()?;
-    client.query(TABLE_DDL).execute().await?;
+    let statements = [
+        (TABLE_DDL, DATAITEM_TAGS),
+        (TENANT_COLUMN_DDL, DATAITEM_TAGS),
+        (OWNER_COLUMN_DDL, DATAITEM_TAGS),
+        (OWNER_INDEX_DDL, DATAITEM_TAGS),
+        (CONTENT_TYPE_INDEX_DDL, DATAITEM_TAGS),
+        (PROVENANCE_COLUMNS_DDL, DATAITEM_TAGS),
+        (FILE_NAME_COLUMN_DDL, DATAITEM_TAGS),
+        (DATA_SIZE_COLUMN_DDL, DATAITEM_TAGS),
+        (CONTENT_HASH_COLUMNS_DDL, DATAITEM_TAGS),
+        (CONTENT_SHA256_INDEX_DDL, DATAITEM_TAGS),
+        (ARWEAVE_POSTS_DDL, ARWEAVE_POSTS),
+        (RAW_EVICTED_COLUMN_DDL, ARWEAVE_POSTS),
+        (POST_STATUS_DDL, POST_STATUS),
+        (JOBS_DDL, JOBS),
+        (BLOCKLIST_DDL, BLOCKLIST),
+        (DELETIONS_DDL, DELETIONS),
+        (IDEMPOTENCY_KEYS_DDL, IDEMPOTENCY_KEYS),
+        (DATAITEM_HITS_DDL, DATAITEM_HITS),
+        (DATAITEM_HITS_HOURLY_DDL, DATAITEM_HITS_HOURLY),
+        (RECEIPTS_DDL, RECEIPTS),
+        (MERKLE_BATCHES_DDL, MERKLE_BATCHES),
+        (MERKLE_LEAVES_DDL, MERKLE_LEAVES),
+        (FEEDS_DDL, FEEDS),
+        (NAMES_DDL, NAMES),
+        (ABUSE_REPORTS_DDL, ABUSE_REPORTS),
+    ];
+    for (ddl, table) in statements {
+        client.query(&ddl.replace("{table}", &prefixed(table))).execute_bounded().await?;
+    }
     Ok(())
 }
 
+/// Pre-flight check of the ClickHouse connection and DDL rights: the schema is applied, then a
+/// scratch table is created and dropped. Tag indexes sorted without `tenant` first fail it.
+pub async fn check_clickhouse() -> Result<()> {
+    ensure_schema().await?;
+    let client = client()?;
+    let suffix: String = rand::random::<[u8; 8]>().iter().map(|b| format!("{b:02x}")).collect();
+    let table = prefixed(&format!("selftest_{suffix}"));
+    client
+        .query(&format!("CREATE TABLE {table} (probe UInt8) ENGINE = Memory"))
+        .execute_bounded()
+        .await
+        .context("failed to create a table")?;
+    client
+        .query(&format!("DROP TABLE {table}"))
+        .execute_bounded()
+        .await
+        .context("failed to drop a table")?;
+    check_tag_index_key().await
+}
+
+#[derive(Debug, Deserialize)]
+struct SortingKeyRow {
+    sorting_key: String,
+}
+
+/// The tag index's `ReplacingMergeTree` dedups rows on its sorting key, which has to start with
+/// `tenant` or the same tags of a dataitem ID indexed by two tenants merge into one row. Tables
+/// created before can't be altered into it and have to be copied, see the README.
+async fn check_tag_index_key() -> Result<()> {
+    let sql = format!(
+        "SELECT sorting_key FROM system.tables WHERE database = currentDatabase() AND name = '{}'",
+        escape_single(&prefixed(DATAITEM_TAGS))
+    );
+    let rows: Vec<SortingKeyRow> = select_rows(&sql).await?;
+    match rows.into_iter().next() {
+        Some(row) if !row.sorting_key.starts_with("tenant,") => Err(anyhow!(
+            "{} is sorted by ({}), without tenant first its rows are deduplicated across tenants",
+            prefixed(DATAITEM_TAGS),
+            row.sorting_key
+        )),
+        _ => Ok(()),
+    }
+}
+
 #[derive(Debug, Deserialize)]
 struct JsonRow {
     dataitem_id: String,
@@ -75,8 +482,43 @@ struct JsonRow {
 }
 
 #[derive(Debug, Deserialize)]
-struct JsonResponse {
-    data: Vec<JsonRow>,
+struct JsonResponse<T> {
+    data: Vec<T>,
+}
+
+/// Send `sql` to the ClickHouse HTTP interface, failing on a non-success status.
+async fn http_query(sql: String) -> Result<reqwest::Response> {
+    let cfg = ClickhouseConfig::load()?;
+    let client = http_client()?;
+    let mut request = client
+        .post(format!("{}/?database={}", cfg.url, cfg.database))
+        .body(sql)
+        .header("content-type", "text/plain");
+
+    if let Some(user) = cfg.user {
+        request = request.basic_auth(user, cfg.password);
+    }
+
+    let response =
+        send_with_retry("clickhouse", request).await.context("clickhouse HTTP query failed")?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(anyhow!("clickhouse http query failed with status {status}"));
+    }
+    Ok(response)
+}
+
+/// Run a SELECT over the ClickHouse HTTP interface and deserialize its `FORMAT JSON` rows.
+async fn select_rows<T: DeserializeOwned>(sql: &str) -> Result<Vec<T>> {
+    let body = with_timeout("clickhouse", CLICKHOUSE_TIMEOUT_SECS, async {
+        let response = http_query(format!("{sql} FORMAT JSON")).await?;
+        response.text().await.context("failed to read clickhouse response body")
+    })
+    .await?;
+
+    let parsed: JsonResponse<T> =
+        serde_json::from_str(&body).context("failed to parse clickhouse json")?;
+    Ok(parsed.data)
 }
 
 #[derive(Debug, Clone)]
@@ -84,6 +526,7 @@ pub struct DataitemRecord {
     pub dataitem_id: String,
     pub content_type: String,
     pub created_at: DateTime<Utc>,
+    pub tags: Vec<(String, String)>,
 }
 
 pub const DEFAULT_PAGE_SIZE: usize = 25;
@@ -108,7 +551,7 @@ pub struct TagQueryPage {
     pub next_cursor: Option<String>,
 }
 
-fn normalize_tags(tags: &[(String, String)]) -> Vec<(String, String)> {
+pub(crate) fn normalize_tags(tags: &[(String, String)]) -> Vec<(String, String)> {
     let mut seen = BTreeSet::new();
     let mut normalized = Vec::new();
     for (key, value) in tags {
@@ -129,12 +572,46 @@ fn normalize_tags(tags: &[(String, String)]) -> Vec<(String, String)> {
     normalized
 }
 
+/// Trim filter keys and values, drop empty or oversized ones and filters left without values.
+pub(crate) fn normalize_filters(filters: &[(String, Vec<String>)]) -> Vec<(String, Vec<String>)> {
+    filters
+        .iter()
+        .filter_map(|(key, values)| {
+            let key = key.trim();
+            if key.is_empty() || key.len() > 1024 {
+                return None;
+            }
+            let values: BTreeSet<String> = values
+                .iter()
+                .map(|value| value.trim())
+                .filter(|value| !value.is_empty() && value.len() <= 1024)
+                .map(String::from)
+                .collect();
+            (!values.is_empty()).then(|| (key.to_string(), values.into_iter().collect()))
+        })
+        .collect()
+}
+
+/// Whether normalized `tags` match every normalized filter, like `query_dataitems_by_tags`.
+pub(crate) fn matches_filters(
+    filters: &[(String, Vec<String>)],
+    tags: &[(String, String)],
+) -> bool {
+    filters.iter().all(|(key, values)| {
+        tags.iter().any(|(tag_key, tag_value)| tag_key == key && values.contains(tag_value))
+    })
+}
+
 pub async fn index_dataitem(
+    tenant: &str,
     dataitem_id: &str,
+    owner: &str,
     content_type: &str,
+    digest: &ContentDigest,
     tags: &[(String, String)],
+    provenance: &Provenance,
 ) -> Result<()> {
-    if tags.is_empty() {
+    if tags.is_empty() || !indexing_enabled() {
         return Ok(());
     }
 
@@ -146,30 +623,96 @@ pub async fn index_dataitem(
     if normalized.is_empty() {
         return Ok(());
     }
+    let file_name = normalized
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(FILE_NAME_TAG))
+        .map(|(_, value)| value.as_str())
+        .unwrap_or_default();
 
     for (tag_key, tag_value) in normalized.iter() {
         client
-            .query(
-                "INSERT INTO dataitem_tags \
-                 (dataitem_id, content_type, created_at, tag_key, tag_value) \
-                 VALUES (?, ?, ?, ?, ?)",
-            )
+            .query(&format!(
+                "INSERT INTO {} \
+                 (dataitem_id, content_type, created_at, tag_key, tag_value, tenant, owner, \
+                 principal, source_ip, user_agent, file_name, data_size, content_sha256, \
+                 content_blake3) \
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                prefixed(DATAITEM_TAGS)
+            ))
             .bind(dataitem_id)
             .bind(content_type)
             .bind(created_at)
             .bind(tag_key)
             .bind(tag_value)
-            .execute()
+            .bind(tenant)
+            .bind(owner)
+            .bind(&provenance.principal)
+            .bind(&provenance.source_ip)
+            .bind(&provenance.user_agent)
+            .bind(file_name)
+            .bind(digest.size as u64)
+            .bind(&digest.sha256)
+            .bind(&digest.blake3)
+            .execute_bounded()
             .await
             .with_context(|| {
                 format!("failed to insert tag ({tag_key}, {tag_value}) for dataitem {dataitem_id}")
             })?;
     }
+    query_cache::invalidate_matching(tenant, &normalized);
     Ok(())
 }
 
+/// Row counts of the tag index, across tenants.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TagIndexStats {
+    pub rows: u64,
+    /// rows left once merged, one per `(tenant, tag_key, tag_value, dataitem_id)` sorting key
+    pub unique_rows: u64,
+    pub dataitems: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct TagIndexStatsRow {
+    rows: String,
+    unique_rows: String,
+    dataitems: String,
+}
+
+pub async fn tag_index_stats() -> Result<TagIndexStats> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT toString(count()) AS rows,
+                toString(uniqExact(tenant, tag_key, tag_value, dataitem_id)) AS unique_rows,
+                toString(uniqExact(tenant, dataitem_id)) AS dataitems
+         FROM {}",
+        prefixed(DATAITEM_TAGS)
+    );
+    let rows: Vec<TagIndexStatsRow> = select_rows(&sql).await?;
+    let row = rows.into_iter().next().ok_or_else(|| anyhow!("no tag index stats returned"))?;
+    Ok(TagIndexStats {
+        rows: parse_count(&row.rows)?,
+        unique_rows: parse_count(&row.unique_rows)?,
+        dataitems: parse_count(&row.dataitems)?,
+    })
+}
+
+/// Merge every part of the tag index, dropping the duplicate rows of re-indexed dataitems. It
+/// rewrites the whole table, so it is bounded by `CLICKHOUSE_OPTIMIZE_TIMEOUT_SECS` (default an
+/// hour) rather than `CLICKHOUSE_TIMEOUT_SECS`.
+pub async fn optimize_tag_index() -> Result<()> {
+    ensure_schema().await?;
+    let query = client()?.query(&format!("OPTIMIZE TABLE {} FINAL", prefixed(DATAITEM_TAGS)));
+    with_timeout("clickhouse_optimize", CLICKHOUSE_OPTIMIZE_TIMEOUT_SECS, query.execute())
+        .await
+        .context("failed to optimize the tag index")
+}
+
+/// Dataitems matching every filter, a filter being a tag key and the values it may have. Pages are
+/// cached for `TAG_QUERY_CACHE_TTL_SECS`, see `query_cache`.
 pub async fn query_dataitems_by_tags(
-    filters: &[(String, String)],
+    tenant: &str,
+    filters: &[(String, Vec<String>)],
     pagination: &TagQueryPagination,
 ) -> Result<TagQueryPage> {
     if filters.is_empty() {
@@ -178,21 +721,191 @@ pub async fn query_dataitems_by_tags(
 
     ensure_schema().await?;
 
-    let normalized_filters =
-        normalize_tags(&filters.iter().map(|(k, v)| (k.clone(), v.clone())).collect::<Vec<_>>());
+    let normalized_filters = normalize_filters(filters);
     if normalized_filters.is_empty() {
         return Ok(TagQueryPage { items: Vec::new(), has_more: false, next_cursor: None });
     }
 
-    let limit = pagination.first.clamp(1, MAX_PAGE_SIZE);
-    let fetch_limit = limit + 1;
+    let conditions: Vec<String> = normalized_filters
+        .iter()
+        .map(|(key, values)| {
+            let values_sql = values
+                .iter()
+                .map(|value| format!("'{}'", escape_single(value)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            format!("(tag_key = '{}' AND tag_value IN ({values_sql}))", escape_single(key))
+        })
+        .collect();
+    if let Some(page) = query_cache::get(tenant, &normalized_filters, pagination) {
+        return Ok(page);
+    }
+    let any_condition = conditions.join(" OR ");
+    // every filter must be matched by at least one of the dataitem's tags
+    let all_condition =
+        conditions.iter().map(|c| format!("countIf({c}) > 0")).collect::<Vec<_>>().join(" AND ");
+
+    let tenant_sql = escape_single(tenant);
+    let tags = prefixed(DATAITEM_TAGS);
+    let base_query = format!(
+        "SELECT dataitem_id,
+                any(content_type) AS content_type,
+                max(created_at) AS created_at
+         FROM {tags}
+         WHERE tenant = '{tenant_sql}' AND ({any_condition})
+         GROUP BY dataitem_id
+         HAVING {all_condition}"
+    );
+
+    let page = query_page(tenant, &base_query, pagination).await?;
+    query_cache::put(tenant, &normalized_filters, pagination, &page);
+    Ok(page)
+}
+
+/// Dataitems signed by `owner` (ANS-104 owner address), newest first.
+pub async fn query_dataitems_by_owner(
+    tenant: &str,
+    owner: &str,
+    pagination: &TagQueryPagination,
+) -> Result<TagQueryPage> {
+    ensure_schema().await?;
+
+    let tags = prefixed(DATAITEM_TAGS);
+
+    let base_query = format!(
+        "SELECT dataitem_id,
+                any(content_type) AS content_type,
+                max(created_at) AS created_at
+         FROM {tags}
+         WHERE tenant = '{}' AND owner = '{}'
+         GROUP BY dataitem_id",
+        escape_single(tenant),
+        escape_single(owner)
+    );
+
+    query_page(tenant, &base_query, pagination).await
+}
+
+/// Dataitems whose data has the hex sha256 `sha256`, newest first. Dataitems indexed before
+/// their digest was recorded are never found.
+pub async fn query_dataitems_by_sha256(
+    tenant: &str,
+    sha256: &str,
+    pagination: &TagQueryPagination,
+) -> Result<TagQueryPage> {
+    ensure_schema().await?;
+
+    let tags = prefixed(DATAITEM_TAGS);
+
+    let base_query = format!(
+        "SELECT dataitem_id,
+                any(content_type) AS content_type,
+                max(created_at) AS created_at
+         FROM {tags}
+         WHERE tenant = '{}' AND content_sha256 = '{}'
+         GROUP BY dataitem_id",
+        escape_single(tenant),
+        escape_single(sha256)
+    );
+
+    query_page(tenant, &base_query, pagination).await
+}
+
+/// Every indexed dataitem of the tenant, newest first.
+pub async fn query_recent_dataitems(
+    tenant: &str,
+    pagination: &TagQueryPagination,
+) -> Result<TagQueryPage> {
+    ensure_schema().await?;
+
+    let tags = prefixed(DATAITEM_TAGS);
+
+    let base_query = format!(
+        "SELECT dataitem_id,
+                any(content_type) AS content_type,
+                max(created_at) AS created_at
+         FROM {tags}
+         WHERE tenant = '{}'
+         GROUP BY dataitem_id",
+        escape_single(tenant)
+    );
+
+    query_page(tenant, &base_query, pagination).await
+}
+
+/// Dataitems of the `type/subtype` media type (parameters such as `charset` ignored), or of any
+/// subtype for `type/*`, newest first.
+pub async fn query_dataitems_by_content_type(
+    tenant: &str,
+    media_type: &str,
+    pagination: &TagQueryPagination,
+) -> Result<TagQueryPage> {
+    ensure_schema().await?;
+
+    let media_type_sql = escape_single(media_type);
+    let condition = match media_type_sql.strip_suffix('*') {
+        Some(type_prefix) => format!("startsWith(content_type, '{type_prefix}')"),
+        None => format!(
+            "(content_type = '{media_type_sql}' OR startsWith(content_type, '{media_type_sql};'))"
+        ),
+    };
+    // filtered in a subquery, the aggregate's alias would shadow `content_type` in a WHERE
+    let tags = prefixed(DATAITEM_TAGS);
+    let base_query = format!(
+        "SELECT dataitem_id,
+                any(content_type) AS content_type,
+                max(created_at) AS created_at
+         FROM (SELECT dataitem_id, content_type, created_at FROM {tags}
+               WHERE tenant = '{}' AND {condition})
+         GROUP BY dataitem_id",
+        escape_single(tenant)
+    );
+
+    query_page(tenant, &base_query, pagination).await
+}
+
+#[derive(Debug, Deserialize)]
+struct DataitemTagsRow {
+    dataitem_id: String,
+    tags: Vec<(String, String)>,
+}
 
-    let expected = normalized_filters.len();
-    let tuple_sql = normalized_filters
+/// Indexed tags of the given dataitems, keyed by dataitem ID.
+pub async fn dataitem_tags(
+    tenant: &str,
+    dataitem_ids: &[String],
+) -> Result<HashMap<String, Vec<(String, String)>>> {
+    if dataitem_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    ensure_schema().await?;
+
+    let ids_sql = dataitem_ids
         .iter()
-        .map(|(k, v)| format!("('{}','{}')", escape_single(k), escape_single(v)))
+        .map(|id| format!("'{}'", escape_single(id)))
         .collect::<Vec<_>>()
         .join(", ");
+    let tags = prefixed(DATAITEM_TAGS);
+    let sql = format!(
+        "SELECT dataitem_id, arraySort(groupUniqArray((tag_key, tag_value))) AS tags
+         FROM {tags}
+         WHERE tenant = '{}' AND dataitem_id IN ({ids_sql})
+         GROUP BY dataitem_id",
+        escape_single(tenant)
+    );
+
+    let rows: Vec<DataitemTagsRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().map(|row| (row.dataitem_id, row.tags)).collect())
+}
+
+/// Keyset-paginate a `(dataitem_id, content_type, created_at)` query, newest first.
+async fn query_page(
+    tenant: &str,
+    base_query: &str,
+    pagination: &TagQueryPagination,
+) -> Result<TagQueryPage> {
+    let limit = pagination.first.clamp(1, MAX_PAGE_SIZE);
+    let fetch_limit = limit + 1;
 
     let created_at_condition = pagination.after.as_ref().map(|cursor| {
         let created_at_expr = format!(
@@ -207,58 +920,30 @@ pub async fn query_dataitems_by_tags(
         )
     });
 
-    let base_query = format!(
-        "SELECT dataitem_id,
-                any(content_type) AS content_type,
-                max(created_at) AS created_at
-         FROM dataitem_tags
-         WHERE (tag_key, tag_value) IN ({tuple_sql})
-         GROUP BY dataitem_id
-         HAVING countDistinct(tag_key) = {expected}"
-    );
-
     let mut sql = format!(
         "SELECT dataitem_id, content_type, created_at
-         FROM ({base_query}) AS aggregated"
+         FROM ({base_query}) AS aggregated
+         WHERE {}",
+        not_deleted_condition(tenant)
     );
 
     if let Some(condition) = created_at_condition {
-        sql.push_str(" WHERE ");
-        sql.push_str(&condition);
+        sql.push_str(&format!(" AND ({condition})"));
     }
 
     sql.push_str(" ORDER BY created_at DESC, dataitem_id DESC");
     sql.push_str(&format!(" LIMIT {fetch_limit}"));
 
-    let cfg = ClickhouseConfig::load()?;
-    let client = http_client()?;
-    let mut request = client
-        .post(format!("{}/?database={}", cfg.url, cfg.database))
-        .body(format!("{sql} FORMAT JSON"))
-        .header("content-type", "text/plain");
-
-    if let Some(user) = cfg.user {
-        request = request.basic_auth(user, cfg.password);
-    }
-
-    let response = request.send().await.context("clickhouse HTTP query failed")?;
-    let status = response.status();
-    let body = response.text().await.context("failed to read clickhouse response body")?;
-
-    if !status.is_success() {
-        return Err(anyhow!("clickhouse http query failed with status {status}"));
-    }
-
-    let parsed: JsonResponse =
-        serde_json::from_str(&body).context("failed to parse clickhouse json")?;
+    let rows: Vec<JsonRow> = select_rows(&sql).await?;
 
-    let mut out = Vec::with_capacity(parsed.data.len());
-    for row in parsed.data {
+    let mut out = Vec::with_capacity(rows.len());
+    for row in rows {
         let created_at = parse_clickhouse_datetime(&row.created_at)?;
         out.push(DataitemRecord {
             dataitem_id: row.dataitem_id,
             content_type: row.content_type,
             created_at,
+            tags: Vec::new(),
         });
     }
 
@@ -267,40 +952,1731 @@ pub async fn query_dataitems_by_tags(
         out.truncate(limit);
     }
 
-    let next_cursor = if has_more {
-        out.last().map(|record| encode_tag_query_cursor(record)).transpose()?
-    } else {
-        None
-    };
+    // one lookup for the whole page, the filtered query only saw the matching tags
+    let ids: Vec<String> = out.iter().map(|record| record.dataitem_id.clone()).collect();
+    let mut tags = dataitem_tags(tenant, &ids).await?;
+    for record in out.iter_mut() {
+        record.tags = tags.remove(&record.dataitem_id).unwrap_or_default();
+    }
+
+    let next_cursor =
+        if has_more { out.last().map(encode_tag_query_cursor).transpose()? } else { None };
 
     Ok(TagQueryPage { items: out, has_more, next_cursor })
 }
 
-#[derive(Serialize, Deserialize)]
-struct CursorPayload {
-    created_at: String,
-    dataitem_id: String,
+/// Upload provenance of one indexed dataitem.
+#[derive(Debug)]
+pub struct ProvenanceRecord {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub content_type: String,
+    pub principal: String,
+    pub source_ip: String,
+    pub user_agent: String,
+    pub created_at: DateTime<Utc>,
 }
 
-pub fn decode_tag_query_cursor(encoded: &str) -> Result<TagQueryCursor> {
-    let raw = general_purpose::STANDARD_NO_PAD
-        .decode(encoded)
-        .context("invalid pagination cursor encoding")?;
-    let payload: CursorPayload =
-        serde_json::from_slice(&raw).context("invalid pagination cursor payload")?;
-    let created_at = DateTime::parse_from_rfc3339(&payload.created_at)
-        .context("invalid pagination cursor timestamp")?
-        .with_timezone(&Utc);
-    Ok(TagQueryCursor { created_at, dataitem_id: payload.dataitem_id })
+#[derive(Debug, Deserialize)]
+struct ProvenanceRow {
+    tenant: String,
+    dataitem_id: String,
+    content_type: String,
+    principal: String,
+    source_ip: String,
+    user_agent: String,
+    created_at: String,
+}
+
+/// Provenance of the dataitems matching every given filter, newest first, across tenants
+/// unless `tenant` is set.
+pub async fn query_provenance(
+    tenant: Option<&str>,
+    dataitem_id: Option<&str>,
+    principal: Option<&str>,
+    source_ip: Option<&str>,
+    limit: usize,
+) -> Result<Vec<ProvenanceRecord>> {
+    ensure_schema().await?;
+
+    let conditions: Vec<String> = [
+        ("tenant", tenant),
+        ("dataitem_id", dataitem_id),
+        ("principal", principal),
+        ("source_ip", source_ip),
+    ]
+    .into_iter()
+    .filter_map(|(column, value)| Some(format!("{column} = '{}'", escape_single(value?))))
+    .collect();
+    let where_sql = match conditions.is_empty() {
+        true => "1".to_string(),
+        false => conditions.join(" AND "),
+    };
+    // filtered in a subquery, the aggregates' aliases would shadow the columns in a WHERE
+    let tags = prefixed(DATAITEM_TAGS);
+    let sql = format!(
+        "SELECT tenant, dataitem_id,
+                any(content_type) AS content_type,
+                any(principal) AS principal,
+                any(source_ip) AS source_ip,
+                any(user_agent) AS user_agent,
+                max(created_at) AS created_at
+         FROM (SELECT tenant, dataitem_id, content_type, principal, source_ip, user_agent,
+                      created_at
+               FROM {tags}
+               WHERE {where_sql})
+         GROUP BY tenant, dataitem_id
+         ORDER BY created_at DESC
+         LIMIT {limit}"
+    );
+    let rows: Vec<ProvenanceRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(ProvenanceRecord {
+                created_at: parse_clickhouse_datetime(&row.created_at)?,
+                tenant: row.tenant,
+                dataitem_id: row.dataitem_id,
+                content_type: row.content_type,
+                principal: row.principal,
+                source_ip: row.source_ip,
+                user_agent: row.user_agent,
+            })
+        })
+        .collect()
+}
+
+/// Remember that a dataitem was posted to Arweave.
+pub async fn record_arweave_post(tenant: &str, dataitem_id: &str) -> Result<()> {
+    if !indexing_enabled() {
+        return Ok(());
+    }
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, dataitem_id, posted_at) VALUES (?, ?, ?)",
+            prefixed(ARWEAVE_POSTS)
+        ))
+        .bind(tenant)
+        .bind(dataitem_id)
+        .bind(Utc::now())
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to record arweave post of dataitem {dataitem_id}"))?;
+    Ok(())
+}
+
+pub async fn is_posted_to_arweave(tenant: &str, dataitem_id: &str) -> Result<bool> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT dataitem_id FROM {} WHERE tenant = '{}' AND dataitem_id = '{}' LIMIT 1",
+        prefixed(ARWEAVE_POSTS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<DataitemIdRow> = select_rows(&sql).await?;
+    Ok(!rows.is_empty())
+}
+
+/// Indexed dataitems older than `created_before` that were never posted to Arweave.
+pub async fn dataitems_pending_post(
+    tenant: &str,
+    created_before: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<String>> {
+    ensure_schema().await?;
+    let not_deleted = not_deleted_condition(tenant);
+    let tenant = escape_single(tenant);
+    let tags = prefixed(DATAITEM_TAGS);
+    let posts = prefixed(ARWEAVE_POSTS);
+    let sql = format!(
+        "SELECT dataitem_id
+         FROM {tags}
+         WHERE tenant = '{tenant}'
+           AND dataitem_id NOT IN (SELECT dataitem_id FROM {posts} WHERE tenant = '{tenant}')
+           AND {not_deleted}
+         GROUP BY dataitem_id
+         HAVING max(created_at) < toDateTime64('{}', 3, 'UTC')
+         ORDER BY max(created_at)
+         LIMIT {limit}",
+        created_before.format("%Y-%m-%d %H:%M:%S%.3f")
+    );
+    let rows: Vec<DataitemIdRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().map(|row| row.dataitem_id).collect())
+}
+
+#[derive(Debug, Deserialize)]
+struct DataitemIdRow {
+    dataitem_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ArweavePostRow {
+    dataitem_id: String,
+    posted_at: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct ArweavePost {
+    pub dataitem_id: String,
+    pub posted_at: DateTime<Utc>,
+}
+
+/// Dataitems posted before `posted_before` whose raw body is still kept in S3.
+pub async fn posts_with_raw_body(
+    tenant: &str,
+    posted_before: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<ArweavePost>> {
+    ensure_schema().await?;
+    let posts = prefixed(ARWEAVE_POSTS);
+    let sql = format!(
+        "SELECT dataitem_id, posted_at
+         FROM {posts} FINAL
+         WHERE tenant = '{}' AND raw_evicted_at IS NULL
+           AND posted_at < toDateTime64('{}', 3, 'UTC')
+         ORDER BY posted_at
+         LIMIT {limit}",
+        escape_single(tenant),
+        posted_before.format("%Y-%m-%d %H:%M:%S%.3f")
+    );
+    let rows: Vec<ArweavePostRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(ArweavePost {
+                dataitem_id: row.dataitem_id,
+                posted_at: parse_clickhouse_datetime(&row.posted_at)?,
+            })
+        })
+        .collect()
+}
+
+pub async fn mark_raw_evicted(tenant: &str, post: &ArweavePost) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, dataitem_id, posted_at, raw_evicted_at) VALUES (?, ?, ?, ?)",
+            prefixed(ARWEAVE_POSTS)
+        ))
+        .bind(tenant)
+        .bind(&post.dataitem_id)
+        .bind(post.posted_at)
+        .bind(Some(Utc::now()))
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to mark raw body of {} evicted", post.dataitem_id))?;
+    Ok(())
+}
+
+/// A confirmation status a posted dataitem went through.
+#[derive(Debug, Clone)]
+pub struct PostStatusChange {
+    pub status: String,
+    pub detail: String,
+    pub at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PostStatusRow {
+    status: String,
+    detail: String,
+    at: String,
+}
+
+pub async fn record_post_status(
+    tenant: &str,
+    dataitem_id: &str,
+    status: &str,
+    detail: &str,
+) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, dataitem_id, status, detail, at) VALUES (?, ?, ?, ?, ?)",
+            prefixed(POST_STATUS)
+        ))
+        .bind(tenant)
+        .bind(dataitem_id)
+        .bind(status)
+        .bind(detail)
+        .bind(Utc::now())
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to record post status of dataitem {dataitem_id}"))?;
+    Ok(())
+}
+
+/// Every status the posted dataitem went through, oldest first.
+pub async fn post_status_history(tenant: &str, dataitem_id: &str) -> Result<Vec<PostStatusChange>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT status, detail, at FROM {}
+         WHERE tenant = '{}' AND dataitem_id = '{}'
+         ORDER BY at",
+        prefixed(POST_STATUS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<PostStatusRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(PostStatusChange {
+                at: parse_clickhouse_datetime(&row.at)?,
+                status: row.status,
+                detail: row.detail,
+            })
+        })
+        .collect()
+}
+
+/// A posted dataitem whose latest status is one of the polled ones.
+#[derive(Debug, Clone)]
+pub struct UnsettledPost {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub status: String,
+    pub posted_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct UnsettledPostRow {
+    tenant: String,
+    dataitem_id: String,
+    current_status: String,
+    posted_at: String,
+}
+
+/// Posted dataitems across tenants whose latest status is in `statuses`, oldest post first.
+pub async fn unsettled_posts(statuses: &[&str], limit: usize) -> Result<Vec<UnsettledPost>> {
+    ensure_schema().await?;
+    let statuses_sql =
+        statuses.iter().map(|s| format!("'{}'", escape_single(s))).collect::<Vec<_>>().join(", ");
+    let post_status = prefixed(POST_STATUS);
+    let sql = format!(
+        "SELECT tenant, dataitem_id, argMax(status, at) AS current_status, min(at) AS posted_at
+         FROM {post_status}
+         GROUP BY tenant, dataitem_id
+         HAVING current_status IN ({statuses_sql})
+         ORDER BY posted_at
+         LIMIT {limit}"
+    );
+    let rows: Vec<UnsettledPostRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(UnsettledPost {
+                posted_at: parse_clickhouse_datetime(&row.posted_at)?,
+                tenant: row.tenant,
+                dataitem_id: row.dataitem_id,
+                status: row.current_status,
+            })
+        })
+        .collect()
+}
+
+/// A blocklisted dataitem ID (`kind` "id") or content hash (`kind` "sha256").
+#[derive(Debug, Clone)]
+pub struct BlockEntry {
+    pub kind: String,
+    pub value: String,
+    pub reason: String,
+    pub active: bool,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockRow {
+    kind: String,
+    value: String,
+    reason: String,
+    updated_at: String,
+}
+
+/// Insert a blocklist entry, or lift it with `active` false.
+pub async fn save_block_entry(entry: &BlockEntry) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (kind, value, reason, active, updated_at) VALUES (?, ?, ?, ?, ?)",
+            prefixed(BLOCKLIST)
+        ))
+        .bind(&entry.kind)
+        .bind(&entry.value)
+        .bind(&entry.reason)
+        .bind(u8::from(entry.active))
+        .bind(entry.updated_at)
+        .execute_bounded()
+        .await
+        .with_context(|| {
+            format!("failed to save blocklist entry {} {}", entry.kind, entry.value)
+        })?;
+    Ok(())
+}
+
+/// Every active blocklist entry, newest first.
+pub async fn active_block_entries() -> Result<Vec<BlockEntry>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT kind, value, reason, updated_at FROM {} FINAL WHERE active = 1 \
+         ORDER BY updated_at DESC",
+        prefixed(BLOCKLIST)
+    );
+    let rows: Vec<BlockRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(BlockEntry {
+                updated_at: parse_clickhouse_datetime(&row.updated_at)?,
+                kind: row.kind,
+                value: row.value,
+                reason: row.reason,
+                active: true,
+            })
+        })
+        .collect()
+}
+
+pub const DELETION_DELETED: &str = "deleted";
+pub const DELETION_RESTORED: &str = "restored";
+pub const DELETION_PURGED: &str = "purged";
+
+/// Excludes the tenant's soft-deleted and purged dataitems from a query over `dataitem_id`.
+fn not_deleted_condition(tenant: &str) -> String {
+    format!(
+        "dataitem_id NOT IN (SELECT dataitem_id FROM {} FINAL \
+         WHERE tenant = '{}' AND state != '{DELETION_RESTORED}')",
+        prefixed(DELETIONS),
+        escape_single(tenant)
+    )
+}
+
+/// Soft-deletion state of a dataitem, see `core::trash`.
+#[derive(Debug, Clone)]
+pub struct Deletion {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub state: String,
+    pub deleted_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeletionRow {
+    tenant: String,
+    dataitem_id: String,
+    state: String,
+    deleted_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<DeletionRow> for Deletion {
+    type Error = anyhow::Error;
+
+    fn try_from(row: DeletionRow) -> Result<Self> {
+        Ok(Deletion {
+            deleted_at: parse_clickhouse_datetime(&row.deleted_at)?,
+            updated_at: parse_clickhouse_datetime(&row.updated_at)?,
+            tenant: row.tenant,
+            dataitem_id: row.dataitem_id,
+            state: row.state,
+        })
+    }
+}
+
+async fn select_deletions(conditions: &str, limit: usize) -> Result<Vec<Deletion>> {
+    ensure_schema().await?;
+    let deletions = prefixed(DELETIONS);
+    let sql = format!(
+        "SELECT tenant, dataitem_id, state, deleted_at, updated_at FROM {deletions} FINAL \
+         WHERE {conditions} ORDER BY deleted_at LIMIT {limit}"
+    );
+    let rows: Vec<DeletionRow> = select_rows(&sql).await?;
+    rows.into_iter().map(Deletion::try_from).collect()
+}
+
+/// Insert a new version of a dataitem's deletion state.
+pub async fn save_deletion(deletion: &Deletion) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, dataitem_id, state, deleted_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?)",
+            prefixed(DELETIONS)
+        ))
+        .bind(&deletion.tenant)
+        .bind(&deletion.dataitem_id)
+        .bind(&deletion.state)
+        .bind(deletion.deleted_at)
+        .bind(deletion.updated_at)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save deletion of {}", deletion.dataitem_id))?;
+    query_cache::invalidate_tenant(&deletion.tenant);
+    Ok(())
+}
+
+/// The dataitem's current deletion state, `None` when it was never deleted.
+pub async fn get_deletion(tenant: &str, dataitem_id: &str) -> Result<Option<Deletion>> {
+    let conditions = format!(
+        "tenant = '{}' AND dataitem_id = '{}'",
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    Ok(select_deletions(&conditions, 1).await?.into_iter().next())
+}
+
+/// Every soft-deleted or purged dataitem across tenants.
+pub async fn hidden_dataitems() -> Result<Vec<Deletion>> {
+    let conditions = format!("state != '{DELETION_RESTORED}'");
+    select_deletions(&conditions, usize::MAX).await
+}
+
+/// The tenant's dataitems soft-deleted before `deleted_before` and not purged yet, oldest first.
+pub async fn expired_deletions(
+    tenant: &str,
+    deleted_before: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<Deletion>> {
+    let conditions = format!(
+        "tenant = '{}' AND state = '{DELETION_DELETED}' AND deleted_at < {}",
+        escape_single(tenant),
+        datetime_literal(&deleted_before)
+    );
+    select_deletions(&conditions, limit).await
+}
+
+/// The saved response of a request sent with an `Idempotency-Key`, see `core::idempotency`.
+#[derive(Debug, Clone)]
+pub struct IdempotentResponse {
+    pub principal: String,
+    pub route: String,
+    pub idempotency_key: String,
+    pub request_hash: String,
+    pub status: u16,
+    pub body: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdempotentResponseRow {
+    request_hash: String,
+    status: u16,
+    body: String,
+    created_at: String,
+    expires_at: String,
+}
+
+pub async fn save_idempotent_response(response: &IdempotentResponse) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (principal, route, idempotency_key, request_hash, status, body, \
+             created_at, expires_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            prefixed(IDEMPOTENCY_KEYS)
+        ))
+        .bind(&response.principal)
+        .bind(&response.route)
+        .bind(&response.idempotency_key)
+        .bind(&response.request_hash)
+        .bind(response.status)
+        .bind(&response.body)
+        .bind(response.created_at)
+        .bind(response.expires_at)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save response of {}", response.route))?;
+    Ok(())
+}
+
+/// The unexpired saved response of `principal`'s request to `route` with `idempotency_key`.
+pub async fn get_idempotent_response(
+    principal: &str,
+    route: &str,
+    idempotency_key: &str,
+) -> Result<Option<IdempotentResponse>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT request_hash, status, body, created_at, expires_at FROM {} FINAL
+         WHERE principal = '{}' AND route = '{}' AND idempotency_key = '{}'
+           AND expires_at > now64(3)
+         LIMIT 1",
+        prefixed(IDEMPOTENCY_KEYS),
+        escape_single(principal),
+        escape_single(route),
+        escape_single(idempotency_key)
+    );
+    let rows: Vec<IdempotentResponseRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .next()
+        .map(|row| {
+            Ok(IdempotentResponse {
+                principal: principal.to_string(),
+                route: route.to_string(),
+                idempotency_key: idempotency_key.to_string(),
+                request_hash: row.request_hash,
+                status: row.status,
+                body: row.body,
+                created_at: parse_clickhouse_datetime(&row.created_at)?,
+                expires_at: parse_clickhouse_datetime(&row.expires_at)?,
+            })
+        })
+        .transpose()
+}
+
+/// Serve hits of a dataitem, counted since the last flush or in total.
+#[derive(Debug, Clone)]
+pub struct DataitemHits {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub hits: u64,
+    pub last_access: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DataitemHitsRow {
+    hits: String,
+    last_access: String,
+}
+
+/// Add buffered serve hits to the dataitems' totals, in one insert.
+pub async fn record_dataitem_hits(batch: &[DataitemHits]) -> Result<()> {
+    if batch.is_empty() {
+        return Ok(());
+    }
+    ensure_schema().await?;
+    let placeholders = vec!["(?, ?, ?, ?)"; batch.len()].join(", ");
+    let mut query = client()?.query(&format!(
+        "INSERT INTO {} (tenant, dataitem_id, hits, last_access) VALUES {placeholders}",
+        prefixed(DATAITEM_HITS)
+    ));
+    for entry in batch {
+        query = query
+            .bind(&entry.tenant)
+            .bind(&entry.dataitem_id)
+            .bind(entry.hits)
+            .bind(entry.last_access);
+    }
+    query
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to record hits of {} dataitems", batch.len()))?;
+
+    let mut query = client()?.query(&format!(
+        "INSERT INTO {} (tenant, hour, dataitem_id, hits) VALUES {placeholders}",
+        prefixed(DATAITEM_HITS_HOURLY)
+    ));
+    for entry in batch {
+        let hour = entry.last_access.duration_trunc(chrono::Duration::hours(1));
+        query = query
+            .bind(&entry.tenant)
+            .bind(hour.unwrap_or(entry.last_access))
+            .bind(&entry.dataitem_id)
+            .bind(entry.hits);
+    }
+    query
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to record hourly hits of {} dataitems", batch.len()))?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct TopDataitemRow {
+    dataitem_id: String,
+    hits: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TopTagRow {
+    tag_key: String,
+    #[serde(default)]
+    tag_value: String,
+    dataitems: String,
+}
+
+/// A tag key, or key and value, and how many dataitems carry it.
+#[derive(Debug, Clone)]
+pub struct TagUsage {
+    pub key: String,
+    /// empty when counting keys
+    pub value: String,
+    pub dataitems: u64,
+}
+
+fn parse_count(raw: &str) -> Result<u64> {
+    raw.parse().with_context(|| format!("invalid count returned by clickhouse: {raw}"))
+}
+
+/// The `limit` most served dataitems of `tenant` since `since`, with their hits.
+pub async fn top_dataitems(
+    tenant: &str,
+    since: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<(String, u64)>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT dataitem_id, toString(sum(hits)) AS hits FROM {}
+         WHERE tenant = '{}' AND hour >= toStartOfHour({})
+         GROUP BY dataitem_id
+         ORDER BY sum(hits) DESC, dataitem_id
+         LIMIT {limit}",
+        prefixed(DATAITEM_HITS_HOURLY),
+        escape_single(tenant),
+        datetime_literal(&since)
+    );
+    let rows: Vec<TopDataitemRow> = select_rows(&sql).await?;
+    rows.into_iter().map(|row| Ok((row.dataitem_id, parse_count(&row.hits)?))).collect()
+}
+
+/// The `limit` tag keys, or key/value pairs with `with_values`, carried by the most dataitems of
+/// `tenant` indexed since `since`. Keys in `excluded_keys` (lowercased) are left out.
+pub async fn top_tags(
+    tenant: &str,
+    since: DateTime<Utc>,
+    excluded_keys: &[String],
+    with_values: bool,
+    limit: usize,
+) -> Result<Vec<TagUsage>> {
+    ensure_schema().await?;
+    let excluded = match excluded_keys.is_empty() {
+        true => String::new(),
+        false => format!(
+            " AND lower(tag_key) NOT IN ({})",
+            excluded_keys
+                .iter()
+                .map(|key| format!("'{}'", escape_single(key)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let group_by = if with_values { "tag_key, tag_value" } else { "tag_key" };
+    let sql = format!(
+        "SELECT {group_by}, toString(uniqExact(dataitem_id)) AS dataitems FROM {}
+         WHERE tenant = '{}' AND created_at >= {}{excluded}
+         GROUP BY {group_by}
+         ORDER BY uniqExact(dataitem_id) DESC, {group_by}
+         LIMIT {limit}",
+        prefixed(DATAITEM_TAGS),
+        escape_single(tenant),
+        datetime_literal(&since)
+    );
+    let rows: Vec<TopTagRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(TagUsage {
+                key: row.tag_key,
+                value: row.tag_value,
+                dataitems: parse_count(&row.dataitems)?,
+            })
+        })
+        .collect()
+}
+
+/// Total serve hits of a dataitem, `None` if it was never served since hits are counted.
+pub async fn dataitem_hits(tenant: &str, dataitem_id: &str) -> Result<Option<DataitemHits>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT toString(sum(hits)) AS hits, max(last_access) AS last_access FROM {}
+         WHERE tenant = '{}' AND dataitem_id = '{}'
+         HAVING count() > 0",
+        prefixed(DATAITEM_HITS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<DataitemHitsRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .next()
+        .map(|row| {
+            Ok(DataitemHits {
+                tenant: tenant.to_string(),
+                dataitem_id: dataitem_id.to_string(),
+                hits: row.hits.parse().with_context(|| format!("invalid hits {}", row.hits))?,
+                last_access: parse_clickhouse_datetime(&row.last_access)?,
+            })
+        })
+        .transpose()
+}
+
+#[derive(Debug, Deserialize)]
+struct FileNameRow {
+    file_name: String,
+}
+
+/// The indexed `File-Name` of a dataitem, `None` when it was uploaded without one.
+pub async fn dataitem_file_name(tenant: &str, dataitem_id: &str) -> Result<Option<String>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT any(file_name) AS file_name FROM {}
+         WHERE tenant = '{}' AND dataitem_id = '{}' AND file_name != ''",
+        prefixed(DATAITEM_TAGS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<FileNameRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().next().map(|row| row.file_name).filter(|name| !name.is_empty()))
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentHashRow {
+    sha256: String,
+}
+
+/// The indexed hex sha256 of a dataitem's data, `None` when it isn't indexed or was indexed
+/// before content digests were recorded.
+pub async fn dataitem_sha256(tenant: &str, dataitem_id: &str) -> Result<Option<String>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT any(content_sha256) AS sha256 FROM {}
+         WHERE tenant = '{}' AND dataitem_id = '{}' AND content_sha256 != ''",
+        prefixed(DATAITEM_TAGS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<ContentHashRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().next().map(|row| row.sha256).filter(|sha256| !sha256.is_empty()))
+}
+
+#[derive(Debug, Deserialize)]
+struct DataSizeRow {
+    dataitem_id: String,
+    size: String,
+    content_type: String,
+}
+
+/// Indexed data size and content type of each of the tenant's `dataitem_ids`, leaving out the
+/// dataitems not indexed and the ones indexed before their size was recorded.
+pub async fn dataitem_sizes(
+    tenant: &str,
+    dataitem_ids: &[String],
+) -> Result<HashMap<String, (u64, String)>> {
+    if dataitem_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    ensure_schema().await?;
+    let ids_sql = dataitem_ids
+        .iter()
+        .map(|id| format!("'{}'", escape_single(id)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT dataitem_id, toString(max(data_size)) AS size, any(content_type) AS content_type
+         FROM {}
+         WHERE tenant = '{}' AND dataitem_id IN ({ids_sql})
+         GROUP BY dataitem_id
+         HAVING max(data_size) > 0",
+        prefixed(DATAITEM_TAGS),
+        escape_single(tenant)
+    );
+    let rows: Vec<DataSizeRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| Ok((row.dataitem_id, (parse_count(&row.size)?, row.content_type))))
+        .collect()
+}
+
+/// An upload receipt signed by the agent, see `core::receipts`.
+#[derive(Debug, Clone)]
+pub struct Receipt {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub size: u64,
+    pub sha256: String,
+    pub issued_at: DateTime<Utc>,
+    /// Arweave address of the signing key
+    pub signer: String,
+    /// b64url modulus of the signing key
+    pub owner: String,
+    /// b64url
+    pub signature: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReceiptRow {
+    size: String,
+    sha256: String,
+    issued_at: String,
+    signer: String,
+    owner: String,
+    signature: String,
+}
+
+pub async fn save_receipt(receipt: &Receipt) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, dataitem_id, size, sha256, issued_at, signer, owner, \
+             signature) VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            prefixed(RECEIPTS)
+        ))
+        .bind(&receipt.tenant)
+        .bind(&receipt.dataitem_id)
+        .bind(receipt.size)
+        .bind(&receipt.sha256)
+        .bind(receipt.issued_at)
+        .bind(&receipt.signer)
+        .bind(&receipt.owner)
+        .bind(&receipt.signature)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save receipt of {}", receipt.dataitem_id))?;
+    Ok(())
+}
+
+/// The latest receipt issued for a dataitem, `None` when it has none.
+pub async fn get_receipt(tenant: &str, dataitem_id: &str) -> Result<Option<Receipt>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT toString(size) AS size, sha256, issued_at, signer, owner, signature FROM {}
+         WHERE tenant = '{}' AND dataitem_id = '{}'
+         ORDER BY issued_at DESC
+         LIMIT 1",
+        prefixed(RECEIPTS),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    let rows: Vec<ReceiptRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .next()
+        .map(|row| {
+            Ok(Receipt {
+                tenant: tenant.to_string(),
+                dataitem_id: dataitem_id.to_string(),
+                size: row.size.parse().with_context(|| format!("invalid size {}", row.size))?,
+                sha256: row.sha256,
+                issued_at: parse_clickhouse_datetime(&row.issued_at)?,
+                signer: row.signer,
+                owner: row.owner,
+                signature: row.signature,
+            })
+        })
+        .transpose()
+}
+
+/// A Merkle batch over the dataitems a tenant indexed between `start_at` and `end_at`, see
+/// `core::merkle`.
+#[derive(Debug, Clone)]
+pub struct MerkleBatch {
+    pub tenant: String,
+    /// ID of the dataitem publishing the root
+    pub batch_id: String,
+    /// hex
+    pub root: String,
+    pub leaf_count: u64,
+    pub start_at: DateTime<Utc>,
+    pub end_at: DateTime<Utc>,
+    /// '' for the tenant's first batch
+    pub previous_batch_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct MerkleBatchRow {
+    batch_id: String,
+    root: String,
+    leaf_count: String,
+    start_at: String,
+    end_at: String,
+    previous_batch_id: String,
+}
+
+const MERKLE_LEAVES_PER_INSERT: usize = 10_000;
+
+async fn select_merkle_batch(
+    tenant: &str,
+    conditions: &str,
+    order: &str,
+) -> Result<Option<MerkleBatch>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT batch_id, root, toString(leaf_count) AS leaf_count, start_at, end_at, \
+         previous_batch_id FROM {} WHERE tenant = '{}' AND {conditions} ORDER BY {order} LIMIT 1",
+        prefixed(MERKLE_BATCHES),
+        escape_single(tenant)
+    );
+    let rows: Vec<MerkleBatchRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .next()
+        .map(|row| {
+            Ok(MerkleBatch {
+                tenant: tenant.to_string(),
+                leaf_count: row
+                    .leaf_count
+                    .parse()
+                    .with_context(|| format!("invalid leaf count {}", row.leaf_count))?,
+                start_at: parse_clickhouse_datetime(&row.start_at)?,
+                end_at: parse_clickhouse_datetime(&row.end_at)?,
+                batch_id: row.batch_id,
+                root: row.root,
+                previous_batch_id: row.previous_batch_id,
+            })
+        })
+        .transpose()
+}
+
+/// The tenant's most recent Merkle batch, `None` before the first one.
+pub async fn latest_merkle_batch(tenant: &str) -> Result<Option<MerkleBatch>> {
+    select_merkle_batch(tenant, "1", "end_at DESC").await
+}
+
+/// The first Merkle batch holding a dataitem, `None` until one is built.
+pub async fn merkle_batch_of(tenant: &str, dataitem_id: &str) -> Result<Option<MerkleBatch>> {
+    // leaves of a batch whose publication failed have no batch row and are ignored
+    let conditions = format!(
+        "batch_id IN (SELECT batch_id FROM {} WHERE tenant = '{}' AND dataitem_id = '{}')",
+        prefixed(MERKLE_LEAVES),
+        escape_single(tenant),
+        escape_single(dataitem_id)
+    );
+    select_merkle_batch(tenant, &conditions, "end_at").await
+}
+
+/// IDs of the dataitems a tenant first indexed in `[start, end)`, oldest first.
+pub async fn dataitems_indexed_between(
+    tenant: &str,
+    start: &DateTime<Utc>,
+    end: &DateTime<Utc>,
+) -> Result<Vec<String>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT dataitem_id FROM {}
+         WHERE tenant = '{}'
+         GROUP BY dataitem_id
+         HAVING min(created_at) >= {} AND min(created_at) < {}
+         ORDER BY min(created_at), dataitem_id",
+        prefixed(DATAITEM_TAGS),
+        escape_single(tenant),
+        datetime_literal(start),
+        datetime_literal(end)
+    );
+    let rows: Vec<DataitemIdRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().map(|row| row.dataitem_id).collect())
+}
+
+/// The dataitem IDs of a Merkle batch, in leaf order.
+pub async fn merkle_leaves(tenant: &str, batch_id: &str) -> Result<Vec<String>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT dataitem_id FROM {} WHERE tenant = '{}' AND batch_id = '{}' ORDER BY leaf_index",
+        prefixed(MERKLE_LEAVES),
+        escape_single(tenant),
+        escape_single(batch_id)
+    );
+    let rows: Vec<DataitemIdRow> = select_rows(&sql).await?;
+    Ok(rows.into_iter().map(|row| row.dataitem_id).collect())
+}
+
+/// Save a Merkle batch: its leaves first, then the batch itself, which makes them visible.
+pub async fn save_merkle_batch(batch: &MerkleBatch, leaves: &[String]) -> Result<()> {
+    ensure_schema().await?;
+    for (chunk_index, chunk) in leaves.chunks(MERKLE_LEAVES_PER_INSERT).enumerate() {
+        let placeholders = vec!["(?, ?, ?, ?)"; chunk.len()].join(", ");
+        let mut query = client()?.query(&format!(
+            "INSERT INTO {} (tenant, batch_id, leaf_index, dataitem_id) VALUES {placeholders}",
+            prefixed(MERKLE_LEAVES)
+        ));
+        for (offset, dataitem_id) in chunk.iter().enumerate() {
+            let leaf_index = (chunk_index * MERKLE_LEAVES_PER_INSERT + offset) as u64;
+            query =
+                query.bind(&batch.tenant).bind(&batch.batch_id).bind(leaf_index).bind(dataitem_id);
+        }
+        query
+            .execute_bounded()
+            .await
+            .with_context(|| format!("failed to save the leaves of batch {}", batch.batch_id))?;
+    }
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, batch_id, root, leaf_count, start_at, end_at, \
+             previous_batch_id) VALUES (?, ?, ?, ?, ?, ?, ?)",
+            prefixed(MERKLE_BATCHES)
+        ))
+        .bind(&batch.tenant)
+        .bind(&batch.batch_id)
+        .bind(&batch.root)
+        .bind(batch.leaf_count)
+        .bind(batch.start_at)
+        .bind(batch.end_at)
+        .bind(&batch.previous_batch_id)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save batch {}", batch.batch_id))?;
+    Ok(())
+}
+
+/// A named tag query of a tenant, see `core::feeds`.
+#[derive(Debug, Clone)]
+pub struct FeedRecord {
+    pub tenant: String,
+    pub name: String,
+    /// normalized filters, as `query_dataitems_by_tags` takes them
+    pub filters: Vec<(String, Vec<String>)>,
+    /// provenance principal of the key that saved the feed
+    pub created_by: String,
+    pub active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FeedRow {
+    tenant: String,
+    name: String,
+    filters: String,
+    created_by: String,
+    created_at: String,
+    updated_at: String,
+}
+
+/// Insert a new version of a feed, or remove it with `active` false.
+pub async fn save_feed(feed: &FeedRecord) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, name, filters, created_by, active, created_at, updated_at) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            prefixed(FEEDS)
+        ))
+        .bind(&feed.tenant)
+        .bind(&feed.name)
+        .bind(serde_json::to_string(&feed.filters)?)
+        .bind(&feed.created_by)
+        .bind(u8::from(feed.active))
+        .bind(feed.created_at)
+        .bind(feed.updated_at)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save feed {}", feed.name))?;
+    Ok(())
+}
+
+async fn select_feeds(conditions: &str) -> Result<Vec<FeedRecord>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT tenant, name, filters, created_by, created_at, updated_at FROM {} FINAL \
+         WHERE active = 1 AND {conditions} ORDER BY name",
+        prefixed(FEEDS)
+    );
+    let rows: Vec<FeedRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(FeedRecord {
+                filters: serde_json::from_str(&row.filters)
+                    .with_context(|| format!("invalid filters of feed {}", row.name))?,
+                created_at: parse_clickhouse_datetime(&row.created_at)?,
+                updated_at: parse_clickhouse_datetime(&row.updated_at)?,
+                tenant: row.tenant,
+                name: row.name,
+                created_by: row.created_by,
+                active: true,
+            })
+        })
+        .collect()
+}
+
+/// The tenant's feed `name`, `None` unless it exists.
+pub async fn get_feed(tenant: &str, name: &str) -> Result<Option<FeedRecord>> {
+    let conditions =
+        format!("tenant = '{}' AND name = '{}'", escape_single(tenant), escape_single(name));
+    Ok(select_feeds(&conditions).await?.into_iter().next())
+}
+
+/// Every feed of the tenant, by name.
+pub async fn list_feeds(tenant: &str) -> Result<Vec<FeedRecord>> {
+    select_feeds(&format!("tenant = '{}'", escape_single(tenant))).await
+}
+
+/// A version of a tenant's mutable name, see `core::names`.
+#[derive(Debug, Clone)]
+pub struct NameRecord {
+    pub tenant: String,
+    pub name: String,
+    /// 1 for the first dataitem the name pointed to, then incremented by every update
+    pub version: u64,
+    pub dataitem_id: String,
+    /// provenance principal of the key that pointed the name
+    pub updated_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct NameRow {
+    tenant: String,
+    name: String,
+    version: String,
+    dataitem_id: String,
+    updated_by: String,
+    created_at: String,
+}
+
+/// Insert a version of a name.
+pub async fn save_name_version(record: &NameRecord) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} (tenant, name, version, dataitem_id, updated_by, created_at) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            prefixed(NAMES)
+        ))
+        .bind(&record.tenant)
+        .bind(&record.name)
+        .bind(record.version)
+        .bind(&record.dataitem_id)
+        .bind(&record.updated_by)
+        .bind(record.created_at)
+        .execute_bounded()
+        .await
+        .with_context(|| {
+            format!("failed to save version {} of name {}", record.version, record.name)
+        })?;
+    Ok(())
+}
+
+async fn select_name_versions(
+    tenant: &str,
+    name: &str,
+    conditions: &str,
+    limit: usize,
+) -> Result<Vec<NameRecord>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT tenant, name, toString(version) AS version, dataitem_id, updated_by, created_at \
+         FROM {} WHERE tenant = '{}' AND name = '{}'{conditions} \
+         ORDER BY version DESC, created_at DESC LIMIT {limit}",
+        prefixed(NAMES),
+        escape_single(tenant),
+        escape_single(name)
+    );
+    let rows: Vec<NameRow> = select_rows(&sql).await?;
+    rows.into_iter()
+        .map(|row| {
+            Ok(NameRecord {
+                version: parse_count(&row.version)?,
+                created_at: parse_clickhouse_datetime(&row.created_at)?,
+                tenant: row.tenant,
+                name: row.name,
+                dataitem_id: row.dataitem_id,
+                updated_by: row.updated_by,
+            })
+        })
+        .collect()
+}
+
+/// The tenant's name at `version`, its latest version without one. `None` unless it exists.
+pub async fn get_name_version(
+    tenant: &str,
+    name: &str,
+    version: Option<u64>,
+) -> Result<Option<NameRecord>> {
+    let conditions = version.map(|version| format!(" AND version = {version}")).unwrap_or_default();
+    Ok(select_name_versions(tenant, name, &conditions, 1).await?.into_iter().next())
+}
+
+/// Up to `limit` versions of the tenant's name, the latest first.
+pub async fn name_history(tenant: &str, name: &str, limit: usize) -> Result<Vec<NameRecord>> {
+    select_name_versions(tenant, name, "", limit).await
+}
+
+/// An abuse report of a dataitem, see `core::reports`.
+#[derive(Debug, Clone)]
+pub struct AbuseReportRecord {
+    pub id: String,
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub reason: String,
+    pub details: String,
+    pub reporter_name: String,
+    pub reporter_email: String,
+    /// provenance source IP of the report
+    pub source_ip: String,
+    /// open, dismissed, blocked or deleted
+    pub state: String,
+    pub resolution_note: String,
+    /// provenance principal of the operator who resolved it
+    pub resolved_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbuseReportRow {
+    id: String,
+    tenant: String,
+    dataitem_id: String,
+    reason: String,
+    details: String,
+    reporter_name: String,
+    reporter_email: String,
+    source_ip: String,
+    state: String,
+    resolution_note: String,
+    resolved_by: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<AbuseReportRow> for AbuseReportRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: AbuseReportRow) -> Result<Self> {
+        Ok(AbuseReportRecord {
+            created_at: parse_clickhouse_datetime(&row.created_at)?,
+            updated_at: parse_clickhouse_datetime(&row.updated_at)?,
+            id: row.id,
+            tenant: row.tenant,
+            dataitem_id: row.dataitem_id,
+            reason: row.reason,
+            details: row.details,
+            reporter_name: row.reporter_name,
+            reporter_email: row.reporter_email,
+            source_ip: row.source_ip,
+            state: row.state,
+            resolution_note: row.resolution_note,
+            resolved_by: row.resolved_by,
+        })
+    }
+}
+
+const ABUSE_REPORT_COLUMNS: &str = "id, tenant, dataitem_id, reason, details, reporter_name, \
+     reporter_email, source_ip, state, resolution_note, resolved_by, created_at, updated_at";
+
+/// Insert `report`, or its new state when it already exists.
+pub async fn save_abuse_report(report: &AbuseReportRecord) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} ({ABUSE_REPORT_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            prefixed(ABUSE_REPORTS)
+        ))
+        .bind(&report.id)
+        .bind(&report.tenant)
+        .bind(&report.dataitem_id)
+        .bind(&report.reason)
+        .bind(&report.details)
+        .bind(&report.reporter_name)
+        .bind(&report.reporter_email)
+        .bind(&report.source_ip)
+        .bind(&report.state)
+        .bind(&report.resolution_note)
+        .bind(&report.resolved_by)
+        .bind(report.created_at)
+        .bind(report.updated_at)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save abuse report {}", report.id))?;
+    Ok(())
+}
+
+async fn select_abuse_reports(conditions: &str, limit: usize) -> Result<Vec<AbuseReportRecord>> {
+    ensure_schema().await?;
+    let sql = format!(
+        "SELECT {ABUSE_REPORT_COLUMNS} FROM {} FINAL WHERE {conditions} \
+         ORDER BY created_at DESC LIMIT {limit}",
+        prefixed(ABUSE_REPORTS)
+    );
+    let rows: Vec<AbuseReportRow> = select_rows(&sql).await?;
+    rows.into_iter().map(AbuseReportRecord::try_from).collect()
+}
+
+pub async fn get_abuse_report(id: &str) -> Result<Option<AbuseReportRecord>> {
+    let conditions = format!("id = '{}'", escape_single(id));
+    Ok(select_abuse_reports(&conditions, 1).await?.into_iter().next())
+}
+
+/// Latest abuse reports, optionally of one state, tenant and dataitem.
+pub async fn list_abuse_reports(
+    state: Option<&str>,
+    tenant: Option<&str>,
+    dataitem_id: Option<&str>,
+    limit: usize,
+) -> Result<Vec<AbuseReportRecord>> {
+    let mut conditions = vec!["1".to_string()];
+    if let Some(state) = state {
+        conditions.push(format!("state = '{}'", escape_single(state)));
+    }
+    if let Some(tenant) = tenant {
+        conditions.push(format!("tenant = '{}'", escape_single(tenant)));
+    }
+    if let Some(dataitem_id) = dataitem_id {
+        conditions.push(format!("dataitem_id = '{}'", escape_single(dataitem_id)));
+    }
+    select_abuse_reports(&conditions.join(" AND "), limit).await
+}
+
+/// A queued background job, see `core::jobs`.
+#[derive(Debug, Clone)]
+pub struct JobRecord {
+    pub id: String,
+    pub kind: String,
+    pub tenant: String,
+    /// JSON arguments of the job
+    pub payload: String,
+    /// pending, running, done or dead
+    pub state: String,
+    pub attempts: u32,
+    pub last_error: String,
+    pub run_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JobRow {
+    id: String,
+    kind: String,
+    tenant: String,
+    payload: String,
+    state: String,
+    attempts: u32,
+    last_error: String,
+    run_at: String,
+    created_at: String,
+    updated_at: String,
+}
+
+impl TryFrom<JobRow> for JobRecord {
+    type Error = anyhow::Error;
+
+    fn try_from(row: JobRow) -> Result<Self> {
+        Ok(JobRecord {
+            run_at: parse_clickhouse_datetime(&row.run_at)?,
+            created_at: parse_clickhouse_datetime(&row.created_at)?,
+            updated_at: parse_clickhouse_datetime(&row.updated_at)?,
+            id: row.id,
+            kind: row.kind,
+            tenant: row.tenant,
+            payload: row.payload,
+            state: row.state,
+            attempts: row.attempts,
+            last_error: row.last_error,
+        })
+    }
+}
+
+const JOB_COLUMNS: &str =
+    "id, kind, tenant, payload, state, attempts, last_error, run_at, created_at, updated_at";
+
+/// Insert `job`, or its new state when it already exists.
+pub async fn save_job(job: &JobRecord) -> Result<()> {
+    ensure_schema().await?;
+    client()?
+        .query(&format!(
+            "INSERT INTO {} ({JOB_COLUMNS}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            prefixed(JOBS)
+        ))
+        .bind(&job.id)
+        .bind(&job.kind)
+        .bind(&job.tenant)
+        .bind(&job.payload)
+        .bind(&job.state)
+        .bind(job.attempts)
+        .bind(&job.last_error)
+        .bind(job.run_at)
+        .bind(job.created_at)
+        .bind(job.updated_at)
+        .execute_bounded()
+        .await
+        .with_context(|| format!("failed to save job {}", job.id))?;
+    Ok(())
+}
+
+async fn select_jobs(conditions: &str, order: &str, limit: usize) -> Result<Vec<JobRecord>> {
+    ensure_schema().await?;
+    let jobs = prefixed(JOBS);
+    let sql = format!(
+        "SELECT {JOB_COLUMNS} FROM {jobs} FINAL WHERE {conditions} ORDER BY {order} LIMIT {limit}"
+    );
+    let rows: Vec<JobRow> = select_rows(&sql).await?;
+    rows.into_iter().map(JobRecord::try_from).collect()
+}
+
+/// Pending jobs whose `run_at` has passed, and running jobs not updated for `lease_secs`
+/// (their agent died mid-run), oldest first.
+pub async fn due_jobs(lease_secs: u64, limit: usize) -> Result<Vec<JobRecord>> {
+    let conditions = format!(
+        "(state = 'pending' AND run_at <= now64(3)) \
+         OR (state = 'running' AND updated_at < now64(3) - INTERVAL {lease_secs} SECOND)"
+    );
+    select_jobs(&conditions, "run_at", limit).await
+}
+
+pub async fn get_job(id: &str) -> Result<Option<JobRecord>> {
+    let conditions = format!("id = '{}'", escape_single(id));
+    Ok(select_jobs(&conditions, "id", 1).await?.into_iter().next())
+}
+
+/// Latest jobs, optionally of one state and kind.
+pub async fn list_jobs(
+    state: Option<&str>,
+    kind: Option<&str>,
+    limit: usize,
+) -> Result<Vec<JobRecord>> {
+    let mut conditions = vec!["1".to_string()];
+    if let Some(state) = state {
+        conditions.push(format!("state = '{}'", escape_single(state)));
+    }
+    if let Some(kind) = kind {
+        conditions.push(format!("kind = '{}'", escape_single(kind)));
+    }
+    select_jobs(&conditions.join(" AND "), "created_at DESC", limit).await
+}
+
+/// Output formats of `export_index`.
+#[derive(Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.to_ascii_lowercase().as_str() {
+            "ndjson" | "jsonl" => Some(ExportFormat::Ndjson),
+            "csv" => Some(ExportFormat::Csv),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+        }
+    }
+
+    fn clickhouse_format(self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "JSONEachRow",
+            ExportFormat::Csv => "CSVWithNames",
+        }
+    }
+}
+
+/// Stream the tenant's tag index rows, optionally restricted to `created_at` in `[from, to)`.
+pub async fn export_index(
+    tenant: &str,
+    format: ExportFormat,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> Result<impl Stream<Item = reqwest::Result<Bytes>> + use<>> {
+    ensure_schema().await?;
+
+    let mut conditions = vec![format!("tenant = '{}'", escape_single(tenant))];
+    if let Some(from) = from {
+        conditions.push(format!("created_at >= {}", datetime_literal(&from)));
+    }
+    if let Some(to) = to {
+        conditions.push(format!("created_at < {}", datetime_literal(&to)));
+    }
+
+    // FINAL collapses rows the ReplacingMergeTree has not merged yet
+    let tags = prefixed(DATAITEM_TAGS);
+    let sql = format!(
+        "SELECT tenant, dataitem_id, owner, content_type, created_at, tag_key, tag_value
+         FROM {tags} FINAL
+         WHERE {}
+         ORDER BY created_at, dataitem_id, tag_key
+         FORMAT {}",
+        conditions.join(" AND "),
+        format.clickhouse_format()
+    );
+
+    // only the response headers are bounded, the export itself may stream for long
+    let response = with_timeout("clickhouse", CLICKHOUSE_TIMEOUT_SECS, http_query(sql)).await?;
+    Ok(response.bytes_stream())
+}
+
+pub const DEFAULT_SYNC_LIMIT: usize = 1000;
+pub const MAX_SYNC_LIMIT: usize = 10_000;
+const DEFAULT_SYNC_SETTLE_SECS: u64 = 10;
+
+/// Decode a `since` cursor of `sync_dataitems`, `{created_at unix millis}.{dataitem_id}`. It is
+/// not signed nor expiring, unlike page cursors: gateways keep it for as long as they sync, and
+/// a forged one only moves the caller within its own tenant's catalogue.
+pub fn decode_sync_cursor(encoded: &str) -> Result<TagQueryCursor, CursorError> {
+    let (millis, dataitem_id) = encoded
+        .split_once('.')
+        .ok_or_else(|| CursorError::Malformed("expected millis.dataitem_id".to_string()))?;
+    let created_at = millis
+        .parse()
+        .ok()
+        .and_then(DateTime::from_timestamp_millis)
+        .ok_or_else(|| CursorError::Malformed(format!("invalid timestamp {millis:?}")))?;
+    Ok(TagQueryCursor { created_at, dataitem_id: dataitem_id.to_string() })
+}
+
+/// Stream up to `limit` of the tenant's live dataitems indexed after `since`, in indexing order,
+/// as NDJSON rows of `id`, `tags`, `content_type`, `size`, the `sha256` and `blake3` hex digests
+/// of the data (empty when not computed), `created_at` and the `cursor` to resume after the row.
+/// Rows younger than `SYNC_SETTLE_SECS` (default 10) are held back, so dataitems still being
+/// indexed, possibly with an earlier `created_at`, aren't skipped.
+pub async fn sync_dataitems(
+    tenant: &str,
+    since: Option<&TagQueryCursor>,
+    limit: usize,
+) -> Result<impl Stream<Item = reqwest::Result<Bytes>> + use<>> {
+    ensure_schema().await?;
+
+    let settle_secs = std::env::var("SYNC_SETTLE_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_SYNC_SETTLE_SECS);
+    let mut conditions = vec![
+        format!("tenant = '{}'", escape_single(tenant)),
+        format!("created_at <= now64(3) - INTERVAL {settle_secs} SECOND"),
+        not_deleted_condition(tenant),
+    ];
+    if let Some(since) = since {
+        conditions.push(format!(
+            "(created_at, dataitem_id) > ({}, '{}')",
+            datetime_literal(&since.created_at),
+            escape_single(&since.dataitem_id)
+        ));
+    }
+
+    // a dataitem's tag rows share its created_at and content type, so (created_at, dataitem_id)
+    // orders dataitems
+    let tags = prefixed(DATAITEM_TAGS);
+    let sql = format!(
+        "SELECT dataitem_id AS id,
+                arrayMap(t -> map('name', t.1, 'value', t.2),
+                         arraySort(groupUniqArray((tag_key, tag_value)))) AS tags,
+                content_type,
+                max(data_size) AS size,
+                max(content_sha256) AS sha256,
+                max(content_blake3) AS blake3,
+                created_at,
+                concat(toString(toUnixTimestamp64Milli(created_at)), '.', dataitem_id) AS cursor
+         FROM {tags} FINAL
+         WHERE {}
+         GROUP BY created_at, dataitem_id, content_type
+         ORDER BY created_at, dataitem_id
+         LIMIT {}
+         SETTINGS output_format_json_quote_64bit_integers = 0
+         FORMAT JSONEachRow",
+        conditions.join(" AND "),
+        limit.clamp(1, MAX_SYNC_LIMIT)
+    );
+
+    let response = with_timeout("clickhouse", CLICKHOUSE_TIMEOUT_SECS, http_query(sql)).await?;
+    Ok(response.bytes_stream())
+}
+
+fn datetime_literal(value: &DateTime<Utc>) -> String {
+    format!("toDateTime64('{}', 3, 'UTC')", value.format("%Y-%m-%d %H:%M:%S%.3f"))
+}
+
+/// Current cursor envelope version, bump it whenever the sort or the payload changes so
+/// cursors issued before are rejected instead of silently paging wrong.
+const CURSOR_VERSION: &str = "v1";
+const DEFAULT_CURSOR_TTL_SECS: i64 = 24 * 60 * 60;
+
+/// HMAC key of the cursors, `CURSOR_SECRET`. Without it a random per-process key is used and
+/// cursors don't survive restarts nor work across instances.
+static CURSOR_KEY: Lazy<Vec<u8>> = Lazy::new(|| match std::env::var("CURSOR_SECRET") {
+    Ok(secret) if !secret.is_empty() => secret.into_bytes(),
+    _ => {
+        println!("CURSOR_SECRET not set, pagination cursors are signed with an ephemeral key");
+        rand::random::<[u8; 32]>().to_vec()
+    }
+});
+
+#[derive(Debug)]
+pub enum CursorError {
+    Malformed(String),
+    UnsupportedVersion(String),
+    InvalidSignature,
+    Expired,
+}
+
+impl std::fmt::Display for CursorError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CursorError::Malformed(reason) => write!(f, "malformed cursor: {reason}"),
+            CursorError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported cursor version {version}, restart pagination from the first page"
+            ),
+            CursorError::InvalidSignature => write!(f, "cursor signature mismatch"),
+            CursorError::Expired => {
+                write!(f, "cursor expired, restart pagination from the first page")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CursorError {}
+
+#[derive(Serialize, Deserialize)]
+struct CursorPayload {
+    created_at: String,
+    dataitem_id: String,
+    /// unix seconds the cursor was issued at
+    issued_at: i64,
+}
+
+fn cursor_mac(version: &str, payload: &str) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(&CURSOR_KEY).expect("hmac accepts keys of any size");
+    mac.update(version.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+/// Decode a `{version}.{payload}.{signature}` cursor, rejecting tampered, outdated and
+/// expired (`CURSOR_TTL_SECS`, default a day) ones.
+pub fn decode_tag_query_cursor(encoded: &str) -> Result<TagQueryCursor, CursorError> {
+    let mut parts = encoded.split('.');
+    let (Some(version), Some(payload), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(CursorError::Malformed("expected version.payload.signature".to_string()));
+    };
+    if version != CURSOR_VERSION {
+        return Err(CursorError::UnsupportedVersion(version.to_string()));
+    }
+
+    let signature = general_purpose::URL_SAFE_NO_PAD
+        .decode(signature)
+        .map_err(|_| CursorError::Malformed("invalid signature encoding".to_string()))?;
+    cursor_mac(version, payload)
+        .verify_slice(&signature)
+        .map_err(|_| CursorError::InvalidSignature)?;
+
+    let raw = general_purpose::URL_SAFE_NO_PAD
+        .decode(payload)
+        .map_err(|_| CursorError::Malformed("invalid payload encoding".to_string()))?;
+    let payload: CursorPayload = serde_json::from_slice(&raw)
+        .map_err(|err| CursorError::Malformed(format!("invalid payload: {err}")))?;
+
+    let ttl = std::env::var("CURSOR_TTL_SECS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_CURSOR_TTL_SECS);
+    if Utc::now().timestamp() - payload.issued_at > ttl {
+        return Err(CursorError::Expired);
+    }
+
+    let created_at = DateTime::parse_from_rfc3339(&payload.created_at)
+        .map_err(|err| CursorError::Malformed(format!("invalid timestamp: {err}")))?
+        .with_timezone(&Utc);
+    Ok(TagQueryCursor { created_at, dataitem_id: payload.dataitem_id })
 }
 
 fn encode_tag_query_cursor(record: &DataitemRecord) -> Result<String> {
     let payload = CursorPayload {
         created_at: record.created_at.to_rfc3339(),
         dataitem_id: record.dataitem_id.clone(),
+        issued_at: Utc::now().timestamp(),
     };
     let raw = serde_json::to_vec(&payload).context("failed to encode pagination cursor")?;
-    Ok(general_purpose::STANDARD_NO_PAD.encode(raw))
+    let payload = general_purpose::URL_SAFE_NO_PAD.encode(raw);
+    let signature = general_purpose::URL_SAFE_NO_PAD
+        .encode(cursor_mac(CURSOR_VERSION, &payload).finalize().into_bytes());
+    Ok(format!("{CURSOR_VERSION}.{payload}.{signature}"))
 }
 
 fn escape_single(input: &str) -> String {
@@ -316,3 +2692,85 @@ fn parse_clickhouse_datetime(value: &str) -> Result<DateTime<Utc>> {
     }
     Err(anyhow!("unsupported datetime format returned by clickhouse: {value}"))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DATAITEM_ID: &str = "ZSobWuj5BXs9qPoIUdOzSRH5SE7UPFz-0i-a2nxr2sk";
+
+    fn record() -> DataitemRecord {
+        DataitemRecord {
+            dataitem_id: DATAITEM_ID.to_string(),
+            content_type: "text/plain".to_string(),
+            created_at: DateTime::parse_from_rfc3339("2026-10-16T09:21:41.123Z")
+                .unwrap()
+                .with_timezone(&Utc),
+            tags: Vec::new(),
+        }
+    }
+
+    fn signed(payload: &CursorPayload) -> String {
+        let payload = general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(payload).unwrap());
+        let signature = general_purpose::URL_SAFE_NO_PAD
+            .encode(cursor_mac(CURSOR_VERSION, &payload).finalize().into_bytes());
+        format!("{CURSOR_VERSION}.{payload}.{signature}")
+    }
+
+    #[test]
+    fn decodes_an_encoded_cursor() {
+        let cursor = decode_tag_query_cursor(&encode_tag_query_cursor(&record()).unwrap()).unwrap();
+        assert_eq!(cursor.dataitem_id, DATAITEM_ID);
+        assert_eq!(cursor.created_at, record().created_at);
+    }
+
+    #[test]
+    fn rejects_tampered_cursors() {
+        let encoded = encode_tag_query_cursor(&record()).unwrap();
+        let (_, signature) = encoded.rsplit_once('.').unwrap();
+        let forged = CursorPayload {
+            created_at: "2020-01-01T00:00:00+00:00".to_string(),
+            dataitem_id: DATAITEM_ID.to_string(),
+            issued_at: Utc::now().timestamp(),
+        };
+        let forged = general_purpose::URL_SAFE_NO_PAD.encode(serde_json::to_vec(&forged).unwrap());
+        let tampered = format!("{CURSOR_VERSION}.{forged}.{signature}");
+        assert!(matches!(decode_tag_query_cursor(&tampered), Err(CursorError::InvalidSignature)));
+    }
+
+    #[test]
+    fn rejects_expired_cursors() {
+        let expired = signed(&CursorPayload {
+            created_at: record().created_at.to_rfc3339(),
+            dataitem_id: DATAITEM_ID.to_string(),
+            issued_at: Utc::now().timestamp() - DEFAULT_CURSOR_TTL_SECS - 60,
+        });
+        assert!(matches!(decode_tag_query_cursor(&expired), Err(CursorError::Expired)));
+    }
+
+    #[test]
+    fn rejects_malformed_cursors() {
+        let encoded = encode_tag_query_cursor(&record()).unwrap();
+        let outdated = encoded.replacen(CURSOR_VERSION, "v0", 1);
+        assert!(matches!(
+            decode_tag_query_cursor(&outdated),
+            Err(CursorError::UnsupportedVersion(version)) if version == "v0"
+        ));
+        for malformed in ["", "v1.payload", &format!("{encoded}.extra"), &format!("{encoded}!")] {
+            assert!(
+                matches!(decode_tag_query_cursor(malformed), Err(CursorError::Malformed(_))),
+                "{malformed:?}"
+            );
+        }
+
+        let bad_timestamp = signed(&CursorPayload {
+            created_at: "yesterday".to_string(),
+            dataitem_id: DATAITEM_ID.to_string(),
+            issued_at: Utc::now().timestamp(),
+        });
+        assert!(matches!(
+            decode_tag_query_cursor(&bad_timestamp),
+            Err(CursorError::Malformed(reason)) if reason.starts_with("invalid timestamp")
+        ));
+    }
+}
@@ -0,0 +1,29 @@
+use once_cell::sync::Lazy;
+use std::{collections::BTreeMap, sync::Mutex};
+
+/// Process-wide counters and gauges, keyed by their Prometheus series name
+/// (labels included, e.g. `s3_retries_total{operation="put"}`).
+static METRICS: Lazy<Mutex<BTreeMap<String, f64>>> = Lazy::new(|| Mutex::new(BTreeMap::new()));
+
+pub(crate) fn increment(series: &str) {
+    add(series, 1.0);
+}
+
+pub(crate) fn add(series: &str, value: f64) {
+    let mut metrics = METRICS.lock().unwrap();
+    *metrics.entry(series.to_string()).or_insert(0.0) += value;
+}
+
+pub(crate) fn set_gauge(series: &str, value: f64) {
+    METRICS.lock().unwrap().insert(series.to_string(), value);
+}
+
+/// Render every series in the Prometheus text exposition format.
+pub(crate) fn render() -> String {
+    METRICS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(series, value)| format!("load_s3_agent_{series} {value}\n"))
+        .collect()
+}
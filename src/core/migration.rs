@@ -0,0 +1,213 @@
+use crate::core::{
+    jobs::{self, PREFIX_MIGRATION_JOB},
+    metadata::{JobRecord, get_job, list_jobs, save_job},
+    metrics,
+    models::{
+        PrefixMigration, PrefixMigrationProgress, PrefixMigrationRequest, PrefixMigrationStatus,
+    },
+    s3::{AgentConfig, list_all_objects},
+    storage::{StorageBackend, storage_backend},
+    tenant::{Tenant, tenant_by_name},
+};
+use anyhow::{Error, anyhow};
+use chrono::Utc;
+
+const DATAITEMS_PHASE: &str = "dataitems";
+const RAW_PHASE: &str = "raw";
+const DONE_PHASE: &str = "done";
+/// Objects handled between two checkpoints of the progress.
+const CHECKPOINT_INTERVAL: u64 = 100;
+
+/// A migration `start` refuses.
+#[derive(Debug)]
+pub(crate) struct InvalidMigration(pub String);
+
+impl std::fmt::Display for InvalidMigration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidMigration {}
+
+/// A migration of the tenant is already queued or running.
+#[derive(Debug)]
+pub(crate) struct MigrationInProgress(pub String);
+
+impl std::fmt::Display for MigrationInProgress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "prefix migration {} of the tenant is not finished yet", self.0)
+    }
+}
+
+impl std::error::Error for MigrationInProgress {}
+
+/// `prefix` without surrounding slashes, `None` when unset or already the current one.
+fn old_prefix(prefix: Option<String>, current: &str) -> Result<Option<String>, InvalidMigration> {
+    let Some(prefix) = prefix else {
+        return Ok(None);
+    };
+    let prefix = prefix.trim_matches('/').to_string();
+    if prefix.is_empty() || prefix.split('/').any(|segment| segment.is_empty() || segment == "..") {
+        return Err(InvalidMigration(format!("invalid prefix {prefix:?}")));
+    }
+    Ok(Some(prefix).filter(|prefix| prefix != current))
+}
+
+/// Queue a job copying the tenant's objects from the old prefixes of `request` to the current
+/// ones, returning its ID. Fails with `InvalidMigration` when there is nothing to migrate and
+/// `MigrationInProgress` while another migration of the tenant is unfinished.
+pub(crate) async fn start(
+    tenant: &Tenant,
+    request: PrefixMigrationRequest,
+) -> Result<(String, PrefixMigration), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let from_dir_name = old_prefix(request.from_dir_name, &agent_config.s3_dir_name)?;
+    let from_raw_dir_name = old_prefix(request.from_raw_dir_name, &agent_config.s3_raw_dir_name)?;
+    if from_dir_name.is_none() && from_raw_dir_name.is_none() {
+        return Err(InvalidMigration(
+            "from_dir_name or from_raw_dir_name must name a prefix other than the current one"
+                .to_string(),
+        )
+        .into());
+    }
+    for state in ["pending", "running"] {
+        let unfinished = list_jobs(Some(state), Some(PREFIX_MIGRATION_JOB), 100).await?;
+        if let Some(job) = unfinished.into_iter().find(|job| job.tenant == tenant.name) {
+            return Err(MigrationInProgress(job.id).into());
+        }
+    }
+
+    let migration = PrefixMigration {
+        from_dir_name,
+        from_raw_dir_name,
+        to_dir_name: agent_config.s3_dir_name,
+        to_raw_dir_name: agent_config.s3_raw_dir_name,
+        delete_source: request.delete_source,
+        progress: PrefixMigrationProgress {
+            phase: DATAITEMS_PHASE.to_string(),
+            ..Default::default()
+        },
+    };
+    let id = jobs::enqueue(PREFIX_MIGRATION_JOB, &tenant.name, serde_json::to_value(&migration)?)
+        .await?;
+    Ok((id, migration))
+}
+
+/// The prefix migration job `id`, `None` if there's no such migration.
+pub(crate) async fn status(id: &str) -> Result<Option<PrefixMigrationStatus>, Error> {
+    let Some(job) = get_job(id).await?.filter(|job| job.kind == PREFIX_MIGRATION_JOB) else {
+        return Ok(None);
+    };
+    Ok(Some(PrefixMigrationStatus {
+        migration: serde_json::from_str(&job.payload)?,
+        last_error: Some(job.last_error).filter(|e| !e.is_empty()),
+        updated_at: job.updated_at.to_rfc3339(),
+        job_id: job.id,
+        tenant: job.tenant,
+        state: job.state,
+        attempts: job.attempts,
+    }))
+}
+
+/// Record the progress in the job, which also renews its lease.
+async fn checkpoint(job: &mut JobRecord, migration: &PrefixMigration) -> Result<(), Error> {
+    job.payload = serde_json::to_string(migration)?;
+    job.updated_at = Utc::now();
+    save_job(job).await
+}
+
+/// Copy the objects directly under `from/` to `to/` in key order, from the progress cursor on.
+async fn migrate_prefix(
+    job: &mut JobRecord,
+    migration: &mut PrefixMigration,
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    from: &str,
+    to: &str,
+) -> Result<(), Error> {
+    let prefix = format!("{from}/");
+    let mut objects = list_all_objects(storage, bucket, &prefix).await?;
+    objects.sort_by(|a, b| a.key.cmp(&b.key));
+    // keys up to the cursor were handled by a previous attempt, and are gone if deleted
+    let cursor = migration.progress.cursor.clone();
+    objects.retain(|obj| cursor.as_ref().is_none_or(|cursor| obj.key > *cursor));
+    migration.progress.phase_objects = migration.progress.phase_processed + objects.len() as u64;
+    checkpoint(job, migration).await?;
+
+    for obj in objects {
+        let Some(name) = obj.key.strip_prefix(&prefix) else {
+            continue;
+        };
+        let target = format!("{to}/{name}");
+        let outcome = if storage.exists(bucket, &target).await? {
+            migration.progress.skipped += 1;
+            "skipped"
+        } else {
+            let object = storage.get_object(bucket, &obj.key).await?;
+            let content_type = object.content_type.as_deref().unwrap_or("application/octet-stream");
+            let size = object.data.len() as u64;
+            match object.content_encoding.as_deref() {
+                Some(encoding) => {
+                    storage
+                        .put_encoded(bucket, &target, object.data, content_type, encoding)
+                        .await?
+                }
+                None => storage.put(bucket, &target, object.data, content_type, None).await?,
+            }
+            migration.progress.copied += 1;
+            migration.progress.bytes_copied += size;
+            "copied"
+        };
+        if migration.delete_source {
+            storage.delete(bucket, &obj.key).await?;
+        }
+        metrics::increment(&format!("prefix_migration_objects_total{{result=\"{outcome}\"}}"));
+
+        migration.progress.cursor = Some(obj.key);
+        migration.progress.phase_processed += 1;
+        if migration.progress.phase_processed % CHECKPOINT_INTERVAL == 0 {
+            checkpoint(job, migration).await?;
+        }
+    }
+    println!(
+        "PREFIX MIGRATION: {} {from}/ -> {to}/ done, {} objects handled",
+        job.id, migration.progress.phase_processed
+    );
+    Ok(())
+}
+
+/// Run a `prefix_migration` job, the dataitems first then the raw bodies. The progress is
+/// checkpointed every `CHECKPOINT_INTERVAL` objects, so a retried or re-leased job resumes
+/// after the last checkpoint. Objects already at the new prefix are left untouched.
+pub(crate) async fn run_prefix_migration_job(job: &mut JobRecord) -> Result<(), Error> {
+    let mut migration: PrefixMigration = serde_json::from_str(&job.payload)
+        .map_err(|err| anyhow!("invalid prefix_migration payload: {err}"))?;
+    let tenant = tenant_by_name(&job.tenant)?;
+    let bucket = AgentConfig::for_tenant(&tenant).s3_bucket_name;
+    let storage = storage_backend().await?;
+
+    let phases = [DATAITEMS_PHASE, RAW_PHASE, DONE_PHASE];
+    let reached = phases.iter().position(|p| *p == migration.progress.phase).unwrap_or(0);
+    for phase in phases[reached..].iter().filter(|phase| **phase != DONE_PHASE) {
+        if migration.progress.phase != *phase {
+            migration.progress = PrefixMigrationProgress {
+                phase: phase.to_string(),
+                copied: migration.progress.copied,
+                skipped: migration.progress.skipped,
+                bytes_copied: migration.progress.bytes_copied,
+                ..Default::default()
+            };
+        }
+        let (from, to) = match *phase {
+            DATAITEMS_PHASE => (migration.from_dir_name.clone(), migration.to_dir_name.clone()),
+            _ => (migration.from_raw_dir_name.clone(), migration.to_raw_dir_name.clone()),
+        };
+        if let Some(from) = from {
+            migrate_prefix(job, &mut migration, storage.as_ref(), &bucket, &from, &to).await?;
+        }
+    }
+    migration.progress.phase = DONE_PHASE.to_string();
+    migration.progress.cursor = None;
+    checkpoint(job, &migration).await
+}
@@ -0,0 +1,58 @@
+use crate::core::utils::get_env_var;
+use anyhow::{Error, anyhow};
+
+pub(crate) const DEFAULT_CONTENT_TYPE: &str = "application/octet-stream";
+
+/// MIME type detected from the payload's magic bytes, if known.
+pub(crate) fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    infer::get(data).map(|kind| kind.mime_type())
+}
+
+/// The declared content type, or the sniffed one when nothing (or only the generic
+/// `application/octet-stream` multipart default) was declared.
+pub(crate) fn resolve_content_type(declared: Option<&str>, data: &[u8]) -> String {
+    match declared.map(str::trim).filter(|ct| !ct.is_empty()) {
+        Some(ct) if !ct.eq_ignore_ascii_case(DEFAULT_CONTENT_TYPE) => ct.to_string(),
+        _ => sniff_content_type(data).unwrap_or(DEFAULT_CONTENT_TYPE).to_string(),
+    }
+}
+
+fn env_mime_list(key: &str) -> Vec<String> {
+    get_env_var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|ct| ct.trim().to_lowercase())
+        .filter(|ct| !ct.is_empty())
+        .collect()
+}
+
+/// `image/*` style patterns match the whole top-level type, anything else matches exactly
+/// (ignoring parameters such as `; charset=utf-8`).
+fn mime_matches(pattern: &str, content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+    match pattern.strip_suffix("/*") {
+        Some(top_level) => essence.split('/').next() == Some(top_level),
+        None => pattern == "*" || pattern == essence,
+    }
+}
+
+/// Enforce `UPLOAD_ALLOWED_CONTENT_TYPES` / `UPLOAD_DENIED_CONTENT_TYPES` (comma separated,
+/// `type/*` wildcards supported). The denylist is checked against both the declared and the
+/// sniffed type, so executables can't be smuggled in under a harmless declared type.
+pub(crate) fn check_content_type_policy(declared: &str, data: &[u8]) -> Result<(), Error> {
+    let sniffed = sniff_content_type(data);
+
+    let denied = env_mime_list("UPLOAD_DENIED_CONTENT_TYPES");
+    for content_type in std::iter::once(declared).chain(sniffed) {
+        if denied.iter().any(|pattern| mime_matches(pattern, content_type)) {
+            return Err(anyhow!("content type {content_type} is not allowed"));
+        }
+    }
+
+    let allowed = env_mime_list("UPLOAD_ALLOWED_CONTENT_TYPES");
+    if !allowed.is_empty() && !allowed.iter().any(|pattern| mime_matches(pattern, declared)) {
+        return Err(anyhow!("content type {declared} is not allowed"));
+    }
+
+    Ok(())
+}
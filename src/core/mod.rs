@@ -1,8 +1,69 @@
+mod access_tokens;
+pub mod agent;
 mod ans104;
+mod arns;
+mod audit;
+mod blocklist;
 mod bundler;
+mod cancellation;
+mod chunked;
+mod compaction;
+mod concurrency;
+mod confirmations;
+mod cors;
+mod credits;
+mod direct_upload;
+mod disk_cache;
+mod extract;
+mod failover;
+mod feeds;
+mod fetch;
+mod gateway;
+mod gc;
+mod hits;
+mod hls;
+mod http;
+mod hyperbeam;
+mod idempotency;
+mod jobs;
 mod lcp;
-mod metadata;
-mod registry;
+mod lifecycle;
+mod limits;
+mod maintenance;
+mod merkle;
+pub mod metadata;
+mod metrics;
+mod migration;
+mod mime;
+pub mod models;
+mod names;
+mod openapi;
+mod progress;
+mod provenance;
+mod query_cache;
+mod raw_compression;
+mod receipts;
+mod refreshed;
+pub mod registry;
+mod reindex;
+mod reload;
+mod render;
+mod replication;
+mod reports;
+mod request_log;
+mod resilience;
 mod s3;
+mod s3_facade;
+mod scan;
+mod scheduler;
+mod selftest;
+mod serve;
 pub mod server;
+mod staging;
+pub mod storage;
+pub mod tenant;
+mod testvectors;
+mod tls;
+mod trash;
 mod utils;
+mod webhooks;
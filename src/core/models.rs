@@ -0,0 +1,1318 @@
+use crate::core::{
+    metadata::IndexingDisabled,
+    provenance,
+    registry::RegistryEntry,
+    resilience::{BreakerOpen, DependencyTimeout},
+    testvectors::TestVector,
+};
+use axum::{
+    Json,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use bundles_rs::bundler::SendTransactionResponse;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use utoipa::{IntoParams, ToSchema};
+
+/// Body of every error response.
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// machine-readable error code, e.g. `not_found` or `dependency_timeout`
+    pub code: String,
+    pub message: String,
+    /// same as `message`, kept for clients of the former error shape
+    pub error: String,
+    /// `X-Request-Id` of the failed request, to correlate with the agent's logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// upstream dependency that timed out (504) or is failing fast (503)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependency: Option<String>,
+}
+
+/// An error answered by a route, rendered as an `ErrorResponse` with the variant's status.
+#[derive(Debug)]
+pub enum AgentError {
+    BadRequest(String),
+    Unauthorized(String),
+    PaymentRequired(String),
+    Forbidden(String),
+    NotFound(String),
+    Conflict(String),
+    Gone(String),
+    PayloadTooLarge(String),
+    UnsupportedMediaType(String),
+    Unprocessable(String),
+    UnavailableForLegalReasons(String),
+    Internal(String),
+    NotImplemented(String),
+    BadGateway(String),
+    /// the agent or one of its dependencies can't serve the request right now
+    Unavailable {
+        message: String,
+        dependency: Option<String>,
+    },
+    DependencyTimeout {
+        message: String,
+        dependency: String,
+    },
+    /// any other status
+    Other {
+        status: StatusCode,
+        message: String,
+    },
+}
+
+impl AgentError {
+    /// The variant answering with `status`.
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        let message = message.into();
+        match status {
+            StatusCode::BAD_REQUEST => AgentError::BadRequest(message),
+            StatusCode::UNAUTHORIZED => AgentError::Unauthorized(message),
+            StatusCode::PAYMENT_REQUIRED => AgentError::PaymentRequired(message),
+            StatusCode::FORBIDDEN => AgentError::Forbidden(message),
+            StatusCode::NOT_FOUND => AgentError::NotFound(message),
+            StatusCode::CONFLICT => AgentError::Conflict(message),
+            StatusCode::GONE => AgentError::Gone(message),
+            StatusCode::PAYLOAD_TOO_LARGE => AgentError::PayloadTooLarge(message),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => AgentError::UnsupportedMediaType(message),
+            StatusCode::UNPROCESSABLE_ENTITY => AgentError::Unprocessable(message),
+            StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => {
+                AgentError::UnavailableForLegalReasons(message)
+            }
+            StatusCode::INTERNAL_SERVER_ERROR => AgentError::Internal(message),
+            StatusCode::NOT_IMPLEMENTED => AgentError::NotImplemented(message),
+            StatusCode::BAD_GATEWAY => AgentError::BadGateway(message),
+            StatusCode::SERVICE_UNAVAILABLE => {
+                AgentError::Unavailable { message, dependency: None }
+            }
+            status => AgentError::Other { status, message },
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AgentError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            AgentError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            AgentError::PaymentRequired(_) => StatusCode::PAYMENT_REQUIRED,
+            AgentError::Forbidden(_) => StatusCode::FORBIDDEN,
+            AgentError::NotFound(_) => StatusCode::NOT_FOUND,
+            AgentError::Conflict(_) => StatusCode::CONFLICT,
+            AgentError::Gone(_) => StatusCode::GONE,
+            AgentError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            AgentError::UnsupportedMediaType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AgentError::Unprocessable(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            AgentError::UnavailableForLegalReasons(_) => StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS,
+            AgentError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            AgentError::NotImplemented(_) => StatusCode::NOT_IMPLEMENTED,
+            AgentError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            AgentError::Unavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            AgentError::DependencyTimeout { .. } => StatusCode::GATEWAY_TIMEOUT,
+            AgentError::Other { status, .. } => *status,
+        }
+    }
+
+    /// Stable machine-readable code of the error.
+    pub fn code(&self) -> &'static str {
+        match self {
+            AgentError::BadRequest(_) => "bad_request",
+            AgentError::Unauthorized(_) => "unauthorized",
+            AgentError::PaymentRequired(_) => "payment_required",
+            AgentError::Forbidden(_) => "forbidden",
+            AgentError::NotFound(_) => "not_found",
+            AgentError::Conflict(_) => "conflict",
+            AgentError::Gone(_) => "gone",
+            AgentError::PayloadTooLarge(_) => "payload_too_large",
+            AgentError::UnsupportedMediaType(_) => "unsupported_media_type",
+            AgentError::Unprocessable(_) => "unprocessable",
+            AgentError::UnavailableForLegalReasons(_) => "unavailable_for_legal_reasons",
+            AgentError::Internal(_) => "internal_error",
+            AgentError::NotImplemented(_) => "not_implemented",
+            AgentError::BadGateway(_) => "bad_gateway",
+            AgentError::Unavailable { .. } => "unavailable",
+            AgentError::DependencyTimeout { .. } => "dependency_timeout",
+            AgentError::Other { .. } => "error",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AgentError::BadRequest(message)
+            | AgentError::Unauthorized(message)
+            | AgentError::PaymentRequired(message)
+            | AgentError::Forbidden(message)
+            | AgentError::NotFound(message)
+            | AgentError::Conflict(message)
+            | AgentError::Gone(message)
+            | AgentError::PayloadTooLarge(message)
+            | AgentError::UnsupportedMediaType(message)
+            | AgentError::Unprocessable(message)
+            | AgentError::UnavailableForLegalReasons(message)
+            | AgentError::Internal(message)
+            | AgentError::NotImplemented(message)
+            | AgentError::BadGateway(message)
+            | AgentError::Unavailable { message, .. }
+            | AgentError::DependencyTimeout { message, .. }
+            | AgentError::Other { message, .. } => message,
+        }
+    }
+
+    fn dependency(&self) -> Option<&str> {
+        match self {
+            AgentError::Unavailable { dependency, .. } => dependency.as_deref(),
+            AgentError::DependencyTimeout { dependency, .. } => Some(dependency),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for AgentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl IntoResponse for AgentError {
+    fn into_response(self) -> Response {
+        let request_id = Some(provenance::current().request_id).filter(|id| !id.is_empty());
+        let body = ErrorResponse {
+            code: self.code().to_string(),
+            message: self.message().to_string(),
+            error: self.message().to_string(),
+            request_id,
+            dependency: self.dependency().map(str::to_string),
+        };
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+pub(crate) fn api_error(status: StatusCode, error: impl Into<String>) -> AgentError {
+    AgentError::new(status, error)
+}
+
+/// `status` with `{context}: {err}`, or an error naming the dependency when `err` is a timeout
+/// (504) or an open circuit breaker (503), and `501` when indexing is disabled.
+pub(crate) fn upstream_error(status: StatusCode, context: &str, err: &anyhow::Error) -> AgentError {
+    let message = format!("{context}: {err}");
+    if let Some(timeout) = err.downcast_ref::<DependencyTimeout>() {
+        AgentError::DependencyTimeout { message, dependency: timeout.dependency.clone() }
+    } else if let Some(open) = err.downcast_ref::<BreakerOpen>() {
+        AgentError::Unavailable { message, dependency: Some(open.dependency.clone()) }
+    } else if err.is::<IndexingDisabled>() {
+        AgentError::NotImplemented(message)
+    } else {
+        AgentError::new(status, message)
+    }
+}
+
+/// Matches dataitems tagged `key` with `value` or any of `values`.
+#[derive(Deserialize, ToSchema)]
+pub struct TagFilter {
+    pub key: String,
+    #[serde(default)]
+    pub value: Option<String>,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+impl TagFilter {
+    pub fn all_values(&self) -> Vec<String> {
+        self.value.iter().chain(self.values.iter()).cloned().collect()
+    }
+}
+
+#[derive(Deserialize, ToSchema)]
+pub struct TagQueryRequest {
+    pub filters: Vec<TagFilter>,
+    /// page size, defaults to 25 and capped at 100
+    #[serde(default)]
+    pub first: Option<usize>,
+    /// `page_info.next_cursor` of the previous page
+    #[serde(default)]
+    pub after: Option<String>,
+}
+
+/// Body of `POST /feeds`.
+#[derive(Deserialize, ToSchema)]
+pub struct CreateFeedRequest {
+    /// 1 to 64 lowercase letters, digits, `-` or `_`
+    pub name: String,
+    /// matched like `POST /tags/query` filters
+    pub filters: Vec<TagFilter>,
+}
+
+/// Body of `PUT /names/{name}`.
+#[derive(Deserialize, ToSchema)]
+pub struct PointNameRequest {
+    /// dataitem the name points to from now on
+    pub dataitem_id: String,
+    /// fail with 409 unless the name is still at this version, 0 for a new name
+    pub expected_version: Option<u64>,
+}
+
+/// Body of `POST /exists`.
+#[derive(Deserialize, ToSchema)]
+pub struct ExistsRequest {
+    /// up to 1,000 dataitem IDs
+    pub ids: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DataitemPresence {
+    pub id: String,
+    /// whether anything is stored for the dataitem
+    pub exists: bool,
+    /// the raw body served by `/{dataitem_id}`
+    pub raw: bool,
+    /// the signed ANS-104 dataitem
+    pub ans104: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ExistsResponse {
+    pub items: Vec<DataitemPresence>,
+    pub present: usize,
+    pub missing: usize,
+}
+
+/// Body of `POST /items/get`.
+#[derive(Deserialize, ToSchema)]
+pub struct ItemsGetRequest {
+    /// up to 100 dataitem IDs
+    pub ids: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FetchedItem {
+    pub id: String,
+    /// `ok`, `not_found`, `blocked`, `deleted` or `error`
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// data bytes, when indexed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    /// presigned URL (or gateway URL) of the raw body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    /// the base64 raw body of small dataitems, instead of `url`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ItemsGetResponse {
+    /// in request order
+    pub items: Vec<FetchedItem>,
+}
+
+/// Query of `GET /export/index`.
+#[derive(Deserialize, IntoParams)]
+pub struct ExportParams {
+    /// `ndjson` (default) or `csv`
+    pub format: Option<String>,
+    /// only rows indexed at or after this RFC 3339 timestamp
+    pub from: Option<DateTime<Utc>>,
+    /// only rows indexed before this RFC 3339 timestamp
+    pub to: Option<DateTime<Utc>>,
+}
+
+/// Query of `GET /sync/dataitems`.
+#[derive(Deserialize, IntoParams)]
+pub struct SyncParams {
+    /// `cursor` of the last row already synced, from the start of the catalogue without it
+    pub since: Option<String>,
+    /// rows per response, defaults to 1000 and capped at 10000
+    pub limit: Option<usize>,
+}
+
+/// Pagination query of the listing routes.
+#[derive(Deserialize, IntoParams)]
+pub struct PageParams {
+    /// page size, defaults to 25 and capped at 100
+    pub first: Option<usize>,
+    /// `page_info.next_cursor` of the previous page
+    pub after: Option<String>,
+}
+
+/// Query of `GET /recent`.
+#[derive(Deserialize, IntoParams)]
+pub struct RecentParams {
+    /// number of dataitems, defaults to 25 and capped at 100
+    pub limit: Option<usize>,
+    /// `page_info.next_cursor` of the previous page
+    pub after: Option<String>,
+}
+
+/// Query of `GET /{id}`.
+#[derive(Deserialize, IntoParams)]
+pub struct ServeParams {
+    /// serve as an attachment named after the upload's `filename` (proxied in redirect mode)
+    #[serde(default)]
+    pub download: bool,
+    /// agent access token of the dataitem, served whatever the `SERVE_MODE`
+    pub token: Option<String>,
+}
+
+/// Query of `GET /names/{name}`.
+#[derive(Deserialize, IntoParams)]
+pub struct NameParams {
+    /// resolve this version rather than the latest
+    pub version: Option<u64>,
+    /// answer the version as JSON instead of redirecting to its dataitem
+    #[serde(default)]
+    pub resolve: bool,
+}
+
+/// Query of `GET /{id}/render`.
+#[derive(Deserialize, IntoParams)]
+pub struct RenderParams {
+    /// max width in pixels, aspect ratio is preserved
+    pub w: Option<u32>,
+    /// max height in pixels, aspect ratio is preserved
+    pub h: Option<u32>,
+    /// webp (default), png or jpeg
+    pub format: Option<String>,
+}
+
+/// Query of the S3 facade's `ListObjectsV2`.
+#[derive(Deserialize, IntoParams)]
+pub struct ListObjectsParams {
+    #[serde(default)]
+    pub prefix: String,
+    pub delimiter: Option<String>,
+    /// capped at 1000
+    #[serde(rename = "max-keys")]
+    #[param(rename = "max-keys")]
+    pub max_keys: Option<usize>,
+    #[serde(rename = "continuation-token")]
+    #[param(rename = "continuation-token")]
+    pub continuation_token: Option<String>,
+    #[serde(rename = "start-after")]
+    #[param(rename = "start-after")]
+    pub start_after: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, ToSchema)]
+pub struct UploadTag {
+    pub key: String,
+    pub value: String,
+}
+
+/// multipart form accepted by the upload routes
+#[derive(ToSchema)]
+#[allow(dead_code)]
+pub struct UploadForm {
+    #[schema(value_type = String, format = Binary)]
+    pub file: Vec<u8>,
+    /// fallback mime type when the file part has none
+    pub content_type: Option<String>,
+    /// JSON array of `{"key": "...", "value": "..."}` objects (public unsigned uploads only)
+    pub tags: Option<String>,
+    /// one `key:value` tag per repeated field, merged with `tags`
+    pub tag: Option<Vec<String>>,
+    /// original file name, added as the `File-Name` tag and served on `?download=true`
+    pub filename: Option<String>,
+    /// gzip compressed JSON array like `tags`, merged with it
+    #[schema(rename = "tags.json.gz", value_type = Option<String>, format = Binary)]
+    pub tags_json_gz: Option<Vec<u8>>,
+}
+
+/// Body of `POST /upload/from-url`.
+#[derive(Deserialize, ToSchema)]
+pub struct UploadFromUrlRequest {
+    /// http(s) URL of the source, downloaded by the agent
+    pub url: String,
+    /// overrides the source's `Content-Type`
+    pub content_type: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<UploadTag>,
+    /// original file name, added as the `File-Name` tag
+    pub filename: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AgentInfo {
+    /// `running`, or `maintenance` while writes are rejected
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintenance_message: Option<String>,
+    pub name: String,
+    pub version: String,
+    pub address: String,
+    pub object_size_limit: usize,
+    /// smaller limits of specific routes, keyed by route pattern
+    pub route_size_limits: BTreeMap<String, usize>,
+    /// size limit tier of the presented API key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_limit_tier: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_size_limit: Option<usize>,
+    pub presigned_url_expiry: u64,
+    pub data_protocol: String,
+    pub hyperbeam_node_url: String,
+    pub upstreams: UpstreamUrls,
+}
+
+/// Upstream services the agent talks to, as configured.
+#[derive(Serialize, ToSchema)]
+pub struct UpstreamUrls {
+    pub auth_server: String,
+    pub hyperbeam_node: String,
+    /// unset when neither `LCP_API_URL` nor `AWS_ENDPOINT_URL` is configured
+    pub lcp_api: Option<String>,
+    /// unset when the gateway fallback is disabled
+    pub arweave_gateway: Option<String>,
+}
+
+/// Query of `POST /upload`.
+#[derive(Deserialize, IntoParams)]
+pub struct UploadOptions {
+    /// answer with a job ID once the body is received and store it in the background
+    #[serde(rename = "async", default)]
+    #[param(rename = "async")]
+    pub run_async: bool,
+    /// validate and sign the upload, answering with its dataitem ID and tags without storing it
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// A staged upload, published by `POST /upload/commit/{staging_id}`.
+#[derive(Serialize, ToSchema)]
+pub struct StageResponse {
+    pub success: bool,
+    pub staging_id: String,
+    /// when the uncommitted upload is removed, absent once discarded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+    pub message: String,
+}
+
+/// Answer of requests deferred to a background job.
+#[derive(Serialize, ToSchema)]
+pub struct JobAccepted {
+    pub success: bool,
+    pub job_id: String,
+    pub message: String,
+}
+
+/// Query of `POST /post/{id}`.
+#[derive(Deserialize, IntoParams)]
+pub struct PostDataitemParams {
+    /// queue the post as a background job, retried until the bundler accepts it
+    #[serde(rename = "async", default)]
+    #[param(rename = "async")]
+    pub run_async: bool,
+    /// queue the post to run at this RFC 3339 time, e.g. in a cheaper fee window
+    pub post_at: Option<String>,
+    /// queue the post to run this many seconds from now
+    pub delay_seconds: Option<u64>,
+}
+
+/// Query of `GET /admin/jobs`.
+#[derive(Deserialize, IntoParams)]
+pub struct JobsParams {
+    /// pending, running, done or dead
+    pub state: Option<String>,
+    pub kind: Option<String>,
+    /// default 50, max 500
+    pub limit: Option<usize>,
+}
+
+/// A background job of `GET /admin/jobs`.
+#[derive(Serialize, ToSchema)]
+pub struct JobInfo {
+    pub id: String,
+    pub kind: String,
+    pub tenant: String,
+    pub payload: serde_json::Value,
+    /// pending, running, done or dead
+    pub state: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub run_at: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct JobsResponse {
+    pub jobs: Vec<JobInfo>,
+}
+
+/// Body of `POST /admin/migrations/prefix`, the layout the tenant's objects were stored under
+/// before `S3_DIR_NAME`/`S3_RAW_DIR_NAME` (or the tenant's prefixes) changed.
+#[derive(Deserialize, ToSchema)]
+pub struct PrefixMigrationRequest {
+    /// previous prefix of the ANS-104 dataitems
+    pub from_dir_name: Option<String>,
+    /// previous prefix of the raw bodies
+    pub from_raw_dir_name: Option<String>,
+    /// delete each object from the old prefix once it is at the new one
+    #[serde(default)]
+    pub delete_source: bool,
+}
+
+/// How far a prefix migration got, checkpointed in its job so a rerun resumes from there.
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema)]
+pub struct PrefixMigrationProgress {
+    /// dataitems, raw or done
+    pub phase: String,
+    /// last key of the phase's old prefix handled
+    pub cursor: Option<String>,
+    /// objects under the phase's old prefix
+    pub phase_objects: u64,
+    pub phase_processed: u64,
+    pub copied: u64,
+    /// objects already at the new prefix
+    pub skipped: u64,
+    pub bytes_copied: u64,
+}
+
+/// Payload of a `prefix_migration` job.
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct PrefixMigration {
+    pub from_dir_name: Option<String>,
+    pub from_raw_dir_name: Option<String>,
+    /// the prefixes in effect when the migration was started
+    pub to_dir_name: String,
+    pub to_raw_dir_name: String,
+    pub delete_source: bool,
+    #[serde(default)]
+    pub progress: PrefixMigrationProgress,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PrefixMigrationStatus {
+    pub job_id: String,
+    pub tenant: String,
+    /// pending, running, done or dead, see `GET /admin/jobs`
+    pub state: String,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub updated_at: String,
+    pub migration: PrefixMigration,
+}
+
+/// Query of `GET /admin/provenance`, at least one filter is required.
+#[derive(Deserialize, IntoParams)]
+pub struct ProvenanceParams {
+    pub dataitem_id: Option<String>,
+    /// `server_key:<sha256 prefix>` or `load_acc:<sha256 prefix>` of the uploader's API key
+    pub principal: Option<String>,
+    pub source_ip: Option<String>,
+    /// defaults to 100, capped at 1000
+    pub limit: Option<usize>,
+}
+
+/// Who uploaded a dataitem and from where, as recorded at indexing time.
+#[derive(Serialize, ToSchema)]
+pub struct UploadProvenance {
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub content_type: String,
+    /// empty for dataitems indexed before provenance was recorded
+    pub principal: String,
+    pub source_ip: String,
+    pub user_agent: String,
+    pub indexed_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ProvenanceResponse {
+    pub items: Vec<UploadProvenance>,
+}
+
+/// Body of `POST /admin/blocklist`.
+#[derive(Deserialize, ToSchema)]
+pub struct BlockRequest {
+    /// `id` for a dataitem ID, `sha256` for the hex sha256 of a dataitem's data
+    pub kind: String,
+    pub value: String,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BlocklistEntry {
+    pub kind: String,
+    pub value: String,
+    pub reason: String,
+    /// false once lifted
+    pub active: bool,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BlocklistResponse {
+    pub entries: Vec<BlocklistEntry>,
+}
+
+/// Body of `POST /report/{id}`.
+#[derive(Deserialize, ToSchema)]
+pub struct AbuseReportRequest {
+    /// e.g. `copyright`, `illegal`, `spam`
+    pub reason: String,
+    /// what is infringing and why, e.g. the DMCA notice
+    #[serde(default)]
+    pub details: Option<String>,
+    #[serde(default)]
+    pub reporter_name: Option<String>,
+    #[serde(default)]
+    pub reporter_email: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AbuseReportAccepted {
+    pub success: bool,
+    pub report_id: String,
+}
+
+/// Query of `GET /admin/reports`.
+#[derive(Deserialize, IntoParams)]
+pub struct AbuseReportsParams {
+    /// open (default), dismissed, blocked, deleted or all
+    pub state: Option<String>,
+    /// only the reports of this tenant, every tenant's when unset
+    pub tenant: Option<String>,
+    /// default 50, max 500
+    pub limit: Option<usize>,
+}
+
+/// An abuse report of `GET /admin/reports`.
+#[derive(Serialize, ToSchema)]
+pub struct AbuseReportInfo {
+    pub id: String,
+    pub tenant: String,
+    pub dataitem_id: String,
+    pub reason: String,
+    pub details: String,
+    pub reporter_name: String,
+    pub reporter_email: String,
+    pub source_ip: String,
+    /// open, dismissed, blocked or deleted
+    pub state: String,
+    pub resolution_note: String,
+    pub resolved_by: String,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct AbuseReportsResponse {
+    pub reports: Vec<AbuseReportInfo>,
+}
+
+/// Body of `POST /admin/reports/{id}/resolve`.
+#[derive(Deserialize, ToSchema)]
+pub struct ResolveReportRequest {
+    /// `block` blocklists the dataitem, `delete` soft-deletes it and `dismiss` leaves it served
+    pub action: String,
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+/// Soft-deletion state of a dataitem, see `DELETE /{id}` and `POST /{id}/restore`.
+#[derive(Serialize, ToSchema)]
+pub struct DeletionInfo {
+    pub dataitem_id: String,
+    /// `deleted`, `restored` or `purged`
+    pub state: String,
+    pub deleted_at: String,
+    /// when the GC may permanently remove a `deleted` dataitem's objects
+    pub purge_after: Option<String>,
+}
+
+/// A recurring maintenance task of `GET /admin/schedule`.
+#[derive(Serialize, ToSchema)]
+pub struct ScheduledTask {
+    pub name: String,
+    /// variable holding the task's schedule
+    pub env: String,
+    /// configured schedule, absent when the task is disabled
+    pub schedule: Option<String>,
+    pub interval_secs: Option<u64>,
+    pub running: bool,
+    pub last_run: Option<String>,
+    pub last_duration_ms: Option<u64>,
+    /// summary of the last successful run
+    pub last_result: Option<String>,
+    pub last_error: Option<String>,
+    pub next_run: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScheduleResponse {
+    pub tasks: Vec<ScheduledTask>,
+}
+
+/// Deferred upload status of `GET /jobs/{id}`.
+#[derive(Serialize, ToSchema)]
+pub struct UploadJob {
+    pub job_id: String,
+    /// queued, running, done or failed
+    pub state: String,
+    /// detailed stage, as in the upload progress
+    pub stage: String,
+    pub bytes_written: u64,
+    pub dataitem_id: Option<String>,
+    pub error: Option<String>,
+    pub updated_at: String,
+}
+
+/// Progress of an upload sent with an `x-upload-id` header.
+#[derive(Serialize, ToSchema)]
+pub struct UploadProgress {
+    pub upload_id: String,
+    /// receiving, queued, signing, storing, indexing, done or failed
+    pub stage: String,
+    /// request body size announced by the client, if any
+    pub bytes_expected: Option<u64>,
+    /// file bytes received so far
+    pub bytes_received: u64,
+    /// bytes written to storage so far (the ANS-104 dataitem and its raw body)
+    pub bytes_written: u64,
+    pub dataitem_id: Option<String>,
+    pub error: Option<String>,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct StorageStats {
+    pub total_dataitems_count: u32,
+    pub total_dataitems_size: u64,
+    /// when the stats were last reconciled, absent when counted for this request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reconciled_at: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TestVectorsResponse {
+    pub success: bool,
+    pub count: usize,
+    pub vectors: Vec<TestVector>,
+}
+
+/// A signed dataitem as parsed by the agent, see `POST /id`.
+#[derive(Serialize, ToSchema)]
+pub struct DataitemIdResponse {
+    pub dataitem_id: String,
+    /// b64url(sha256(owner)) of the signer
+    pub owner: String,
+    pub tags: Vec<UploadTag>,
+    /// bytes after the header, 0 when only the header was sent
+    pub data_size: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TagQueryItem {
+    pub dataitem_id: String,
+    pub content_type: String,
+    pub created_at: String,
+    /// every indexed tag of the dataitem, not only the matched ones
+    pub tags: Vec<UploadTag>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PageInfo {
+    pub has_next_page: bool,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TagQueryResponse {
+    pub success: bool,
+    pub count: usize,
+    pub items: Vec<TagQueryItem>,
+    pub page_info: PageInfo,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedFilter {
+    pub key: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedInfo {
+    pub name: String,
+    pub filters: Vec<FeedFilter>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct FeedsResponse {
+    pub feeds: Vec<FeedInfo>,
+}
+
+/// `item` event of `GET /feeds/{name}/events`.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct FeedItemEvent {
+    pub feed: String,
+    pub dataitem_id: String,
+    pub content_type: String,
+    pub created_at: String,
+    pub tags: Vec<UploadTag>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NameVersion {
+    pub name: String,
+    pub version: u64,
+    pub dataitem_id: String,
+    pub updated_by: String,
+    pub created_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct NameHistoryResponse {
+    pub name: String,
+    /// the latest version first
+    pub versions: Vec<NameVersion>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct UploadResponse {
+    pub success: bool,
+    pub dataitem_id: String,
+    pub custom_tags: Vec<UploadTag>,
+    /// every tag of the dataitem, the custom ones and those added by the agent
+    pub tags: Vec<UploadTag>,
+    /// `/{dataitem_id}` on this agent, from `AGENT_PUBLIC_URL` or the request's `Host`
+    pub agent_url: Option<String>,
+    /// the dataitem on the Arweave gateway, served once posted
+    pub arweave_url: String,
+    /// presigned URL of the raw body, expiring after `presigned_url_expiry` seconds
+    pub raw_presigned_url: Option<String>,
+    /// absent on dry runs or when the agent's key can't sign it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub receipt: Option<UploadReceipt>,
+    pub message: String,
+}
+
+/// Proof that the agent accepted a dataitem's data at `issued_at`: an RSA-PSS SHA-256 signature
+/// by the agent's key, verifiable like an Arweave signature with `owner`, over
+/// `load-s3-agent/receipt/1\n{dataitem_id}\n{size}\n{sha256}\n{issued_at}`.
+#[derive(Serialize, ToSchema)]
+pub struct UploadReceipt {
+    pub dataitem_id: String,
+    /// bytes of the dataitem's data
+    pub size: u64,
+    /// hex SHA-256 of the dataitem's data
+    pub sha256: String,
+    /// RFC 3339 with milliseconds, in UTC
+    pub issued_at: String,
+    /// Arweave address of the agent's key
+    pub signer: String,
+    /// b64url RSA modulus of the agent's key
+    pub owner: String,
+    pub algorithm: String,
+    /// b64url
+    pub signature: String,
+}
+
+#[derive(Clone, Serialize, ToSchema)]
+pub struct ReplicationFailure {
+    pub operation: String,
+    pub bucket: String,
+    pub key: String,
+    pub error: String,
+    pub failed_at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReplicationStatus {
+    pub enabled: bool,
+    pub endpoint: Option<String>,
+    /// writes and deletes not mirrored yet
+    pub pending: usize,
+    /// age of the oldest pending operation
+    pub lag_seconds: f64,
+    pub replicated_total: u64,
+    pub failed_total: u64,
+    pub last_replicated_at: Option<String>,
+    /// most recent failures, oldest first (failed objects are not retried)
+    pub recent_failures: Vec<ReplicationFailure>,
+    /// the primary then each `READ_REPLICA_S3_ENDPOINT_URLS` endpoint reads fail over to
+    pub read_endpoints: Vec<ReadEndpointHealth>,
+}
+
+/// Read failover health of a storage endpoint.
+#[derive(Serialize, ToSchema)]
+pub struct ReadEndpointHealth {
+    /// `primary` or the read replica's endpoint URL
+    pub endpoint: String,
+    /// circuit breaker state: closed, open (skipped) or half_open
+    pub state: String,
+    pub served_total: u64,
+    /// timeouts, and errors on objects another endpoint served
+    pub failures_total: u64,
+    pub last_error: Option<String>,
+    pub last_failure_at: Option<String>,
+}
+
+/// Outcome of one pre-flight check of `GET /admin/selftest` and `--check`.
+#[derive(Serialize, ToSchema)]
+pub struct SelfTestCheck {
+    pub name: String,
+    /// ok, failed or skipped
+    pub status: String,
+    pub detail: Option<String>,
+    pub duration_ms: u64,
+}
+
+/// Query of `DELETE /admin/bucket-ownership`.
+#[derive(Deserialize, IntoParams)]
+pub struct OwnershipCacheParams {
+    /// only forget the owners of this bucket
+    pub bucket: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct OwnershipCacheInvalidated {
+    /// cached (bucket, load_acc) validations dropped
+    pub invalidated: usize,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReloadReport {
+    /// every derived setting was rebuilt
+    pub ok: bool,
+    /// `.env` keys whose value changed
+    pub changed: Vec<String>,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct SelfTestReport {
+    /// no check failed
+    pub ok: bool,
+    pub checks: Vec<SelfTestCheck>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportItemReport {
+    /// position of the dataitem in the bundle or stream
+    pub index: usize,
+    pub dataitem_id: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ImportResponse {
+    pub success: bool,
+    pub imported: usize,
+    pub failed: usize,
+    pub items: Vec<ImportItemReport>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PrivateUploadResponse {
+    pub success: bool,
+    pub dataitem_id: String,
+    pub dataitem_name: String,
+    pub folder_name: String,
+    pub is_signed: bool,
+    pub message: String,
+}
+
+/// Body of `POST /private/buckets`.
+#[derive(Deserialize, ToSchema)]
+pub struct CreateBucketRequest {
+    /// S3 bucket name, 3 to 63 lowercase letters, digits, dots or hyphens
+    pub bucket_name: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct CreateBucketResponse {
+    pub success: bool,
+    pub bucket_name: String,
+    pub message: String,
+}
+
+/// Body of `POST /private/{bucket}/presign-upload`, the options of `POST /upload/private`.
+#[derive(Deserialize, ToSchema)]
+pub struct PresignUploadRequest {
+    /// signed into the URL, the `PUT` must send it as `Content-Type`
+    pub content_type: Option<String>,
+    /// byte size of the file, signed into the URL, the `PUT` must send exactly as many bytes
+    pub size: u64,
+    /// folder (key prefix) inside the bucket
+    #[serde(default)]
+    pub folder_name: String,
+    /// registry name of the dataitem
+    #[serde(default)]
+    pub dataitem_name: String,
+    /// the body is an already signed ANS-104 dataitem
+    #[serde(default)]
+    pub signed: bool,
+}
+
+/// A presigned `PUT` into a private bucket, published by `POST` to its `complete_url`.
+#[derive(Serialize, ToSchema)]
+pub struct PresignUploadResponse {
+    pub success: bool,
+    pub upload_id: String,
+    pub upload_url: String,
+    pub method: String,
+    /// headers the `PUT` must send
+    pub headers: BTreeMap<String, String>,
+    pub expires_at: String,
+    pub complete_url: String,
+    pub message: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostDataitemResponse {
+    pub success: bool,
+    pub dataitem_id: String,
+    #[schema(value_type = Object)]
+    pub bundler_response: SendTransactionResponse,
+    pub message: String,
+}
+
+/// Inclusion proof of a dataitem in the Merkle batch anchoring it. Hashing the `leaf` with each
+/// `path` step in turn yields `root`, published in the `batch_id` dataitem.
+#[derive(Serialize, ToSchema)]
+pub struct MerkleProof {
+    pub dataitem_id: String,
+    /// agent signed dataitem publishing the root
+    pub batch_id: String,
+    /// the batch's dataitem on the Arweave gateway, served once posted
+    pub batch_arweave_url: String,
+    /// hex
+    pub root: String,
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// hex SHA-256 of a `0x00` byte followed by the dataitem ID
+    pub leaf: String,
+    /// sibling hashes from the leaf up
+    pub path: Vec<MerkleStep>,
+    /// RFC 3339, the batch holds the dataitems indexed from `batch_start` until `batch_end`
+    pub batch_start: String,
+    pub batch_end: String,
+}
+
+/// A sibling in a Merkle path, hashed as SHA-256 of a `0x01` byte, the left node and the right
+/// node.
+#[derive(Serialize, ToSchema)]
+pub struct MerkleStep {
+    /// `left` or `right` of the running hash
+    pub side: String,
+    /// hex
+    pub hash: String,
+}
+
+/// How often a dataitem was served by `GET /{id}`, redirects and `304`s included.
+#[derive(Serialize, ToSchema)]
+pub struct DataitemStats {
+    pub dataitem_id: String,
+    pub hits: u64,
+    /// RFC 3339, absent if it was never served
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_access: Option<String>,
+}
+
+/// Query of `GET /analytics/top`.
+#[derive(Deserialize, IntoParams)]
+pub struct AnalyticsParams {
+    /// window ending now, in hours (`24h`) or days (`7d`, the default), at most `365d`
+    pub period: Option<String>,
+    /// entries per list, defaults to 10 and capped at 100
+    pub limit: Option<usize>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TopDataitem {
+    pub dataitem_id: String,
+    pub hits: u64,
+}
+
+/// A tag key, or key and value, and how many dataitems indexed in the period carry it.
+#[derive(Serialize, ToSchema)]
+pub struct TagUsageEntry {
+    pub key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub dataitems: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct TopAnalytics {
+    pub period: String,
+    /// RFC 3339 start of the period
+    pub since: String,
+    /// most served dataitems, by hits
+    pub dataitems: Vec<TopDataitem>,
+    /// most used tag keys of the dataitems indexed in the period, agent injected ones excluded
+    pub tag_keys: Vec<TagUsageEntry>,
+    pub tag_values: Vec<TagUsageEntry>,
+}
+
+/// Upload credits of the caller's API key, as decimal strings like bundler balances.
+#[derive(Serialize, ToSchema)]
+pub struct CreditsResponse {
+    /// whether uploads of this key debit credits, operator keys never do
+    pub metered: bool,
+    /// remaining credits, absent when not metered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub balance: Option<String>,
+    pub credits_per_byte: String,
+}
+
+/// Amounts are winston credits (10^-12 AR) as decimal strings, too large for JSON numbers.
+#[derive(Serialize, ToSchema)]
+pub struct BundlerBalance {
+    pub address: String,
+    pub winc: String,
+    pub ar: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostEstimate {
+    pub dataitem_id: String,
+    /// size of the signed dataitem
+    pub bytes: u64,
+    /// account charged for the post, the dataitem's signer
+    pub payer: String,
+    /// posted free of charge, under the bundler's free upload limit
+    pub free: bool,
+    /// price of the post, `0` when free
+    pub winc: String,
+    pub ar: String,
+    pub balance_winc: String,
+    /// whether the payer's balance covers the price
+    pub affordable: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostStatusEntry {
+    pub status: String,
+    /// e.g. the bundle the dataitem was seeded in
+    pub detail: String,
+    pub at: String,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PostStatusResponse {
+    pub dataitem_id: String,
+    /// latest status: pending, seeded, confirmed or failed
+    pub status: String,
+    /// every status the post went through, oldest first
+    pub history: Vec<PostStatusEntry>,
+}
+
+/// Outcome of `load-s3-agent reindex` over a tenant.
+#[derive(Serialize, Default)]
+pub struct ReindexReport {
+    pub tenant: String,
+    pub dataitems_scanned: usize,
+    /// dataitems with index rows, left untouched so their `created_at` is kept
+    pub already_indexed: usize,
+    pub indexed: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct BucketRegistryResponse {
+    pub success: bool,
+    pub bucket_name: String,
+    pub entries: Vec<RegistryEntry>,
+}
+
+#[derive(Serialize, Default, ToSchema)]
+pub struct GcReport {
+    pub tenant: String,
+    pub dry_run: bool,
+    pub dataitems_scanned: usize,
+    pub raw_objects_scanned: usize,
+    /// raw bodies without their signed `.ans104` dataitem, deleted
+    pub orphaned_raw: Vec<String>,
+    /// signed dataitems without their raw body, repaired from the dataitem's data
+    pub missing_raw: Vec<String>,
+    /// soft-deleted dataitems past their retention, permanently removed
+    pub purged: Vec<String>,
+    /// staging IDs of uploads never committed within `STAGING_TTL_SECS`, removed
+    pub expired_stages: Vec<String>,
+    pub deleted: usize,
+    pub repaired: usize,
+    pub errors: Vec<String>,
+}
+
+/// Sampling of `REQUEST LOG` lines, see `GET /admin/request-log`.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct RequestLogSettings {
+    /// share of the requests logged, 0 to 1
+    pub sample_rate: f64,
+    /// path prefixes sampled, every path when empty
+    pub paths: Vec<String>,
+    /// bodies up to this size are logged, larger ones only by size
+    pub max_body_bytes: usize,
+    /// set with `PUT /admin/request-log` rather than the `REQUEST_LOG_*` variables
+    pub overridden: bool,
+}
+
+/// Body of `PUT /admin/request-log`, unset fields are left as they are.
+#[derive(Deserialize, ToSchema)]
+pub struct RequestLogUpdate {
+    pub sample_rate: Option<f64>,
+    pub paths: Option<Vec<String>>,
+    pub max_body_bytes: Option<usize>,
+    /// start over from the `REQUEST_LOG_*` variables
+    #[serde(default)]
+    pub reset: bool,
+}
+
+/// Read-only mode of the agent, see `GET /admin/maintenance`.
+#[derive(Serialize, Clone, ToSchema)]
+pub struct MaintenanceSettings {
+    /// uploads and other writes are rejected with a `503`, reads are still served
+    pub enabled: bool,
+    /// returned with the rejected requests
+    pub message: String,
+    /// `Retry-After` of the rejected requests
+    pub retry_after_secs: u64,
+    /// set with `PUT /admin/maintenance` rather than the `MAINTENANCE_*` variables
+    pub overridden: bool,
+}
+
+/// Body of `PUT /admin/maintenance`, unset fields are left as they are.
+#[derive(Deserialize, ToSchema)]
+pub struct MaintenanceUpdate {
+    pub enabled: Option<bool>,
+    pub message: Option<String>,
+    pub retry_after_secs: Option<u64>,
+    /// start over from the `MAINTENANCE_*` variables
+    #[serde(default)]
+    pub reset: bool,
+}
+
+/// Answer of `GET /healthz`.
+#[derive(Serialize, ToSchema)]
+pub struct HealthStatus {
+    /// `ok`, or `maintenance` while writes are rejected
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintenance_message: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct IndexCompactionReport {
+    pub dry_run: bool,
+    pub rows_before: u64,
+    /// rows left once merged, one per `(tenant, tag_key, tag_value, dataitem_id)`
+    pub unique_rows: u64,
+    /// rows re-indexing left behind, `rows_before - unique_rows`
+    pub duplicate_rows: u64,
+    pub rows_after: u64,
+    pub removed: u64,
+    pub dataitems: u64,
+    pub duration_ms: u64,
+}
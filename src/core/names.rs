@@ -0,0 +1,95 @@
+use crate::core::{
+    metadata::{NameRecord, get_name_version, save_name_version},
+    provenance, webhooks,
+};
+use anyhow::Error;
+use chrono::{SubsecRound, Utc};
+use once_cell::sync::Lazy;
+use serde_json::json;
+use tokio::sync::Mutex;
+
+const MAX_NAME_LEN: usize = 128;
+
+// updates of this agent are serialized so each gets the next version, agents of a fleet
+// updating a name at once may still record the same version twice, the last one winning
+static UPDATE_LOCK: Lazy<Mutex<()>> = Lazy::new(Default::default);
+
+/// A name `point` refuses.
+#[derive(Debug)]
+pub(crate) struct InvalidName(pub String);
+
+impl std::fmt::Display for InvalidName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidName {}
+
+/// The `expected_version` of an update is no longer the latest version of the name.
+#[derive(Debug)]
+pub(crate) struct VersionConflict {
+    pub expected: u64,
+    pub current: u64,
+}
+
+impl std::fmt::Display for VersionConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "expected version {} but the name is at version {}", self.expected, self.current)
+    }
+}
+
+impl std::error::Error for VersionConflict {}
+
+fn check_name(name: &str) -> Result<(), InvalidName> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_NAME_LEN
+        && name.bytes().all(|b| {
+            b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-' || b == b'_' || b == b'.'
+        });
+    match valid {
+        true => Ok(()),
+        false => Err(InvalidName(format!(
+            "invalid name {name:?}, expected 1 to {MAX_NAME_LEN} lowercase letters, digits, '-', '_' or '.'"
+        ))),
+    }
+}
+
+/// Point the tenant's name at `dataitem_id`, recording it as the name's next version. With
+/// `expected_version` (0 for a new name) the update fails with `VersionConflict` unless the
+/// name is still at that version. Fails with `InvalidName`.
+pub(crate) async fn point(
+    tenant: &str,
+    name: &str,
+    dataitem_id: &str,
+    expected_version: Option<u64>,
+) -> Result<NameRecord, Error> {
+    check_name(name)?;
+    let _guard = UPDATE_LOCK.lock().await;
+    let current = get_name_version(tenant, name, None).await?.map_or(0, |latest| latest.version);
+    if let Some(expected) = expected_version {
+        if expected != current {
+            return Err(VersionConflict { expected, current }.into());
+        }
+    }
+
+    let record = NameRecord {
+        tenant: tenant.to_string(),
+        name: name.to_string(),
+        version: current + 1,
+        dataitem_id: dataitem_id.to_string(),
+        updated_by: provenance::current().principal,
+        created_at: Utc::now().trunc_subsecs(3),
+    };
+    save_name_version(&record).await?;
+    webhooks::notify(
+        "name_updated",
+        json!({
+            "tenant": record.tenant,
+            "name": record.name,
+            "version": record.version,
+            "dataitem_id": record.dataitem_id,
+        }),
+    );
+    Ok(record)
+}
@@ -0,0 +1,110 @@
+use crate::core::server;
+use utoipa::{
+    Modify, OpenApi,
+    openapi::security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "load-s3-agent", description = "Load S3 data agent API"),
+    paths(
+        server::handle_route,
+        server::handle_healthz,
+        server::handle_storage_stats,
+        server::handle_metrics,
+        server::handle_test_vectors,
+        server::handle_dataitem_id,
+        server::handle_openapi,
+        server::handle_query_tags,
+        server::handle_create_feed,
+        server::handle_list_feeds,
+        server::handle_get_feed,
+        server::handle_delete_feed,
+        server::handle_feed_events,
+        server::handle_point_name,
+        server::handle_resolve_name,
+        server::handle_name_history,
+        server::handle_owner_dataitems,
+        server::handle_hash_dataitems,
+        server::handle_content_type_dataitems,
+        server::handle_recent_dataitems,
+        server::handle_exists,
+        server::handle_items_get,
+        server::serve_dataitem,
+        server::handle_arns,
+        server::handle_render_dataitem,
+        server::handle_dataitem_proof,
+        server::handle_dataitem_receipt,
+        server::handle_dataitem_stats,
+        server::handle_hls_playlist,
+        server::handle_hls_segment,
+        server::upload_file,
+        server::upload_raw_file,
+        server::upload_from_url,
+        server::handle_stage_upload,
+        server::handle_commit_upload,
+        server::handle_discard_upload,
+        server::handle_upload_progress,
+        server::handle_upload_job,
+        server::handle_private_file,
+        server::handle_create_private_bucket,
+        server::handle_presign_private_upload,
+        server::handle_complete_private_upload,
+        server::handle_post_dataitem,
+        server::handle_get_bucket_registry,
+        server::handle_s3_put_object,
+        server::handle_s3_get_object,
+        server::handle_s3_list_objects,
+        server::handle_gc_report,
+        server::handle_index_compaction_report,
+        server::handle_compact_index,
+        server::handle_replication_status,
+        server::handle_selftest,
+        server::handle_reload,
+        server::handle_request_log_settings,
+        server::handle_update_request_log,
+        server::handle_maintenance,
+        server::handle_update_maintenance,
+        server::handle_list_jobs,
+        server::handle_retry_job,
+        server::handle_list_reports,
+        server::handle_resolve_report,
+        server::handle_start_prefix_migration,
+        server::handle_prefix_migration,
+        server::handle_schedule,
+        server::handle_provenance,
+        server::handle_analytics_top,
+        server::handle_list_blocklist,
+        server::handle_block,
+        server::handle_unblock,
+        server::handle_invalidate_bucket_ownership,
+        server::handle_post_estimate,
+        server::handle_bundler_balance,
+        server::handle_credits,
+        server::handle_post_status,
+        server::handle_delete_dataitem,
+        server::handle_restore_dataitem,
+        server::handle_report_dataitem,
+        server::handle_export_index,
+        server::handle_sync_dataitems,
+        server::handle_import,
+    ),
+    modifiers(&BearerAuth)
+)]
+struct ApiDoc;
+
+struct BearerAuth;
+
+impl Modify for BearerAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+    }
+}
+
+pub(crate) fn api_doc() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
@@ -0,0 +1,259 @@
+use crate::core::{
+    models::{UploadJob, UploadProgress},
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+const MAX_UPLOAD_ID_LEN: usize = 128;
+
+// keyed by tenant and upload ID
+type Uploads = HashMap<(String, String), Arc<Tracker>>;
+
+static UPLOADS: Lazy<Mutex<Uploads>> = Lazy::new(Default::default);
+static JOB_PERMITS: Lazy<Semaphore> = Lazy::new(|| {
+    let permits = get_env_var("UPLOAD_JOB_CONCURRENCY").ok().and_then(|v| v.parse().ok());
+    Semaphore::new(permits.unwrap_or(4).max(1))
+});
+
+tokio::task_local! {
+    static CURRENT: Arc<Tracker>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum UploadStage {
+    Receiving,
+    Queued,
+    Signing,
+    Storing,
+    Indexing,
+    Done,
+    Failed,
+}
+
+impl UploadStage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UploadStage::Receiving => "receiving",
+            UploadStage::Queued => "queued",
+            UploadStage::Signing => "signing",
+            UploadStage::Storing => "storing",
+            UploadStage::Indexing => "indexing",
+            UploadStage::Done => "done",
+            UploadStage::Failed => "failed",
+        }
+    }
+
+    fn finished(&self) -> bool {
+        matches!(self, UploadStage::Done | UploadStage::Failed)
+    }
+
+    /// Coarse job state of `GET /jobs/{id}`: queued, running, done or failed.
+    fn job_state(&self) -> &'static str {
+        match self {
+            UploadStage::Queued => "queued",
+            UploadStage::Done => "done",
+            UploadStage::Failed => "failed",
+            _ => "running",
+        }
+    }
+}
+
+/// Progress of one upload, registered under its client chosen `x-upload-id`.
+pub(crate) struct Tracker {
+    upload_id: String,
+    state: Mutex<TrackerState>,
+}
+
+struct TrackerState {
+    stage: UploadStage,
+    bytes_expected: Option<u64>,
+    bytes_received: u64,
+    bytes_written: u64,
+    dataitem_id: Option<String>,
+    error: Option<String>,
+    updated_at: DateTime<Utc>,
+    finished_at: Option<Instant>,
+}
+
+impl Tracker {
+    fn update(&self, change: impl FnOnce(&mut TrackerState)) {
+        let mut state = self.state.lock().unwrap();
+        change(&mut state);
+        state.updated_at = Utc::now();
+    }
+
+    pub fn finish(&self, dataitem_id: &str) {
+        self.update(|state| {
+            state.stage = UploadStage::Done;
+            state.dataitem_id = Some(dataitem_id.to_string());
+            state.finished_at = Some(Instant::now());
+        });
+    }
+
+    pub fn fail(&self, error: &str) {
+        self.update(|state| {
+            state.stage = UploadStage::Failed;
+            state.error = Some(error.to_string());
+            state.finished_at = Some(Instant::now());
+        });
+    }
+
+    fn job(&self) -> UploadJob {
+        let state = self.state.lock().unwrap();
+        UploadJob {
+            job_id: self.upload_id.clone(),
+            state: state.stage.job_state().to_string(),
+            stage: state.stage.as_str().to_string(),
+            bytes_written: state.bytes_written,
+            dataitem_id: state.dataitem_id.clone(),
+            error: state.error.clone(),
+            updated_at: state.updated_at.to_rfc3339(),
+        }
+    }
+
+    fn snapshot(&self) -> UploadProgress {
+        let state = self.state.lock().unwrap();
+        UploadProgress {
+            upload_id: self.upload_id.clone(),
+            stage: state.stage.as_str().to_string(),
+            bytes_expected: state.bytes_expected,
+            bytes_received: state.bytes_received,
+            bytes_written: state.bytes_written,
+            dataitem_id: state.dataitem_id.clone(),
+            error: state.error.clone(),
+            updated_at: state.updated_at.to_rfc3339(),
+        }
+    }
+}
+
+/// Marks the upload failed if the request is dropped (e.g. the client disconnected) before it
+/// finished, so its ID can be reused.
+pub(crate) struct AbortOnDrop(pub Arc<Tracker>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        let finished = self.0.state.lock().unwrap().stage.finished();
+        if !finished {
+            self.0.fail("upload aborted");
+        }
+    }
+}
+
+/// Finished uploads stay queryable for `UPLOAD_PROGRESS_TTL_SECS` (default 600).
+fn retention() -> Duration {
+    let secs = get_env_var("UPLOAD_PROGRESS_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(600))
+}
+
+fn purge_expired(uploads: &mut Uploads) {
+    let retention = retention();
+    uploads.retain(|_, tracker| {
+        let state = tracker.state.lock().unwrap();
+        state.finished_at.is_none_or(|at| at.elapsed() < retention)
+    });
+}
+
+pub(crate) fn validate_upload_id(upload_id: &str) -> Result<(), Error> {
+    let valid = !upload_id.is_empty()
+        && upload_id.len() <= MAX_UPLOAD_ID_LEN
+        && upload_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    match valid {
+        true => Ok(()),
+        false => {
+            Err(anyhow!("upload id must be 1-{MAX_UPLOAD_ID_LEN} characters of [A-Za-z0-9_-]"))
+        }
+    }
+}
+
+/// Register a new upload. Fails while another upload with the same ID is still in flight.
+pub(crate) fn start(
+    upload_id: &str,
+    tenant: &str,
+    bytes_expected: Option<u64>,
+) -> Result<Arc<Tracker>, Error> {
+    let mut uploads = UPLOADS.lock().unwrap();
+    purge_expired(&mut uploads);
+    let key = (tenant.to_string(), upload_id.to_string());
+    let in_flight =
+        uploads.get(&key).is_some_and(|tracker| !tracker.state.lock().unwrap().stage.finished());
+    if in_flight {
+        return Err(anyhow!("upload {upload_id} is already in progress"));
+    }
+    let tracker = Arc::new(Tracker {
+        upload_id: upload_id.to_string(),
+        state: Mutex::new(TrackerState {
+            stage: UploadStage::Receiving,
+            bytes_expected,
+            bytes_received: 0,
+            bytes_written: 0,
+            dataitem_id: None,
+            error: None,
+            updated_at: Utc::now(),
+            finished_at: None,
+        }),
+    });
+    uploads.insert(key, tracker.clone());
+    Ok(tracker)
+}
+
+/// Progress of the tenant's `upload_id`, unless unknown or expired.
+pub(crate) fn upload_progress(tenant: &str, upload_id: &str) -> Option<UploadProgress> {
+    let mut uploads = UPLOADS.lock().unwrap();
+    purge_expired(&mut uploads);
+    uploads.get(&(tenant.to_string(), upload_id.to_string())).map(|tracker| tracker.snapshot())
+}
+
+/// Status of the tenant's deferred upload `job_id`, unless unknown or expired.
+pub(crate) fn upload_job(tenant: &str, job_id: &str) -> Option<UploadJob> {
+    let mut uploads = UPLOADS.lock().unwrap();
+    purge_expired(&mut uploads);
+    uploads.get(&(tenant.to_string(), job_id.to_string())).map(|tracker| tracker.job())
+}
+
+/// ID of a deferred upload sent without its own `x-upload-id`.
+pub(crate) fn new_job_id() -> String {
+    let bytes: [u8; 16] = rand::random();
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(crate) fn set_queued(tracker: &Tracker) {
+    tracker.update(|state| state.stage = UploadStage::Queued);
+}
+
+/// Bounds the deferred uploads signed and stored at once, `UPLOAD_JOB_CONCURRENCY` (default 4).
+pub(crate) async fn job_permit() -> SemaphorePermit<'static> {
+    JOB_PERMITS.acquire().await.expect("upload job semaphore is never closed")
+}
+
+/// Run `upload` with `tracker` as the current upload, so the storage path can report its stages.
+pub(crate) async fn track<F: Future>(tracker: Arc<Tracker>, upload: F) -> F::Output {
+    CURRENT.scope(tracker, upload).await
+}
+
+/// The current upload, if tracked.
+pub(crate) fn current() -> Option<Arc<Tracker>> {
+    CURRENT.try_with(Arc::clone).ok()
+}
+
+/// Move the current upload, if tracked, to `stage`.
+pub(crate) fn set_stage(stage: UploadStage) {
+    let _ = CURRENT.try_with(|tracker| tracker.update(|state| state.stage = stage));
+}
+
+pub(crate) fn add_received(bytes: usize) {
+    let _ =
+        CURRENT.try_with(|tracker| tracker.update(|state| state.bytes_received += bytes as u64));
+}
+
+pub(crate) fn add_written(bytes: usize) {
+    let _ = CURRENT.try_with(|tracker| tracker.update(|state| state.bytes_written += bytes as u64));
+}
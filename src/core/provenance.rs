@@ -0,0 +1,89 @@
+use crate::core::{
+    progress::new_job_id,
+    utils::{get_env_var, sha256_hex},
+};
+use axum::{extract::ConnectInfo, http::Request};
+use std::{future::Future, net::SocketAddr};
+
+const MAX_USER_AGENT_LEN: usize = 512;
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: Provenance;
+}
+
+/// Who uploaded a dataitem and from where. Indexed alongside the dataitem's tags for operators,
+/// never written into the dataitem itself.
+#[derive(Debug, Clone, Default)]
+pub struct Provenance {
+    /// `server_key:` or `load_acc:` followed by a sha256 prefix of the bearer token, the token
+    /// itself is never stored
+    pub principal: String,
+    pub source_ip: String,
+    pub user_agent: String,
+    /// the client's `X-Request-Id`, or a random one, echoed in the response and error bodies
+    pub request_id: String,
+}
+
+impl Provenance {
+    /// From the request's bearer token, peer address (or the first `X-Forwarded-For` hop when
+    /// `TRUST_FORWARDED_FOR=true`), `User-Agent` and `X-Request-Id`.
+    pub fn from_request<B>(request: &Request<B>) -> Provenance {
+        let headers = request.headers();
+        let principal = headers
+            .get("authorization")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(principal)
+            .unwrap_or_default();
+
+        let trust_forwarded =
+            get_env_var("TRUST_FORWARDED_FOR").map(|v| v == "true").unwrap_or(false);
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| trust_forwarded && !ip.is_empty());
+        let peer = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let source_ip = forwarded.or(peer).unwrap_or_default();
+
+        let user_agent: String = headers
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .chars()
+            .take(MAX_USER_AGENT_LEN)
+            .collect();
+
+        let request_id = headers
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+            .filter(|id| id.bytes().all(|byte| byte.is_ascii_graphic()))
+            .map(str::to_string)
+            .unwrap_or_else(new_job_id);
+
+        Provenance { principal, source_ip, user_agent, request_id }
+    }
+}
+
+fn principal(token: &str) -> String {
+    let is_server_key = get_env_var("SERVER_API_KEYS")
+        .is_ok_and(|keys| keys.split(',').any(|key| key.trim() == token));
+    let kind = if is_server_key { "server_key" } else { "load_acc" };
+    format!("{kind}:{}", &sha256_hex(token.as_bytes())[..16])
+}
+
+/// Run `upload` with `provenance` recorded for whatever it indexes.
+pub(crate) async fn scope<F: Future>(provenance: Provenance, upload: F) -> F::Output {
+    CURRENT.scope(provenance, upload).await
+}
+
+/// Provenance of the current request, empty outside of one (e.g. background tasks).
+pub(crate) fn current() -> Provenance {
+    CURRENT.try_with(Clone::clone).unwrap_or_default()
+}
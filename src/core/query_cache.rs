@@ -0,0 +1,115 @@
+use crate::core::{
+    metadata::{TagQueryPage, TagQueryPagination, matches_filters},
+    metrics,
+    utils::get_env_var,
+};
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+const DEFAULT_MAX_ENTRIES: usize = 1000;
+
+/// Tenant, normalized filters, page size and cursor of a `query_dataitems_by_tags` page.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    tenant: String,
+    filters: Vec<(String, Vec<String>)>,
+    first: usize,
+    after: Option<(DateTime<Utc>, String)>,
+}
+
+struct CachedPage {
+    page: TagQueryPage,
+    cached_at: Instant,
+}
+
+static TAG_QUERY_CACHE: Lazy<Mutex<HashMap<CacheKey, CachedPage>>> = Lazy::new(Default::default);
+
+/// Tag query pages are kept for `TAG_QUERY_CACHE_TTL_SECS` (default 0, no caching).
+fn cache_ttl() -> Duration {
+    let secs = get_env_var("TAG_QUERY_CACHE_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(0))
+}
+
+/// At most `TAG_QUERY_CACHE_MAX_ENTRIES` (default 1000) pages are kept, the oldest being evicted.
+fn max_entries() -> usize {
+    let entries = get_env_var("TAG_QUERY_CACHE_MAX_ENTRIES").ok().and_then(|v| v.parse().ok());
+    entries.unwrap_or(DEFAULT_MAX_ENTRIES)
+}
+
+fn cache_key(
+    tenant: &str,
+    filters: &[(String, Vec<String>)],
+    pagination: &TagQueryPagination,
+) -> CacheKey {
+    CacheKey {
+        tenant: tenant.to_string(),
+        filters: filters.to_vec(),
+        first: pagination.first,
+        after: pagination
+            .after
+            .as_ref()
+            .map(|cursor| (cursor.created_at, cursor.dataitem_id.clone())),
+    }
+}
+
+/// The cached page of a query, `filters` being normalized.
+pub(crate) fn get(
+    tenant: &str,
+    filters: &[(String, Vec<String>)],
+    pagination: &TagQueryPagination,
+) -> Option<TagQueryPage> {
+    let ttl = cache_ttl();
+    if ttl.is_zero() {
+        return None;
+    }
+    let key = cache_key(tenant, filters, pagination);
+    let cache = TAG_QUERY_CACHE.lock().unwrap();
+    let page = cache.get(&key).filter(|cached| cached.cached_at.elapsed() < ttl);
+    let result = if page.is_some() { "hit" } else { "miss" };
+    metrics::increment(&format!("tag_query_cache_total{{result=\"{result}\"}}"));
+    page.map(|cached| cached.page.clone())
+}
+
+/// Keep the page ClickHouse answered for a query, `filters` being normalized.
+pub(crate) fn put(
+    tenant: &str,
+    filters: &[(String, Vec<String>)],
+    pagination: &TagQueryPagination,
+    page: &TagQueryPage,
+) {
+    let ttl = cache_ttl();
+    let max_entries = max_entries();
+    if ttl.is_zero() || max_entries == 0 {
+        return;
+    }
+    let mut cache = TAG_QUERY_CACHE.lock().unwrap();
+    cache.retain(|_, cached| cached.cached_at.elapsed() < ttl);
+    while cache.len() >= max_entries {
+        let oldest =
+            cache.iter().min_by_key(|(_, cached)| cached.cached_at).map(|(k, _)| k.clone());
+        let Some(oldest) = oldest else { break };
+        cache.remove(&oldest);
+    }
+    cache.insert(
+        cache_key(tenant, filters, pagination),
+        CachedPage { page: page.clone(), cached_at: Instant::now() },
+    );
+    metrics::set_gauge("tag_query_cache_entries", cache.len() as f64);
+}
+
+/// Drop the tenant's cached queries a dataitem newly indexed with the normalized `tags` matches,
+/// every filter having one of its values among them.
+pub(crate) fn invalidate_matching(tenant: &str, tags: &[(String, String)]) {
+    let mut cache = TAG_QUERY_CACHE.lock().unwrap();
+    cache.retain(|key, _| key.tenant != tenant || !matches_filters(&key.filters, tags));
+}
+
+/// Drop every cached query of the tenant, e.g. once one of its dataitems is deleted or restored.
+pub(crate) fn invalidate_tenant(tenant: &str) {
+    TAG_QUERY_CACHE.lock().unwrap().retain(|key, _| key.tenant != tenant);
+}
@@ -0,0 +1,90 @@
+use crate::core::{
+    storage::{ObjectBody, StorageBackend},
+    utils::get_env_var,
+};
+use anyhow::Error;
+
+/// `Content-Encoding` of the raw bodies `put_raw` compresses.
+pub(crate) const ZSTD_ENCODING: &str = "zstd";
+const DEFAULT_LEVEL: i32 = 3;
+// smaller bodies save next to nothing once the frame overhead is paid
+const MIN_COMPRESSED_LEN: usize = 1024;
+
+/// Text, JSON, XML, JavaScript and the like, media and archives being compressed already.
+fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type.split(';').next().unwrap_or_default().trim().to_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/json"
+                | "application/x-ndjson"
+                | "application/xml"
+                | "application/javascript"
+                | "application/ecmascript"
+                | "application/yaml"
+                | "application/x-yaml"
+                | "application/toml"
+                | "application/sql"
+                | "application/graphql"
+                | "application/wasm"
+        )
+}
+
+/// `RAW_COMPRESSION=zstd` compresses raw bodies at `RAW_COMPRESSION_LEVEL` (default 3).
+fn compression_level() -> Option<i32> {
+    let mode = get_env_var("RAW_COMPRESSION").unwrap_or_default();
+    if !mode.trim().eq_ignore_ascii_case(ZSTD_ENCODING) {
+        return None;
+    }
+    let level = get_env_var("RAW_COMPRESSION_LEVEL").ok().and_then(|v| v.parse().ok());
+    Some(level.unwrap_or(DEFAULT_LEVEL))
+}
+
+/// Store the raw body `raw` of a dataitem at `key`. With `RAW_COMPRESSION=zstd` compressible
+/// content types are stored zstd compressed with a `Content-Encoding: zstd`, when the backend
+/// records it and compressing saves space.
+pub(crate) async fn put_raw(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    key: &str,
+    raw: Vec<u8>,
+    content_type: &str,
+) -> Result<(), Error> {
+    let level = compression_level().filter(|_| {
+        raw.len() >= MIN_COMPRESSED_LEN
+            && is_compressible(content_type)
+            && storage.keeps_content_encoding()
+    });
+    let Some(level) = level else {
+        return storage.put(bucket, key, raw, content_type, None).await;
+    };
+
+    let (raw, compressed) = tokio::task::spawn_blocking(move || {
+        let compressed = zstd::bulk::compress(&raw, level);
+        (raw, compressed)
+    })
+    .await?;
+    match compressed {
+        Ok(compressed) if compressed.len() < raw.len() => {
+            storage.put_encoded(bucket, key, compressed, content_type, ZSTD_ENCODING).await
+        }
+        Ok(_) => storage.put(bucket, key, raw, content_type, None).await,
+        Err(err) => {
+            println!("RAW COMPRESSION: {key} stored uncompressed: {err}");
+            storage.put(bucket, key, raw, content_type, None).await
+        }
+    }
+}
+
+/// The raw body `object` as uploaded, decompressed if `put_raw` compressed it.
+pub(crate) async fn decode_raw(object: ObjectBody) -> Result<ObjectBody, Error> {
+    if object.content_encoding.as_deref() != Some(ZSTD_ENCODING) {
+        return Ok(object);
+    }
+    let compressed = object.data;
+    let data =
+        tokio::task::spawn_blocking(move || zstd::stream::decode_all(&compressed[..])).await??;
+    Ok(ObjectBody { data, content_type: object.content_type, content_encoding: None })
+}
@@ -0,0 +1,124 @@
+use crate::core::{
+    ans104::{ContentDigest, agent_address},
+    metadata::{Receipt, get_receipt, indexing_enabled, save_receipt},
+    models::UploadReceipt,
+    tenant::Tenant,
+    utils::get_env_var,
+};
+use anyhow::Error;
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{SecondsFormat, SubsecRound, Utc};
+use rsa::{
+    BigUint, RsaPrivateKey,
+    pss::SigningKey,
+    rand_core::OsRng,
+    signature::{RandomizedSigner, SignatureEncoding},
+};
+use serde::Deserialize;
+use sha2::Sha256;
+
+/// First line of the signed message, bumped if its layout ever changes.
+const RECEIPT_VERSION: &str = "load-s3-agent/receipt/1";
+const ALGORITHM: &str = "RSA-PSS-SHA256";
+
+/// The RSA key of `UPLOADER_JWK`.
+#[derive(Deserialize)]
+struct Jwk {
+    n: String,
+    e: String,
+    d: String,
+    p: String,
+    q: String,
+}
+
+fn uint(value: &str) -> Result<BigUint, Error> {
+    let bytes = general_purpose::URL_SAFE_NO_PAD.decode(value.trim_end_matches('='))?;
+    Ok(BigUint::from_bytes_be(&bytes))
+}
+
+/// The agent's `UPLOADER_JWK` key and its b64url modulus.
+fn agent_key() -> Result<(RsaPrivateKey, String), Error> {
+    let jwk: Jwk = serde_json::from_str(&get_env_var("UPLOADER_JWK")?)?;
+    let primes = vec![uint(&jwk.p)?, uint(&jwk.q)?];
+    let key = RsaPrivateKey::from_components(uint(&jwk.n)?, uint(&jwk.e)?, uint(&jwk.d)?, primes)?;
+    Ok((key, jwk.n.trim_end_matches('=').to_string()))
+}
+
+fn issued_at(receipt: &Receipt) -> String {
+    receipt.issued_at.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+fn signed_message(receipt: &Receipt) -> String {
+    format!(
+        "{RECEIPT_VERSION}\n{}\n{}\n{}\n{}",
+        receipt.dataitem_id,
+        receipt.size,
+        receipt.sha256,
+        issued_at(receipt)
+    )
+}
+
+impl From<Receipt> for UploadReceipt {
+    fn from(receipt: Receipt) -> Self {
+        UploadReceipt {
+            issued_at: issued_at(&receipt),
+            dataitem_id: receipt.dataitem_id,
+            size: receipt.size,
+            sha256: receipt.sha256,
+            signer: receipt.signer,
+            owner: receipt.owner,
+            algorithm: ALGORITHM.to_string(),
+            signature: receipt.signature,
+        }
+    }
+}
+
+fn sign(tenant: &Tenant, dataitem_id: &str, digest: &ContentDigest) -> Result<Receipt, Error> {
+    let (key, owner) = agent_key()?;
+    let mut receipt = Receipt {
+        tenant: tenant.name.clone(),
+        dataitem_id: dataitem_id.to_string(),
+        size: digest.size as u64,
+        sha256: digest.sha256.clone(),
+        // stored with millisecond precision, the signed timestamp has to survive the round trip
+        issued_at: Utc::now().trunc_subsecs(3),
+        signer: agent_address()?,
+        owner,
+        signature: String::new(),
+    };
+    let signature = SigningKey::<Sha256>::new(key)
+        .sign_with_rng(&mut OsRng, signed_message(&receipt).as_bytes());
+    receipt.signature = general_purpose::URL_SAFE_NO_PAD.encode(signature.to_vec());
+    Ok(receipt)
+}
+
+/// Sign a receipt for the dataitem `dataitem_id` the agent just stored, `digest` being the
+/// `ContentDigest` of its data, and keep it for `GET /{id}/receipt` unless indexing is disabled.
+/// The upload succeeded already, so failures are logged and answered with `None`.
+pub(crate) async fn issue(
+    tenant: &Tenant,
+    dataitem_id: &str,
+    digest: &ContentDigest,
+) -> Option<UploadReceipt> {
+    let receipt = match sign(tenant, dataitem_id, digest) {
+        Ok(receipt) => receipt,
+        Err(err) => {
+            println!("RECEIPT: failed to sign the receipt of {dataitem_id}: {err}");
+            return None;
+        }
+    };
+    if indexing_enabled() {
+        if let Err(err) = save_receipt(&receipt).await {
+            println!("RECEIPT: failed to save the receipt of {dataitem_id}: {err}");
+        }
+    }
+    Some(receipt.into())
+}
+
+/// The latest receipt issued for a tenant's dataitem.
+pub(crate) async fn load(
+    tenant: &Tenant,
+    dataitem_id: &str,
+) -> Result<Option<UploadReceipt>, Error> {
+    Ok(get_receipt(&tenant.name, dataitem_id).await?.map(UploadReceipt::from))
+}
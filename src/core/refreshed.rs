@@ -0,0 +1,66 @@
+use anyhow::Error;
+use std::{
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A value loaded from ClickHouse and kept in memory, reloaded once older than an interval.
+/// Changes made on another agent apply here within that interval.
+pub(crate) struct Refreshed<T> {
+    name: &'static str,
+    loaded: Mutex<Option<(Arc<T>, Instant)>>,
+    // one reload at a time, concurrent callers keep using the previous value meanwhile
+    reload: tokio::sync::Mutex<()>,
+}
+
+impl<T: Clone + Default> Refreshed<T> {
+    pub(crate) fn new(name: &'static str) -> Self {
+        Refreshed { name, loaded: Mutex::new(None), reload: tokio::sync::Mutex::new(()) }
+    }
+
+    fn cached(&self) -> Option<(Arc<T>, Instant)> {
+        self.loaded.lock().unwrap().clone()
+    }
+
+    /// The current value, reloaded with `load` when older than `interval`. A failed reload keeps
+    /// the previous value (the default before the first successful load) and is retried after
+    /// the next interval.
+    pub(crate) async fn get<F>(&self, interval: Duration, load: F) -> Arc<T>
+    where
+        F: Future<Output = Result<T, Error>>,
+    {
+        let fresh = |cached: &(Arc<T>, Instant)| cached.1.elapsed() < interval;
+        if let Some((value, _)) = self.cached().filter(fresh) {
+            return value;
+        }
+        let _reload = match (self.reload.try_lock(), self.cached()) {
+            (Ok(guard), _) => guard,
+            (Err(_), Some((previous, _))) => return previous,
+            (Err(_), None) => self.reload.lock().await,
+        };
+        if let Some((value, _)) = self.cached().filter(fresh) {
+            return value;
+        }
+
+        let value = match load.await {
+            Ok(value) => Arc::new(value),
+            Err(err) => {
+                println!("{}: reload failed: {err}", self.name);
+                self.cached().map(|(previous, _)| previous).unwrap_or_default()
+            }
+        };
+        *self.loaded.lock().unwrap() = Some((value.clone(), Instant::now()));
+        value
+    }
+
+    /// Apply a change made by this agent right away, without waiting for the next reload.
+    pub(crate) fn update(&self, change: impl FnOnce(&mut T)) {
+        let mut loaded = self.loaded.lock().unwrap();
+        if let Some((current, at)) = loaded.as_ref() {
+            let mut value = T::clone(current);
+            change(&mut value);
+            *loaded = Some((Arc::new(value), *at));
+        }
+    }
+}
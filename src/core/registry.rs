@@ -1,15 +1,23 @@
 use crate::core::utils::get_env_var;
 use anyhow::Error;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::HashSet,
     fs,
     path::{Path, PathBuf},
 };
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, Default, Clone)]
+#[derive(Serialize, Deserialize, Default, Clone, ToSchema)]
 pub struct RegistryEntry {
     pub dataitem_id: String,
     pub dataitem_name: String,
+    /// raw body size, recorded for objects written through the S3 facade
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<DateTime<Utc>>,
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -66,6 +74,7 @@ pub(crate) fn set_dataitem_name(
         registry.data.push(RegistryEntry {
             dataitem_id: dataitem_id.to_string(),
             dataitem_name: dataitem_name.to_string(),
+            ..Default::default()
         });
     }
 
@@ -77,3 +86,50 @@ pub(crate) fn get_bucket_registry(bucket_name: &str) -> Result<Vec<RegistryEntry
     let registry = load_bucket_registry(bucket_name)?;
     Ok(registry.data)
 }
+
+/// Point `entry.dataitem_name` at `entry.dataitem_id`, dropping entries previously registered
+/// under the same name (names behave like object keys).
+pub(crate) fn replace_named_entry(bucket_name: &str, entry: RegistryEntry) -> Result<(), Error> {
+    let mut registry = load_bucket_registry(bucket_name)?;
+    registry.data.retain(|existing| existing.dataitem_name != entry.dataitem_name);
+    registry.data.push(entry);
+    save_bucket_registry(&registry)
+}
+
+/// Most recent entry registered under `dataitem_name`.
+pub(crate) fn find_named_entry(
+    bucket_name: &str,
+    dataitem_name: &str,
+) -> Result<Option<RegistryEntry>, Error> {
+    let registry = load_bucket_registry(bucket_name)?;
+    Ok(registry.data.into_iter().rev().find(|entry| entry.dataitem_name == dataitem_name))
+}
+
+/// Drop registry entries superseded by a later entry for the same dataitem, rewriting only the
+/// bucket files that changed. Returns the number of entries removed.
+pub(crate) fn compact_registries() -> Result<usize, Error> {
+    let registry_dir = PathBuf::from(get_env_var("S3_AGENT_REGISTRY_DIR_PATH")?);
+    if !registry_dir.exists() {
+        return Ok(0);
+    }
+
+    let mut removed = 0;
+    for file in fs::read_dir(&registry_dir)? {
+        let path = file?.path();
+        if path.extension().is_none_or(|ext| ext != "json") {
+            continue;
+        }
+        let mut registry: BucketRegistry = serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let before = registry.data.len();
+        // keep the last entry of each dataitem, in registration order
+        let mut seen = HashSet::new();
+        registry.data.reverse();
+        registry.data.retain(|entry| seen.insert(entry.dataitem_id.clone()));
+        registry.data.reverse();
+        if registry.data.len() < before {
+            removed += before - registry.data.len();
+            fs::write(&path, serde_json::to_string_pretty(&registry)?)?;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,71 @@
+use crate::core::{
+    ans104::{ContentDigest, owner_address, reconstruct_dataitem_data},
+    metadata::{dataitem_tags, index_dataitem},
+    models::ReindexReport,
+    provenance::Provenance,
+    s3::{AgentConfig, list_all_objects},
+    storage::storage_backend,
+    tenant::Tenant,
+};
+use anyhow::Error;
+
+const ANS104_SUFFIX: &str = ".ans104";
+const BATCH_SIZE: usize = 500;
+
+/// Index the tenant's stored dataitems missing from the tag index, e.g. after ClickHouse was
+/// restored from an old backup or the agent ran with `INDEXING_ENABLED=false`. Indexed ones are
+/// skipped, so it can be rerun at will.
+pub(crate) async fn reindex(tenant: &Tenant) -> Result<ReindexReport, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    let bucket = &agent_config.s3_bucket_name;
+    let prefix = format!("{}/", agent_config.s3_dir_name);
+
+    let dataitem_ids: Vec<String> = list_all_objects(storage.as_ref(), bucket, &prefix)
+        .await?
+        .into_iter()
+        .filter_map(|obj| {
+            obj.key.strip_prefix(&prefix)?.strip_suffix(ANS104_SUFFIX).map(String::from)
+        })
+        .collect();
+
+    let mut report = ReindexReport {
+        tenant: tenant.name.clone(),
+        dataitems_scanned: dataitem_ids.len(),
+        ..Default::default()
+    };
+    // provenance of the original uploads is unknown
+    let provenance = Provenance::default();
+    for batch in dataitem_ids.chunks(BATCH_SIZE) {
+        let indexed = dataitem_tags(&tenant.name, batch).await?;
+        for dataitem_id in batch {
+            if indexed.contains_key(dataitem_id) {
+                report.already_indexed += 1;
+                continue;
+            }
+            let key = format!("{prefix}{dataitem_id}{ANS104_SUFFIX}");
+            let result = async {
+                let (dataitem, content_type) =
+                    reconstruct_dataitem_data(storage.get(bucket, &key).await?)?;
+                let tags: Vec<(String, String)> =
+                    dataitem.tags.iter().map(|tag| (tag.name.clone(), tag.value.clone())).collect();
+                index_dataitem(
+                    &tenant.name,
+                    dataitem_id,
+                    &owner_address(&dataitem),
+                    &content_type,
+                    &ContentDigest::compute(&dataitem.data),
+                    &tags,
+                    &provenance,
+                )
+                .await
+            }
+            .await;
+            match result {
+                Ok(()) => report.indexed += 1,
+                Err(err) => report.errors.push(format!("{dataitem_id}: {err}")),
+            }
+        }
+    }
+    Ok(report)
+}
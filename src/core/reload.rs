@@ -0,0 +1,88 @@
+use crate::core::{
+    concurrency, cors::cors_layer, lcp::invalidate_bucket_ownership, limits::BodyLimits,
+    models::ReloadReport, utils::clear_auth_cache,
+};
+use anyhow::{Error, anyhow};
+use axum::{extract::Request, middleware::Next, response::Response};
+use std::{env, sync::RwLock};
+use tower::{Layer, ServiceExt};
+use tower_http::cors::CorsLayer;
+
+static CORS: RwLock<Option<CorsLayer>> = RwLock::new(None);
+
+/// Apply `layer` to the requests passing through `apply_cors` from now on.
+pub(crate) fn install_cors(layer: CorsLayer) {
+    *CORS.write().unwrap() = Some(layer);
+}
+
+/// Middleware applying the installed CORS policy, requests pass through untouched until one is.
+pub(crate) async fn apply_cors(request: Request, next: Next) -> Response {
+    let layer = CORS.read().unwrap().clone();
+    match layer {
+        Some(layer) => match layer.layer(next).oneshot(request).await {
+            Ok(response) => response,
+            Err(never) => match never {},
+        },
+        None => next.run(request).await,
+    }
+}
+
+/// Re-read `.env`, its values overriding the ones loaded before, and rebuild the state derived
+/// from the configuration at startup. Settings read on each request (API keys, size limits,
+/// bundler and upstream URLs, ...) apply right away. Keys removed from `.env` keep their value
+/// until restart, like the listening address, TLS, storage and ClickHouse clients.
+pub(crate) fn reload_config() -> Result<ReloadReport, Error> {
+    // parsed in full first, a malformed file changes nothing
+    let entries = match dotenvy::dotenv_iter() {
+        Ok(iter) => {
+            iter.collect::<Result<Vec<_>, _>>().map_err(|err| anyhow!("invalid .env: {err}"))?
+        }
+        Err(err) if err.not_found() => Vec::new(),
+        Err(err) => return Err(anyhow!("invalid .env: {err}")),
+    };
+    let changed: Vec<String> = entries
+        .iter()
+        .filter(|(key, value)| env::var(key).ok().as_ref() != Some(value))
+        .map(|(key, _)| key.clone())
+        .collect();
+    if !changed.is_empty() {
+        dotenvy::dotenv_override()?;
+    }
+
+    let mut errors = Vec::new();
+    match cors_layer() {
+        Ok(layer) => install_cors(layer),
+        Err(err) => errors.push(format!("{err:#}, the previous CORS policy stays")),
+    }
+    if let Err(err) = BodyLimits::load() {
+        errors.push(format!("{err:#}, uploads are refused until it is fixed"));
+    }
+    if let Err(err) = concurrency::reload() {
+        errors.push(format!("{err:#}, the previous concurrency limits stay"));
+    }
+    // load_acc keys and bucket owners are verified again, with the services now configured
+    clear_auth_cache();
+    invalidate_bucket_ownership(None);
+
+    println!("CONFIG RELOAD: changed {changed:?}, {} errors", errors.len());
+    Ok(ReloadReport { ok: errors.is_empty(), changed, errors })
+}
+
+/// Reload the configuration on `SIGHUP`, like `POST /admin/reload`.
+pub(crate) fn spawn_sighup_reload() {
+    tokio::spawn(async {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(err) => {
+                println!("CONFIG RELOAD: can't listen for SIGHUP: {err}");
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            if let Err(err) = reload_config() {
+                println!("CONFIG RELOAD: {err:#}");
+            }
+        }
+    });
+}
@@ -0,0 +1,136 @@
+use crate::core::{
+    disk_cache::DiskCache, s3::get_dataitem_raw, tenant::Tenant, utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use image::{ImageFormat, ImageReader, Limits, imageops::FilterType};
+use once_cell::sync::Lazy;
+use std::io::Cursor;
+
+pub(crate) const MAX_RENDER_DIMENSION: u32 = 4096;
+const MAX_SOURCE_DIMENSION: u32 = 16384;
+const DEFAULT_RENDER_CACHE_MAX_BYTES: u64 = 512 * 1024 * 1024; // 512 MB
+
+static RENDER_CACHE: Lazy<DiskCache> = Lazy::new(|| {
+    let dir = get_env_var("RENDER_CACHE_DIR").unwrap_or_else(|_| "./render-cache".to_string());
+    let max_bytes = get_env_var("RENDER_CACHE_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_RENDER_CACHE_MAX_BYTES);
+    DiskCache::new(dir, max_bytes)
+});
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RenderFormat {
+    Webp,
+    Png,
+    Jpeg,
+}
+
+impl RenderFormat {
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        match value.to_lowercase().as_str() {
+            "webp" => Ok(RenderFormat::Webp),
+            "png" => Ok(RenderFormat::Png),
+            "jpg" | "jpeg" => Ok(RenderFormat::Jpeg),
+            other => Err(anyhow!("unsupported render format: {other}, expected webp, png or jpeg")),
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            RenderFormat::Webp => "webp",
+            RenderFormat::Png => "png",
+            RenderFormat::Jpeg => "jpeg",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            RenderFormat::Webp => "image/webp",
+            RenderFormat::Png => "image/png",
+            RenderFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            RenderFormat::Webp => ImageFormat::WebP,
+            RenderFormat::Png => ImageFormat::Png,
+            RenderFormat::Jpeg => ImageFormat::Jpeg,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RenderOptions {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub format: RenderFormat,
+}
+
+/// Error raised when the source dataitem is not an image.
+#[derive(Debug)]
+pub(crate) struct NotAnImage(pub String);
+
+impl std::fmt::Display for NotAnImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dataitem content type {} is not an image", self.0)
+    }
+}
+
+impl std::error::Error for NotAnImage {}
+
+/// Resize (preserving aspect ratio, within `width`x`height`) and transcode an image dataitem.
+/// Derivatives are cached on disk keyed by tenant, dataitem and render options.
+pub(crate) async fn render_dataitem(
+    dataitem_id: &str,
+    tenant: &Tenant,
+    options: RenderOptions,
+) -> Result<Vec<u8>, Error> {
+    let cache_key = format!(
+        "{}/{dataitem_id}/{}x{}.{}",
+        tenant.name,
+        options.width.unwrap_or(0),
+        options.height.unwrap_or(0),
+        options.format.extension()
+    );
+    if let Some(cached) = RENDER_CACHE.get(&cache_key).await {
+        return Ok(cached);
+    }
+
+    let source = get_dataitem_raw(dataitem_id, tenant).await?;
+    let content_type = source.content_type.unwrap_or_default();
+    // content type is not stored by every backend, let the decoder sniff those
+    if !content_type.is_empty() && !content_type.starts_with("image/") {
+        return Err(NotAnImage(content_type).into());
+    }
+
+    let rendered =
+        tokio::task::spawn_blocking(move || transform_image(&source.data, options)).await??;
+    RENDER_CACHE.put(&cache_key, &rendered).await;
+    Ok(rendered)
+}
+
+fn transform_image(data: &[u8], options: RenderOptions) -> Result<Vec<u8>, Error> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+    let mut image = reader.decode()?;
+
+    if options.width.is_some() || options.height.is_some() {
+        let width = options.width.unwrap_or(MAX_RENDER_DIMENSION);
+        let height = options.height.unwrap_or(MAX_RENDER_DIMENSION);
+        image = image.resize(width, height, FilterType::Lanczos3);
+    }
+    // jpeg has no alpha channel
+    if options.format == RenderFormat::Jpeg {
+        image = image.to_rgb8().into();
+    }
+
+    let mut out = Cursor::new(Vec::new());
+    image.write_to(&mut out, options.format.image_format())?;
+    Ok(out.into_inner())
+}
@@ -0,0 +1,395 @@
+use crate::core::{
+    failover::read_endpoints_health,
+    jobs::{self, REPLICATE_OBJECT_JOB},
+    metadata::indexing_enabled,
+    metrics,
+    models::{ReplicationFailure, ReplicationStatus},
+    resilience::{CircuitBreaker, RetryPolicy},
+    s3::{S3Backend, s3_client_for},
+    storage::{ListPage, ObjectBody, StorageBackend, primary_backend},
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::{OnceCell, mpsc};
+
+const RECENT_FAILURES: usize = 50;
+
+static REPLICA_BREAKER: Lazy<CircuitBreaker> =
+    Lazy::new(|| CircuitBreaker::from_env("s3_replica", "REPLICA_S3"));
+static REPLICA: OnceCell<Option<Arc<Replica>>> = OnceCell::const_new();
+static STATE: Lazy<Mutex<ReplicationState>> = Lazy::new(Default::default);
+// `bucket/key` of the objects being mirrored, so the writes to one land in order
+static KEY_LOCKS: Lazy<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>> =
+    Lazy::new(Default::default);
+
+/// Secondary S3-compatible endpoint every write is mirrored to, configured by
+/// `REPLICA_S3_ENDPOINT_URL`, `REPLICA_S3_REGION`, `REPLICA_S3_ACCESS_KEY_ID` and
+/// `REPLICA_S3_SECRET_ACCESS_KEY`. Objects keep their bucket name and key on the replica.
+struct Replica {
+    endpoint_url: String,
+    backend: S3Backend,
+    // objects to mirror, `REPLICATION_QUEUE_CAPACITY` (default 1000) before writes wait for room
+    queue: mpsc::Sender<ReplicaOp>,
+}
+
+/// An object written or deleted on the primary, its replica being brought up to date with
+/// whatever the primary holds once its turn comes.
+struct ReplicaOp {
+    id: u64,
+    bucket: String,
+    key: String,
+    // the body's type, for primaries that don't record one
+    content_type: Option<String>,
+    tagging: Option<String>,
+}
+
+#[derive(Default)]
+struct ReplicationState {
+    next_id: u64,
+    pending: BTreeMap<u64, Instant>,
+    replicated_total: u64,
+    failed_total: u64,
+    last_replicated_at: Option<DateTime<Utc>>,
+    recent_failures: VecDeque<ReplicationFailure>,
+}
+
+impl ReplicationState {
+    fn lag(&self) -> Duration {
+        self.pending.values().min().map(|at| at.elapsed()).unwrap_or_default()
+    }
+
+    fn publish(&self) {
+        metrics::set_gauge("replication_pending", self.pending.len() as f64);
+        metrics::set_gauge("replication_lag_seconds", self.lag().as_secs_f64());
+    }
+
+    fn replicated(&mut self, operation: &str) {
+        self.replicated_total += 1;
+        self.last_replicated_at = Some(Utc::now());
+        metrics::increment(&format!(
+            "replication_total{{operation=\"{operation}\",result=\"ok\"}}"
+        ));
+    }
+
+    fn failed(&mut self, bucket: &str, key: &str, err: &Error) {
+        println!("REPLICATION FAILED: {bucket}/{key}: {err}");
+        self.failed_total += 1;
+        if self.recent_failures.len() == RECENT_FAILURES {
+            self.recent_failures.pop_front();
+        }
+        self.recent_failures.push_back(ReplicationFailure {
+            operation: "sync".to_string(),
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            error: err.to_string(),
+            failed_at: Utc::now().to_rfc3339(),
+        });
+        metrics::increment("replication_total{operation=\"sync\",result=\"failed\"}");
+    }
+}
+
+fn env_usize(key: &str, default: usize) -> usize {
+    get_env_var(key).ok().and_then(|v| v.parse::<usize>().ok()).unwrap_or(default).max(1)
+}
+
+async fn replica() -> Option<Arc<Replica>> {
+    REPLICA
+        .get_or_init(|| async {
+            let endpoint_url = get_env_var("REPLICA_S3_ENDPOINT_URL").ok()?;
+            let region = get_env_var("REPLICA_S3_REGION").unwrap_or_else(|_| "auto".to_string());
+            let access_key_id = get_env_var("REPLICA_S3_ACCESS_KEY_ID").unwrap_or_default();
+            let secret_access_key = get_env_var("REPLICA_S3_SECRET_ACCESS_KEY").unwrap_or_default();
+            let concurrency = env_usize("REPLICATION_CONCURRENCY", 4);
+            let capacity = env_usize("REPLICATION_QUEUE_CAPACITY", 1000);
+
+            let client =
+                s3_client_for(&endpoint_url, &region, &access_key_id, &secret_access_key).await;
+            let backend = S3Backend::with_client(
+                client,
+                RetryPolicy::from_env("REPLICA_S3"),
+                &REPLICA_BREAKER,
+            );
+            let (queue, ops) = mpsc::channel(capacity);
+            let replica = Arc::new(Replica { endpoint_url, backend, queue });
+            let ops = Arc::new(tokio::sync::Mutex::new(ops));
+            for _ in 0..concurrency {
+                tokio::spawn(mirror_queued(replica.clone(), ops.clone()));
+            }
+            println!("REPLICATION: mirroring writes to {}", replica.endpoint_url);
+            Some(replica)
+        })
+        .await
+        .clone()
+}
+
+/// Worker mirroring the queued objects one at a time.
+async fn mirror_queued(
+    replica: Arc<Replica>,
+    ops: Arc<tokio::sync::Mutex<mpsc::Receiver<ReplicaOp>>>,
+) {
+    loop {
+        let Some(op) = ops.lock().await.recv().await else {
+            return;
+        };
+        let result = mirror(&replica, &op).await;
+        let failed = {
+            let mut state = STATE.lock().unwrap();
+            state.pending.remove(&op.id);
+            match &result {
+                Ok(operation) => state.replicated(operation),
+                Err(err) => state.failed(&op.bucket, &op.key, err),
+            }
+            state.publish();
+            result.is_err()
+        };
+        if failed {
+            retry_later(&op).await;
+        }
+    }
+}
+
+/// Hand a failed object to a `replicate_object` job, retried with backoff until the replica
+/// is back. Without ClickHouse to queue it in, it stays listed among the recent failures.
+async fn retry_later(op: &ReplicaOp) {
+    if !indexing_enabled() {
+        return;
+    }
+    let payload = serde_json::json!({
+        "bucket": op.bucket,
+        "key": op.key,
+        "content_type": op.content_type,
+        "tagging": op.tagging,
+    });
+    if let Err(err) = jobs::enqueue(REPLICATE_OBJECT_JOB, "", payload).await {
+        println!("REPLICATION: failed to queue a retry of {}/{}: {err}", op.bucket, op.key);
+    }
+}
+
+/// Bring the replica's `key` up to date with the primary: copy the object, or delete the copy
+/// when it is gone from the primary. Runs one at a time per object, so a put and a delete of
+/// the same key can't land out of order. Returns the operation that ran.
+async fn mirror(replica: &Replica, op: &ReplicaOp) -> Result<&'static str, Error> {
+    let lock_key = format!("{}/{}", op.bucket, op.key);
+    let lock = KEY_LOCKS.lock().unwrap().entry(lock_key.clone()).or_default().clone();
+    let result = {
+        let _turn = lock.lock().await;
+        copy_to_replica(replica, op).await
+    };
+    drop(lock);
+    let mut locks = KEY_LOCKS.lock().unwrap();
+    if locks.get(&lock_key).is_some_and(|lock| Arc::strong_count(lock) == 1) {
+        locks.remove(&lock_key);
+    }
+    result
+}
+
+async fn copy_to_replica(replica: &Replica, op: &ReplicaOp) -> Result<&'static str, Error> {
+    let (bucket, key) = (op.bucket.as_str(), op.key.as_str());
+    let primary = primary_backend().await?;
+    if !primary.exists(bucket, key).await? {
+        replica.backend.delete(bucket, key).await?;
+        return Ok("delete");
+    }
+    let object = primary.get_object(bucket, key).await?;
+    let content_type = object.content_type.as_deref().or(op.content_type.as_deref());
+    let content_type = content_type.unwrap_or("application/octet-stream");
+    match object.content_encoding.as_deref() {
+        Some(encoding) => {
+            replica.backend.put_encoded(bucket, key, object.data, content_type, encoding).await?
+        }
+        None => {
+            let tagging = op.tagging.as_deref();
+            replica.backend.put(bucket, key, object.data, content_type, tagging).await?
+        }
+    }
+    Ok("put")
+}
+
+/// Retry mirroring an object, payload
+/// `{"bucket": "...", "key": "...", "content_type": ..., "tagging": ...}`.
+pub(crate) async fn run_replicate_object_job(payload: &serde_json::Value) -> Result<(), Error> {
+    let (Some(bucket), Some(key)) = (payload["bucket"].as_str(), payload["key"].as_str()) else {
+        return Err(anyhow!("replicate_object job without a bucket and key"));
+    };
+    let op = ReplicaOp {
+        id: 0,
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        content_type: payload["content_type"].as_str().map(String::from),
+        tagging: payload["tagging"].as_str().map(String::from),
+    };
+    // replication was turned off since, nothing left to mirror to
+    let Some(replica) = replica().await else {
+        return Ok(());
+    };
+    let operation = mirror(&replica, &op).await?;
+    STATE.lock().unwrap().replicated(operation);
+    Ok(())
+}
+
+/// Wrap `primary` in a `ReplicatedBackend` when a replica endpoint is configured.
+pub(crate) async fn with_replication(primary: Box<dyn StorageBackend>) -> Box<dyn StorageBackend> {
+    match replica().await {
+        Some(replica) => Box::new(ReplicatedBackend { primary, replica }),
+        None => primary,
+    }
+}
+
+/// Writes and deletes go to the primary backend and are then mirrored to the replica in the
+/// background. Reads fall back to the replica when the primary fails.
+struct ReplicatedBackend {
+    primary: Box<dyn StorageBackend>,
+    replica: Arc<Replica>,
+}
+
+impl ReplicatedBackend {
+    /// Queue `key` to be mirrored, waiting for room while the queue is full so writes slow down
+    /// to the pace of the replica instead of piling up.
+    async fn enqueue(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: Option<&str>,
+        tagging: Option<&str>,
+    ) {
+        let id = {
+            let mut state = STATE.lock().unwrap();
+            let id = state.next_id;
+            state.next_id += 1;
+            state.pending.insert(id, Instant::now());
+            state.publish();
+            id
+        };
+        let op = ReplicaOp {
+            id,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            content_type: content_type.map(String::from),
+            tagging: tagging.map(String::from),
+        };
+        if self.replica.queue.send(op).await.is_err() {
+            // the workers only stop with the runtime
+            let mut state = STATE.lock().unwrap();
+            state.pending.remove(&id);
+            state.publish();
+        }
+    }
+}
+
+#[async_trait]
+impl StorageBackend for ReplicatedBackend {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        tagging: Option<&str>,
+    ) -> Result<(), Error> {
+        self.primary.put(bucket, key, body, content_type, tagging).await?;
+        self.enqueue(bucket, key, Some(content_type), tagging).await;
+        Ok(())
+    }
+
+    /// Only when the replica records the encoding too, it would store the encoded body as is.
+    fn keeps_content_encoding(&self) -> bool {
+        self.primary.keeps_content_encoding() && self.replica.backend.keeps_content_encoding()
+    }
+
+    async fn put_encoded(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        content_encoding: &str,
+    ) -> Result<(), Error> {
+        self.primary.put_encoded(bucket, key, body, content_type, content_encoding).await?;
+        self.enqueue(bucket, key, Some(content_type), None).await;
+        Ok(())
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error> {
+        match self.primary.get(bucket, key).await {
+            Ok(data) => Ok(data),
+            Err(err) => self.replica.backend.get(bucket, key).await.map_err(|_| err),
+        }
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        match self.primary.get_object(bucket, key).await {
+            Ok(object) => Ok(object),
+            Err(err) => self.replica.backend.get_object(bucket, key).await.map_err(|_| err),
+        }
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        self.primary.exists(bucket, key).await
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        self.primary.size(bucket, key).await
+    }
+
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        self.primary.presign(bucket, key, expires_in).await
+    }
+
+    // bodies uploaded there bypass the agent, so they are only on the primary
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        content_length: u64,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        self.primary.presign_put(bucket, key, content_type, content_length, expires_in).await
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListPage, Error> {
+        self.primary.list(bucket, prefix, continuation_token).await
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        self.primary.delete(bucket, key).await?;
+        self.enqueue(bucket, key, None, None).await;
+        Ok(())
+    }
+}
+
+/// Snapshot of the replication queue for `/admin/replication/status`.
+pub(crate) async fn replication_status() -> ReplicationStatus {
+    let replica = replica().await;
+    let read_endpoints = read_endpoints_health().await;
+    let state = STATE.lock().unwrap();
+    ReplicationStatus {
+        enabled: replica.is_some(),
+        endpoint: replica.map(|r| r.endpoint_url.clone()),
+        pending: state.pending.len(),
+        lag_seconds: state.lag().as_secs_f64(),
+        replicated_total: state.replicated_total,
+        failed_total: state.failed_total,
+        last_replicated_at: state.last_replicated_at.map(|at| at.to_rfc3339()),
+        recent_failures: state.recent_failures.iter().cloned().collect(),
+        read_endpoints,
+    }
+}
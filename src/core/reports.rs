@@ -0,0 +1,170 @@
+use crate::core::{
+    blocklist::{self, BLOCK_BY_ID},
+    metadata::{AbuseReportRecord, get_abuse_report, list_abuse_reports, save_abuse_report},
+    metrics,
+    models::AbuseReportRequest,
+    provenance,
+    tenant::{Tenant, tenant_by_name},
+    trash, webhooks,
+};
+use anyhow::Error;
+use chrono::{SubsecRound, Utc};
+use serde_json::json;
+
+pub(crate) const REPORT_OPEN: &str = "open";
+const REPORT_DISMISSED: &str = "dismissed";
+const REPORT_BLOCKED: &str = "blocked";
+const REPORT_DELETED: &str = "deleted";
+
+const MAX_FIELD_LEN: usize = 256;
+const MAX_DETAILS_LEN: usize = 16 * 1024;
+// open reports of a dataitem resolved along with the one acted on
+const MAX_SIBLING_REPORTS: usize = 1000;
+
+/// A report or resolution refused as malformed.
+#[derive(Debug)]
+pub(crate) struct InvalidReport(pub String);
+
+impl std::fmt::Display for InvalidReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidReport {}
+
+/// The report was already resolved.
+#[derive(Debug)]
+pub(crate) struct ReportResolved {
+    pub id: String,
+    pub state: String,
+}
+
+impl std::fmt::Display for ReportResolved {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "report {} is already {}", self.id, self.state)
+    }
+}
+
+impl std::error::Error for ReportResolved {}
+
+fn bounded(field: &str, value: Option<String>, max_len: usize) -> Result<String, InvalidReport> {
+    let value = value.unwrap_or_default().trim().to_string();
+    match value.len() > max_len {
+        true => Err(InvalidReport(format!("{field} is longer than {max_len} bytes"))),
+        false => Ok(value),
+    }
+}
+
+/// Record a report of the tenant's dataitem, open until an operator resolves it, and notify the
+/// `abuse_reported` webhook. Fails with `InvalidReport`.
+pub(crate) async fn submit(
+    tenant: &Tenant,
+    dataitem_id: &str,
+    request: AbuseReportRequest,
+) -> Result<AbuseReportRecord, Error> {
+    let reason = bounded("reason", Some(request.reason), MAX_FIELD_LEN)?;
+    if reason.is_empty() {
+        return Err(InvalidReport("reason is required".to_string()).into());
+    }
+    let reporter_email = bounded("reporter_email", request.reporter_email, MAX_FIELD_LEN)?;
+    if !reporter_email.is_empty() && !reporter_email.contains('@') {
+        return Err(InvalidReport(format!("invalid reporter_email {reporter_email:?}")).into());
+    }
+
+    let now = Utc::now().trunc_subsecs(3);
+    let report = AbuseReportRecord {
+        id: rand::random::<[u8; 16]>().iter().map(|b| format!("{b:02x}")).collect(),
+        tenant: tenant.name.clone(),
+        dataitem_id: dataitem_id.to_string(),
+        reason,
+        details: bounded("details", request.details, MAX_DETAILS_LEN)?,
+        reporter_name: bounded("reporter_name", request.reporter_name, MAX_FIELD_LEN)?,
+        reporter_email,
+        source_ip: provenance::current().source_ip,
+        state: REPORT_OPEN.to_string(),
+        resolution_note: String::new(),
+        resolved_by: String::new(),
+        created_at: now,
+        updated_at: now,
+    };
+    save_abuse_report(&report).await?;
+    metrics::increment("abuse_reports_total");
+    webhooks::notify(
+        "abuse_reported",
+        json!({
+            "report_id": report.id,
+            "tenant": report.tenant,
+            "dataitem_id": report.dataitem_id,
+            "reason": report.reason,
+        }),
+    );
+    Ok(report)
+}
+
+/// Act on an open report: `block` blocklists the dataitem's ID, `delete` soft-deletes it from
+/// the report's tenant and `dismiss` leaves it served. The dataitem's other open reports are
+/// resolved with it. `None` if there's no such report, fails with `InvalidReport` or
+/// `ReportResolved`.
+pub(crate) async fn resolve(
+    id: &str,
+    action: &str,
+    note: Option<String>,
+) -> Result<Option<AbuseReportRecord>, Error> {
+    let state = match action {
+        "block" => REPORT_BLOCKED,
+        "delete" => REPORT_DELETED,
+        "dismiss" => REPORT_DISMISSED,
+        other => {
+            return Err(InvalidReport(format!(
+                "unknown action {other:?}, expected block, delete or dismiss"
+            ))
+            .into());
+        }
+    };
+    let note = bounded("note", note, MAX_DETAILS_LEN)?;
+    let Some(report) = get_abuse_report(id).await? else {
+        return Ok(None);
+    };
+    if report.state != REPORT_OPEN {
+        return Err(ReportResolved { id: report.id, state: report.state }.into());
+    }
+
+    match state {
+        REPORT_BLOCKED => {
+            let reason = match note.is_empty() {
+                true => format!("abuse report {}: {}", report.id, report.reason),
+                false => format!("abuse report {}: {note}", report.id),
+            };
+            blocklist::set_blocked(BLOCK_BY_ID, &report.dataitem_id, &reason, true).await?;
+        }
+        REPORT_DELETED => {
+            trash::soft_delete(&tenant_by_name(&report.tenant)?, &report.dataitem_id).await?;
+        }
+        _ => {}
+    }
+
+    let mut reports = list_abuse_reports(
+        Some(REPORT_OPEN),
+        Some(&report.tenant),
+        Some(&report.dataitem_id),
+        MAX_SIBLING_REPORTS,
+    )
+    .await?;
+    reports.retain(|sibling| sibling.id != report.id);
+    let resolved_by = provenance::current().principal;
+    let now = Utc::now().trunc_subsecs(3);
+    let resolve = |open: AbuseReportRecord| AbuseReportRecord {
+        state: state.to_string(),
+        resolution_note: note.clone(),
+        resolved_by: resolved_by.clone(),
+        updated_at: now,
+        ..open
+    };
+    for sibling in reports {
+        save_abuse_report(&resolve(sibling)).await?;
+    }
+    let report = resolve(report);
+    save_abuse_report(&report).await?;
+    Ok(Some(report))
+}
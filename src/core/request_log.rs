@@ -0,0 +1,318 @@
+use crate::core::{
+    metrics,
+    models::{RequestLogSettings, RequestLogUpdate},
+    provenance,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use axum::{
+    body::{Body, Bytes, HttpBody, to_bytes},
+    extract::Request,
+    http::{HeaderMap, header},
+    middleware::Next,
+    response::Response,
+};
+use serde_json::{Map, Value, json};
+use std::{sync::RwLock, time::Instant};
+
+const DEFAULT_MAX_BODY_BYTES: usize = 4096;
+const REDACTED: &str = "[redacted]";
+// JWK members holding private key material, a JWK with any of them is redacted whole
+const PRIVATE_JWK_MEMBERS: [&str; 7] = ["d", "p", "q", "dp", "dq", "qi", "k"];
+// headers, query parameters and JSON members whose name contains one of these are redacted
+const SECRET_NAMES: [&str; 12] = [
+    "authorization",
+    "cookie",
+    "token",
+    "secret",
+    "signature",
+    "password",
+    "jwk",
+    "api-key",
+    "api_key",
+    "apikey",
+    "private_key",
+    "credential",
+];
+
+/// Settings changed with `PUT /admin/request-log`, overriding the `REQUEST_LOG_*` variables
+/// until restart.
+static OVERRIDE: RwLock<Option<RequestLogSettings>> = RwLock::new(None);
+
+fn env_settings() -> RequestLogSettings {
+    RequestLogSettings {
+        sample_rate: get_env_var("REQUEST_LOG_SAMPLE_RATE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|rate: &f64| (0.0..=1.0).contains(rate))
+            .unwrap_or(0.0),
+        paths: get_env_var("REQUEST_LOG_PATHS")
+            .unwrap_or_default()
+            .split(',')
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty())
+            .collect(),
+        max_body_bytes: get_env_var("REQUEST_LOG_MAX_BODY_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BODY_BYTES),
+        overridden: false,
+    }
+}
+
+/// The sampling in effect, from the admin API or else `REQUEST_LOG_SAMPLE_RATE` (default 0, no
+/// logging), `REQUEST_LOG_PATHS` and `REQUEST_LOG_MAX_BODY_BYTES`.
+pub(crate) fn settings() -> RequestLogSettings {
+    OVERRIDE.read().unwrap().clone().unwrap_or_else(env_settings)
+}
+
+/// Apply an admin update on top of the settings in effect, `reset` going back to the
+/// environment first.
+pub(crate) fn update(change: RequestLogUpdate) -> Result<RequestLogSettings, Error> {
+    if let Some(rate) = change.sample_rate {
+        if !(0.0..=1.0).contains(&rate) {
+            return Err(anyhow!("sample_rate must be between 0 and 1, got {rate}"));
+        }
+    }
+    let unchanged =
+        change.sample_rate.is_none() && change.paths.is_none() && change.max_body_bytes.is_none();
+    if change.reset && unchanged {
+        *OVERRIDE.write().unwrap() = None;
+        return Ok(env_settings());
+    }
+
+    let mut settings = if change.reset { env_settings() } else { settings() };
+    if let Some(rate) = change.sample_rate {
+        settings.sample_rate = rate;
+    }
+    if let Some(paths) = change.paths {
+        settings.paths = paths;
+    }
+    if let Some(max_body_bytes) = change.max_body_bytes {
+        settings.max_body_bytes = max_body_bytes;
+    }
+    settings.overridden = true;
+    *OVERRIDE.write().unwrap() = Some(settings.clone());
+    Ok(settings)
+}
+
+fn sampled(settings: &RequestLogSettings, path: &str) -> bool {
+    settings.sample_rate > 0.0
+        && (settings.paths.is_empty() || settings.paths.iter().any(|p| path.starts_with(p)))
+        && rand::random::<f64>() < settings.sample_rate
+}
+
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_lowercase();
+    SECRET_NAMES.iter().any(|secret| name.contains(secret))
+}
+
+fn redact_headers(headers: &HeaderMap) -> Value {
+    let headers = headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match is_secret_name(name.as_str()) {
+                true => REDACTED.to_string(),
+                false => redact_url(&String::from_utf8_lossy(value.as_bytes())),
+            };
+            (name.to_string(), Value::String(value))
+        })
+        .collect::<Map<_, _>>();
+    Value::Object(headers)
+}
+
+/// The path and query, the values of secret looking parameters (`token`, `X-Amz-Signature`,
+/// ...) redacted.
+fn redact_uri(path: &str, query: Option<&str>) -> String {
+    let Some(query) = query else {
+        return path.to_string();
+    };
+    let query: Vec<String> = query
+        .split('&')
+        .map(|pair| match pair.split_once('=') {
+            Some((name, _)) if is_secret_name(name) => format!("{name}={REDACTED}"),
+            _ => pair.to_string(),
+        })
+        .collect();
+    format!("{path}?{}", query.join("&"))
+}
+
+/// `value` with its secret query parameters redacted when it is a URL (`Location`, presigned
+/// and token URLs), absolute or relative to the agent, other values as they are.
+fn redact_url(value: &str) -> String {
+    let is_url = value.starts_with('/') || reqwest::Url::parse(value).is_ok();
+    match value.split_once('?') {
+        Some((path, query)) if is_url => redact_uri(path, Some(query)),
+        _ => value.to_string(),
+    }
+}
+
+fn is_private_jwk(object: &Map<String, Value>) -> bool {
+    object.contains_key("kty") && PRIVATE_JWK_MEMBERS.iter().any(|m| object.contains_key(*m))
+}
+
+fn redact_json(value: &mut Value) {
+    match value {
+        Value::Object(object) if is_private_jwk(object) => {
+            *value = Value::String(REDACTED.to_string());
+        }
+        Value::Object(object) => {
+            for (name, member) in object.iter_mut() {
+                if is_secret_name(name) {
+                    *member = Value::String(REDACTED.to_string());
+                } else {
+                    redact_json(member);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_json),
+        Value::String(text) => *text = redact_url(text),
+        _ => {}
+    }
+}
+
+/// A logged body: redacted JSON, text, or only its size for binary or JWK looking content.
+fn describe_body(headers: &HeaderMap, body: Option<&Bytes>, size: Option<u64>) -> Value {
+    let Some(body) = body else {
+        return json!({ "omitted_bytes": size });
+    };
+    if body.is_empty() {
+        return Value::Null;
+    }
+    if let Ok(mut value) = serde_json::from_slice::<Value>(body) {
+        redact_json(&mut value);
+        return value;
+    }
+    let content_type =
+        headers.get(header::CONTENT_TYPE).and_then(|h| h.to_str().ok()).unwrap_or_default();
+    match std::str::from_utf8(body) {
+        // a JWK that doesn't parse as JSON is still a JWK
+        Ok(text) if text.contains("\"kty\"") => json!({ "redacted_bytes": body.len() }),
+        Ok(text) if content_type.starts_with("text/") => Value::String(text.to_string()),
+        _ => json!({ "omitted_bytes": body.len() }),
+    }
+}
+
+/// Buffer the body when it is known to be at most `max_bytes`, so it can be logged and passed
+/// on. Larger or streamed bodies pass through untouched.
+async fn capture(body: Body, max_bytes: usize) -> (Body, Option<Bytes>, Option<u64>) {
+    let size = body.size_hint().exact();
+    match size {
+        Some(size) if size <= max_bytes as u64 => match to_bytes(body, max_bytes).await {
+            Ok(bytes) => (Body::from(bytes.clone()), Some(bytes), Some(size)),
+            Err(_) => (Body::empty(), None, Some(size)),
+        },
+        _ => (body, None, size),
+    }
+}
+
+/// Log a sample of the requests, with their response, as a `REQUEST LOG` JSON line. Secret
+/// headers, query parameters and JSON members, and private JWKs are redacted.
+pub(crate) async fn log_sampled(request: Request, next: Next) -> Response {
+    let settings = settings();
+    if !sampled(&settings, request.uri().path()) {
+        return next.run(request).await;
+    }
+    metrics::increment("request_log_sampled_total");
+    let started = Instant::now();
+    let (parts, body) = request.into_parts();
+    let (body, request_body, request_size) = capture(body, settings.max_body_bytes).await;
+    let mut entry = json!({
+        "request_id": provenance::current().request_id,
+        "method": parts.method.as_str(),
+        "uri": redact_uri(parts.uri.path(), parts.uri.query()),
+        "request_headers": redact_headers(&parts.headers),
+        "request_body": describe_body(&parts.headers, request_body.as_ref(), request_size),
+    });
+    let response = next.run(Request::from_parts(parts, body)).await;
+
+    let (parts, body) = response.into_parts();
+    let (body, response_body, response_size) = capture(body, settings.max_body_bytes).await;
+    if let Some(entry) = entry.as_object_mut() {
+        entry.insert("status".into(), json!(parts.status.as_u16()));
+        entry.insert("duration_ms".into(), json!(started.elapsed().as_millis() as u64));
+        entry.insert("response_headers".into(), redact_headers(&parts.headers));
+        entry.insert(
+            "response_body".into(),
+            describe_body(&parts.headers, response_body.as_ref(), response_size),
+        );
+    }
+    println!("REQUEST LOG: {entry}");
+    Response::from_parts(parts, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn redacts_secret_query_parameters() {
+        assert_eq!(redact_uri("/upload", None), "/upload");
+        assert_eq!(
+            redact_uri("/abc", Some("token=t1.p.s&download=true")),
+            "/abc?token=[redacted]&download=true"
+        );
+        assert_eq!(
+            redact_uri("/b/k", Some("X-Amz-Credential=AKID&X-Amz-Signature=ff&X-Amz-Expires=60")),
+            "/b/k?X-Amz-Credential=[redacted]&X-Amz-Signature=[redacted]&X-Amz-Expires=60"
+        );
+        // valueless parameters have nothing to hide
+        assert_eq!(redact_uri("/abc", Some("token&flag")), "/abc?token&flag");
+    }
+
+    #[test]
+    fn redacts_urls_only() {
+        assert_eq!(
+            redact_url("https://s3.example.com/b/k?X-Amz-Signature=ff&x-id=GetObject"),
+            "https://s3.example.com/b/k?X-Amz-Signature=[redacted]&x-id=GetObject"
+        );
+        assert_eq!(redact_url("/abc?token=secret"), "/abc?token=[redacted]");
+        assert_eq!(redact_url("what?token=secret"), "what?token=secret");
+        assert_eq!(redact_url("https://example.com/plain"), "https://example.com/plain");
+    }
+
+    #[test]
+    fn redacts_secret_headers_and_url_values() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer k1"));
+        headers.insert("x-api-key", HeaderValue::from_static("k1"));
+        headers.insert(header::LOCATION, HeaderValue::from_static("/abc?token=t1.p.s"));
+        headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        assert_eq!(
+            redact_headers(&headers),
+            json!({
+                "authorization": REDACTED,
+                "x-api-key": REDACTED,
+                "location": "/abc?token=[redacted]",
+                "content-type": "text/plain",
+            })
+        );
+    }
+
+    #[test]
+    fn redacts_json_secrets() {
+        let mut value = json!({
+            "dataitem_id": "abc",
+            "apiKey": "k1",
+            "nested": [{"password": "hunter2", "name": "x"}],
+            "raw_presigned_url": "https://s3.example.com/b/k?X-Amz-Signature=ff",
+            "wallet": {"kty": "RSA", "n": "modulus", "d": "private"},
+            "public_jwk": {"kty": "RSA", "n": "modulus"},
+            "size": 10,
+        });
+        redact_json(&mut value);
+        assert_eq!(
+            value,
+            json!({
+                "dataitem_id": "abc",
+                "apiKey": REDACTED,
+                "nested": [{"password": REDACTED, "name": "x"}],
+                "raw_presigned_url": "https://s3.example.com/b/k?X-Amz-Signature=[redacted]",
+                "wallet": REDACTED,
+                "public_jwk": REDACTED,
+                "size": 10,
+            })
+        );
+    }
+}
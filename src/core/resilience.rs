@@ -0,0 +1,283 @@
+use crate::core::{metrics, utils::get_env_var};
+use anyhow::Error;
+use std::{
+    future::Future,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Clone)]
+pub(crate) struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl RetryPolicy {
+    /// Load `{PREFIX}_RETRY_MAX_ATTEMPTS`, `{PREFIX}_RETRY_BASE_DELAY_MS` and
+    /// `{PREFIX}_RETRY_MAX_DELAY_MS`, falling back to 3 attempts, 100ms and 2s.
+    pub fn from_env(prefix: &str) -> Self {
+        RetryPolicy {
+            max_attempts: env_or(&format!("{prefix}_RETRY_MAX_ATTEMPTS"), 3).max(1) as u32,
+            base_delay: Duration::from_millis(env_or(
+                &format!("{prefix}_RETRY_BASE_DELAY_MS"),
+                100,
+            )),
+            max_delay: Duration::from_millis(env_or(&format!("{prefix}_RETRY_MAX_DELAY_MS"), 2000)),
+        }
+    }
+
+    /// Exponential backoff with full jitter: a random delay in `[0, min(max, base * 2^attempt)]`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        let cap = exp.min(self.max_delay).as_millis() as u64;
+        Duration::from_millis(rand::random_range(0..=cap))
+    }
+}
+
+fn env_or(key: &str, default: u64) -> u64 {
+    get_env_var(key).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+/// A dependency call that did not complete within its `{DEPENDENCY}_TIMEOUT_SECS`.
+#[derive(Debug)]
+pub(crate) struct DependencyTimeout {
+    pub dependency: String,
+    pub after: Duration,
+}
+
+impl std::fmt::Display for DependencyTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} did not respond within {}s", self.dependency, self.after.as_secs())
+    }
+}
+
+impl std::error::Error for DependencyTimeout {}
+
+/// A call refused because the dependency's circuit breaker is open.
+#[derive(Debug)]
+pub(crate) struct BreakerOpen {
+    pub dependency: String,
+}
+
+impl std::fmt::Display for BreakerOpen {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is unavailable (circuit breaker open), failing fast", self.dependency)
+    }
+}
+
+impl std::error::Error for BreakerOpen {}
+
+/// Bound a call to `dependency` by `{DEPENDENCY}_TIMEOUT_SECS` (e.g. `CLICKHOUSE_TIMEOUT_SECS`),
+/// `default_secs` when unset. Timeouts are counted in `timeouts_total{dependency="..."}`.
+pub(crate) async fn with_timeout<T, E: Into<Error>>(
+    dependency: &str,
+    default_secs: u64,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, Error> {
+    let after = Duration::from_secs(env_or(
+        &format!("{}_TIMEOUT_SECS", dependency.to_uppercase()),
+        default_secs,
+    ));
+    match tokio::time::timeout(after, call).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(_) => {
+            metrics::increment(&format!("timeouts_total{{dependency=\"{dependency}\"}}"));
+            Err(DependencyTimeout { dependency: dependency.to_string(), after }.into())
+        }
+    }
+}
+
+/// Run `op` until it succeeds, returns a non-transient error, or the policy's attempts run out.
+/// Every retry is counted in `{dependency}_retries_total{operation="..."}`.
+pub(crate) async fn retry<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    dependency: &str,
+    operation: &str,
+    is_transient: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, E>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Ok(value) => return Ok(value),
+            Err(err) if attempt + 1 < policy.max_attempts && is_transient(&err) => {
+                metrics::increment(&format!(
+                    "{dependency}_retries_total{{operation=\"{operation}\"}}"
+                ));
+                tokio::time::sleep(policy.backoff(attempt)).await;
+                attempt += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum BreakerState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+}
+
+struct BreakerInner {
+    state: BreakerState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    // start of the half-open probe in flight, if any
+    probe_started_at: Option<Instant>,
+    probe_successes: u32,
+}
+
+/// Consecutive-failure circuit breaker. Once open it fast-fails calls until the cooldown
+/// elapses, then lets half-open probes through one at a time to decide whether to close again.
+pub(crate) struct CircuitBreaker {
+    name: String,
+    failure_threshold: u32,
+    cooldown: Duration,
+    half_open_probes: u32,
+    inner: Mutex<BreakerInner>,
+}
+
+impl CircuitBreaker {
+    /// Load `{PREFIX}_BREAKER_FAILURE_THRESHOLD` (default 5), `{PREFIX}_BREAKER_COOLDOWN_SECS`
+    /// (default 30) and `{PREFIX}_BREAKER_HALF_OPEN_PROBES`, the successful probes needed to
+    /// close the breaker again (default 1).
+    pub fn from_env(name: &str, prefix: &str) -> Self {
+        let breaker = CircuitBreaker {
+            name: name.to_string(),
+            failure_threshold: env_or(&format!("{prefix}_BREAKER_FAILURE_THRESHOLD"), 5).max(1)
+                as u32,
+            cooldown: Duration::from_secs(env_or(&format!("{prefix}_BREAKER_COOLDOWN_SECS"), 30)),
+            half_open_probes: env_or(&format!("{prefix}_BREAKER_HALF_OPEN_PROBES"), 1).max(1)
+                as u32,
+            inner: Mutex::new(BreakerInner {
+                state: BreakerState::Closed,
+                consecutive_failures: 0,
+                opened_at: None,
+                probe_started_at: None,
+                probe_successes: 0,
+            }),
+        };
+        breaker.publish(BreakerState::Closed);
+        breaker
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Whether a call may proceed. Transitions open -> half-open once the cooldown elapsed.
+    /// A probe that never reported back (e.g. its request was dropped) is replaced after
+    /// another cooldown.
+    pub fn allow(&self) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.state {
+            BreakerState::Closed => true,
+            BreakerState::HalfOpen => {
+                let free = inner.probe_started_at.is_none_or(|at| at.elapsed() >= self.cooldown);
+                if free {
+                    inner.probe_started_at = Some(Instant::now());
+                }
+                free
+            }
+            BreakerState::Open => {
+                let cooled_down =
+                    inner.opened_at.map(|at| at.elapsed() >= self.cooldown).unwrap_or(true);
+                if cooled_down {
+                    inner.probe_started_at = Some(Instant::now());
+                    inner.probe_successes = 0;
+                    self.transition(&mut inner, BreakerState::HalfOpen);
+                }
+                cooled_down
+            }
+        }
+    }
+
+    /// Whether calls are fast-failed right now, open and still cooling down. Unlike `allow` it
+    /// never claims the half-open probe.
+    pub fn rejecting(&self) -> bool {
+        let inner = self.inner.lock().unwrap();
+        inner.state == BreakerState::Open
+            && inner.opened_at.is_some_and(|at| at.elapsed() < self.cooldown)
+    }
+
+    pub fn state(&self) -> BreakerState {
+        self.inner.lock().unwrap().state
+    }
+
+    /// `allow`, as an error naming the dependency for the caller to surface.
+    pub fn check(&self) -> Result<(), BreakerOpen> {
+        match self.allow() {
+            true => Ok(()),
+            false => Err(BreakerOpen { dependency: self.name.clone() }),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures = 0;
+        if inner.state == BreakerState::HalfOpen {
+            inner.probe_started_at = None;
+            inner.probe_successes += 1;
+            if inner.probe_successes < self.half_open_probes {
+                return;
+            }
+        }
+        if inner.state != BreakerState::Closed {
+            inner.opened_at = None;
+            self.transition(&mut inner, BreakerState::Closed);
+        }
+    }
+
+    pub fn record_failure(&self) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.consecutive_failures += 1;
+        if inner.state == BreakerState::HalfOpen
+            || inner.consecutive_failures >= self.failure_threshold
+        {
+            inner.opened_at = Some(Instant::now());
+            inner.probe_started_at = None;
+            self.transition(&mut inner, BreakerState::Open);
+        }
+    }
+
+    fn transition(&self, inner: &mut BreakerInner, state: BreakerState) {
+        if inner.state != state {
+            println!(
+                "circuit breaker {}: {} -> {}",
+                self.name,
+                inner.state.as_str(),
+                state.as_str()
+            );
+        }
+        inner.state = state;
+        self.publish(state);
+    }
+
+    fn publish(&self, state: BreakerState) {
+        let value = match state {
+            BreakerState::Closed => 0.0,
+            BreakerState::HalfOpen => 0.5,
+            BreakerState::Open => 1.0,
+        };
+        metrics::set_gauge(
+            &format!("circuit_breaker_state{{dependency=\"{}\"}}", self.name),
+            value,
+        );
+    }
+}
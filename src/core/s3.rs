@@ -1,13 +1,55 @@
 use crate::core::{
-    ans104::{create_dataitem, reconstruct_dataitem_data},
-    lcp::validate_bucket_ownership,
+    ans104::{
+        ContentDigest, create_dataitem, default_tags, owner_address, reconstruct_dataitem_data,
+    },
+    blocklist,
+    cancellation::{self, UploadAborted},
+    feeds,
+    gateway::GatewayFallback,
+    hyperbeam,
+    lcp::{BucketExists, check_private_bucket, validate_bucket_ownership},
     metadata::index_dataitem,
+    metrics,
+    progress::{self, UploadStage},
+    provenance,
+    raw_compression::{decode_raw, put_raw},
     registry::set_dataitem_name,
+    resilience::{
+        BreakerOpen, CircuitBreaker, DependencyTimeout, RetryPolicy, retry, with_timeout,
+    },
+    storage::{ListPage, ObjectBody, StorageBackend, StoredObject, storage_backend},
+    tenant::Tenant,
     utils::{PRESIGNED_URL_EXPIRY, get_env_var},
 };
 use anyhow::{Error, anyhow};
+use async_trait::async_trait;
 use aws_config::{BehaviorVersion, Region};
-use aws_sdk_s3::Client;
+use aws_sdk_s3::{
+    Client,
+    config::{http::HttpResponse, retry::RetryConfig},
+    error::SdkError,
+    operation::{
+        get_bucket_tagging::GetBucketTaggingError, get_object::GetObjectError,
+        head_object::HeadObjectError,
+    },
+    primitives::ByteStream,
+    types::{CreateBucketConfiguration, Tag, Tagging},
+};
+use axum::body::Bytes;
+use chrono::{DateTime, Utc};
+use futures::{StreamExt, TryStreamExt, stream};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, future::Future, sync::Mutex, time::Duration};
+
+static S3_BREAKER: Lazy<CircuitBreaker> = Lazy::new(|| CircuitBreaker::from_env("s3", "S3"));
+static LCP_BREAKER: Lazy<CircuitBreaker> = Lazy::new(|| CircuitBreaker::from_env("lcp", "LCP"));
+// per tenant name: dataitems count and size, and when they were counted
+type ReconciledStats = HashMap<String, (u32, u64, DateTime<Utc>)>;
+
+static RECONCILED_STATS: Lazy<Mutex<ReconciledStats>> = Lazy::new(Default::default);
+const DEFAULT_DOWNLOAD_PART_BYTES: u64 = 8 * 1024 * 1024;
+// key of the bucket tag holding the owner's load_acc key
+const LOAD_ACC_BUCKET_TAG: &str = "load_acc";
 
 #[derive(Debug, Clone)]
 pub(crate) struct AgentConfig {
@@ -18,31 +60,161 @@ pub(crate) struct AgentConfig {
     pub s3_bucket_name: String,
     pub s3_dir_name: String,
     pub s3_raw_dir_name: String,
+    /// prefixes `s3_dir_name` had before, searched in order by reads
+    pub s3_legacy_dir_names: Vec<String>,
+    pub s3_legacy_raw_dir_names: Vec<String>,
+}
+
+/// Comma separated prefixes of `key`, none when unset.
+fn prefix_list(key: &str) -> Vec<String> {
+    get_env_var(key)
+        .unwrap_or_default()
+        .split(',')
+        .map(|prefix| prefix.trim().trim_matches('/').to_string())
+        .filter(|prefix| !prefix.is_empty())
+        .collect()
 }
 
 impl AgentConfig {
     pub fn load() -> AgentConfig {
         AgentConfig {
-            endpoint_url: get_env_var("AWS_ENDPOINT_URL").unwrap(),
-            region: get_env_var("AWS_REGION").unwrap(),
-            access_key_id: get_env_var("AWS_ACCESS_KEY_ID").unwrap(),
-            secret_access_key: get_env_var("AWS_SECRET_ACCESS_KEY").unwrap(),
+            // AWS connection settings are only required by the s3 storage backend
+            endpoint_url: get_env_var("AWS_ENDPOINT_URL").unwrap_or_default(),
+            region: get_env_var("AWS_REGION").unwrap_or_default(),
+            access_key_id: get_env_var("AWS_ACCESS_KEY_ID").unwrap_or_default(),
+            secret_access_key: get_env_var("AWS_SECRET_ACCESS_KEY").unwrap_or_default(),
             s3_bucket_name: get_env_var("S3_BUCKET_NAME").unwrap(),
             s3_dir_name: get_env_var("S3_DIR_NAME").unwrap(),
             s3_raw_dir_name: get_env_var("S3_RAW_DIR_NAME").unwrap(),
+            s3_legacy_dir_names: prefix_list("S3_LEGACY_DIR_NAMES"),
+            s3_legacy_raw_dir_names: prefix_list("S3_LEGACY_RAW_DIR_NAMES"),
         }
     }
+
+    /// Agent config with the tenant's bucket and prefixes applied over the defaults.
+    pub fn for_tenant(tenant: &Tenant) -> AgentConfig {
+        let mut config = AgentConfig::load();
+        if let Some(bucket) = &tenant.bucket {
+            config.s3_bucket_name = bucket.clone();
+        }
+        if let Some(dir_name) = &tenant.dir_name {
+            config.s3_dir_name = dir_name.clone();
+        }
+        if let Some(raw_dir_name) = &tenant.raw_dir_name {
+            config.s3_raw_dir_name = raw_dir_name.clone();
+        }
+        if let Some(legacy_dir_names) = &tenant.legacy_dir_names {
+            config.s3_legacy_dir_names = legacy_dir_names.clone();
+        }
+        if let Some(legacy_raw_dir_names) = &tenant.legacy_raw_dir_names {
+            config.s3_legacy_raw_dir_names = legacy_raw_dir_names.clone();
+        }
+        config
+    }
+
+    /// Keys the dataitem's signed `.ans104` may be stored at, the current prefix first.
+    pub fn dataitem_keys(&self, dataitem_id: &str) -> Vec<String> {
+        std::iter::once(&self.s3_dir_name)
+            .chain(&self.s3_legacy_dir_names)
+            .map(|dir_name| format!("{dir_name}/{dataitem_id}.ans104"))
+            .collect()
+    }
+
+    /// Keys the dataitem's raw body may be stored at, the current prefix first.
+    pub fn raw_keys(&self, dataitem_id: &str) -> Vec<String> {
+        std::iter::once(&self.s3_raw_dir_name)
+            .chain(&self.s3_legacy_raw_dir_names)
+            .map(|raw_dir_name| format!("{raw_dir_name}/{dataitem_id}"))
+            .collect()
+    }
+}
+
+/// The first of `keys` stored, `None` when none is.
+async fn first_stored_key(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    keys: &[String],
+) -> Result<Option<String>, Error> {
+    for key in keys {
+        if storage.exists(bucket, key).await? {
+            return Ok(Some(key.clone()));
+        }
+    }
+    Ok(None)
+}
+
+/// The object at the first of `keys` stored, failing with the error of the current key when
+/// none is.
+async fn get_first_stored(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    keys: &[String],
+) -> Result<ObjectBody, Error> {
+    let mut first_err = None;
+    for key in keys {
+        match storage.get_object(bucket, key).await {
+            Ok(object) => return Ok(object),
+            Err(err) => {
+                first_err.get_or_insert(err);
+            }
+        }
+    }
+    Err(first_err.unwrap_or_else(|| anyhow!("no key to read")))
 }
 
 /// Initialize the ~s3@1.0 device connection using the aws s3 sdk.
 async fn s3_client() -> Result<Client, Error> {
     let agent_config = AgentConfig::load();
+    if agent_config.endpoint_url.is_empty() || agent_config.region.is_empty() {
+        return Err(anyhow!("AWS_ENDPOINT_URL and AWS_REGION must be set for the s3 backend"));
+    }
+    Ok(s3_client_for(
+        &agent_config.endpoint_url,
+        &agent_config.region,
+        &agent_config.access_key_id,
+        &agent_config.secret_access_key,
+    )
+    .await)
+}
+
+/// S3-compatible LCP API holding the private bucket ownership tags, `LCP_API_URL` or the
+/// storage endpoint (`AWS_ENDPOINT_URL`) when unset.
+pub(crate) fn lcp_api_url() -> Option<String> {
+    get_env_var("LCP_API_URL")
+        .ok()
+        .filter(|url| !url.trim().is_empty())
+        .or_else(|| get_env_var("AWS_ENDPOINT_URL").ok().filter(|url| !url.is_empty()))
+}
+
+async fn lcp_client() -> Result<Client, Error> {
+    let agent_config = AgentConfig::load();
+    let endpoint_url = lcp_api_url().ok_or_else(|| {
+        anyhow!("LCP_API_URL or AWS_ENDPOINT_URL must be set for private buckets")
+    })?;
+    let region =
+        if agent_config.region.is_empty() { "auto".to_string() } else { agent_config.region };
+    Ok(s3_client_for(
+        &endpoint_url,
+        &region,
+        &agent_config.access_key_id,
+        &agent_config.secret_access_key,
+    )
+    .await)
+}
+
+/// Path-style client for any S3-compatible endpoint.
+pub(crate) async fn s3_client_for(
+    endpoint_url: &str,
+    region: &str,
+    access_key_id: &str,
+    secret_access_key: &str,
+) -> Client {
     let config = aws_config::defaults(BehaviorVersion::latest())
-        .endpoint_url(agent_config.endpoint_url)
-        .region(Region::new(agent_config.region))
+        .endpoint_url(endpoint_url)
+        .region(Region::new(region.to_string()))
         .credentials_provider(aws_sdk_s3::config::Credentials::new(
-            &agent_config.access_key_id,
-            &agent_config.secret_access_key,
+            access_key_id,
+            secret_access_key,
             None,
             None,
             "custom",
@@ -50,159 +222,777 @@ async fn s3_client() -> Result<Client, Error> {
         .load()
         .await;
 
-    let s3_config = aws_sdk_s3::config::Builder::from(&config).force_path_style(true).build();
-    Ok(Client::from_conf(s3_config))
+    // retries are handled by `S3Backend` so they are observable and share the circuit breaker
+    let s3_config = aws_sdk_s3::config::Builder::from(&config)
+        .force_path_style(true)
+        .retry_config(RetryConfig::disabled())
+        .build();
+    Client::from_conf(s3_config)
+}
+
+/// Timeouts, connection failures, throttling and 5xx responses are worth retrying.
+fn is_transient<E>(err: &SdkError<E, HttpResponse>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(_) => err
+            .raw_response()
+            .map(|response| {
+                let status = response.status().as_u16();
+                status == 429 || status >= 500
+            })
+            .unwrap_or(false),
+        _ => false,
+    }
+}
+
+/// The range an empty object was read with is unsatisfiable.
+fn is_invalid_range(err: &Error) -> bool {
+    err.downcast_ref::<SdkError<GetObjectError, HttpResponse>>()
+        .and_then(|err| err.raw_response())
+        .is_some_and(|response| response.status().as_u16() == 416)
+}
+
+/// Objects are read in ranged GETs of `S3_DOWNLOAD_PART_BYTES` (default 8 MiB) issued
+/// `S3_DOWNLOAD_CONCURRENCY` at a time, unset or 1 reading them in a single GET.
+#[derive(Debug, Clone, Copy)]
+struct DownloadParts {
+    part_bytes: u64,
+    concurrency: usize,
+}
+
+impl DownloadParts {
+    fn load() -> Option<Self> {
+        let concurrency = get_env_var("S3_DOWNLOAD_CONCURRENCY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&concurrency: &usize| concurrency > 1)?;
+        let part_bytes = get_env_var("S3_DOWNLOAD_PART_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&part_bytes: &u64| part_bytes > 0)
+            .unwrap_or(DEFAULT_DOWNLOAD_PART_BYTES);
+        Some(DownloadParts { part_bytes, concurrency })
+    }
+}
+
+/// `StorageBackend` over the ~s3@1.0 device (or any S3-compatible endpoint).
+pub struct S3Backend {
+    client: Client,
+    retry_policy: RetryPolicy,
+    breaker: &'static CircuitBreaker,
+}
+
+impl S3Backend {
+    pub(crate) async fn new() -> Result<Self, Error> {
+        Ok(S3Backend::with_client(s3_client().await?, RetryPolicy::from_env("S3"), &S3_BREAKER))
+    }
+
+    pub(crate) fn with_client(
+        client: Client,
+        retry_policy: RetryPolicy,
+        breaker: &'static CircuitBreaker,
+    ) -> Self {
+        S3Backend { client, retry_policy, breaker }
+    }
+
+    /// Run an S3 request behind the circuit breaker, retrying transient failures.
+    async fn guarded<T, E, F, Fut>(&self, operation: &str, op: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.breaker.check()?;
+        match retry(&self.retry_policy, self.breaker.name(), operation, is_transient, op).await {
+            Ok(value) => {
+                self.breaker.record_success();
+                Ok(value)
+            }
+            Err(err) => {
+                // a non-transient error (e.g. missing key) still proves the endpoint is healthy
+                if is_transient(&err) {
+                    self.breaker.record_failure();
+                } else {
+                    self.breaker.record_success();
+                }
+                Err(err.into())
+            }
+        }
+    }
+
+    async fn get_whole_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        let object =
+            self.guarded("get", || self.client.get_object().bucket(bucket).key(key).send()).await?;
+        let content_type = object.content_type().map(|ct| ct.to_string());
+        let content_encoding = object.content_encoding().map(|ce| ce.to_string());
+        let data = object.body.collect().await?.into_bytes().to_vec();
+        Ok(ObjectBody { data, content_type, content_encoding })
+    }
+
+    /// Read the first part to learn the object size, then the others concurrently, stitched
+    /// back in order. They are pinned to the first part's ETag, an object overwritten halfway
+    /// fails instead of mixing both versions.
+    async fn get_object_in_parts(
+        &self,
+        bucket: &str,
+        key: &str,
+        parts: DownloadParts,
+    ) -> Result<ObjectBody, Error> {
+        let first_range = format!("bytes=0-{}", parts.part_bytes - 1);
+        let first = match self
+            .guarded("get", || {
+                self.client.get_object().bucket(bucket).key(key).range(&first_range).send()
+            })
+            .await
+        {
+            Ok(first) => first,
+            Err(err) if is_invalid_range(&err) => return self.get_whole_object(bucket, key).await,
+            Err(err) => return Err(err),
+        };
+        let content_type = first.content_type().map(|ct| ct.to_string());
+        let content_encoding = first.content_encoding().map(|ce| ce.to_string());
+        let etag = first.e_tag().map(String::from);
+        // `bytes 0-{end}/{size}`, absent when the whole object was returned
+        let size = first
+            .content_range()
+            .and_then(|range| range.rsplit('/').next())
+            .and_then(|size| size.parse::<u64>().ok());
+        let mut data = first.body.collect().await?.into_bytes().to_vec();
+        let size = size.unwrap_or(data.len() as u64);
+
+        let ranges: Vec<String> = (data.len() as u64..size)
+            .step_by(parts.part_bytes as usize)
+            .map(|start| format!("bytes={start}-{}", (start + parts.part_bytes).min(size) - 1))
+            .collect();
+        let rest: Vec<Vec<u8>> = stream::iter(ranges)
+            .map(|range| {
+                let etag = etag.clone();
+                async move {
+                    let part = self
+                        .guarded("get", || {
+                            self.client
+                                .get_object()
+                                .bucket(bucket)
+                                .key(key)
+                                .range(&range)
+                                .set_if_match(etag.clone())
+                                .send()
+                        })
+                        .await?;
+                    Ok::<_, Error>(part.body.collect().await?.into_bytes().to_vec())
+                }
+            })
+            .buffered(parts.concurrency)
+            .try_collect()
+            .await?;
+        data.reserve_exact(rest.iter().map(Vec::len).sum());
+        for part in rest {
+            data.extend(part);
+        }
+        if data.len() as u64 != size {
+            return Err(anyhow!("read {} of the {size} bytes of {key}", data.len()));
+        }
+        Ok(ObjectBody { data, content_type, content_encoding })
+    }
+}
+
+#[async_trait]
+impl StorageBackend for S3Backend {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        tagging: Option<&str>,
+    ) -> Result<(), Error> {
+        // every attempt streams the same buffer, cloning `Bytes` only bumps a refcount
+        let body = Bytes::from(body);
+        self.guarded("put", || {
+            self.client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(ByteStream::from(body.clone()))
+                .set_tagging(tagging.map(|t| t.to_string()))
+                .content_type(content_type)
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(self.get_object(bucket, key).await?.data)
+    }
+
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        match DownloadParts::load() {
+            Some(parts) => self.get_object_in_parts(bucket, key, parts).await,
+            None => self.get_whole_object(bucket, key).await,
+        }
+    }
+
+    fn keeps_content_encoding(&self) -> bool {
+        true
+    }
+
+    async fn put_encoded(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        content_encoding: &str,
+    ) -> Result<(), Error> {
+        let body = Bytes::from(body);
+        self.guarded("put", || {
+            self.client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .body(ByteStream::from(body.clone()))
+                .content_type(content_type)
+                .content_encoding(content_encoding)
+                .send()
+        })
+        .await?;
+        Ok(())
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        Ok(self.size(bucket, key).await?.is_some())
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        match self
+            .guarded("head", || self.client.head_object().bucket(bucket).key(key).send())
+            .await
+        {
+            Ok(head) => Ok(Some(head.content_length().unwrap_or_default() as u64)),
+            Err(err) => {
+                let not_found = err
+                    .downcast_ref::<SdkError<HeadObjectError, HttpResponse>>()
+                    .and_then(|err| err.as_service_error())
+                    .map(|err| err.is_not_found())
+                    .unwrap_or(false);
+                if not_found { Ok(None) } else { Err(err) }
+            }
+        }
+    }
+
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        let presigned_url = self
+            .client
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(presigned_url.uri().to_string())
+    }
+
+    async fn presign_put(
+        &self,
+        bucket: &str,
+        key: &str,
+        content_type: &str,
+        content_length: u64,
+        expires_in: Duration,
+    ) -> Result<String, Error> {
+        let presigned_url = self
+            .client
+            .put_object()
+            .bucket(bucket)
+            .key(key)
+            .content_type(content_type)
+            // signed, S3 refuses a body of any other size
+            .content_length(content_length as i64)
+            .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(expires_in)?)
+            .await?;
+        Ok(presigned_url.uri().to_string())
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListPage, Error> {
+        let req = self
+            .guarded("list", || {
+                self.client
+                    .list_objects_v2()
+                    .bucket(bucket)
+                    .delimiter("/")
+                    .prefix(prefix)
+                    .max_keys(1000)
+                    .set_continuation_token(continuation_token.clone())
+                    .send()
+            })
+            .await?;
+
+        let objects = req
+            .contents()
+            .iter()
+            .map(|obj| StoredObject {
+                key: obj.key().unwrap_or_default().to_string(),
+                size: obj.size().unwrap_or_default() as u64,
+            })
+            .collect();
+
+        let next_continuation_token = if req.is_truncated().unwrap_or_default() {
+            req.next_continuation_token().map(|token| token.to_string())
+        } else {
+            None
+        };
+
+        Ok(ListPage { objects, next_continuation_token })
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        self.guarded("delete", || self.client.delete_object().bucket(bucket).key(key).send())
+            .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct DataitemExists(pub String);
+
+impl std::fmt::Display for DataitemExists {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dataitem {} already exists", self.0)
+    }
+}
+
+impl std::error::Error for DataitemExists {}
+
+/// Write the ans-104 serialized dataitem and its raw body. If the raw body write fails the
+/// dataitem object is deleted again, so a failed upload never leaves a half-written pair behind.
+async fn put_dataitem_objects(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    key_dataitem: &str,
+    dataitem_bytes: Vec<u8>,
+    key_raw: &str,
+    raw: Vec<u8>,
+    content_type: &str,
+) -> Result<(), Error> {
+    // store it as ans-104 serialized dataitem
+    let dataitem_len = dataitem_bytes.len();
+    storage.put(bucket, key_dataitem, dataitem_bytes, "application/octet-stream", None).await?;
+    progress::add_written(dataitem_len);
+
+    // store the dataitem raw body for fast retrievals
+    let raw_len = raw.len();
+    if let Err(err) = put_raw(storage, bucket, key_raw, raw, content_type).await {
+        if let Err(rollback_err) = storage.delete(bucket, key_dataitem).await {
+            println!("ROLLBACK FAILED: orphaned {key_dataitem}: {rollback_err}");
+            return Err(err.context(format!(
+                "raw body write failed and rollback of {key_dataitem} failed: {rollback_err}"
+            )));
+        }
+        return Err(err.context("raw body write failed, dataitem write rolled back"));
+    }
+    progress::add_written(raw_len);
+
+    Ok(())
+}
+
+/// The objects and index entry of a dataitem about to be stored.
+struct PendingDataitem {
+    tenant: Tenant,
+    bucket: String,
+    owner: String,
+    dataitem_id: String,
+    key_dataitem: String,
+    dataitem_bytes: Vec<u8>,
+    key_raw: String,
+    raw: Vec<u8>,
+    content_type: String,
+    tags: Vec<(String, String)>,
+    digest: ContentDigest,
+}
+
+/// Delete what an aborted upload wrote of `keys`.
+async fn remove_partial_writes(storage: &dyn StorageBackend, bucket: &str, keys: &[&str]) {
+    for key in keys {
+        let removed = match storage.exists(bucket, key).await {
+            Ok(true) => storage.delete(bucket, key).await,
+            Ok(false) => Ok(()),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = removed {
+            println!("ROLLBACK FAILED: orphaned {key}: {err}");
+        }
+    }
 }
 
+/// Write and index `pending` detached from the request, see `cancellation::detached`. Writes
+/// the request's budget aborts are removed again and never indexed, those committed are kept.
+/// Indexing itself isn't interrupted, the dataitem is complete once it starts.
+async fn persist_dataitem(
+    storage: Box<dyn StorageBackend>,
+    pending: PendingDataitem,
+) -> Result<(), Error> {
+    cancellation::detached(async move {
+        let budget = cancellation::current();
+        let p = pending;
+        let size = p.dataitem_bytes.len();
+
+        progress::set_stage(UploadStage::Storing);
+        let written = budget
+            .run(put_dataitem_objects(
+                storage.as_ref(),
+                &p.bucket,
+                &p.key_dataitem,
+                p.dataitem_bytes,
+                &p.key_raw,
+                p.raw,
+                &p.content_type,
+            ))
+            .await
+            .and_then(|()| Ok(budget.commit()?));
+        if let Err(err) = written {
+            if let Some(aborted) = err.downcast_ref::<UploadAborted>() {
+                println!("UPLOAD ABORTED: {}: {aborted}", p.dataitem_id);
+                metrics::increment("uploads_aborted_total");
+                remove_partial_writes(storage.as_ref(), &p.bucket, &[&p.key_dataitem, &p.key_raw])
+                    .await;
+            }
+            return Err(err);
+        }
+
+        println!("INDEX DATA: {:?} {:?} {:?}", &p.dataitem_id, &p.content_type, &p.tags);
+        progress::set_stage(UploadStage::Indexing);
+        let provenance = provenance::current();
+        index_dataitem(
+            &p.tenant.name,
+            &p.dataitem_id,
+            &p.owner,
+            &p.content_type,
+            &p.digest,
+            &p.tags,
+            &provenance,
+        )
+        .await?;
+        hyperbeam::announce(&p.tenant, &p.bucket, &p.dataitem_id, size, &p.content_type);
+        feeds::publish(&p.tenant.name, &p.dataitem_id, &p.content_type, &p.tags);
+        Ok(())
+    })
+    .await
+}
+
+/// A dataitem stored and indexed by the agent.
+#[derive(Debug, Clone)]
+pub struct StoredDataitem {
+    pub id: String,
+    /// every tag of the dataitem, including the ones injected by the agent
+    pub tags: Vec<(String, String)>,
+    /// size and digests of the dataitem's data
+    pub digest: ContentDigest,
+}
+
+/// Sign `data` as an agent dataitem with the request's `extra_tags` and the API key's
+/// `default_tags`, see `create_dataitem`, then store and index it.
 pub async fn store_dataitem(
     data: Vec<u8>,
     content_type: &str,
     extra_tags: &[(String, String)],
-) -> Result<String, Error> {
-    let agent_config = AgentConfig::load();
-    let client = s3_client().await?;
-    let dataitem = create_dataitem(data.clone(), content_type, extra_tags)?;
+    default_tags: &[(String, String)],
+    tenant: &Tenant,
+) -> Result<StoredDataitem, Error> {
+    blocklist::check_content(&data).await?;
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    progress::set_stage(UploadStage::Signing);
+    let digest = ContentDigest::compute(&data);
+    let dataitem = create_dataitem(data.clone(), &digest, content_type, extra_tags, default_tags)?;
     let tags_for_index: Vec<(String, String)> =
         dataitem.tags.iter().map(|tag| (tag.name.clone(), tag.value.clone())).collect();
     let dataitem_id = dataitem.arweave_id();
 
     let key_dataitem: String = format!("{}/{dataitem_id}.ans104", agent_config.s3_dir_name);
     let key_raw: String = format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name);
+    let dataitem_bytes = dataitem.to_bytes()?;
 
-    // store it as ans-104 serialized dataitem
-    client
-        .put_object()
-        .bucket(&agent_config.s3_bucket_name)
-        .key(key_dataitem)
-        .body(dataitem.to_bytes()?.into())
-        .content_type("application/octet-stream")
-        .send()
-        .await?;
+    let pending = PendingDataitem {
+        tenant: tenant.clone(),
+        bucket: agent_config.s3_bucket_name,
+        owner: owner_address(&dataitem),
+        dataitem_id: dataitem_id.clone(),
+        key_dataitem,
+        dataitem_bytes,
+        key_raw,
+        raw: data,
+        content_type: content_type.to_string(),
+        tags: tags_for_index.clone(),
+        digest: digest.clone(),
+    };
+    persist_dataitem(storage, pending).await?;
 
-    // store the dataitem raw body for fast retrievals
+    Ok(StoredDataitem { id: dataitem_id, tags: tags_for_index, digest })
+}
 
-    client
-        .put_object()
-        .bucket(agent_config.s3_bucket_name)
-        .key(key_raw)
-        .body(data.into())
-        .content_type(content_type)
-        .send()
-        .await?;
+/// The dataitem `store_dataitem` would sign for `data`, without storing or indexing it. The
+/// agent's signature is salted, so storing the same data later yields another ID.
+pub async fn preview_dataitem(
+    data: Vec<u8>,
+    content_type: &str,
+    extra_tags: &[(String, String)],
+    default_tags: &[(String, String)],
+) -> Result<StoredDataitem, Error> {
+    blocklist::check_content(&data).await?;
+    let digest = ContentDigest::compute(&data);
+    let dataitem = create_dataitem(data, &digest, content_type, extra_tags, default_tags)?;
+    let tags = dataitem.tags.iter().map(|tag| (tag.name.clone(), tag.value.clone())).collect();
+    Ok(StoredDataitem { id: dataitem.arweave_id(), tags, digest })
+}
 
-    println!("INDEX DATA: {:?} {:?} {:?}", &dataitem_id, &content_type, &tags_for_index);
-    index_dataitem(&dataitem_id, content_type, &tags_for_index).await.unwrap();
+/// The checks of `store_signed_dataitem` on a signed dataitem, without storing or indexing it.
+pub async fn preview_signed_dataitem(
+    data: Vec<u8>,
+    tenant: &Tenant,
+) -> Result<StoredDataitem, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    let (dataitem, _) = reconstruct_dataitem_data(data)?;
+    let dataitem_id = dataitem.arweave_id();
+    blocklist::check_id(&dataitem_id).await?;
+    blocklist::check_content(&dataitem.data).await?;
 
-    Ok(dataitem_id)
+    let key_dataitem = format!("{}/{dataitem_id}.ans104", agent_config.s3_dir_name);
+    if storage.exists(&agent_config.s3_bucket_name, &key_dataitem).await? {
+        return Err(DataitemExists(dataitem_id).into());
+    }
+    let tags = dataitem.tags.iter().map(|tag| (tag.name.clone(), tag.value.clone())).collect();
+    let digest = ContentDigest::compute(&dataitem.data);
+    Ok(StoredDataitem { id: dataitem_id, tags, digest })
 }
 
-pub async fn store_signed_dataitem(data: Vec<u8>) -> Result<String, Error> {
-    let agent_config = AgentConfig::load();
-    let client = s3_client().await?;
+pub async fn store_signed_dataitem(
+    data: Vec<u8>,
+    tenant: &Tenant,
+) -> Result<StoredDataitem, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
     let (dataitem, content_type) = reconstruct_dataitem_data(data)?;
     let dataitem_id = dataitem.arweave_id();
+    blocklist::check_id(&dataitem_id).await?;
+    blocklist::check_content(&dataitem.data).await?;
     let tags_for_index: Vec<(String, String)> =
         dataitem.tags.iter().map(|tag| (tag.name.clone(), tag.value.clone())).collect();
 
     let key_dataitem: String = format!("{}/{dataitem_id}.ans104", agent_config.s3_dir_name);
     let key_raw: String = format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name);
 
-    // store it as ans-104 serialized dataitem
-    client
-        .put_object()
-        .bucket(&agent_config.s3_bucket_name)
-        .key(key_dataitem)
-        .body(dataitem.to_bytes()?.into())
-        .content_type("application/octet-stream")
-        .send()
-        .await?;
-
-    // store the dataitem raw body for fast retrievals
+    // signed dataitems keep their ID, don't silently overwrite an existing one
+    if storage.exists(&agent_config.s3_bucket_name, &key_dataitem).await? {
+        return Err(DataitemExists(dataitem_id).into());
+    }
 
-    client
-        .put_object()
-        .bucket(agent_config.s3_bucket_name)
-        .key(key_raw)
-        .body(dataitem.data.clone().into())
-        .content_type(content_type.clone())
-        .send()
-        .await?;
+    let digest = ContentDigest::compute(&dataitem.data);
+    let pending = PendingDataitem {
+        tenant: tenant.clone(),
+        bucket: agent_config.s3_bucket_name,
+        owner: owner_address(&dataitem),
+        dataitem_id: dataitem_id.clone(),
+        key_dataitem,
+        dataitem_bytes: dataitem.to_bytes()?,
+        key_raw,
+        digest: digest.clone(),
+        raw: dataitem.data,
+        content_type,
+        tags: tags_for_index.clone(),
+    };
+    persist_dataitem(storage, pending).await?;
 
-    index_dataitem(&dataitem_id, &content_type, &tags_for_index).await?;
+    Ok(StoredDataitem { id: dataitem_id, tags: tags_for_index, digest })
+}
 
-    Ok(dataitem_id)
+/// Presigned URL of the dataitem's raw body in storage, valid for `PRESIGNED_URL_EXPIRY`.
+pub async fn presign_raw(dataitem_id: &str, tenant: &Tenant) -> Result<String, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let key = format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name);
+    storage_backend()
+        .await?
+        .presign(&agent_config.s3_bucket_name, &key, Duration::from_secs(PRESIGNED_URL_EXPIRY))
+        .await
 }
 
-pub async fn get_dataitem_url(dataitem_id: &str) -> Result<String, Error> {
-    let agent_config = AgentConfig::load();
-    let client = s3_client().await?;
+/// Presigned URL of the dataitem's raw body, searched under the current then the legacy raw
+/// prefixes.
+pub async fn get_dataitem_url(dataitem_id: &str, tenant: &Tenant) -> Result<String, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    let bucket = &agent_config.s3_bucket_name;
     // i think we should default to signed dataitems: agent_config.s3_dir_name
     // TODO: check which dependencies rely on dataitem's data expected response
-    let key: String = format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name);
-
-    let presigned_url = client
-        .get_object()
-        .bucket(agent_config.s3_bucket_name)
-        .key(key)
-        .presigned(aws_sdk_s3::presigning::PresigningConfig::expires_in(
-            std::time::Duration::from_secs(PRESIGNED_URL_EXPIRY),
-        )?)
-        .await?;
+    let mut keys = agent_config.raw_keys(dataitem_id);
+    let key = keys.remove(0);
+
+    // only look the key up when there are legacy prefixes to fall back to
+    if !keys.is_empty() && !storage.exists(bucket, &key).await? {
+        if let Some(legacy_key) = first_stored_key(storage.as_ref(), bucket, &keys).await? {
+            let expires_in = Duration::from_secs(PRESIGNED_URL_EXPIRY);
+            return storage.presign(bucket, &legacy_key, expires_in).await;
+        }
+    }
+
+    if let Some(gateway) = GatewayFallback::load() {
+        if gateway.applies(storage.as_ref(), bucket, &key, dataitem_id, tenant).await {
+            return Ok(gateway.dataitem_url(dataitem_id));
+        }
+    }
 
-    Ok(presigned_url.uri().to_string())
+    presign_raw(dataitem_id, tenant).await
 }
 
-pub(crate) async fn get_dataitem(dataitem_id: &str) -> Result<Vec<u8>, Error> {
-    let agent_config = AgentConfig::load();
-    let client = s3_client().await?;
+/// The signed `.ans104` dataitem, searched under the current then the legacy prefixes.
+pub(crate) async fn get_dataitem(dataitem_id: &str, tenant: &Tenant) -> Result<Vec<u8>, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+
+    let keys = agent_config.dataitem_keys(dataitem_id);
+    let object = get_first_stored(storage.as_ref(), &agent_config.s3_bucket_name, &keys).await?;
+    Ok(object.data)
+}
 
-    let key: String = format!("{}/{dataitem_id}.ans104", agent_config.s3_dir_name);
+/// Raw (unwrapped) dataitem body as stored for fast retrievals, under the current or a legacy
+/// raw prefix.
+pub(crate) async fn get_dataitem_raw(
+    dataitem_id: &str,
+    tenant: &Tenant,
+) -> Result<ObjectBody, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
 
-    let dataitem = client.get_object().bucket(agent_config.s3_bucket_name).key(key).send().await?;
+    let keys = agent_config.raw_keys(dataitem_id);
+    let key = &keys[0];
+    let bucket = &agent_config.s3_bucket_name;
 
-    let data = dataitem.body.collect().await?.into_bytes().to_vec();
+    let err = match get_first_stored(storage.as_ref(), bucket, &keys).await {
+        Ok(object) => return decode_raw(object).await,
+        Err(err) => err,
+    };
+    let Some(gateway) = GatewayFallback::load() else {
+        return Err(err);
+    };
+    if !gateway.applies(storage.as_ref(), bucket, key, dataitem_id, tenant).await {
+        return Err(err);
+    }
 
-    Ok(data)
+    let object = gateway.fetch(dataitem_id).await?;
+    if gateway.rehydrate {
+        let content_type = object.content_type.as_deref().unwrap_or("application/octet-stream");
+        let raw = object.data.clone();
+        if let Err(err) = put_raw(storage.as_ref(), bucket, key, raw, content_type).await {
+            println!("REHYDRATE FAILED: {key}: {err}");
+        }
+    }
+    Ok(object)
 }
 
-pub async fn get_bucket_stats() -> Result<(u32, u64), Error> {
-    let agent_config = AgentConfig::load();
+/// Whether each dataitem has its raw body and its signed `.ans104` stored, under the current or
+/// a legacy prefix, checked `EXISTS_CONCURRENCY` (default 32) objects at a time.
+pub(crate) async fn dataitems_presence(
+    tenant: &Tenant,
+    dataitem_ids: &[String],
+) -> Result<Vec<(bool, bool)>, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
+    let concurrency = get_env_var("EXISTS_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(32_usize)
+        .max(1);
+
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage.as_ref();
+    let keys: Vec<(Vec<String>, Vec<String>)> = dataitem_ids
+        .iter()
+        .map(|dataitem_id| {
+            (agent_config.raw_keys(dataitem_id), agent_config.dataitem_keys(dataitem_id))
+        })
+        .collect();
+    stream::iter(keys)
+        .map(|(keys_raw, keys_dataitem)| async move {
+            let (raw, ans104) = tokio::try_join!(
+                first_stored_key(storage, bucket, &keys_raw),
+                first_stored_key(storage, bucket, &keys_dataitem)
+            )?;
+            Ok::<_, Error>((raw.is_some(), ans104.is_some()))
+        })
+        .buffered(concurrency.div_ceil(2))
+        .try_collect()
+        .await
+}
+
+/// Every object directly under `prefix`, following continuation tokens.
+pub(crate) async fn list_all_objects(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<StoredObject>, Error> {
+    let mut objects = Vec::new();
+    let mut continuation_token = None;
+    loop {
+        let page = storage.list(bucket, prefix, continuation_token).await?;
+        objects.extend(page.objects);
+        continuation_token = page.next_continuation_token;
+        if continuation_token.is_none() {
+            break;
+        }
+    }
+    Ok(objects)
+}
+
+pub async fn get_bucket_stats(tenant: &Tenant) -> Result<(u32, u64), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let storage = storage_backend().await?;
     let mut continuation_token = None;
-    let client: Client = s3_client().await?;
     let mut total_objects_count: u32 = 0;
     let mut total_objects_size: u64 = 0;
-    // with the total_objects_count == 0 loop condition
-    // we hack a known condition to simulate do-while.
-    // the LCP's bucket will always have more than 1 object.
-    while continuation_token.is_some() || total_objects_count == 0 {
-        let req = client
-            .list_objects_v2()
-            .bucket(&agent_config.s3_bucket_name)
-            .delimiter("/")
-            .prefix(&agent_config.s3_dir_name)
-            .max_keys(1000)
-            .set_continuation_token(continuation_token.clone())
-            .send()
+
+    loop {
+        let page = storage
+            .list(&agent_config.s3_bucket_name, &agent_config.s3_dir_name, continuation_token)
             .await?;
 
-        if !req.contents().is_empty() {
-            for obj in req.contents() {
-                total_objects_count += 1;
-                total_objects_size += obj.size().unwrap_or_default() as u64;
-            }
+        for obj in &page.objects {
+            total_objects_count += 1;
+            total_objects_size += obj.size;
+        }
 
-            if req.is_truncated().unwrap_or_default() {
-                continuation_token =
-                    Some(req.next_continuation_token().unwrap_or_default().to_string());
-            } else {
-                continuation_token = None;
-            }
+        continuation_token = page.next_continuation_token;
+        if continuation_token.is_none() {
+            break;
         }
     }
 
     Ok((total_objects_count, total_objects_size))
 }
 
+/// Recount the tenant's bucket stats and keep them for `cached_bucket_stats`.
+pub(crate) async fn reconcile_bucket_stats(tenant: &Tenant) -> Result<(u32, u64), Error> {
+    let (count, size) = get_bucket_stats(tenant).await?;
+    RECONCILED_STATS.lock().unwrap().insert(tenant.name.clone(), (count, size, Utc::now()));
+    Ok((count, size))
+}
+
+/// The tenant's stats from the last reconciliation, if any ran.
+pub(crate) fn cached_bucket_stats(tenant: &Tenant) -> Option<(u32, u64, DateTime<Utc>)> {
+    RECONCILED_STATS.lock().unwrap().get(&tenant.name).copied()
+}
+
 pub async fn store_lcp_priv_bucket_dataitem(
     data: Vec<u8>,
     content_type: &str,
@@ -216,12 +1006,13 @@ pub async fn store_lcp_priv_bucket_dataitem(
         return Err(anyhow!("invalid load_acc api key"));
     }
 
-    let client = s3_client().await?;
+    let storage = storage_backend().await?;
 
     let dataitem = if is_signed {
         reconstruct_dataitem_data(data)?.0
     } else {
-        create_dataitem(data.clone(), content_type, &[])?
+        let digest = ContentDigest::compute(&data);
+        create_dataitem(data.clone(), &digest, content_type, &[], &default_tags(Some(load_acc))?)?
     };
 
     let dataitem_id = dataitem.arweave_id();
@@ -236,16 +1027,18 @@ pub async fn store_lcp_priv_bucket_dataitem(
         dataitem_name.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|', '(', ')', '`'], "_");
 
     // store it as ans-104 serialized dataitem
-    client
-        .put_object()
-        .bucket(bucket_name)
-        .key(&key_dataitem)
-        .body(dataitem.to_bytes()?.into())
-        // set name even if its empty
-        .tagging(format!("dataitem-name={dataitem_name}"))
-        .content_type("application/octet-stream")
-        .send()
+    storage
+        .put(
+            bucket_name,
+            &key_dataitem,
+            dataitem.to_bytes()?,
+            "application/octet-stream",
+            // set name even if its empty
+            Some(&format!("dataitem-name={dataitem_name}")),
+        )
         .await?;
+    // stored, an upload request dropped from now on still keeps its debit
+    cancellation::current().commit()?;
 
     // register the dataitem name if provided
     if !dataitem_name.is_empty() {
@@ -255,13 +1048,140 @@ pub async fn store_lcp_priv_bucket_dataitem(
     Ok(dataitem_id)
 }
 
+/// Create `bucket_name` with `client`, failing with `BucketExists` when the name is taken.
+async fn create_bucket(client: &Client, bucket_name: &str) -> Result<(), Error> {
+    // us-east-1 answers a create of a bucket the agent's credentials own with a success, which
+    // would hand it over to the caller
+    match client.head_bucket().bucket(bucket_name).send().await {
+        Err(err) if err.as_service_error().is_some_and(|err| err.is_not_found()) => {}
+        Err(err) if err.raw_response().is_some_and(|r| r.status().as_u16() == 403) => {
+            return Err(BucketExists(bucket_name.to_string()).into());
+        }
+        Err(err) => return Err(err.into()),
+        Ok(_) => return Err(BucketExists(bucket_name.to_string()).into()),
+    }
+    let region = client.config().region().map(|region| region.to_string()).unwrap_or_default();
+    // us-east-1 is the default location, and the one S3-compatible endpoints without regions use
+    let location = (!matches!(region.as_str(), "" | "us-east-1" | "auto")).then(|| {
+        CreateBucketConfiguration::builder().location_constraint(region.as_str().into()).build()
+    });
+    match client
+        .create_bucket()
+        .bucket(bucket_name)
+        .set_create_bucket_configuration(location)
+        .send()
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(err)
+            if err.as_service_error().is_some_and(|err| {
+                err.is_bucket_already_exists() || err.is_bucket_already_owned_by_you()
+            }) =>
+        {
+            Err(BucketExists(bucket_name.to_string()).into())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Create the private bucket `bucket_name` owned by `load_acc`: the storage bucket, registered
+/// on the LCP API with the `load_acc` bucket tag `get_bucket_tags` reads ownership from. The
+/// buckets are removed again if the registration fails.
+pub(crate) async fn create_private_bucket(bucket_name: &str, load_acc: &str) -> Result<(), Error> {
+    check_private_bucket(bucket_name)?;
+    let storage_client = s3_client().await?;
+    let lcp_client = lcp_client().await?;
+    // the LCP API is usually the storage endpoint itself
+    let separate_lcp = lcp_api_url() != Some(AgentConfig::load().endpoint_url);
+
+    create_bucket(&storage_client, bucket_name).await?;
+    let registered = async {
+        if separate_lcp {
+            create_bucket(&lcp_client, bucket_name).await?;
+        }
+        let tag = Tag::builder().key(LOAD_ACC_BUCKET_TAG).value(load_acc).build()?;
+        let tagging = Tagging::builder().tag_set(tag).build()?;
+        LCP_BREAKER.check()?;
+        with_timeout("lcp", 10, async {
+            let put = lcp_client.put_bucket_tagging().bucket(bucket_name).tagging(tagging.clone());
+            put.send().await
+        })
+        .await?;
+        // for the ownership fallback while the LCP API is unreachable
+        if separate_lcp {
+            let put = storage_client.put_bucket_tagging().bucket(bucket_name).tagging(tagging);
+            put.send().await?;
+        }
+        Ok::<_, Error>(())
+    }
+    .await;
+
+    if let Err(err) = registered {
+        let mut clients = vec![&storage_client];
+        if separate_lcp && !err.is::<BucketExists>() {
+            clients.push(&lcp_client);
+        }
+        for client in clients {
+            if let Err(rollback_err) = client.delete_bucket().bucket(bucket_name).send().await {
+                println!("CREATE BUCKET: rollback of {bucket_name} failed: {rollback_err}");
+            }
+        }
+        return Err(err);
+    }
+    println!("CREATE BUCKET: {bucket_name} for {}", provenance::current().principal);
+    Ok(())
+}
+
 pub(crate) async fn get_bucket_tags(bucket_name: &str) -> Result<Vec<String>, Error> {
-    let client = s3_client().await?;
+    let client = lcp_client().await?;
+
+    // bucket tags are the LCP ownership records
+    LCP_BREAKER.check()?;
+    let result = with_timeout("lcp", 10, async {
+        let result = client.get_bucket_tagging().bucket(bucket_name).send().await;
+        // a missing bucket or tag set still proves the LCP API is healthy
+        match &result {
+            Err(err) if is_transient(err) => LCP_BREAKER.record_failure(),
+            _ => LCP_BREAKER.record_success(),
+        }
+        result
+    })
+    .await;
+    if result.as_ref().is_err_and(|err| err.is::<DependencyTimeout>()) {
+        LCP_BREAKER.record_failure();
+    }
+    Ok(load_acc_tags(result?.tag_set()))
+}
 
-    let req = client.get_bucket_tagging().bucket(bucket_name).send().await?;
-    let tags: Vec<(String, String)> =
-        req.tag_set.iter().map(|tag| (tag.key.to_string(), tag.value.to_string())).collect();
-    let load_acc_tags: Vec<String> =
-        tags.iter().filter(|tag| tag.1.starts_with("load_acc_")).map(|tag| tag.1.clone()).collect();
-    Ok(load_acc_tags)
+fn load_acc_tags(tag_set: &[Tag]) -> Vec<String> {
+    tag_set
+        .iter()
+        .map(|tag| tag.value.to_string())
+        .filter(|value| value.starts_with("load_acc_"))
+        .collect()
+}
+
+/// Whether `get_bucket_tags` failed for want of a reachable LCP API rather than an answer.
+pub(crate) fn is_lcp_unreachable(err: &Error) -> bool {
+    err.is::<DependencyTimeout>()
+        || err.is::<BreakerOpen>()
+        || err
+            .downcast_ref::<SdkError<GetBucketTaggingError, HttpResponse>>()
+            .is_some_and(is_transient)
+}
+
+/// The `load_acc_*` tags of `bucket_name` as the storage endpoint has them, `None` when it is
+/// the LCP API itself.
+pub(crate) async fn get_storage_bucket_tags(
+    bucket_name: &str,
+) -> Result<Option<Vec<String>>, Error> {
+    if lcp_api_url() == Some(AgentConfig::load().endpoint_url) {
+        return Ok(None);
+    }
+    let client = s3_client().await?;
+    let result = with_timeout("s3", 10, async {
+        client.get_bucket_tagging().bucket(bucket_name).send().await
+    })
+    .await?;
+    Ok(Some(load_acc_tags(result.tag_set())))
 }
@@ -0,0 +1,425 @@
+use crate::core::{
+    models::AgentError,
+    registry::RegistryEntry,
+    tenant::Tenant,
+    utils::{get_env_var, is_valid_api_key, sha256_hex},
+};
+use axum::{
+    body::Bytes,
+    http::{HeaderMap, Method, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+};
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use chrono::{NaiveDateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::collections::BTreeSet;
+
+const S3_XMLNS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+const SIGV4_ALGORITHM: &str = "AWS4-HMAC-SHA256";
+const MAX_CLOCK_SKEW_SECS: i64 = 15 * 60;
+pub(crate) const MAX_LIST_KEYS: usize = 1000;
+
+/// S3 style error, rendered as the XML `<Error>` document SDKs parse.
+#[derive(Debug)]
+pub struct S3Error {
+    status: StatusCode,
+    code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    pub(crate) fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        S3Error { status, code, message: message.into() }
+    }
+
+    pub(crate) fn access_denied(message: impl Into<String>) -> Self {
+        S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", message)
+    }
+
+    pub(crate) fn no_such_key(key: &str) -> Self {
+        S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", format!("no object stored under {key}"))
+    }
+
+    pub(crate) fn internal(message: impl Into<String>) -> Self {
+        S3Error::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", message)
+    }
+}
+
+impl From<AgentError> for S3Error {
+    fn from(err: AgentError) -> Self {
+        let status = err.status();
+        let code = match status {
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => "AccessDenied",
+            StatusCode::NOT_FOUND => "NoSuchKey",
+            StatusCode::PAYLOAD_TOO_LARGE => "EntityTooLarge",
+            StatusCode::SERVICE_UNAVAILABLE => "ServiceUnavailable",
+            status if status.is_client_error() => "InvalidRequest",
+            _ => "InternalError",
+        };
+        S3Error { status, code, message: err.message().to_string() }
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>",
+            self.code,
+            xml_escape(&self.message)
+        );
+        (self.status, [(header::CONTENT_TYPE, "application/xml")], body).into_response()
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+/// Registry namespace holding the key -> dataitem mapping of a facade bucket.
+pub(crate) fn facade_registry(tenant: &Tenant, bucket: &str) -> String {
+    if tenant.is_default() {
+        format!("s3-facade.{bucket}")
+    } else {
+        format!("s3-facade.{}.{bucket}", tenant.name)
+    }
+}
+
+/// `x-amz-meta-*` user metadata, stored as dataitem tags.
+pub(crate) fn user_metadata_tags(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let key = name.as_str().strip_prefix("x-amz-meta-")?;
+            Some((key.to_string(), value.to_str().ok()?.to_string()))
+        })
+        .collect()
+}
+
+/// Authenticate a facade request: AWS SigV4 with `S3_FACADE_ACCESS_KEY_ID` /
+/// `S3_FACADE_SECRET_ACCESS_KEY`, or the agent's usual `Bearer` API keys.
+pub(crate) async fn authorize(
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), S3Error> {
+    let authorization = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| S3Error::access_denied("missing Authorization header"))?;
+
+    if authorization.starts_with(SIGV4_ALGORITHM) {
+        let access_key_id = get_env_var("S3_FACADE_ACCESS_KEY_ID").ok();
+        let secret_access_key = get_env_var("S3_FACADE_SECRET_ACCESS_KEY").ok();
+        let (Some(access_key_id), Some(secret_access_key)) = (access_key_id, secret_access_key)
+        else {
+            return Err(S3Error::access_denied("SigV4 credentials are not configured"));
+        };
+        return verify_sigv4(
+            &access_key_id,
+            &secret_access_key,
+            authorization,
+            method,
+            uri,
+            headers,
+            body,
+        );
+    }
+
+    let token = authorization
+        .strip_prefix("Bearer ")
+        .ok_or_else(|| S3Error::access_denied("unsupported Authorization scheme"))?;
+    let server_api_keys = get_env_var("SERVER_API_KEYS").unwrap_or_default();
+    if server_api_keys.split(',').any(|key| key.trim() == token) {
+        return Ok(());
+    }
+    match is_valid_api_key(token).await {
+        Ok(true) => Ok(()),
+        _ => Err(S3Error::access_denied("invalid API key")),
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts keys of any size");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn canonical_query(query: Option<&str>) -> String {
+    let mut pairs: Vec<(&str, &str)> = query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| pair.split_once('=').unwrap_or((pair, "")))
+        .collect();
+    pairs.sort();
+    pairs.iter().map(|(key, value)| format!("{key}={value}")).collect::<Vec<_>>().join("&")
+}
+
+fn canonical_header_value(headers: &HeaderMap, name: &str) -> String {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .map(|value| value.split_whitespace().collect::<Vec<_>>().join(" "))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn verify_sigv4(
+    access_key_id: &str,
+    secret_access_key: &str,
+    authorization: &str,
+    method: &Method,
+    uri: &Uri,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<(), S3Error> {
+    let mut credential = None;
+    let mut signed_headers = None;
+    let mut signature = None;
+    for part in authorization.trim_start_matches(SIGV4_ALGORITHM).split(',') {
+        match part.trim().split_once('=') {
+            Some(("Credential", value)) => credential = Some(value),
+            Some(("SignedHeaders", value)) => signed_headers = Some(value),
+            Some(("Signature", value)) => signature = Some(value),
+            _ => {}
+        }
+    }
+    let (Some(credential), Some(signed_headers), Some(signature)) =
+        (credential, signed_headers, signature)
+    else {
+        return Err(S3Error::access_denied("malformed SigV4 Authorization header"));
+    };
+
+    // AKID/yyyymmdd/region/service/aws4_request
+    let scope_parts: Vec<&str> = credential.split('/').collect();
+    if scope_parts.len() != 5 || scope_parts[4] != "aws4_request" {
+        return Err(S3Error::access_denied("malformed SigV4 credential scope"));
+    }
+    if scope_parts[0] != access_key_id {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "InvalidAccessKeyId",
+            "unknown access key id",
+        ));
+    }
+
+    let amz_date = headers
+        .get("x-amz-date")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| S3Error::access_denied("missing x-amz-date header"))?;
+    let signed_at = NaiveDateTime::parse_from_str(amz_date, "%Y%m%dT%H%M%SZ")
+        .map_err(|_| S3Error::access_denied("invalid x-amz-date header"))?
+        .and_utc();
+    if (Utc::now() - signed_at).num_seconds().abs() > MAX_CLOCK_SKEW_SECS {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "RequestTimeTooSkewed",
+            "request time differs too much from the server time",
+        ));
+    }
+
+    let payload_hash = headers
+        .get("x-amz-content-sha256")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| S3Error::access_denied("missing x-amz-content-sha256 header"))?;
+
+    let canonical_headers: String = signed_headers
+        .split(';')
+        .map(|name| format!("{name}:{}\n", canonical_header_value(headers, name)))
+        .collect();
+    let canonical_request = format!(
+        "{method}\n{}\n{}\n{canonical_headers}\n{signed_headers}\n{payload_hash}",
+        uri.path(),
+        canonical_query(uri.query())
+    );
+    let scope = scope_parts[1..].join("/");
+    let string_to_sign = format!(
+        "{SIGV4_ALGORITHM}\n{amz_date}\n{scope}\n{}",
+        sha256_hex(canonical_request.as_bytes())
+    );
+
+    let mut key = format!("AWS4{secret_access_key}").into_bytes();
+    for part in &scope_parts[1..] {
+        key = hmac_sha256(&key, part);
+    }
+    let expected: String =
+        hmac_sha256(&key, &string_to_sign).iter().map(|byte| format!("{byte:02x}")).collect();
+
+    // constant time comparison
+    let matches = expected.len() == signature.len()
+        && expected.bytes().zip(signature.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0;
+    if !matches {
+        return Err(S3Error::new(
+            StatusCode::FORBIDDEN,
+            "SignatureDoesNotMatch",
+            "the request signature does not match",
+        ));
+    }
+
+    // a literal body hash is part of the signature, so the body must match it
+    let is_literal_hash =
+        payload_hash.len() == 64 && payload_hash.bytes().all(|b| b.is_ascii_hexdigit());
+    if is_literal_hash && !sha256_hex(body).eq_ignore_ascii_case(payload_hash) {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "XAmzContentSHA256Mismatch",
+            "the body does not match x-amz-content-sha256",
+        ));
+    }
+
+    Ok(())
+}
+
+/// Object body of a PUT, unwrapping the `aws-chunked` framing SDKs use for streaming uploads.
+/// Chunk signatures are not verified, the seed request signature covers the headers.
+pub(crate) fn decode_payload(headers: &HeaderMap, body: Bytes) -> Result<Vec<u8>, S3Error> {
+    let content_sha256 =
+        headers.get("x-amz-content-sha256").and_then(|h| h.to_str().ok()).unwrap_or_default();
+    let content_encoding =
+        headers.get(header::CONTENT_ENCODING).and_then(|h| h.to_str().ok()).unwrap_or_default();
+    if !content_sha256.starts_with("STREAMING-") && !content_encoding.contains("aws-chunked") {
+        return Ok(body.to_vec());
+    }
+
+    let invalid =
+        || S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", "malformed aws-chunked body");
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut pos = 0;
+    loop {
+        let line_end = body[pos..]
+            .windows(2)
+            .position(|w| w == b"\r\n")
+            .map(|i| pos + i)
+            .ok_or_else(invalid)?;
+        let line = std::str::from_utf8(&body[pos..line_end]).map_err(|_| invalid())?;
+        let size_hex = line.split(';').next().unwrap_or_default().trim();
+        let size = usize::from_str_radix(size_hex, 16).map_err(|_| invalid())?;
+        pos = line_end + 2;
+        // the zero sized chunk may be followed by trailing checksum headers
+        if size == 0 {
+            break;
+        }
+        let chunk = body.get(pos..pos + size).ok_or_else(invalid)?;
+        decoded.extend_from_slice(chunk);
+        pos += size + 2;
+    }
+
+    let decoded_length = headers
+        .get("x-amz-decoded-content-length")
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if decoded_length.is_some_and(|length| length != decoded.len()) {
+        return Err(invalid());
+    }
+    Ok(decoded)
+}
+
+/// `ListObjectsV2` over the registry entries of a facade bucket.
+pub(crate) fn list_objects_xml(
+    bucket: &str,
+    mut entries: Vec<RegistryEntry>,
+    prefix: &str,
+    delimiter: Option<&str>,
+    max_keys: usize,
+    continuation_token: Option<&str>,
+    start_after: Option<&str>,
+) -> Result<String, S3Error> {
+    let after = match continuation_token {
+        Some(token) => {
+            let raw = URL_SAFE_NO_PAD.decode(token).map_err(|_| {
+                S3Error::new(
+                    StatusCode::BAD_REQUEST,
+                    "InvalidArgument",
+                    "invalid continuation token",
+                )
+            })?;
+            Some(String::from_utf8_lossy(&raw).into_owned())
+        }
+        None => start_after.map(|key| key.to_string()),
+    };
+
+    entries.retain(|entry| {
+        entry.dataitem_name.starts_with(prefix)
+            && after.as_deref().is_none_or(|after| entry.dataitem_name.as_str() > after)
+    });
+    entries.sort_by(|a, b| a.dataitem_name.cmp(&b.dataitem_name));
+
+    let max_keys = max_keys.min(MAX_LIST_KEYS);
+    let mut contents = Vec::new();
+    let mut common_prefixes = BTreeSet::new();
+    let mut last_key = None;
+    let mut is_truncated = false;
+    for entry in &entries {
+        let rolled_up = delimiter.filter(|d| !d.is_empty()).and_then(|d| {
+            let rest = &entry.dataitem_name[prefix.len()..];
+            rest.find(d).map(|i| entry.dataitem_name[..prefix.len() + i + d.len()].to_string())
+        });
+        if let Some(common_prefix) = &rolled_up {
+            if common_prefixes.contains(common_prefix) {
+                continue;
+            }
+        }
+        if contents.len() + common_prefixes.len() >= max_keys {
+            is_truncated = true;
+            break;
+        }
+        match rolled_up {
+            Some(common_prefix) => {
+                // resume past every key rolled up into this prefix
+                last_key = Some(format!("{common_prefix}\u{10FFFF}"));
+                common_prefixes.insert(common_prefix);
+            }
+            None => {
+                last_key = Some(entry.dataitem_name.clone());
+                contents.push(entry);
+            }
+        }
+    }
+
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<ListBucketResult xmlns=\"{S3_XMLNS}\"><Name>{}</Name><Prefix>{}</Prefix><KeyCount>{}</KeyCount><MaxKeys>{max_keys}</MaxKeys><IsTruncated>{is_truncated}</IsTruncated>",
+        xml_escape(bucket),
+        xml_escape(prefix),
+        contents.len() + common_prefixes.len()
+    );
+    if let Some(delimiter) = delimiter {
+        xml.push_str(&format!("<Delimiter>{}</Delimiter>", xml_escape(delimiter)));
+    }
+    if let Some(token) = continuation_token {
+        xml.push_str(&format!("<ContinuationToken>{}</ContinuationToken>", xml_escape(token)));
+    }
+    if is_truncated {
+        if let Some(last_key) = last_key {
+            let token = URL_SAFE_NO_PAD.encode(last_key);
+            xml.push_str(&format!("<NextContinuationToken>{token}</NextContinuationToken>"));
+        }
+    }
+    for entry in contents {
+        let last_modified = entry
+            .last_modified
+            .map(|at| at.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+            .unwrap_or_else(|| "1970-01-01T00:00:00.000Z".to_string());
+        xml.push_str(&format!(
+            "<Contents><Key>{}</Key><LastModified>{last_modified}</LastModified><ETag>&quot;{}&quot;</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+            xml_escape(&entry.dataitem_name),
+            entry.dataitem_id,
+            entry.size.unwrap_or_default()
+        ));
+    }
+    for common_prefix in common_prefixes {
+        xml.push_str(&format!(
+            "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+            xml_escape(&common_prefix)
+        ));
+    }
+    xml.push_str("</ListBucketResult>");
+    Ok(xml)
+}
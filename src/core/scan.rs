@@ -0,0 +1,175 @@
+use crate::core::utils::get_env_var;
+use anyhow::{Error, anyhow};
+use std::time::Duration;
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+
+const CLAMD_CHUNK_SIZE: usize = 64 * 1024;
+const MAX_SCANNER_RESPONSE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ScanVerdict {
+    Clean,
+    Infected(String),
+}
+
+impl ScanVerdict {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScanVerdict::Clean => "clean",
+            ScanVerdict::Infected(_) => "infected",
+        }
+    }
+}
+
+/// Malware scanner the upload pipeline hands payloads to before storing them, selected by
+/// `SCAN_BACKEND`. Unset means no scanning.
+#[derive(Debug, Clone)]
+pub(crate) enum Scanner {
+    /// clamd `INSTREAM` over TCP, `CLAMD_ADDR` (e.g. `127.0.0.1:3310`)
+    Clamd { addr: String },
+    /// ICAP `REQMOD`, `ICAP_URL` (e.g. `icap://127.0.0.1:1344/avscan`)
+    Icap { addr: String, url: String },
+}
+
+pub(crate) struct ScanConfig {
+    pub scanner: Scanner,
+    pub timeout: Duration,
+    /// accept uploads when the scanner can't be reached (`SCAN_FAIL_OPEN=true`)
+    pub fail_open: bool,
+}
+
+impl ScanConfig {
+    pub fn load() -> Result<Option<Self>, Error> {
+        let backend = get_env_var("SCAN_BACKEND").unwrap_or_default().to_lowercase();
+        let scanner = match backend.as_str() {
+            "" | "none" => return Ok(None),
+            "clamd" => Scanner::Clamd { addr: get_env_var("CLAMD_ADDR")? },
+            "icap" => {
+                let url = get_env_var("ICAP_URL")?;
+                let authority = url
+                    .strip_prefix("icap://")
+                    .ok_or_else(|| anyhow!("ICAP_URL must start with icap://"))?
+                    .split('/')
+                    .next()
+                    .unwrap_or_default();
+                let addr = if authority.contains(':') {
+                    authority.to_string()
+                } else {
+                    format!("{authority}:1344")
+                };
+                Scanner::Icap { addr, url }
+            }
+            other => return Err(anyhow!("unsupported SCAN_BACKEND: {other}")),
+        };
+        let timeout = get_env_var("SCAN_TIMEOUT_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(Duration::from_secs(30));
+        let fail_open = get_env_var("SCAN_FAIL_OPEN").map(|v| v == "true").unwrap_or(false);
+
+        Ok(Some(ScanConfig { scanner, timeout, fail_open }))
+    }
+
+    pub fn backend_name(&self) -> &'static str {
+        match self.scanner {
+            Scanner::Clamd { .. } => "clamd",
+            Scanner::Icap { .. } => "icap",
+        }
+    }
+
+    pub async fn scan(&self, data: &[u8]) -> Result<ScanVerdict, Error> {
+        let scan = async {
+            match &self.scanner {
+                Scanner::Clamd { addr } => scan_clamd(addr, data).await,
+                Scanner::Icap { addr, url } => scan_icap(addr, url, data).await,
+            }
+        };
+        tokio::time::timeout(self.timeout, scan)
+            .await
+            .map_err(|_| anyhow!("scanner timed out after {:?}", self.timeout))?
+    }
+}
+
+async fn read_response(stream: &mut TcpStream, until_headers_end: bool) -> Result<String, Error> {
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = stream.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+        let done = if until_headers_end {
+            response.windows(4).any(|w| w == b"\r\n\r\n")
+        } else {
+            response.contains(&0)
+        };
+        if done || response.len() > MAX_SCANNER_RESPONSE {
+            break;
+        }
+    }
+    Ok(String::from_utf8_lossy(&response).trim_end_matches('\0').to_string())
+}
+
+async fn scan_clamd(addr: &str, data: &[u8]) -> Result<ScanVerdict, Error> {
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(CLAMD_CHUNK_SIZE) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes()).await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&0u32.to_be_bytes()).await?;
+
+    // `stream: OK` or `stream: <signature> FOUND`
+    let response = read_response(&mut stream, false).await?;
+    let result = response.trim().strip_prefix("stream:").unwrap_or(&response).trim();
+    if result == "OK" {
+        Ok(ScanVerdict::Clean)
+    } else if let Some(signature) = result.strip_suffix("FOUND") {
+        Ok(ScanVerdict::Infected(signature.trim().to_string()))
+    } else {
+        Err(anyhow!("unexpected clamd response: {response}"))
+    }
+}
+
+async fn scan_icap(addr: &str, url: &str, data: &[u8]) -> Result<ScanVerdict, Error> {
+    let host = addr.split(':').next().unwrap_or(addr);
+    let http_headers = "POST /upload HTTP/1.1\r\nHost: load-s3-agent\r\n\r\n";
+    let request = format!(
+        "REQMOD {url} ICAP/1.0\r\nHost: {host}\r\nAllow: 204\r\nEncapsulated: req-hdr=0, req-body={}\r\n\r\n{http_headers}",
+        http_headers.len()
+    );
+
+    let mut stream = TcpStream::connect(addr).await?;
+    stream.write_all(request.as_bytes()).await?;
+    if !data.is_empty() {
+        stream.write_all(format!("{:x}\r\n", data.len()).as_bytes()).await?;
+        stream.write_all(data).await?;
+        stream.write_all(b"\r\n").await?;
+    }
+    stream.write_all(b"0\r\n\r\n").await?;
+
+    // 204: unmodified, hence clean. 200: the service replaced the request, i.e. blocked it
+    let response = read_response(&mut stream, true).await?;
+    let mut lines = response.lines();
+    let status = lines.next().and_then(|line| line.split_whitespace().nth(1)).unwrap_or_default();
+    match status {
+        "204" => Ok(ScanVerdict::Clean),
+        "200" => {
+            let threat = lines
+                .filter_map(|line| line.split_once(':'))
+                .find(|(name, _)| {
+                    name.eq_ignore_ascii_case("x-infection-found")
+                        || name.eq_ignore_ascii_case("x-virus-id")
+                })
+                .map(|(_, value)| value.trim().to_string())
+                .unwrap_or_else(|| "unknown".to_string());
+            Ok(ScanVerdict::Infected(threat))
+        }
+        _ => Err(anyhow!("unexpected ICAP response status: {status}")),
+    }
+}
@@ -0,0 +1,226 @@
+use crate::core::{
+    bundler::check_agent_balance,
+    compaction::compact_tag_index_scheduled,
+    confirmations::poll_confirmations,
+    gc::collect_garbage_all,
+    jobs::requeue_dead_posts,
+    maintenance,
+    merkle::anchor_all,
+    models::ScheduledTask,
+    registry::compact_registries,
+    s3::reconcile_bucket_stats,
+    tenant::all_tenants,
+    utils::{get_env_var, purge_auth_cache},
+};
+use anyhow::{Error, anyhow};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use std::{sync::Mutex, time::Duration};
+use tokio::time::{Instant, MissedTickBehavior};
+
+const BUNDLER_RETRY_BATCH: usize = 500;
+
+/// A recurring maintenance task, scheduled by its `SCHEDULE_*` variable.
+struct Task {
+    name: &'static str,
+    env: &'static str,
+    run: fn() -> BoxFuture<'static, Result<String, Error>>,
+}
+
+const TASKS: [Task; 9] = [
+    Task {
+        name: "stats_reconciliation",
+        env: "SCHEDULE_STATS_RECONCILIATION",
+        run: || Box::pin(reconcile_stats()),
+    },
+    Task { name: "gc", env: "SCHEDULE_GC", run: || Box::pin(collect_garbage_all()) },
+    Task {
+        name: "registry_compaction",
+        env: "SCHEDULE_REGISTRY_COMPACTION",
+        run: || Box::pin(async { Ok(format!("removed={}", compact_registries()?)) }),
+    },
+    Task {
+        name: "auth_cache_purge",
+        env: "SCHEDULE_AUTH_CACHE_PURGE",
+        run: || Box::pin(async { Ok(format!("purged={}", purge_auth_cache())) }),
+    },
+    Task {
+        name: "bundler_retry_sweep",
+        env: "SCHEDULE_BUNDLER_RETRY_SWEEP",
+        run: || {
+            Box::pin(async {
+                Ok(format!("requeued={}", requeue_dead_posts(BUNDLER_RETRY_BATCH).await?))
+            })
+        },
+    },
+    Task {
+        name: "bundler_balance_check",
+        env: "SCHEDULE_BUNDLER_BALANCE_CHECK",
+        run: || Box::pin(check_agent_balance()),
+    },
+    Task {
+        name: "post_confirmations",
+        env: "SCHEDULE_POST_CONFIRMATIONS",
+        run: || Box::pin(poll_confirmations()),
+    },
+    Task { name: "merkle_anchors", env: "SCHEDULE_MERKLE_ANCHORS", run: || Box::pin(anchor_all()) },
+    Task {
+        name: "index_compaction",
+        env: "SCHEDULE_INDEX_COMPACTION",
+        run: || Box::pin(compact_tag_index_scheduled()),
+    },
+];
+
+#[derive(Default)]
+struct TaskState {
+    schedule: Option<String>,
+    every: Option<Duration>,
+    running: bool,
+    last_run: Option<DateTime<Utc>>,
+    last_duration_ms: Option<u64>,
+    last_result: Option<String>,
+    last_error: Option<String>,
+    next_run: Option<DateTime<Utc>>,
+}
+
+static STATE: Lazy<Mutex<Vec<TaskState>>> =
+    Lazy::new(|| Mutex::new(TASKS.iter().map(|_| TaskState::default()).collect()));
+
+async fn reconcile_stats() -> Result<String, Error> {
+    let (mut tenants, mut failed) = (0, 0);
+    for tenant in all_tenants()? {
+        match reconcile_bucket_stats(&tenant).await {
+            Ok(_) => tenants += 1,
+            Err(err) => {
+                println!("SCHEDULER: stats of tenant {:?} failed: {err}", tenant.name);
+                failed += 1;
+            }
+        }
+    }
+    Ok(format!("tenants={tenants} failed={failed}"))
+}
+
+/// Parse an interval: seconds, a number with an `s`, `m`, `h` or `d` unit, or one of `@hourly`,
+/// `@daily` and `@weekly`. `off` and `0` disable the task.
+fn parse_schedule(spec: &str) -> Result<Option<Duration>, Error> {
+    let spec = spec.trim();
+    let secs = match spec {
+        "" | "off" | "0" => return Ok(None),
+        "@hourly" => 3600,
+        "@daily" => 86_400,
+        "@weekly" => 7 * 86_400,
+        _ => {
+            let (number, unit) = match spec.find(|c: char| !c.is_ascii_digit()) {
+                Some(at) => spec.split_at(at),
+                None => (spec, "s"),
+            };
+            let multiplier = match unit {
+                "s" => 1,
+                "m" => 60,
+                "h" => 3600,
+                "d" => 86_400,
+                _ => return Err(anyhow!("unknown unit in schedule {spec:?}")),
+            };
+            let number: u64 = number.parse().map_err(|_| anyhow!("invalid schedule {spec:?}"))?;
+            number.saturating_mul(multiplier)
+        }
+    };
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
+
+/// The task's schedule spec, `GC_INTERVAL_SECS` still scheduling the GC when `SCHEDULE_GC`
+/// is unset.
+fn schedule_spec(task: &Task) -> Option<String> {
+    get_env_var(task.env)
+        .ok()
+        .or_else(|| (task.name == "gc").then(|| get_env_var("GC_INTERVAL_SECS").ok()).flatten())
+}
+
+fn to_utc(at: Instant) -> DateTime<Utc> {
+    let from_now = at.saturating_duration_since(Instant::now());
+    Utc::now() + chrono::Duration::from_std(from_now).unwrap_or_default()
+}
+
+/// Start every task with a schedule. Each task runs on its own timer, a run that overlaps the
+/// next tick delays it rather than running twice.
+pub(crate) fn spawn_scheduler() {
+    for (index, task) in TASKS.iter().enumerate() {
+        let Some(spec) = schedule_spec(task) else {
+            continue;
+        };
+        let every = match parse_schedule(&spec) {
+            Ok(Some(every)) => every,
+            Ok(None) => continue,
+            Err(err) => {
+                println!("SCHEDULER: {} disabled: {err}", task.env);
+                continue;
+            }
+        };
+        {
+            let mut state = STATE.lock().unwrap();
+            state[index].schedule = Some(spec);
+            state[index].every = Some(every);
+        }
+
+        tokio::spawn(async move {
+            let task = &TASKS[index];
+            let mut ticker = tokio::time::interval_at(Instant::now() + every, every);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            STATE.lock().unwrap()[index].next_run = Some(to_utc(Instant::now() + every));
+            loop {
+                ticker.tick().await;
+                if maintenance::enabled() {
+                    println!("SCHEDULER: {} skipped, the agent is in maintenance", task.name);
+                    STATE.lock().unwrap()[index].next_run = Some(to_utc(Instant::now() + every));
+                    continue;
+                }
+                let started = Instant::now();
+                {
+                    let mut state = STATE.lock().unwrap();
+                    state[index].running = true;
+                    state[index].last_run = Some(Utc::now());
+                }
+                let result = (task.run)().await;
+                let elapsed = started.elapsed();
+                match &result {
+                    Ok(summary) => println!("SCHEDULER: {} done: {summary}", task.name),
+                    Err(err) => println!("SCHEDULER: {} failed: {err}", task.name),
+                }
+                let mut state = STATE.lock().unwrap();
+                let state = &mut state[index];
+                state.running = false;
+                state.last_duration_ms = Some(elapsed.as_millis() as u64);
+                state.next_run = Some(to_utc(started + every.max(elapsed)));
+                match result {
+                    Ok(summary) => {
+                        state.last_result = Some(summary);
+                        state.last_error = None;
+                    }
+                    Err(err) => state.last_error = Some(err.to_string()),
+                }
+            }
+        });
+    }
+}
+
+/// Every maintenance task with its schedule and last and next runs, for `GET /admin/schedule`.
+pub(crate) fn scheduled_tasks() -> Vec<ScheduledTask> {
+    let state = STATE.lock().unwrap();
+    TASKS
+        .iter()
+        .zip(state.iter())
+        .map(|(task, state)| ScheduledTask {
+            name: task.name.to_string(),
+            env: task.env.to_string(),
+            schedule: state.schedule.clone(),
+            interval_secs: state.every.map(|every| every.as_secs()),
+            running: state.running,
+            last_run: state.last_run.map(|at| at.to_rfc3339()),
+            last_duration_ms: state.last_duration_ms,
+            last_result: state.last_result.clone(),
+            last_error: state.last_error.clone(),
+            next_run: state.next_run.map(|at| at.to_rfc3339()),
+        })
+        .collect()
+}
@@ -0,0 +1,157 @@
+use crate::core::{
+    ans104::{ContentDigest, agent_address, create_dataitem},
+    http::http_client,
+    metadata::{check_clickhouse, indexing_enabled},
+    models::{SelfTestCheck, SelfTestReport},
+    resilience::with_timeout,
+    s3::AgentConfig,
+    storage::storage_backend,
+    utils::{bind_addr, get_env_var, internal_auth_server},
+};
+use anyhow::{Context, Error, anyhow};
+use std::{
+    future::Future,
+    time::{Duration, Instant},
+};
+
+const SELFTEST_PREFIX: &str = "selftest/";
+
+enum Outcome {
+    Ok(Option<String>),
+    Skipped(String),
+}
+
+async fn check(name: &str, run: impl Future<Output = Result<Outcome, Error>>) -> SelfTestCheck {
+    let started = Instant::now();
+    let (status, detail) = match run.await {
+        Ok(Outcome::Ok(detail)) => ("ok", detail),
+        Ok(Outcome::Skipped(reason)) => ("skipped", Some(reason)),
+        Err(err) => ("failed", Some(format!("{err:#}"))),
+    };
+    SelfTestCheck {
+        name: name.to_string(),
+        status: status.to_string(),
+        detail,
+        duration_ms: started.elapsed().as_millis() as u64,
+    }
+}
+
+/// Round trip a probe object through the default bucket: put, get, list, presign and delete.
+async fn check_storage(checks: &mut Vec<SelfTestCheck>) {
+    let config = AgentConfig::load();
+    let bucket = config.s3_bucket_name;
+    let suffix: String = rand::random::<[u8; 8]>().iter().map(|b| format!("{b:02x}")).collect();
+    let key = format!("{SELFTEST_PREFIX}{suffix}");
+    let body = format!("load-s3-agent selftest {suffix}").into_bytes();
+
+    let storage = match storage_backend().await {
+        Ok(storage) => storage,
+        Err(err) => {
+            checks.push(check("storage", async { Err(err) }).await);
+            return;
+        }
+    };
+    let storage = storage.as_ref();
+
+    let put = check("storage_put", async {
+        storage.put(&bucket, &key, body.clone(), "text/plain", None).await?;
+        Ok(Outcome::Ok(Some(format!("{bucket}/{key}"))))
+    })
+    .await;
+    let stored = put.status == "ok";
+    checks.push(put);
+    let needs_put = || Outcome::Skipped("the probe object could not be put".to_string());
+
+    checks.push(
+        check("storage_get", async {
+            if !stored {
+                return Ok(needs_put());
+            }
+            match storage.get(&bucket, &key).await? == body {
+                true => Ok(Outcome::Ok(None)),
+                false => Err(anyhow!("read back different bytes than were put")),
+            }
+        })
+        .await,
+    );
+    checks.push(
+        check("storage_list", async {
+            if !stored {
+                return Ok(needs_put());
+            }
+            let page = storage.list(&bucket, SELFTEST_PREFIX, None).await?;
+            match page.objects.iter().any(|object| object.key == key) {
+                true => Ok(Outcome::Ok(None)),
+                false => Err(anyhow!("the probe object is missing from the listing")),
+            }
+        })
+        .await,
+    );
+    checks.push(
+        check("storage_presign", async {
+            storage.presign(&bucket, &key, Duration::from_secs(60)).await?;
+            Ok(Outcome::Ok(None))
+        })
+        .await,
+    );
+    checks.push(
+        check("storage_delete", async {
+            if !stored {
+                return Ok(needs_put());
+            }
+            storage.delete(&bucket, &key).await?;
+            Ok(Outcome::Ok(None))
+        })
+        .await,
+    );
+}
+
+/// Verify the agent's configuration against its dependencies: the listen address, storage
+/// permissions, ClickHouse connectivity and DDL rights, the `UPLOADER_JWK` signer and the auth
+/// service. Run by `--check` and `GET /admin/selftest`.
+pub(crate) async fn run_self_test() -> SelfTestReport {
+    let mut checks = Vec::new();
+    checks
+        .push(check("bind_addr", async { Ok(Outcome::Ok(Some(bind_addr()?.to_string()))) }).await);
+    check_storage(&mut checks).await;
+
+    checks.push(
+        check("clickhouse", async {
+            if !indexing_enabled() {
+                return Ok(Outcome::Skipped("INDEXING_ENABLED=false".to_string()));
+            }
+            check_clickhouse().await?;
+            Ok(Outcome::Ok(None))
+        })
+        .await,
+    );
+    checks.push(
+        check("uploader_jwk", async {
+            let invalid = "UPLOADER_JWK is not a valid Arweave JWK";
+            let data = b"selftest".to_vec();
+            let digest = ContentDigest::compute(&data);
+            create_dataitem(data, &digest, "text/plain", &[], &[]).context(invalid)?;
+            let address = agent_address().context(invalid)?;
+            Ok(Outcome::Ok(Some(format!("signer {address}"))))
+        })
+        .await,
+    );
+    checks.push(
+        check("auth_server", async {
+            let server_auth = get_env_var("AUTH_SERVER_KEY").map_err(|_| {
+                anyhow!("AUTH_SERVER_KEY is not set, load_acc keys can't be verified")
+            })?;
+            let url = format!("{}/internal/verify/selftest", internal_auth_server());
+            let request = http_client()?.get(&url).header("X-Load-Auth-Token", server_auth);
+            let response = with_timeout("auth", 10, request.send()).await?;
+            match response.status().is_success() {
+                true => Ok(Outcome::Ok(Some(internal_auth_server()))),
+                false => Err(anyhow!("{} answered {}", internal_auth_server(), response.status())),
+            }
+        })
+        .await,
+    );
+
+    let ok = checks.iter().all(|check| check.status != "failed");
+    SelfTestReport { ok, checks }
+}
@@ -0,0 +1,284 @@
+use crate::core::{
+    disk_cache::DiskCache,
+    gateway::GatewayFallback,
+    metadata::{dataitem_sha256, dataitem_sizes, indexing_enabled},
+    metrics,
+    s3::get_dataitem_raw,
+    storage::ObjectBody,
+    tenant::Tenant,
+    utils::{PRESIGNED_URL_EXPIRY, get_env_var},
+};
+use anyhow::Error;
+use axum::{
+    body::HttpBody,
+    http::{HeaderValue, Response, header},
+};
+use headers::HeaderMap;
+use once_cell::sync::Lazy;
+use tower_http::compression::{
+    CompressionLayer,
+    predicate::{DefaultPredicate, Predicate},
+};
+
+pub(crate) const DEFAULT_CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+const DEFAULT_DATAITEM_CACHE_MAX_BYTES: u64 = 1024 * 1024 * 1024; // 1 GB
+const DEFAULT_ITEMS_INLINE_MAX_BYTES: u64 = 16 * 1024;
+// content codings of the compression layer, each a representation with its own ETag
+const CONTENT_CODINGS: [&str; 2] = ["gzip", "br"];
+
+/// Read-through cache of proxied raw bodies, disabled with `DATAITEM_CACHE_MAX_BYTES=0`.
+static DATAITEM_CACHE: Lazy<Option<DiskCache>> = Lazy::new(|| {
+    let dir = get_env_var("DATAITEM_CACHE_DIR").unwrap_or_else(|_| "./dataitem-cache".to_string());
+    let max_bytes = get_env_var("DATAITEM_CACHE_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_DATAITEM_CACHE_MAX_BYTES);
+    (max_bytes > 0).then(|| DiskCache::new(dir, max_bytes))
+});
+
+/// How `GET /{id}` serves dataitems, selected by `SERVE_MODE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ServeMode {
+    /// deprecated since v0.7.0, clients are pointed to the gateway (default)
+    Disabled,
+    /// 302 to a presigned URL of the raw body
+    Redirect,
+    /// stream the raw body through the agent
+    Proxy,
+}
+
+pub(crate) fn serve_mode() -> ServeMode {
+    match get_env_var("SERVE_MODE").unwrap_or_default().to_lowercase().as_str() {
+        "redirect" => ServeMode::Redirect,
+        "proxy" => ServeMode::Proxy,
+        _ => ServeMode::Disabled,
+    }
+}
+
+/// `POST /items/get` returns the bodies of dataitems up to `ITEMS_INLINE_MAX_BYTES` (default
+/// 16 KiB, `0` disables it) inline instead of their URL.
+pub(crate) fn items_inline_max_bytes() -> u64 {
+    get_env_var("ITEMS_INLINE_MAX_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_ITEMS_INLINE_MAX_BYTES)
+}
+
+/// Whether `SERVE_MODE=redirect` serves the dataitem's body itself rather than a redirect, for
+/// dataitems of at most `SERVE_INLINE_MAX_BYTES` (default 0, always redirect). Sizes come from
+/// the index, dataitems of an unknown size are redirected.
+pub(crate) async fn serve_inline(tenant: &Tenant, dataitem_id: &str) -> bool {
+    let max_bytes: u64 =
+        get_env_var("SERVE_INLINE_MAX_BYTES").ok().and_then(|v| v.parse().ok()).unwrap_or(0);
+    if max_bytes == 0 || !indexing_enabled() {
+        return false;
+    }
+    match dataitem_sizes(&tenant.name, &[dataitem_id.to_string()]).await {
+        Ok(sizes) => sizes.get(dataitem_id).is_some_and(|(size, _)| *size <= max_bytes),
+        Err(err) => {
+            println!("SERVE INLINE: size of {dataitem_id} unavailable, redirecting: {err}");
+            false
+        }
+    }
+}
+
+/// ETag of a dataitem's content: its indexed SHA-256, so the same bytes uploaded twice share
+/// it, suffixed with the `variant` of a transformed response (a render, an HLS playlist or
+/// segment). Dataitems without an indexed hash fall back to their ID, which is derived from the
+/// signed content and so is a strong validator too.
+pub(crate) async fn dataitem_etag(
+    tenant: &str,
+    dataitem_id: &str,
+    variant: Option<&str>,
+) -> String {
+    let sha256 = match indexing_enabled() {
+        true => dataitem_sha256(tenant, dataitem_id).await.unwrap_or_else(|err| {
+            println!("ETAG: content hash of {dataitem_id} unavailable: {err}");
+            None
+        }),
+        false => None,
+    };
+    let validator = sha256.as_deref().unwrap_or(dataitem_id);
+    match variant {
+        Some(variant) => format!("\"{validator}-{variant}\""),
+        None => format!("\"{validator}\""),
+    }
+}
+
+/// ETag naming `value` as is, e.g. an Arweave transaction ID.
+pub(crate) fn quoted_etag(value: &str) -> String {
+    format!("\"{value}\"")
+}
+
+/// A compressed body is another representation of the content: suffix its ETag with the
+/// content coding, e.g. `"<sha256>-gzip"`.
+pub(crate) fn tag_content_coding<B>(response: &mut Response<B>) {
+    let headers = response.headers();
+    let Some(coding) = headers.get(header::CONTENT_ENCODING).and_then(|h| h.to_str().ok()) else {
+        return;
+    };
+    let Some(etag) = headers.get(header::ETAG).and_then(|h| h.to_str().ok()) else {
+        return;
+    };
+    let Some(validator) = etag.strip_suffix('"') else {
+        return;
+    };
+    if let Ok(value) = HeaderValue::from_str(&format!("{validator}-{coding}\"")) {
+        response.headers_mut().insert(header::ETAG, value);
+    }
+}
+
+/// `If-None-Match` handling per RFC 9110: `*` or any (weak-compared) listed tag matches, the
+/// compressed representations of `etag` included.
+pub(crate) fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    let Some(if_none_match) = headers.get("if-none-match").and_then(|h| h.to_str().ok()) else {
+        return false;
+    };
+    let etag = etag.strip_prefix("W/").unwrap_or(etag);
+    if_none_match.split(',').map(|tag| tag.trim()).any(|tag| {
+        let tag = tag.strip_prefix("W/").unwrap_or(tag);
+        tag == "*"
+            || tag == etag
+            || CONTENT_CODINGS.iter().any(|coding| {
+                let representation = tag.strip_suffix(&format!("-{coding}\""));
+                representation.is_some_and(|tag| Some(tag) == etag.strip_suffix('"'))
+            })
+    })
+}
+
+/// `Cache-Control` of served content, configurable with `SERVE_CACHE_CONTROL`.
+pub(crate) fn cache_control() -> String {
+    get_env_var("SERVE_CACHE_CONTROL").unwrap_or_else(|_| DEFAULT_CACHE_CONTROL.to_string())
+}
+
+/// Redirects must not be cached beyond the lifetime of the presigned URL they point to.
+pub(crate) fn redirect_cache_control() -> String {
+    format!("private, max-age={}", PRESIGNED_URL_EXPIRY / 2)
+}
+
+/// `Content-Disposition` of a dataitem downloaded with `?download=true`: a plain ASCII
+/// `filename` for old clients and the exact name as RFC 5987 `filename*`.
+pub(crate) fn content_disposition(file_name: &str) -> String {
+    let ascii: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c == ' ' || c.is_ascii_graphic() => c,
+            _ => '_',
+        })
+        .collect();
+    let mut encoded = String::new();
+    for byte in file_name.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    format!("attachment; filename=\"{ascii}\"; filename*=UTF-8''{encoded}")
+}
+
+/// Raw body of a dataitem served in proxy mode, read through the local disk cache. Dataitems
+/// are immutable, so cached bodies never need invalidating.
+pub(crate) async fn cached_dataitem_raw(
+    dataitem_id: &str,
+    tenant: &Tenant,
+) -> Result<ObjectBody, Error> {
+    let cache_key = format!("{}/{dataitem_id}", tenant.name);
+    read_through(&cache_key, get_dataitem_raw(dataitem_id, tenant)).await
+}
+
+/// Body of an Arweave transaction served by `gateway`, read through the same disk cache as
+/// proxied dataitems.
+pub(crate) async fn cached_gateway_raw(
+    gateway: &GatewayFallback,
+    tx_id: &str,
+) -> Result<ObjectBody, Error> {
+    // an ID names the same bytes wherever they are read from, a colliding tenant key is harmless
+    read_through(&format!("~gateway/{tx_id}"), gateway.fetch(tx_id)).await
+}
+
+async fn read_through(
+    cache_key: &str,
+    fetch: impl Future<Output = Result<ObjectBody, Error>>,
+) -> Result<ObjectBody, Error> {
+    let Some(cache) = DATAITEM_CACHE.as_ref() else {
+        return fetch.await;
+    };
+
+    if let Some(object) = cache.get(cache_key).await.and_then(|entry| decode_cache_entry(&entry)) {
+        metrics::increment("dataitem_cache_total{result=\"hit\"}");
+        return Ok(object);
+    }
+    metrics::increment("dataitem_cache_total{result=\"miss\"}");
+
+    let object = fetch.await?;
+    cache.put(cache_key, &encode_cache_entry(&object)).await;
+    metrics::set_gauge("dataitem_cache_bytes", cache.size_bytes() as f64);
+    Ok(object)
+}
+
+// cache entries are the content type, a newline, then the body
+fn encode_cache_entry(object: &ObjectBody) -> Vec<u8> {
+    let content_type = object.content_type.as_deref().unwrap_or_default();
+    let mut entry = Vec::with_capacity(content_type.len() + 1 + object.data.len());
+    entry.extend_from_slice(content_type.as_bytes());
+    entry.push(b'\n');
+    entry.extend_from_slice(&object.data);
+    entry
+}
+
+fn decode_cache_entry(entry: &[u8]) -> Option<ObjectBody> {
+    let newline = entry.iter().position(|b| *b == b'\n')?;
+    let content_type = std::str::from_utf8(&entry[..newline]).ok()?;
+    Some(ObjectBody {
+        data: entry[newline + 1..].to_vec(),
+        content_type: (!content_type.is_empty()).then(|| content_type.to_string()),
+        content_encoding: None,
+    })
+}
+
+/// Compress proxied bodies unless their content type is already compressed media or an archive.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CompressibleContent;
+
+const COMPRESSED_CONTENT_TYPES: [&str; 12] = [
+    "image/",
+    "video/",
+    "audio/",
+    "font/woff",
+    "application/zip",
+    "application/gzip",
+    "application/x-gzip",
+    "application/zstd",
+    "application/x-7z-compressed",
+    "application/x-rar-compressed",
+    "application/x-bzip2",
+    "application/x-xz",
+];
+
+impl Predicate for CompressibleContent {
+    fn should_compress<B>(&self, response: &Response<B>) -> bool
+    where
+        B: HttpBody,
+    {
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|h| h.to_str().ok())
+            .unwrap_or("")
+            .to_lowercase();
+        // svg is text, unlike the other image types
+        let already_compressed = !content_type.starts_with("image/svg")
+            && COMPRESSED_CONTENT_TYPES.iter().any(|prefix| content_type.starts_with(prefix));
+
+        !already_compressed && DefaultPredicate::new().should_compress(response)
+    }
+}
+
+/// gzip/brotli layer for proxied dataitems, enabled with `RESPONSE_COMPRESSION=true`.
+pub(crate) fn compression_layer() -> Option<CompressionLayer<CompressibleContent>> {
+    let enabled = get_env_var("RESPONSE_COMPRESSION").map(|v| v == "true").unwrap_or(false);
+    enabled.then(|| CompressionLayer::new().gzip(true).br(true).compress_when(CompressibleContent))
+}
@@ -1,564 +1,4638 @@
 use crate::core::{
-    bundler::post_dataitem,
+    access_tokens,
+    ans104::{
+        TagPolicy, check_tag_bytes, decode_gzip_tags, default_tags, file_name_tag, owner_address,
+        parse_tag_pair, reconstruct_dataitem_data, unpack_bundle,
+    },
+    arns, audit,
+    blocklist::{self, BLOCK_BY_HASH, BLOCK_BY_ID, Blocked},
+    bundler::{self, post_dataitem},
+    cancellation::{self, UploadAborted},
+    chunked::ChunkedObject,
+    compaction::compact_tag_index,
+    concurrency,
+    cors::cors_layer,
+    credits::{self, CreditsConfig, Debit, InsufficientCredits},
+    direct_upload::{self, DirectUploadNotFound, NotBucketOwner},
+    extract::{DataitemId, is_arweave_id},
+    feeds::{self, InvalidFeed},
+    fetch::{SourceTooLarge, UrlNotAllowed, fetch_url},
+    gateway::{GatewayFallback, arweave_gateway_url},
+    gc::collect_garbage,
+    hits,
+    hls::{self, HlsPlaylist, NotAVideo, RemuxFailed, SegmentNotFound},
+    idempotency::{self, IdempotencyKey},
+    jobs::{self, JobNotDead, POST_DATAITEM_JOB},
+    lcp::{
+        BucketExists, BucketNotAllowed, InvalidBucketName, check_private_bucket,
+        invalidate_bucket_ownership,
+    },
+    lifecycle::spawn_lifecycle_task,
+    limits::BodyLimits,
+    maintenance, merkle,
     metadata::{
-        DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE, TagQueryPagination, decode_tag_query_cursor,
-        query_dataitems_by_tags,
+        AbuseReportRecord, BlockEntry, DEFAULT_PAGE_SIZE, DEFAULT_SYNC_LIMIT, DELETION_DELETED,
+        Deletion, ExportFormat, FeedRecord, JobRecord, MAX_PAGE_SIZE, NameRecord, TagQueryPage,
+        TagQueryPagination, TagUsage, active_block_entries, dataitem_file_name, dataitem_hits,
+        dataitem_sizes, decode_sync_cursor, decode_tag_query_cursor, export_index, get_feed,
+        get_name_version, indexing_enabled, is_posted_to_arweave, list_abuse_reports, list_feeds,
+        list_jobs, name_history, post_status_history, query_dataitems_by_content_type,
+        query_dataitems_by_owner, query_dataitems_by_sha256, query_dataitems_by_tags,
+        query_provenance, query_recent_dataitems, sync_dataitems, top_dataitems, top_tags,
     },
-    registry::get_bucket_registry,
+    metrics,
+    migration::{self, InvalidMigration, MigrationInProgress},
+    mime::{check_content_type_policy, resolve_content_type},
+    models::{
+        AbuseReportAccepted, AbuseReportInfo, AbuseReportRequest, AbuseReportsParams,
+        AbuseReportsResponse, AgentError, AgentInfo, AnalyticsParams, BlockRequest, BlocklistEntry,
+        BlocklistResponse, BucketRegistryResponse, BundlerBalance, CreateBucketRequest,
+        CreateBucketResponse, CreateFeedRequest, CreditsResponse, DataitemIdResponse,
+        DataitemPresence, DataitemStats, DeletionInfo, ErrorResponse, ExistsRequest,
+        ExistsResponse, ExportParams, FeedFilter, FeedInfo, FeedItemEvent, FeedsResponse,
+        FetchedItem, GcReport, HealthStatus, ImportItemReport, ImportResponse,
+        IndexCompactionReport, ItemsGetRequest, ItemsGetResponse, JobAccepted, JobInfo, JobsParams,
+        JobsResponse, ListObjectsParams, MaintenanceSettings, MaintenanceUpdate, MerkleProof,
+        NameHistoryResponse, NameParams, NameVersion, OwnershipCacheInvalidated,
+        OwnershipCacheParams, PageInfo, PageParams, PointNameRequest, PostDataitemParams,
+        PostDataitemResponse, PostEstimate, PostStatusEntry, PostStatusResponse,
+        PrefixMigrationRequest, PrefixMigrationStatus, PresignUploadRequest, PresignUploadResponse,
+        PrivateUploadResponse, ProvenanceParams, ProvenanceResponse, RecentParams, ReloadReport,
+        RenderParams, ReplicationStatus, RequestLogSettings, RequestLogUpdate,
+        ResolveReportRequest, ScheduleResponse, SelfTestReport, ServeParams, StageResponse,
+        StorageStats, SyncParams, TagFilter, TagQueryItem, TagQueryRequest, TagQueryResponse,
+        TagUsageEntry, TestVectorsResponse, TopAnalytics, TopDataitem, UploadForm,
+        UploadFromUrlRequest, UploadJob, UploadOptions, UploadProgress, UploadProvenance,
+        UploadReceipt, UploadResponse, UploadTag, UpstreamUrls, api_error, upstream_error,
+    },
+    names::{self, InvalidName, VersionConflict},
+    progress::{self, AbortOnDrop, new_job_id, upload_job, upload_progress, validate_upload_id},
+    provenance::{self, Provenance},
+    receipts,
+    registry::{RegistryEntry, find_named_entry, get_bucket_registry, replace_named_entry},
+    reload::{apply_cors, install_cors, reload_config, spawn_sighup_reload},
+    render::{MAX_RENDER_DIMENSION, NotAnImage, RenderFormat, RenderOptions, render_dataitem},
+    replication::replication_status,
+    reports::{self, InvalidReport, REPORT_OPEN, ReportResolved},
+    request_log,
+    resilience::{BreakerOpen, DependencyTimeout},
     s3::{
-        get_bucket_stats, get_dataitem_url, store_dataitem, store_lcp_priv_bucket_dataitem,
-        store_signed_dataitem,
+        DataitemExists, cached_bucket_stats, create_private_bucket, dataitems_presence,
+        get_bucket_stats, get_dataitem, get_dataitem_raw, get_dataitem_url, lcp_api_url,
+        presign_raw, preview_dataitem, preview_signed_dataitem, store_dataitem,
+        store_lcp_priv_bucket_dataitem, store_signed_dataitem,
+    },
+    s3_facade::{self, S3Error},
+    scan::{ScanConfig, ScanVerdict},
+    scheduler::{scheduled_tasks, spawn_scheduler},
+    selftest::run_self_test,
+    serve::{
+        ServeMode, cache_control, cached_dataitem_raw, cached_gateway_raw, compression_layer,
+        content_disposition, dataitem_etag, etag_matches, items_inline_max_bytes, quoted_etag,
+        redirect_cache_control, serve_inline, serve_mode, tag_content_coding,
     },
-    utils::{get_env_var, is_valid_api_key},
+    staging::{self, StagingNotFound},
+    tenant::{Tenant, resolve_tenant},
+    testvectors::get_test_vectors,
+    trash::{self, Deleted, NotRestorable},
+    utils::{get_env_var, hyperbeam_node_url, internal_auth_server, is_valid_api_key, sha256_hex},
 };
 use axum::{
     Json,
-    body::Body,
-    extract::Path,
-    http::StatusCode,
-    response::{IntoResponse, Response},
+    body::{Body, Bytes, HttpBody, to_bytes},
+    extract::{MatchedPath, OriginalUri, Path, Query, Request},
+    http::{HeaderName, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::{
+        IntoResponse, Response,
+        sse::{Event, KeepAlive, Sse},
+    },
 };
-use axum_extra::extract::Multipart;
+use axum_extra::extract::{Multipart, multipart::MultipartError};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::StreamExt;
 use headers::HeaderMap;
-use serde::{Deserialize, Serialize};
-use serde_json::{Value, json};
+use http_body_util::{LengthLimitError, Limited};
+use reqwest::Url;
+use serde_json::json;
+use std::collections::BTreeMap;
+use tower_http::{compression::CompressionLayer, cors::CorsLayer};
+
+pub use crate::core::{
+    serve::CompressibleContent,
+    tls::tls_config,
+    utils::{BindAddr, OBJECT_SIZE_LIMIT, SERVER_PORT, bind_addr},
+};
 
-pub use crate::core::utils::{OBJECT_SIZE_LIMIT, SERVER_PORT};
+const MAX_FILTER_VALUES: usize = 100;
+const MAX_EXISTS_IDS: usize = 1000;
+const MAX_ITEMS_GET_IDS: usize = 100;
+const ITEMS_GET_CONCURRENCY: usize = 16;
+const MAX_NAME_VERSIONS: usize = 1000;
+const X_DATAITEM_ID: HeaderName = HeaderName::from_static("x-amz-meta-dataitem-id");
+const X_ARNS_RESOLVED_ID: HeaderName = HeaderName::from_static("x-arns-resolved-id");
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+const MAX_REWRAPPED_ERROR_LEN: usize = 64 * 1024;
+const HLS_RETRY_AFTER_SECS: u64 = 5;
+const MAX_POST_DELAY_DAYS: i64 = 30;
 
-#[derive(Deserialize)]
-pub(crate) struct TagFilter {
-    key: String,
-    value: String,
+/// Start the agent's periodic background jobs.
+pub fn spawn_background_tasks() {
+    spawn_lifecycle_task();
+    arns::spawn_arns_resolver();
+    jobs::spawn_job_workers();
+    hits::spawn_hit_flusher();
+    spawn_scheduler();
+    spawn_sighup_reload();
 }
 
-#[derive(Deserialize)]
-pub(crate) struct TagQueryRequest {
-    filters: Vec<TagFilter>,
-    #[serde(default)]
-    first: Option<usize>,
-    #[serde(default)]
-    after: Option<String>,
+/// Pre-flight check of the configuration against the agent's dependencies, see
+/// `selftest::run_self_test`.
+pub async fn self_test() -> SelfTestReport {
+    run_self_test().await
 }
 
-#[derive(Deserialize, Serialize, Clone)]
-struct UploadTag {
-    key: String,
-    value: String,
+/// CORS policy applied to every route, see `cors::cors_layer`.
+pub fn cors_policy() -> Result<CorsLayer, anyhow::Error> {
+    cors_layer()
 }
 
-#[derive(Serialize)]
-pub(crate) struct TagQueryItem {
-    dataitem_id: String,
-    content_type: String,
-    created_at: String,
-}
-
-pub async fn handle_route() -> Json<Value> {
-    Json(serde_json::json!({
-        "status": "running",
-        "name": "load-s3-agent",
-        "version": env!("CARGO_PKG_VERSION"),
-        "address": crate::core::utils::DATAITEMS_ADDRESS,
-        "object_size_limit": crate::core::utils::OBJECT_SIZE_LIMIT,
-        "presigned_url_expiry": crate::core::utils::PRESIGNED_URL_EXPIRY,
-        "data_protocol": crate::core::utils::STORAGE_PROVIDER_NAME,
-        "hyperbeam_node_url": crate::core::utils::HYPERBEAM_NODE_URL,
-    }))
+/// Build the CORS policy applied by the `apply_cors_policy` middleware, rebuilt on reload.
+pub fn install_cors_policy() -> Result<(), anyhow::Error> {
+    install_cors(cors_layer()?);
+    Ok(())
 }
 
-pub async fn handle_storage_stats() -> Json<Value> {
-    let stats = get_bucket_stats().await.unwrap_or_default();
-    Json(serde_json::json!({
-        "total_dataitems_count": stats.0,
-        "total_dataitems_size": stats.1
-    }))
+/// Middleware applying the current CORS policy, see `install_cors_policy`.
+pub async fn apply_cors_policy(request: Request, next: Next) -> Response {
+    apply_cors(request, next).await
 }
 
-pub async fn handle_query_tags(
-    Json(payload): Json<TagQueryRequest>,
-) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    if payload.filters.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "filters array must not be empty"})),
-        ));
-    }
+/// Response compression for the dataitem serving route, if enabled.
+pub fn dataitem_compression_layer() -> Option<CompressionLayer<CompressibleContent>> {
+    compression_layer()
+}
 
-    let filters: Vec<(String, String)> =
-        payload.filters.iter().map(|f| (f.key.clone(), f.value.clone())).collect();
+/// Layered outside the compression: compressed responses get their own ETag.
+pub async fn tag_compressed_etag(mut response: Response) -> Response {
+    tag_content_coding(&mut response);
+    response
+}
 
-    let requested_first = payload.first.unwrap_or(DEFAULT_PAGE_SIZE);
-    if requested_first == 0 {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": "first must be greater than 0"})),
-        ));
-    }
-    if requested_first > MAX_PAGE_SIZE {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({"error": format!("first must not exceed {MAX_PAGE_SIZE}")})),
-        ));
+/// Base URL clients reach this agent on, `AGENT_PUBLIC_URL` or the request's `Host` with the
+/// `X-Forwarded-Proto` scheme (default http).
+fn agent_public_url(headers: &HeaderMap) -> Option<String> {
+    if let Ok(url) = get_env_var("AGENT_PUBLIC_URL") {
+        return Some(url.trim_end_matches('/').to_string());
     }
-    let first = requested_first;
-
-    let after_cursor = match payload.after.as_deref() {
-        Some(cursor) => Some(decode_tag_query_cursor(cursor).map_err(|err| {
-            (StatusCode::BAD_REQUEST, Json(json!({"error": format!("invalid cursor: {err}")})))
-        })?),
-        None => None,
-    };
+    let host = headers.get(header::HOST).and_then(|h| h.to_str().ok())?;
+    let scheme = headers.get("x-forwarded-proto").and_then(|h| h.to_str().ok()).unwrap_or("http");
+    Some(format!("{scheme}://{host}"))
+}
 
-    let pagination = TagQueryPagination { first, after: after_cursor };
-
-    match query_dataitems_by_tags(&filters, &pagination).await {
-        Ok(page) => {
-            let items: Vec<TagQueryItem> = page
-                .items
-                .into_iter()
-                .map(|record| TagQueryItem {
-                    dataitem_id: record.dataitem_id,
-                    content_type: record.content_type,
-                    created_at: record.created_at.to_rfc3339(),
-                })
-                .collect();
-
-            Ok(Json(json!({
-                "success": true,
-                "count": items.len(),
-                "items": items,
-                "page_info": {
-                    "has_next_page": page.has_more,
-                    "next_cursor": page.next_cursor
-                }
-            })))
-        }
-        Err(err) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("failed to query tags: {err}")})),
-        )),
-    }
+fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers.get("authorization").and_then(|h| h.to_str().ok())?.strip_prefix("Bearer ")
 }
 
-pub async fn serve_dataitem(Path(dataitem_id): Path<String>) -> impl IntoResponse {
-    Response::builder()
-            .status(StatusCode::FORBIDDEN)
-            .header("content-type", "application/json")
-            .body(Body::from(format!(r#"{{"error": "method deprecated since v0.7.0 - please access dataitem from 'https://gateway.s3-node-1.load.network/resolve/{dataitem_id}'"}}"#)))
-            .unwrap()
+/// Operator-only routes authenticate with `Bearer $ADMIN_API_KEY`.
+fn require_admin(headers: &HeaderMap) -> Result<(), AgentError> {
+    let token = bearer_token(headers)
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+    let admin_key = get_env_var("ADMIN_API_KEY")
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "server configuration error"))?;
+    if token != admin_key {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "invalid admin API key"));
+    }
+    Ok(())
 }
 
-pub async fn upload_file(
-    headers: HeaderMap,
-    mut multipart: Multipart,
-) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let auth_header =
-        headers.get("authorization").and_then(|h| h.to_str().ok()).ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(json!({
-                    "error": "missing Authorization header"
-                })),
-            )
-        })?;
+/// Accept `SERVER_API_KEYS` entries only, for routes acting on already stored dataitems.
+fn require_server_key(headers: &HeaderMap) -> Result<(), AgentError> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
 
     let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-        (
+        api_error(
             StatusCode::UNAUTHORIZED,
-            Json(json!({
-                "error": "invalid Authorization header format. Expected 'Bearer <token>'"
-            })),
+            "invalid Authorization header format. Expected 'Bearer <token>'",
         )
     })?;
 
-    let server_api_keys = get_env_var("SERVER_API_KEYS").map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": "server configuration error"
-            })),
-        )
-    })?;
+    let server_api_keys = get_env_var("SERVER_API_KEYS")
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "server configuration error"))?;
 
     let api_keys: Vec<String> = server_api_keys.split(',').map(|s| s.trim().to_string()).collect();
 
     if !api_keys.contains(&token.to_string()) {
-        let potential_valid_load_acc = is_valid_api_key(&token).await.map_err(|_| {
-            (StatusCode::UNAUTHORIZED, Json(json!({"error": "invalid load_acc key"})))
-        })?;
-
-        if !potential_valid_load_acc {
-            return Err((
-                StatusCode::UNAUTHORIZED,
-                Json(json!({
-                    "error": "invalid API key"
-                })),
-            ));
-        }
+        return Err(api_error(StatusCode::UNAUTHORIZED, "invalid API key"));
     }
+    Ok(())
+}
 
-    let mut file_data: Option<Vec<u8>> = None;
-    let mut content_type: Option<String> = None;
-    let mut extra_tags: Vec<UploadTag> = Vec::new();
+/// Accept `SERVER_API_KEYS` entries and valid load_acc keys.
+async fn require_api_key(headers: &HeaderMap) -> Result<(), AgentError> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
 
-    while let Some(field) = multipart.next_field().await.map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "invalid multipart data"
-            })),
+    let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
+        api_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid Authorization header format. Expected 'Bearer <token>'",
         )
-    })? {
-        let field_name = field.name().unwrap_or("");
+    })?;
 
-        match field_name {
-            "file" => {
-                content_type = field.content_type().map(|ct| ct.to_string());
-                file_data = Some(
-                    field
-                        .bytes()
-                        .await
-                        .map_err(|_| {
-                            (
-                                StatusCode::BAD_REQUEST,
-                                Json(json!({
-                                    "error": "failed to read file data"
-                                })),
-                            )
-                        })?
-                        .to_vec(),
-                );
-            }
-            "content_type" => {
-                if content_type.is_none() {
-                    content_type = Some(field.text().await.map_err(|_| {
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "error": "failed to read content type"
-                            })),
-                        )
-                    })?);
-                }
-            }
-            "tags" => {
-                let text = field.text().await.map_err(|_| {
-                    (
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "error": "failed to read tags field"
-                        })),
-                    )
-                })?;
+    let server_api_keys = get_env_var("SERVER_API_KEYS")
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "server configuration error"))?;
 
-                let parsed: Vec<UploadTag> = serde_json::from_str(&text).map_err(|_| {
-                    (
-                        StatusCode::BAD_REQUEST,
-                        Json(json!({
-                            "error": "invalid tags payload, expected JSON array of objects with key/value"
-                        })),
-                    )
-                })?;
+    let api_keys: Vec<String> = server_api_keys.split(',').map(|s| s.trim().to_string()).collect();
 
-                extra_tags = parsed;
-            }
-            _ => {
-                // skip
+    if !api_keys.contains(&token.to_string()) {
+        let potential_valid_load_acc = is_valid_api_key(token).await.map_err(|e| {
+            match e.is::<DependencyTimeout>() || e.is::<BreakerOpen>() {
+                true => upstream_error(StatusCode::UNAUTHORIZED, "failed to verify API key", &e),
+                false => api_error(StatusCode::UNAUTHORIZED, "invalid load_acc key"),
             }
+        })?;
+
+        if !potential_valid_load_acc {
+            return Err(api_error(StatusCode::UNAUTHORIZED, "invalid API key"));
         }
     }
+    Ok(())
+}
 
-    let file_bytes = file_data.ok_or_else(|| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "no file data provided"
-            })),
-        )
-    })?;
+/// Whether reading the body failed on the size limit, which is nested a few layers deep.
+fn exceeds_body_limit(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if e.is::<LengthLimitError>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
 
-    if file_bytes.len() > OBJECT_SIZE_LIMIT {
-        return Err((
-            StatusCode::PAYLOAD_TOO_LARGE,
-            Json(json!({"error": format!("file size exceeds limit - {OBJECT_SIZE_LIMIT} bytes")})),
-        ));
+fn multipart_error(err: MultipartError) -> AgentError {
+    match exceeds_body_limit(&err) {
+        true => api_error(StatusCode::PAYLOAD_TOO_LARGE, "request body exceeds the size limit"),
+        false => api_error(StatusCode::BAD_REQUEST, "invalid multipart data"),
     }
+}
 
-    let content_type_str = content_type.as_deref().unwrap_or("application/octet-stream");
+fn blocked_error(blocked: Blocked) -> AgentError {
+    api_error(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, blocked.to_string())
+}
 
-    let is_signed =
-        headers.get("signed").and_then(|h| h.to_str().ok()).map(|s| s == "true").unwrap_or(false);
+fn deleted_error(deleted: Deleted) -> AgentError {
+    api_error(StatusCode::GONE, deleted.to_string())
+}
 
-    if is_signed && !extra_tags.is_empty() {
-        return Err((
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "custom tags are not supported when uploading signed dataitems, the dataitems tags will be extracted and appied instead"
-            })),
-        ));
-    }
+fn request_tenant(headers: &HeaderMap) -> Result<Tenant, AgentError> {
+    resolve_tenant(headers, bearer_token(headers))
+        .map_err(|err| api_error(StatusCode::BAD_REQUEST, err.to_string()))
+}
 
-    let extra_tag_pairs: Vec<(String, String)> =
-        extra_tags.iter().map(|tag| (tag.key.clone(), tag.value.clone())).collect();
+/// Run the configured malware scanner (if any) over an upload, auditing the verdict.
+async fn scan_upload(route: &str, data: &[u8]) -> Result<(), AgentError> {
+    let Some(config) = ScanConfig::load()
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("scan config: {e}")))?
+    else {
+        return Ok(());
+    };
 
-    let result = if is_signed {
-        store_signed_dataitem(file_bytes).await
-    } else {
-        store_dataitem(file_bytes, content_type_str, &extra_tag_pairs).await
+    let result = config.scan(data).await;
+    let (verdict, threat, error) = match &result {
+        Ok(verdict @ ScanVerdict::Infected(threat)) => {
+            (verdict.as_str(), Some(threat.clone()), None)
+        }
+        Ok(verdict) => (verdict.as_str(), None, None),
+        Err(err) => ("error", None, Some(err.to_string())),
     };
+    audit::record(
+        "upload_scan",
+        json!({
+            "route": route,
+            "scanner": config.backend_name(),
+            "verdict": verdict,
+            "threat": threat,
+            "error": error,
+            "size": data.len(),
+            "sha256": sha256_hex(data),
+        }),
+    )
+    .await;
+    metrics::increment(&format!("upload_scans_total{{verdict=\"{verdict}\"}}"));
 
     match result {
-        Ok(dataitem_id) => Ok(Json(json!({
-            "success": true,
-            "dataitem_id": dataitem_id,
-            "custom_tags": extra_tags,
-            "message": "file uploaded successfully"
-        }))),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": format!("failed to store file: {}", e)
-            })),
+        Ok(ScanVerdict::Clean) => Ok(()),
+        Ok(ScanVerdict::Infected(threat)) => Err(api_error(
+            StatusCode::UNPROCESSABLE_ENTITY,
+            format!("upload rejected by malware scan: {threat}"),
+        )),
+        Err(_) if config.fail_open => Ok(()),
+        Err(err) => Err(api_error(
+            StatusCode::SERVICE_UNAVAILABLE,
+            format!("malware scanner unavailable: {err}"),
         )),
     }
 }
 
-pub async fn handle_private_file(
-    headers: HeaderMap,
-    mut multipart: Multipart,
-) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let auth_header =
-        headers.get("authorization").and_then(|h| h.to_str().ok()).ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(json!({
-                    "error": "missing Authorization header"
-                })),
-            )
-        })?;
-
-    let load_acc = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(json!({
-                "error": "invalid Authorization header format. Expected 'Bearer <token>'"
-            })),
-        )
-    })?;
-
-    let bucket_name = headers
-        .get("bucket_name")
-        .or_else(|| headers.get("bucket-name"))
-        .or_else(|| headers.get("x-bucket-name"))
-        .or_else(|| headers.get("bucketname"))
-        .and_then(|h| h.to_str().ok())
-        .ok_or_else(|| {
-            (
-                StatusCode::BAD_REQUEST,
-                Json(json!({
-                    "error": "missing bucket_name header"
-                })),
-            )
-        })?;
-
-    let dataitem_name = headers
-        .get("x-dataitem-name")
-        .or_else(|| headers.get("dataitem-name"))
-        .or_else(|| headers.get("dataitemname"))
-        .and_then(|h| h.to_str().ok())
-        .unwrap_or("");
-
-    let folder_name = headers.get("x-folder-name").and_then(|h| h.to_str().ok()).unwrap_or("");
+#[utoipa::path(get, path = "/", tag = "agent", responses((status = 200, body = AgentInfo)))]
+pub async fn handle_route(headers: HeaderMap) -> Result<Json<AgentInfo>, AgentError> {
+    let limits = BodyLimits::load()
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let tier = bearer_token(&headers).and_then(|token| limits.key_tier(token));
 
-    let mut file_data: Option<Vec<u8>> = None;
-    let mut content_type: Option<String> = None;
+    let maintenance = maintenance::settings();
+    Ok(Json(AgentInfo {
+        status: if maintenance.enabled { "maintenance" } else { "running" }.to_string(),
+        maintenance_message: maintenance.enabled.then_some(maintenance.message),
+        name: "load-s3-agent".to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        address: crate::core::utils::DATAITEMS_ADDRESS.to_string(),
+        object_size_limit: crate::core::utils::OBJECT_SIZE_LIMIT,
+        route_size_limits: limits.routes.clone(),
+        size_limit_tier: tier.map(|tier| tier.name.clone()),
+        key_size_limit: tier.map(|tier| tier.max_bytes.min(OBJECT_SIZE_LIMIT)),
+        presigned_url_expiry: crate::core::utils::PRESIGNED_URL_EXPIRY,
+        data_protocol: crate::core::utils::STORAGE_PROVIDER_NAME.to_string(),
+        hyperbeam_node_url: hyperbeam_node_url(),
+        upstreams: UpstreamUrls {
+            auth_server: internal_auth_server(),
+            hyperbeam_node: hyperbeam_node_url(),
+            lcp_api: lcp_api_url(),
+            arweave_gateway: GatewayFallback::load().map(|gateway| gateway.url),
+        },
+    }))
+}
 
-    while let Some(field) = multipart.next_field().await.map_err(|_| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "invalid multipart data"
-            })),
-        )
-    })? {
-        let field_name = field.name().unwrap_or("");
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "agent",
+    responses((status = 200, body = HealthStatus, description = "the agent is up, maybe read-only"))
+)]
+pub async fn handle_healthz() -> Json<HealthStatus> {
+    let maintenance = maintenance::settings();
+    Json(HealthStatus {
+        status: if maintenance.enabled { "maintenance" } else { "ok" }.to_string(),
+        maintenance_message: maintenance.enabled.then_some(maintenance.message),
+    })
+}
 
-        match field_name {
-            "file" => {
-                content_type = field.content_type().map(|ct| ct.to_string());
-                file_data = Some(
-                    field
-                        .bytes()
-                        .await
-                        .map_err(|_| {
-                            (
-                                StatusCode::BAD_REQUEST,
-                                Json(json!({
-                                    "error": "failed to read file data"
-                                })),
-                            )
-                        })?
-                        .to_vec(),
-                );
-            }
-            "content_type" => {
-                if content_type.is_none() {
-                    content_type = Some(field.text().await.map_err(|_| {
-                        (
-                            StatusCode::BAD_REQUEST,
-                            Json(json!({
-                                "error": "failed to read content type"
-                            })),
-                        )
-                    })?);
-                }
-            }
-            _ => {
-                // skip
-            }
-        }
+/// Route layer rejecting the writes with a `503`, the maintenance message and `Retry-After`
+/// while the agent is in maintenance. Reads and the admin API are still served.
+pub async fn enforce_maintenance(request: Request, next: Next) -> Response {
+    let settings = maintenance::settings();
+    if !settings.enabled {
+        return next.run(request).await;
+    }
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let route = route.unwrap_or_default();
+    if maintenance::allows(request.method(), &route) {
+        return next.run(request).await;
     }
+    metrics::increment(&format!("requests_rejected_maintenance_total{{route=\"{route}\"}}"));
+    let mut response =
+        AgentError::Unavailable { message: settings.message, dependency: None }.into_response();
+    response
+        .headers_mut()
+        .insert(header::RETRY_AFTER, HeaderValue::from(settings.retry_after_secs));
+    response
+}
 
-    let file_bytes = file_data.ok_or_else(|| {
-        (
-            StatusCode::BAD_REQUEST,
-            Json(json!({
-                "error": "no file data provided"
-            })),
-        )
-    })?;
+/// Route layer enforcing the `ROUTE_SIZE_LIMITS` and `SIZE_LIMIT_TIERS` body limits: declared
+/// oversized bodies are rejected upfront, streamed ones are cut off once they exceed the limit.
+pub async fn enforce_body_limits(request: Request, next: Next) -> Result<Response, AgentError> {
+    let limits = BodyLimits::load()
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str());
+    let limit = limits.limit_for(route, bearer_token(request.headers()));
+    if limit >= OBJECT_SIZE_LIMIT {
+        return Ok(next.run(request).await);
+    }
 
-    if file_bytes.len() > OBJECT_SIZE_LIMIT {
-        return Err((
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if declared.is_some_and(|len| len > limit) {
+        return Err(api_error(
             StatusCode::PAYLOAD_TOO_LARGE,
-            Json(json!({"error": format!("file size exceeds limit - {OBJECT_SIZE_LIMIT} bytes")})),
+            format!("request body exceeds limit - {limit} bytes"),
         ));
     }
 
-    let content_type_str = content_type.as_deref().unwrap_or("application/octet-stream");
+    let request = request.map(|body| Body::new(Limited::new(body, limit)));
+    Ok(next.run(request).await)
+}
 
-    let is_signed =
-        headers.get("signed").and_then(|h| h.to_str().ok()).map(|s| s == "true").unwrap_or(false);
+/// Route layer shedding the requests beyond the `MAX_CONCURRENT_REQUESTS` and
+/// `ROUTE_CONCURRENCY_LIMITS` limits with a `503` and `Retry-After`, before their body is read.
+pub async fn limit_concurrency(request: Request, next: Next) -> Response {
+    let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str().to_string());
+    let route = route.unwrap_or_default();
+    let Some(_in_flight) = concurrency::admit(&route) else {
+        let retry_after = concurrency::retry_after_secs();
+        let mut response = AgentError::Unavailable {
+            message: format!("too many concurrent requests on {route}, retry in {retry_after}s"),
+            dependency: None,
+        }
+        .into_response();
+        response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    };
+    next.run(request).await
+}
 
-    // private dataitems store
-    // supports signed (ANS-104 ready) and unsigned (raw dataitem's data) data ingress
-    match store_lcp_priv_bucket_dataitem(
-        file_bytes,
-        content_type_str,
-        bucket_name,
-        folder_name,
-        load_acc,
-        dataitem_name,
-        is_signed,
-    )
-    .await
-    {
-        Ok(dataitem_id) => Ok(Json(json!({
-            "success": true,
-            "dataitem_id": dataitem_id,
-            "dataitem_name": dataitem_name,
-            "folder_name": folder_name,
-            "is_signed": is_signed,
-            "message": "file uploaded to private bucket successfully"
-        }))),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": format!("failed to store file: {}", e)
-            })),
-        )),
+/// Route layer holding the body of each `POST`/`PUT` against `UPLOAD_MEMORY_BUDGET_BYTES`
+/// until it is handled, and stored when that is left to the background: its `Content-Length`,
+/// or the route's body limit when it is streamed. Uploads the budget can't take are rejected
+/// with a `503` and `Retry-After`.
+pub async fn enforce_memory_budget(request: Request, next: Next) -> Result<Response, AgentError> {
+    if !matches!(*request.method(), Method::POST | Method::PUT) {
+        return Ok(next.run(request).await);
     }
+    // a bodiless request (e.g. `/upload/commit/{staging_id}`) is known to be empty
+    let declared = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .or_else(|| request.body().size_hint().exact());
+    let bytes = match declared {
+        Some(bytes) => bytes,
+        None => {
+            let limits = BodyLimits::load()
+                .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+            let route = request.extensions().get::<MatchedPath>().map(|path| path.as_str());
+            limits.limit_for(route, bearer_token(request.headers())) as u64
+        }
+    };
+    let Some(reservation) = concurrency::reserve_upload_bytes(bytes) else {
+        return Ok(memory_budget_exceeded(bytes));
+    };
+    Ok(concurrency::holding(reservation, next.run(request)).await)
 }
 
-pub async fn handle_post_dataitem(
-    headers: HeaderMap,
-    Path(dataitem_id): Path<String>,
-) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let auth_header =
-        headers.get("authorization").and_then(|h| h.to_str().ok()).ok_or_else(|| {
-            (
-                StatusCode::UNAUTHORIZED,
-                Json(json!({
-                    "error": "missing Authorization header"
-                })),
-            )
-        })?;
+fn memory_budget_exceeded(bytes: u64) -> Response {
+    let retry_after = concurrency::retry_after_secs();
+    let mut response = AgentError::Unavailable {
+        message: format!(
+            "the agent is buffering too many uploads to take {bytes} more bytes, retry in {retry_after}s"
+        ),
+        dependency: None,
+    }
+    .into_response();
+    response.headers_mut().insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+    response
+}
 
-    let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-        (
-            StatusCode::UNAUTHORIZED,
-            Json(json!({
-                "error": "invalid Authorization header format. Expected 'Bearer <token>'"
-            })),
-        )
-    })?;
+// extractor rejections (malformed JSON, query, path or multipart) answer plain text, rewrap them
+// so every error has the `ErrorResponse` shape
+async fn rewrap_plain_error(response: Response) -> Response {
+    let is_plain_text = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("text/plain"));
+    let status = response.status();
+    if !is_plain_text || !(status.is_client_error() || status.is_server_error()) {
+        return response;
+    }
+    let body = to_bytes(response.into_body(), MAX_REWRAPPED_ERROR_LEN).await.unwrap_or_default();
+    AgentError::new(status, String::from_utf8_lossy(&body).trim()).into_response()
+}
 
-    let server_api_keys = get_env_var("SERVER_API_KEYS").map_err(|_| {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": "server configuration error"
-            })),
-        )
-    })?;
+/// Layer recording who sent each request and from where, for the uploads it indexes, and
+/// answering with its `X-Request-Id`.
+pub async fn record_provenance(request: Request, next: Next) -> Response {
+    let origin = Provenance::from_request(&request);
+    let request_id = HeaderValue::from_str(&origin.request_id).ok();
+    let handled = async { rewrap_plain_error(next.run(request).await).await };
+    let mut response = provenance::scope(origin, handled).await;
+    if let Some(request_id) = request_id {
+        response.headers_mut().insert(X_REQUEST_ID, request_id);
+    }
+    response
+}
 
-    let api_keys: Vec<String> = server_api_keys.split(',').map(|s| s.trim().to_string()).collect();
+/// Layer logging a sample of the requests for debugging, see `request_log::log_sampled`.
+pub async fn log_sampled_requests(request: Request, next: Next) -> Response {
+    request_log::log_sampled(request, next).await
+}
 
-    if !api_keys.contains(&token.to_string()) {
-        return Err((
-            StatusCode::UNAUTHORIZED,
-            Json(json!({
-                "error": "invalid API key"
-            })),
-        ));
+/// Layer giving each upload request its time budget, see `cancellation::scope`. Its writes are
+/// rolled back when it is dropped or exceeds `UPLOAD_DEADLINE_SECS`, answering `408` for the
+/// latter.
+pub async fn enforce_upload_budget(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::POST | Method::PUT) {
+        return next.run(request).await;
+    }
+    match cancellation::scope(next.run(request)).await {
+        Ok(response) => response,
+        Err(aborted) => api_error(StatusCode::REQUEST_TIMEOUT, aborted.to_string()).into_response(),
     }
+}
 
-    match post_dataitem(dataitem_id.clone()).await {
-        Ok(response) => Ok(Json(json!({
-            "success": true,
-            "dataitem_id": dataitem_id,
-            "bundler_response": response,
-            "message": "dataitem posted to arweave successfully"
-        }))),
-        Err(e) => Err((
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({
-                "error": format!("failed to post dataitem: {}", e)
-            })),
-        )),
+/// Unknown routes answer a `not_found` error like any other.
+pub async fn handle_not_found(method: Method, OriginalUri(uri): OriginalUri) -> AgentError {
+    AgentError::NotFound(format!("no route for {method} {}", uri.path()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "agent",
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses((status = 200, body = StorageStats), (status = 400, body = ErrorResponse))
+)]
+pub async fn handle_storage_stats(headers: HeaderMap) -> Result<Json<StorageStats>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    if let Some((count, size, reconciled_at)) = cached_bucket_stats(&tenant) {
+        return Ok(Json(StorageStats {
+            total_dataitems_count: count,
+            total_dataitems_size: size,
+            reconciled_at: Some(reconciled_at.to_rfc3339()),
+        }));
     }
+    let stats = get_bucket_stats(&tenant).await.unwrap_or_default();
+    Ok(Json(StorageStats {
+        total_dataitems_count: stats.0,
+        total_dataitems_size: stats.1,
+        reconciled_at: None,
+    }))
 }
 
-pub async fn handle_get_bucket_registry(
-    headers: HeaderMap,
-    Path(bucket_name): Path<String>,
-) -> Result<Json<Value>, (StatusCode, Json<Value>)> {
-    let auth_header =
-        headers.get("authorization").and_then(|h| h.to_str().ok()).ok_or_else(|| {
-            (StatusCode::UNAUTHORIZED, Json(json!({"error": "missing Authorization header"})))
-        })?;
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "agent",
+    responses((status = 200, description = "Prometheus text exposition of the agent metrics"))
+)]
+pub async fn handle_metrics() -> impl IntoResponse {
+    ([(header::CONTENT_TYPE, "text/plain; version=0.0.4")], metrics::render())
+}
 
-    let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
-        (StatusCode::UNAUTHORIZED, Json(json!({"error": "invalid Authorization header format"})))
-    })?;
+#[utoipa::path(
+    get,
+    path = "/testvectors",
+    tag = "agent",
+    responses(
+        (status = 200, body = TestVectorsResponse)
+    )
+)]
+pub async fn handle_test_vectors() -> Json<TestVectorsResponse> {
+    let vectors = get_test_vectors();
+    Json(TestVectorsResponse { success: true, count: vectors.len(), vectors: vectors.to_vec() })
+}
 
-    let aws_secret = get_env_var("REGISTRY_SECRET_KEY").map_err(|_| {
-        (StatusCode::INTERNAL_SERVER_ERROR, Json(json!({"error": "server configuration error"})))
-    })?;
+#[utoipa::path(
+    post,
+    path = "/id",
+    tag = "agent",
+    request_body(content = Vec<u8>, description = "a signed ANS-104 dataitem, or only its header", content_type = "application/octet-stream"),
+    responses(
+        (status = 200, body = DataitemIdResponse),
+        (status = 400, body = ErrorResponse, description = "not a parsable ANS-104 dataitem")
+    )
+)]
+pub async fn handle_dataitem_id(body: Bytes) -> Result<Json<DataitemIdResponse>, AgentError> {
+    // the ID derives from the signature alone, so the header is enough to compute it
+    let (dataitem, _) = reconstruct_dataitem_data(body.to_vec())
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("invalid dataitem: {e}")))?;
+    Ok(Json(DataitemIdResponse {
+        dataitem_id: dataitem.arweave_id(),
+        owner: owner_address(&dataitem),
+        data_size: dataitem.data.len(),
+        tags: dataitem
+            .tags
+            .into_iter()
+            .map(|tag| UploadTag { key: tag.name, value: tag.value })
+            .collect(),
+    }))
+}
 
-    if token != aws_secret {
-        return Err((StatusCode::UNAUTHORIZED, Json(json!({"error": "invalid API key"}))));
-    }
+#[utoipa::path(
+    post,
+    path = "/tags/query",
+    tag = "query",
+    request_body = TagQueryRequest,
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = TagQueryResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_query_tags(
+    headers: HeaderMap,
+    Json(payload): Json<TagQueryRequest>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let filters = tag_filters(&payload.filters)?;
+    let pagination = tag_query_pagination(payload.first, payload.after.as_deref())?;
 
-    match get_bucket_registry(&bucket_name) {
-        Ok(registry_entries) => Ok(Json(json!({
-            "success": true,
-            "bucket_name": bucket_name,
-            "entries": registry_entries
-        }))),
-        Err(e) => Err((
+    match query_dataitems_by_tags(&tenant.name, &filters, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => {
+            Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to query tags", &err))
+        }
+    }
+}
+
+fn tag_filters(filters: &[TagFilter]) -> Result<Vec<(String, Vec<String>)>, AgentError> {
+    if filters.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "filters array must not be empty"));
+    }
+
+    let filters: Vec<(String, Vec<String>)> =
+        filters.iter().map(|f| (f.key.clone(), f.all_values())).collect();
+    if filters.iter().any(|(_, values)| values.is_empty()) {
+        return Err(api_error(StatusCode::BAD_REQUEST, "every filter needs a value or values"));
+    }
+    if filters.iter().any(|(_, values)| values.len() > MAX_FILTER_VALUES) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("a filter must not list more than {MAX_FILTER_VALUES} values"),
+        ));
+    }
+    Ok(filters)
+}
+
+fn tag_query_pagination(
+    first: Option<usize>,
+    after: Option<&str>,
+) -> Result<TagQueryPagination, AgentError> {
+    let requested_first = first.unwrap_or(DEFAULT_PAGE_SIZE);
+    if requested_first == 0 {
+        return Err(api_error(StatusCode::BAD_REQUEST, "first must be greater than 0"));
+    }
+    if requested_first > MAX_PAGE_SIZE {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("first must not exceed {MAX_PAGE_SIZE}"),
+        ));
+    }
+    let first = requested_first;
+
+    let after_cursor = after
+        .map(decode_tag_query_cursor)
+        .transpose()
+        .map_err(|err| api_error(StatusCode::BAD_REQUEST, err.to_string()))?;
+
+    Ok(TagQueryPagination { first, after: after_cursor })
+}
+
+fn tag_query_response(page: TagQueryPage) -> TagQueryResponse {
+    let items: Vec<TagQueryItem> = page
+        .items
+        .into_iter()
+        .map(|record| TagQueryItem {
+            dataitem_id: record.dataitem_id,
+            content_type: record.content_type,
+            created_at: record.created_at.to_rfc3339(),
+            tags: record.tags.into_iter().map(|(key, value)| UploadTag { key, value }).collect(),
+        })
+        .collect();
+
+    TagQueryResponse {
+        success: true,
+        count: items.len(),
+        items,
+        page_info: PageInfo { has_next_page: page.has_more, next_cursor: page.next_cursor },
+    }
+}
+
+fn feed_info(feed: FeedRecord) -> FeedInfo {
+    FeedInfo {
+        name: feed.name,
+        filters: feed.filters.into_iter().map(|(key, values)| FeedFilter { key, values }).collect(),
+        created_at: feed.created_at.to_rfc3339(),
+        updated_at: feed.updated_at.to_rfc3339(),
+    }
+}
+
+async fn load_feed(tenant: &Tenant, name: &str) -> Result<FeedRecord, AgentError> {
+    get_feed(&tenant.name, name)
+        .await
+        .map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to load the feed", &e)
+        })?
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("feed {name} not found")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/feeds",
+    tag = "query",
+    request_body = CreateFeedRequest,
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = FeedInfo, description = "the feed, replacing the one of that name"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_create_feed(
+    headers: HeaderMap,
+    Json(payload): Json<CreateFeedRequest>,
+) -> Result<Json<FeedInfo>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let filters = tag_filters(&payload.filters)?;
+
+    let feed =
+        feeds::save(&tenant.name, &payload.name, &filters).await.map_err(|e| match e
+            .is::<InvalidFeed>()
+        {
+            true => api_error(StatusCode::BAD_REQUEST, e.to_string()),
+            false => {
+                upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to save the feed", &e)
+            }
+        })?;
+    Ok(Json(feed_info(feed)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/feeds",
+    tag = "query",
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = FeedsResponse, description = "the tenant's feeds, by name"),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_list_feeds(headers: HeaderMap) -> Result<Json<FeedsResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let feeds = list_feeds(&tenant.name).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to list the feeds", &e)
+    })?;
+    Ok(Json(FeedsResponse { feeds: feeds.into_iter().map(feed_info).collect() }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/feeds/{name}",
+    tag = "query",
+    params(
+        ("name" = String, Path, description = "feed name"),
+        PageParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = TagQueryResponse, description = "dataitems matching the feed, newest first"),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_get_feed(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let pagination = tag_query_pagination(params.first, params.after.as_deref())?;
+    let feed = load_feed(&tenant, &name).await?;
+
+    match query_dataitems_by_tags(&tenant.name, &feed.filters, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => {
+            Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to query the feed", &err))
+        }
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/feeds/{name}",
+    tag = "query",
+    params(
+        ("name" = String, Path, description = "feed name"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = FeedInfo, description = "the removed feed"),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_delete_feed(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<FeedInfo>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let feed = feeds::remove(&tenant.name, &name).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to remove the feed", &e)
+    })?;
+    feed.map(|feed| Json(feed_info(feed)))
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("feed {name} not found")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/feeds/{name}/events",
+    tag = "query",
+    params(
+        ("name" = String, Path, description = "feed name"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, content_type = "text/event-stream", body = FeedItemEvent, description = "an `item` event per dataitem the agent indexes matching the feed"),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_feed_events(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    // subscribed first, so no item indexed while the feed is looked up is missed
+    let events = feeds::subscribe(&tenant.name, &name);
+    load_feed(&tenant, &name).await?;
+
+    let events = events.map(|item| Event::default().event("item").json_data(item));
+    Ok(Sse::new(events).keep_alive(KeepAlive::default()).into_response())
+}
+
+fn name_version(record: NameRecord) -> NameVersion {
+    NameVersion {
+        name: record.name,
+        version: record.version,
+        dataitem_id: record.dataitem_id,
+        updated_by: record.updated_by,
+        created_at: record.created_at.to_rfc3339(),
+    }
+}
+
+#[utoipa::path(
+    put,
+    path = "/names/{name}",
+    tag = "dataitems",
+    request_body = PointNameRequest,
+    params(
+        ("name" = String, Path, description = "1 to 128 lowercase letters, digits, `-`, `_` or `.`"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = NameVersion, description = "the new version of the name"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "dataitem not stored"),
+        (status = 409, body = ErrorResponse, description = "the name is no longer at `expected_version`"),
+        (status = 410, body = ErrorResponse, description = "dataitem deleted"),
+        (status = 451, body = ErrorResponse, description = "dataitem blocked"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_point_name(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Json(payload): Json<PointNameRequest>,
+) -> Result<Json<NameVersion>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let dataitem_id = payload.dataitem_id;
+    if !is_arweave_id(&dataitem_id) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("invalid dataitem id: {dataitem_id}"),
+        ));
+    }
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+    let presence =
+        dataitems_presence(&tenant, std::slice::from_ref(&dataitem_id)).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check the dataitem", &e)
+        })?;
+    if !presence.iter().any(|&(raw, ans104)| raw || ans104) {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("dataitem {dataitem_id} not found")));
+    }
+
+    let record = names::point(&tenant.name, &name, &dataitem_id, payload.expected_version)
+        .await
+        .map_err(|e| {
+            if e.is::<InvalidName>() {
+                api_error(StatusCode::BAD_REQUEST, e.to_string())
+            } else if e.is::<VersionConflict>() {
+                api_error(StatusCode::CONFLICT, e.to_string())
+            } else {
+                upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to update the name", &e)
+            }
+        })?;
+    Ok(Json(name_version(record)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/names/{name}",
+    tag = "dataitems",
+    params(
+        ("name" = String, Path, description = "name"),
+        NameParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = NameVersion, description = "the version, with `resolve=true`"),
+        (status = 302, description = "redirect to `/{dataitem_id}` of the version"),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_resolve_name(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Query(params): Query<NameParams>,
+) -> Result<Response, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let record = get_name_version(&tenant.name, &name, params.version)
+        .await
+        .map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to resolve the name", &e)
+        })?
+        .ok_or_else(|| {
+            let message = match params.version {
+                Some(version) => format!("version {version} of name {name} not found"),
+                None => format!("name {name} not found"),
+            };
+            api_error(StatusCode::NOT_FOUND, message)
+        })?;
+    if params.resolve {
+        return Ok(Json(name_version(record)).into_response());
+    }
+
+    // a version never changes, the latest one may any time
+    let cache_control = match params.version {
+        Some(_) => cache_control(),
+        None => "no-store".to_string(),
+    };
+    Ok((
+        StatusCode::FOUND,
+        [
+            (header::LOCATION, format!("/{}", record.dataitem_id)),
+            (header::CACHE_CONTROL, cache_control),
+        ],
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/names/{name}/history",
+    tag = "dataitems",
+    params(
+        ("name" = String, Path, description = "name"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = NameHistoryResponse, description = "up to the 1,000 latest versions of the name"),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_name_history(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Json<NameHistoryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let versions = name_history(&tenant.name, &name, MAX_NAME_VERSIONS).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to load the name history", &e)
+    })?;
+    if versions.is_empty() {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("name {name} not found")));
+    }
+    Ok(Json(NameHistoryResponse {
+        name,
+        versions: versions.into_iter().map(name_version).collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/address/{owner}/dataitems",
+    tag = "query",
+    params(
+        ("owner" = String, Path, description = "ANS-104 owner address (b64url sha256 of the owner key)"),
+        PageParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = TagQueryResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_owner_dataitems(
+    headers: HeaderMap,
+    Path(owner): Path<String>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+
+    if !is_arweave_id(&owner) {
+        return Err(api_error(StatusCode::BAD_REQUEST, "invalid owner address"));
+    }
+
+    let pagination = tag_query_pagination(params.first, params.after.as_deref())?;
+
+    match query_dataitems_by_owner(&tenant.name, &owner, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => Err(upstream_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failed to query owner dataitems",
+            &err,
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/by-hash/{sha256}",
+    tag = "query",
+    params(
+        ("sha256" = String, Path, description = "hex sha256 of the dataitem's data"),
+        PageParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = TagQueryResponse, description = "dataitems with that content, newest first"),
+        (status = 400, body = ErrorResponse),
+        (status = 451, body = ErrorResponse, description = "content blocked"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_hash_dataitems(
+    headers: HeaderMap,
+    Path(sha256): Path<String>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+
+    let sha256 = sha256.to_ascii_lowercase();
+    if sha256.len() != 64 || !sha256.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(api_error(StatusCode::BAD_REQUEST, "invalid sha256, expected 64 hex digits"));
+    }
+    blocklist::check_hash(&sha256).await.map_err(blocked_error)?;
+
+    let pagination = tag_query_pagination(params.first, params.after.as_deref())?;
+
+    match query_dataitems_by_sha256(&tenant.name, &sha256, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => Err(upstream_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failed to query dataitems by hash",
+            &err,
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/recent",
+    tag = "query",
+    params(RecentParams, ("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = TagQueryResponse, description = "newest indexed dataitems first"),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_recent_dataitems(
+    headers: HeaderMap,
+    Query(params): Query<RecentParams>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let limit = params.limit.map(|limit| limit.clamp(1, MAX_PAGE_SIZE));
+    let pagination = tag_query_pagination(limit, params.after.as_deref())?;
+
+    match query_recent_dataitems(&tenant.name, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => Err(upstream_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failed to query recent dataitems",
+            &err,
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/content-type/{type}/{subtype}/dataitems",
+    tag = "query",
+    params(
+        ("type" = String, Path, description = "media type, e.g. `video`"),
+        ("subtype" = String, Path, description = "media subtype, e.g. `mp4`, or `*` for any"),
+        PageParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = TagQueryResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_content_type_dataitems(
+    headers: HeaderMap,
+    Path(segments): Path<Vec<String>>,
+    Query(params): Query<PageParams>,
+) -> Result<Json<TagQueryResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+
+    // `video/mp4` as two path segments, or percent-encoded as one
+    let media_type = segments.join("/").to_ascii_lowercase();
+    if !is_media_type(&media_type) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("invalid media type {media_type:?}, expected type/subtype or type/*"),
+        ));
+    }
+
+    let pagination = tag_query_pagination(params.first, params.after.as_deref())?;
+
+    match query_dataitems_by_content_type(&tenant.name, &media_type, &pagination).await {
+        Ok(page) => Ok(Json(tag_query_response(page))),
+        Err(err) => Err(upstream_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "failed to query dataitems by content type",
+            &err,
+        )),
+    }
+}
+
+/// `type/subtype` of RFC 6838 token characters, the subtype possibly `*`.
+fn is_media_type(media_type: &str) -> bool {
+    let is_token = |part: &str| {
+        !part.is_empty()
+            && part.len() <= 127
+            && part.bytes().all(|b| b.is_ascii_alphanumeric() || b"!#$&^_.+-".contains(&b))
+    };
+    match media_type.split_once('/') {
+        Some((kind, subtype)) => is_token(kind) && (subtype == "*" || is_token(subtype)),
+        None => false,
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/exists",
+    tag = "dataitems",
+    request_body = ExistsRequest,
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = ExistsResponse, description = "presence of each ID, in request order"),
+        (status = 400, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "storage circuit breaker open")
+    )
+)]
+pub async fn handle_exists(
+    headers: HeaderMap,
+    Json(payload): Json<ExistsRequest>,
+) -> Result<Json<ExistsResponse>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+
+    if payload.ids.len() > MAX_EXISTS_IDS {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_EXISTS_IDS} ids can be checked at once"),
+        ));
+    }
+    if let Some(invalid) = payload.ids.iter().find(|id| !is_arweave_id(id)) {
+        return Err(api_error(StatusCode::BAD_REQUEST, format!("invalid dataitem id: {invalid}")));
+    }
+
+    let presence = dataitems_presence(&tenant, &payload.ids).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check dataitems", &e)
+    })?;
+    let items: Vec<DataitemPresence> = payload
+        .ids
+        .into_iter()
+        .zip(presence)
+        .map(|(id, (raw, ans104))| DataitemPresence { id, exists: raw || ans104, raw, ans104 })
+        .collect();
+    let present = items.iter().filter(|item| item.exists).count();
+    Ok(Json(ExistsResponse { missing: items.len() - present, present, items }))
+}
+
+/// URL of a dataitem's raw body: a token URL on this agent with `ACCESS_TOKEN_SECRET` set,
+/// otherwise a presigned storage URL (or the gateway's, once evicted).
+async fn raw_body_url(
+    public_url: Option<&str>,
+    tenant: &Tenant,
+    dataitem_id: &str,
+) -> Result<String, anyhow::Error> {
+    match public_url.and_then(|url| access_tokens::token_url(url, tenant, dataitem_id)) {
+        Some(url) => Ok(url),
+        None => get_dataitem_url(dataitem_id, tenant).await,
+    }
+}
+
+/// Resolve a dataitem of `POST /items/get` to its inline body or URL. `stored` tells whether its
+/// raw body is in the bucket and `indexed` holds its indexed size and content type.
+async fn fetch_item(
+    tenant: &Tenant,
+    public_url: Option<&str>,
+    id: String,
+    stored: bool,
+    indexed: Option<(u64, String)>,
+    inline_max_bytes: u64,
+) -> FetchedItem {
+    let (size, content_type) = indexed.unzip();
+    let mut item = FetchedItem {
+        id,
+        status: "ok".to_string(),
+        content_type,
+        size,
+        url: None,
+        data: None,
+        error: None,
+    };
+    let status = |mut item: FetchedItem, status: &str| {
+        item.status = status.to_string();
+        item
+    };
+    if blocklist::check_id(&item.id).await.is_err() {
+        return status(item, "blocked");
+    }
+    let content_checked = match blocklist::check_indexed_content(&tenant.name, &item.id).await {
+        Ok(checked) => checked,
+        Err(_) => return status(item, "blocked"),
+    };
+    if trash::check_not_deleted(tenant, &item.id).await.is_err() {
+        return status(item, "deleted");
+    }
+    if !stored {
+        // evicted dataitems posted to Arweave are fetched from the gateway, like `GET /{id}`
+        let gateway = match GatewayFallback::load() {
+            Some(gateway)
+                if is_posted_to_arweave(&tenant.name, &item.id).await.unwrap_or(false) =>
+            {
+                gateway
+            }
+            _ => return status(item, "not_found"),
+        };
+        hits::record_hit(&tenant.name, &item.id);
+        item.url = Some(gateway.dataitem_url(&item.id));
+        return item;
+    }
+
+    if size.is_some_and(|size| size <= inline_max_bytes) {
+        match cached_dataitem_raw(&item.id, tenant).await {
+            Ok(object) => {
+                if blocklist::check_content(&object.data).await.is_err() {
+                    return status(item, "blocked");
+                }
+                hits::record_hit(&tenant.name, &item.id);
+                item.content_type = object.content_type.or(item.content_type);
+                item.data = Some(general_purpose::STANDARD.encode(&object.data));
+                return item;
+            }
+            Err(err) => println!("ITEMS GET: {} not inlined: {err}", item.id),
+        }
+    }
+    // a URL hands out content of an unknown hash unchecked, read it first
+    if !content_checked {
+        let checked = match cached_dataitem_raw(&item.id, tenant).await {
+            Ok(object) => blocklist::check_content(&object.data).await,
+            Err(_) => Ok(()),
+        };
+        if checked.is_err() {
+            return status(item, "blocked");
+        }
+    }
+    match raw_body_url(public_url, tenant, &item.id).await {
+        Ok(url) => {
+            hits::record_hit(&tenant.name, &item.id);
+            item.url = Some(url);
+            item
+        }
+        Err(err) => {
+            item.error = Some(match err.is::<ChunkedObject>() {
+                true => format!("stored in chunks, fetch it from GET /{}", item.id),
+                false => err.to_string(),
+            });
+            status(item, "error")
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/items/get",
+    tag = "dataitems",
+    request_body = ItemsGetRequest,
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = ItemsGetResponse, description = "each dataitem's body or URL, in request order"),
+        (status = 400, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "SERVE_MODE is disabled"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "storage circuit breaker open")
+    )
+)]
+pub async fn handle_items_get(
+    headers: HeaderMap,
+    Json(payload): Json<ItemsGetRequest>,
+) -> Result<Json<ItemsGetResponse>, AgentError> {
+    if serve_mode() == ServeMode::Disabled {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "fetching dataitems requires SERVE_MODE=redirect or SERVE_MODE=proxy",
+        ));
+    }
+    let tenant = request_tenant(&headers)?;
+
+    if payload.ids.len() > MAX_ITEMS_GET_IDS {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("at most {MAX_ITEMS_GET_IDS} ids can be fetched at once"),
+        ));
+    }
+    if let Some(invalid) = payload.ids.iter().find(|id| !is_arweave_id(id)) {
+        return Err(api_error(StatusCode::BAD_REQUEST, format!("invalid dataitem id: {invalid}")));
+    }
+
+    let presence = dataitems_presence(&tenant, &payload.ids).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check dataitems", &e)
+    })?;
+    // without the index, sizes are unknown and every dataitem gets a URL
+    let mut sizes = match indexing_enabled() {
+        true => dataitem_sizes(&tenant.name, &payload.ids).await.unwrap_or_else(|err| {
+            println!("ITEMS GET: sizes unavailable, nothing is inlined: {err}");
+            Default::default()
+        }),
+        false => Default::default(),
+    };
+    let inline_max_bytes = items_inline_max_bytes();
+    let public_url = agent_public_url(&headers);
+
+    let (tenant, public_url) = (&tenant, public_url.as_deref());
+    let lookups: Vec<_> = payload
+        .ids
+        .into_iter()
+        .zip(presence)
+        .map(|(id, (raw, _))| {
+            let indexed = sizes.remove(&id);
+            (id, raw, indexed)
+        })
+        .collect();
+    let items = futures::stream::iter(lookups)
+        .map(|(id, stored, indexed)| {
+            fetch_item(tenant, public_url, id, stored, indexed, inline_max_bytes)
+        })
+        .buffered(ITEMS_GET_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(Json(ItemsGetResponse { items }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ServeParams,
+        ("if-none-match" = Option<String>, Header, description = "previously received ETag"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "raw dataitem body (SERVE_MODE=proxy, download=true, or up to SERVE_INLINE_MAX_BYTES)"),
+        (status = 302, description = "presigned URL of the raw body (SERVE_MODE=redirect)"),
+        (status = 304, description = "ETag matched If-None-Match"),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 403, body = ErrorResponse, description = "deprecated since v0.7.0 (default), or an invalid or expired access token"),
+        (status = 404, body = ErrorResponse),
+        (status = 410, body = ErrorResponse, description = "dataitem was deleted"),
+        (status = 504, body = ErrorResponse, description = "the Arweave gateway timed out")
+    )
+)]
+pub async fn serve_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+    Query(params): Query<ServeParams>,
+) -> Result<Response, AgentError> {
+    // a token minted by the agent names its tenant and stands in for a presigned URL
+    let token_tenant = params
+        .token
+        .as_deref()
+        .map(|token| access_tokens::verify(token, &dataitem_id))
+        .transpose()
+        .map_err(|e| api_error(StatusCode::FORBIDDEN, e.to_string()))?;
+    let mode = serve_mode();
+    if mode == ServeMode::Disabled && token_tenant.is_none() {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            format!(
+                "method deprecated since v0.7.0 - please access dataitem from 'https://gateway.s3-node-1.load.network/resolve/{dataitem_id}'"
+            ),
+        ));
+    }
+
+    let tenant = match token_tenant {
+        Some(tenant) => tenant,
+        None => request_tenant(&headers)?,
+    };
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    // content of an unknown hash is only served through the proxy, which checks it
+    let content_checked = blocklist::check_indexed_content(&tenant.name, &dataitem_id)
+        .await
+        .map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+    let etag = dataitem_etag(&tenant.name, &dataitem_id, None).await;
+
+    if etag_matches(&headers, &etag) {
+        hits::record_hit(&tenant.name, &dataitem_id);
+        let cache_control = match mode == ServeMode::Redirect && !access_tokens::enabled() {
+            true => redirect_cache_control(),
+            false => cache_control(),
+        };
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control)],
+        )
+            .into_response());
+    }
+
+    // a redirect can't name the download, so downloads are proxied in either mode, as are small
+    // bodies a redirect would only delay. Storage URLs stay hidden while access tokens are on.
+    let redirect = mode == ServeMode::Redirect
+        && content_checked
+        && params.token.is_none()
+        && !params.download
+        && !access_tokens::enabled();
+    match mode {
+        ServeMode::Redirect if redirect && !serve_inline(&tenant, &dataitem_id).await => {
+            match get_dataitem_url(&dataitem_id, &tenant).await {
+                Ok(url) => {
+                    hits::record_hit(&tenant.name, &dataitem_id);
+                    Ok((
+                        StatusCode::FOUND,
+                        [
+                            (header::LOCATION, url),
+                            (header::ETAG, etag),
+                            (header::CACHE_CONTROL, redirect_cache_control()),
+                        ],
+                    )
+                        .into_response())
+                }
+                // chunked bodies have no URL of their own
+                Err(e) if e.is::<ChunkedObject>() => {
+                    proxy_dataitem(&tenant, &dataitem_id, etag, false).await
+                }
+                Err(e) => Err(api_error(
+                    StatusCode::NOT_FOUND,
+                    format!("failed to resolve dataitem: {}", e),
+                )),
+            }
+        }
+        _ => proxy_dataitem(&tenant, &dataitem_id, etag, params.download).await,
+    }
+}
+
+async fn proxy_dataitem(
+    tenant: &Tenant,
+    dataitem_id: &str,
+    etag: String,
+    download: bool,
+) -> Result<Response, AgentError> {
+    let object = cached_dataitem_raw(dataitem_id, tenant)
+        .await
+        .map_err(|e| upstream_error(StatusCode::NOT_FOUND, "failed to fetch dataitem", &e))?;
+    blocklist::check_content(&object.data).await.map_err(blocked_error)?;
+    hits::record_hit(&tenant.name, dataitem_id);
+    let content_type =
+        object.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control()),
+        ],
+        object.data,
+    )
+        .into_response();
+    if download {
+        // without an indexed name the download is named after the dataitem
+        let file_name = dataitem_file_name(&tenant.name, dataitem_id).await.ok().flatten();
+        let disposition = content_disposition(file_name.as_deref().unwrap_or(dataitem_id));
+        if let Ok(value) = HeaderValue::from_str(&disposition) {
+            response.headers_mut().insert(header::CONTENT_DISPOSITION, value);
+        }
+    }
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/arns/{name}",
+    tag = "dataitems",
+    params(
+        ("name" = String, Path, description = "ArNS name or undername listed in ARNS_NAMES"),
+        ("if-none-match" = Option<String>, Header, description = "previously received ETag")
+    ),
+    responses(
+        (status = 200, description = "content of the transaction the name resolves to, with its ID in X-Arns-Resolved-Id"),
+        (status = 304, description = "ETag matched If-None-Match"),
+        (status = 404, body = ErrorResponse, description = "name not in ARNS_NAMES"),
+        (status = 451, body = ErrorResponse, description = "content blocked"),
+        (status = 502, body = ErrorResponse, description = "the resolver or gateway failed"),
+        (status = 504, body = ErrorResponse, description = "the resolver or gateway timed out")
+    )
+)]
+pub async fn handle_arns(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+) -> Result<Response, AgentError> {
+    let record = arns::resolve(&name)
+        .await
+        .map_err(|e| {
+            upstream_error(StatusCode::BAD_GATEWAY, "failed to resolve the ArNS name", &e)
+        })?
+        .ok_or_else(|| {
+            api_error(
+                StatusCode::NOT_FOUND,
+                format!("ArNS name {name} is not served by this agent"),
+            )
+        })?;
+    blocklist::check_id(&record.tx_id).await.map_err(blocked_error)?;
+    let etag = quoted_etag(&record.tx_id);
+    // the name may move, caches revalidate once its registry TTL is over
+    let cache_control = format!("public, max-age={}", record.ttl_secs);
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache_control),
+                (X_ARNS_RESOLVED_ID, record.tx_id),
+            ],
+        )
+            .into_response());
+    }
+
+    let object =
+        cached_gateway_raw(&arns::content_gateway(), &record.tx_id).await.map_err(|e| {
+            upstream_error(StatusCode::BAD_GATEWAY, "failed to fetch the ArNS content", &e)
+        })?;
+    blocklist::check_content(&object.data).await.map_err(blocked_error)?;
+    let content_type =
+        object.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control),
+            (X_ARNS_RESOLVED_ID, record.tx_id),
+        ],
+        object.data,
+    )
+        .into_response())
+}
+
+fn hls_error(context: &str, err: anyhow::Error) -> AgentError {
+    if err.is::<NotAVideo>() {
+        api_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, err.to_string())
+    } else if err.is::<RemuxFailed>() {
+        api_error(StatusCode::UNPROCESSABLE_ENTITY, err.to_string())
+    } else if err.is::<SegmentNotFound>() {
+        api_error(StatusCode::NOT_FOUND, err.to_string())
+    } else {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, context, &err)
+    }
+}
+
+/// HLS and renders are served by the agent like proxied dataitems, unavailable while
+/// `GET /{id}` is disabled.
+fn require_serving(feature: &str) -> Result<(), AgentError> {
+    match serve_mode() {
+        ServeMode::Disabled => Err(api_error(
+            StatusCode::FORBIDDEN,
+            format!("{feature} requires SERVE_MODE=redirect or SERVE_MODE=proxy"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/hls",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "video dataitem id"),
+        ("if-none-match" = Option<String>, Header, description = "previously received ETag"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "HLS playlist, its segments are under /{id}/hls/"),
+        (status = 202, body = JobAccepted, description = "the playlist is being generated, retry after Retry-After"),
+        (status = 304, description = "ETag matched If-None-Match"),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 403, body = ErrorResponse, description = "SERVE_MODE is disabled"),
+        (status = 404, body = ErrorResponse),
+        (status = 410, body = ErrorResponse, description = "dataitem was deleted"),
+        (status = 415, body = ErrorResponse, description = "dataitem is not a video"),
+        (status = 422, body = ErrorResponse, description = "video could not be remuxed"),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled, remux jobs need ClickHouse")
+    )
+)]
+pub async fn handle_hls_playlist(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Response, AgentError> {
+    require_serving("HLS streaming")?;
+    let tenant = request_tenant(&headers)?;
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    blocklist::check_indexed_content(&tenant.name, &dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+    let etag = dataitem_etag(&tenant.name, &dataitem_id, Some("m3u8")).await;
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control())],
+        )
+            .into_response());
+    }
+    let playlist = hls::playlist(&tenant, &dataitem_id)
+        .await
+        .map_err(|e| hls_error("failed to prepare the HLS playlist", e))?;
+    match playlist {
+        HlsPlaylist::Ready(playlist) => Ok((
+            [
+                (header::CONTENT_TYPE, hls::PLAYLIST_CONTENT_TYPE.to_string()),
+                (header::ETAG, etag),
+                (header::CACHE_CONTROL, cache_control()),
+            ],
+            playlist,
+        )
+            .into_response()),
+        HlsPlaylist::Pending { job_id } => Ok((
+            StatusCode::ACCEPTED,
+            [(header::RETRY_AFTER, HLS_RETRY_AFTER_SECS.to_string())],
+            Json(JobAccepted {
+                success: true,
+                message: "the HLS playlist is being generated, retry shortly".to_string(),
+                job_id,
+            }),
+        )
+            .into_response()),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/hls/{segment}",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "video dataitem id"),
+        ("segment" = String, Path, description = "segment name listed in the playlist"),
+        ("if-none-match" = Option<String>, Header, description = "previously received ETag"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "MPEG-TS segment"),
+        (status = 304, description = "ETag matched If-None-Match"),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 403, body = ErrorResponse, description = "SERVE_MODE is disabled"),
+        (status = 404, body = ErrorResponse),
+        (status = 410, body = ErrorResponse, description = "dataitem was deleted")
+    )
+)]
+pub async fn handle_hls_segment(
+    headers: HeaderMap,
+    Path((dataitem_id, segment)): Path<(String, String)>,
+) -> Result<Response, AgentError> {
+    if !is_arweave_id(&dataitem_id) {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            format!("invalid dataitem id {dataitem_id:?}, expected 43 base64url characters"),
+        ));
+    }
+    require_serving("HLS streaming")?;
+    let tenant = request_tenant(&headers)?;
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    blocklist::check_indexed_content(&tenant.name, &dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+    if !hls::is_segment_name(&segment) {
+        return Err(hls_error("failed to fetch the HLS segment", SegmentNotFound(segment).into()));
+    }
+    let etag = dataitem_etag(&tenant.name, &dataitem_id, Some(&segment)).await;
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control())],
+        )
+            .into_response());
+    }
+    let data = hls::segment(&tenant, &dataitem_id, &segment)
+        .await
+        .map_err(|e| hls_error("failed to fetch the HLS segment", e))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, hls::SEGMENT_CONTENT_TYPE.to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control()),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/proof",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = MerkleProof),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 404, body = ErrorResponse, description = "the dataitem is not anchored yet"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    )
+)]
+pub async fn handle_dataitem_proof(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<MerkleProof>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let proof = merkle::proof(&tenant, &dataitem_id).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to build the proof", &e)
+    })?;
+    proof.map(Json).ok_or_else(|| {
+        api_error(StatusCode::NOT_FOUND, format!("{dataitem_id} is not in a Merkle batch yet"))
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/receipt",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = UploadReceipt),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 404, body = ErrorResponse, description = "no receipt was issued for the dataitem"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    )
+)]
+pub async fn handle_dataitem_receipt(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<UploadReceipt>, AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let receipt = receipts::load(&tenant, &dataitem_id).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the receipt", &e)
+    })?;
+    receipt
+        .map(Json)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("no receipt for {dataitem_id}")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/stats",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = DataitemStats),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_dataitem_stats(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<DataitemStats>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    let flushed = dataitem_hits(&tenant.name, &dataitem_id).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the dataitem stats", &e)
+    })?;
+    let flushed = flushed.map(|flushed| (flushed.hits, flushed.last_access));
+    let pending = hits::pending_hits(&tenant.name, &dataitem_id)
+        .map(|pending| (pending.hits, pending.last_access));
+    // hits served by this agent since its last flush aren't in ClickHouse yet
+    let (hits, last_access) = match (flushed, pending) {
+        (Some((hits, last)), Some((pending, pending_last))) => {
+            (hits + pending, Some(last.max(pending_last)))
+        }
+        (Some((hits, last)), None) | (None, Some((hits, last))) => (hits, Some(last)),
+        (None, None) => (0, None),
+    };
+    Ok(Json(DataitemStats {
+        dataitem_id,
+        hits,
+        last_access: last_access.map(|at| at.to_rfc3339()),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/render",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "image dataitem id"),
+        RenderParams,
+        ("if-none-match" = Option<String>, Header, description = "previously received ETag"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "resized and transcoded image"),
+        (status = 304, description = "ETag matched If-None-Match"),
+        (status = 400, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "SERVE_MODE is disabled"),
+        (status = 404, body = ErrorResponse),
+        (status = 410, body = ErrorResponse, description = "dataitem was deleted"),
+        (status = 415, body = ErrorResponse, description = "dataitem is not an image"),
+        (status = 422, body = ErrorResponse, description = "image could not be decoded")
+    )
+)]
+pub async fn handle_render_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+    Query(params): Query<RenderParams>,
+) -> Result<Response, AgentError> {
+    require_serving("rendering images")?;
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    for dimension in [params.w, params.h].into_iter().flatten() {
+        if dimension == 0 || dimension > MAX_RENDER_DIMENSION {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                format!("w and h must be between 1 and {MAX_RENDER_DIMENSION}"),
+            ));
+        }
+    }
+    let format = match params.format.as_deref() {
+        Some(format) => RenderFormat::parse(format)
+            .map_err(|err| api_error(StatusCode::BAD_REQUEST, err.to_string()))?,
+        None => RenderFormat::Webp,
+    };
+    let options = RenderOptions { width: params.w, height: params.h, format };
+
+    let tenant = request_tenant(&headers)?;
+    blocklist::check_indexed_content(&tenant.name, &dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+    let variant = format!(
+        "{}x{}.{}",
+        options.width.unwrap_or(0),
+        options.height.unwrap_or(0),
+        format.extension()
+    );
+    let etag = dataitem_etag(&tenant.name, &dataitem_id, Some(&variant)).await;
+    if etag_matches(&headers, &etag) {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, cache_control())],
+        )
+            .into_response());
+    }
+
+    let rendered = render_dataitem(&dataitem_id, &tenant, options).await.map_err(|err| {
+        if err.is::<NotAnImage>() {
+            api_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, err.to_string())
+        } else if err.is::<image::ImageError>() {
+            api_error(StatusCode::UNPROCESSABLE_ENTITY, format!("failed to render image: {err}"))
+        } else {
+            api_error(StatusCode::NOT_FOUND, format!("failed to fetch dataitem: {err}"))
+        }
+    })?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::ETAG, etag),
+            (header::CACHE_CONTROL, cache_control()),
+        ],
+        rendered,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload",
+    tag = "dataitems",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    params(
+        ("signed" = Option<bool>, Header, description = "file is an already signed ANS-104 dataitem"),
+        ("x-upload-id" = Option<String>, Header, description = "client chosen ID to follow the upload on /upload/{upload_id}/progress"),
+        ("Idempotency-Key" = Option<String>, Header, description = "repeated uploads with the same key get the original response"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace"),
+        UploadOptions
+    ),
+    responses(
+        (status = 200, body = UploadResponse),
+        (status = 202, body = JobAccepted, description = "async=true: stored in the background, see /jobs/{id}"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "signed dataitem already stored, upload ID or Idempotency-Key in use"),
+        (status = 413, body = ErrorResponse),
+        (status = 415, body = ErrorResponse, description = "content type rejected by the upload policy"),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan, or Idempotency-Key used for another request"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "a dependency is failing fast"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out"),
+        (status = 503, body = ErrorResponse, description = "malware scanner unavailable")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn upload_file(
+    headers: HeaderMap,
+    Query(options): Query<UploadOptions>,
+    multipart: Multipart,
+) -> Result<Response, AgentError> {
+    let is_signed = is_signed_upload(&headers);
+    accept_upload(&headers, "/upload", options, prepare_upload(multipart, is_signed)).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/raw",
+    tag = "dataitems",
+    request_body(content = Vec<u8>, description = "the file bytes, typed by the `Content-Type` header", content_type = "application/octet-stream"),
+    params(
+        ("x-tags" = Option<String>, Header, description = "base64 of a JSON array of `{\"key\": ..., \"value\": ...}` tags"),
+        ("x-file-name" = Option<String>, Header, description = "original file name, added as the `File-Name` tag"),
+        ("signed" = Option<bool>, Header, description = "body is an already signed ANS-104 dataitem"),
+        ("x-upload-id" = Option<String>, Header, description = "client chosen ID to follow the upload on /upload/{upload_id}/progress"),
+        ("Idempotency-Key" = Option<String>, Header, description = "repeated uploads with the same key get the original response"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace"),
+        UploadOptions
+    ),
+    responses(
+        (status = 200, body = UploadResponse),
+        (status = 202, body = JobAccepted, description = "async=true: stored in the background, see /jobs/{id}"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "signed dataitem already stored, upload ID or Idempotency-Key in use"),
+        (status = 413, body = ErrorResponse),
+        (status = 415, body = ErrorResponse, description = "content type rejected by the upload policy"),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan, or Idempotency-Key used for another request"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "a dependency is failing fast"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out"),
+        (status = 503, body = ErrorResponse, description = "malware scanner unavailable")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn upload_raw_file(
+    headers: HeaderMap,
+    Query(options): Query<UploadOptions>,
+    body: Body,
+) -> Result<Response, AgentError> {
+    let is_signed = is_signed_upload(&headers);
+    let content_type =
+        headers.get(header::CONTENT_TYPE).and_then(|h| h.to_str().ok()).map(String::from);
+    let tags = headers.get("x-tags").map(|h| h.as_bytes().to_vec());
+    let file_name = headers.get("x-file-name").map(|h| h.to_str().map(String::from));
+    let prepare = async move {
+        let mut extra_tags = match tags {
+            Some(encoded) => decode_tags_header(&encoded)?,
+            None => Vec::new(),
+        };
+        if let Some(file_name) = file_name {
+            let file_name = file_name
+                .map_err(|_| api_error(StatusCode::BAD_REQUEST, "invalid x-file-name header"))?;
+            extra_tags.insert(0, upload_file_name_tag(&file_name)?);
+        }
+        // read chunk by chunk so tracked uploads report the bytes received so far
+        let mut stream = body.into_data_stream();
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(|err| match exceeds_body_limit(&err) {
+                true => {
+                    api_error(StatusCode::PAYLOAD_TOO_LARGE, "request body exceeds the size limit")
+                }
+                false => api_error(StatusCode::BAD_REQUEST, "failed to read request body"),
+            })?;
+            progress::add_received(chunk.len());
+            data.extend_from_slice(&chunk);
+        }
+        validate_upload(data, content_type.as_deref(), extra_tags, is_signed, "/upload/raw").await
+    };
+    accept_upload(&headers, "/upload/raw", options, prepare).await
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/from-url",
+    tag = "dataitems",
+    request_body = UploadFromUrlRequest,
+    params(
+        ("signed" = Option<bool>, Header, description = "source is an already signed ANS-104 dataitem"),
+        ("x-upload-id" = Option<String>, Header, description = "client chosen ID to follow the upload on /upload/{upload_id}/progress"),
+        ("Idempotency-Key" = Option<String>, Header, description = "repeated uploads with the same key get the original response"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace"),
+        UploadOptions
+    ),
+    responses(
+        (status = 200, body = UploadResponse),
+        (status = 202, body = JobAccepted, description = "async=true: stored in the background, see /jobs/{id}"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "source URL not allowed"),
+        (status = 409, body = ErrorResponse, description = "signed dataitem already stored, upload ID or Idempotency-Key in use"),
+        (status = 413, body = ErrorResponse),
+        (status = 415, body = ErrorResponse, description = "content type rejected by the upload policy"),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan, or Idempotency-Key used for another request"),
+        (status = 502, body = ErrorResponse, description = "the source could not be downloaded"),
+        (status = 503, body = ErrorResponse, description = "upload memory budget exhausted, see Retry-After"),
+        (status = 504, body = ErrorResponse, description = "the download timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn upload_from_url(
+    headers: HeaderMap,
+    Query(options): Query<UploadOptions>,
+    Json(request): Json<UploadFromUrlRequest>,
+) -> Result<Response, AgentError> {
+    let is_signed = is_signed_upload(&headers);
+    let prepare = async move {
+        let url = Url::parse(&request.url)
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("invalid source URL: {e}")))?;
+        let mut extra_tags = request.tags;
+        if let Some(file_name) = request.filename {
+            extra_tags.insert(0, upload_file_name_tag(&file_name)?);
+        }
+        let fetched = fetch_url(url, OBJECT_SIZE_LIMIT).await.map_err(fetch_error)?;
+        let content_type = request.content_type.or(fetched.content_type);
+        validate_upload(
+            fetched.data,
+            content_type.as_deref(),
+            extra_tags,
+            is_signed,
+            "/upload/from-url",
+        )
+        .await
+    };
+    // the request is a small JSON, the download counts against the memory budget instead
+    let fetch_limit = OBJECT_SIZE_LIMIT as u64;
+    let Some(reservation) = concurrency::reserve_upload_bytes(fetch_limit) else {
+        return Ok(memory_budget_exceeded(fetch_limit));
+    };
+    concurrency::holding(reservation, accept_upload(&headers, "/upload/from-url", options, prepare))
+        .await
+}
+
+fn fetch_error(err: anyhow::Error) -> AgentError {
+    if err.is::<UrlNotAllowed>() {
+        api_error(StatusCode::FORBIDDEN, err.to_string())
+    } else if err.is::<SourceTooLarge>() {
+        api_error(StatusCode::PAYLOAD_TOO_LARGE, err.to_string())
+    } else {
+        upstream_error(StatusCode::BAD_GATEWAY, "failed to download the source", &err)
+    }
+}
+
+/// Tags of `/upload/raw`, the base64 encoded JSON array of the multipart `tags` field.
+fn decode_tags_header(encoded: &[u8]) -> Result<Vec<UploadTag>, AgentError> {
+    let invalid = || {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "invalid x-tags header, expected base64 of a JSON array of objects with key/value",
+        )
+    };
+    let json = general_purpose::STANDARD.decode(encoded).map_err(|_| invalid())?;
+    serde_json::from_slice(&json).map_err(|_| invalid())
+}
+
+/// The `File-Name` tag of an upload's `filename`, ahead of the custom tags so it wins over a
+/// `File-Name` among them.
+fn upload_file_name_tag(file_name: &str) -> Result<UploadTag, AgentError> {
+    file_name_tag(file_name).map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+fn is_signed_upload(headers: &HeaderMap) -> bool {
+    headers.get("signed").and_then(|h| h.to_str().ok()).map(|s| s == "true").unwrap_or(false)
+}
+
+/// Authenticate an upload, then receive it with `prepare` and store it, in the background with
+/// `async=true`. Repeated uploads with the same `Idempotency-Key` get the original response.
+async fn accept_upload(
+    headers: &HeaderMap,
+    route: &str,
+    options: UploadOptions,
+    prepare: impl Future<Output = Result<PreparedUpload, AgentError>>,
+) -> Result<Response, AgentError> {
+    require_api_key(headers).await?;
+    if options.dry_run {
+        return preview_upload(headers, prepare)
+            .await
+            .map(|response| Json(response).into_response());
+    }
+    let tenant = request_tenant(headers)?;
+    let signed = is_signed_upload(headers).to_string();
+    let run_async = options.run_async.to_string();
+    let idempotency =
+        IdempotencyKey::from_headers(headers, route, &[&tenant.name, &signed, &run_async])?;
+    let Some(idempotency) = idempotency else {
+        return receive_upload(headers, options, prepare).await;
+    };
+
+    // the upload is part of the fingerprint, a saved response is replayed once the repeated
+    // upload is received and matches it
+    if let Some(saved) = idempotency::saved(&idempotency).await? {
+        let upload = prepare.await?;
+        return idempotency.including(&upload.fingerprint()).replay(saved);
+    }
+    let mut fingerprint = String::new();
+    let prepare = async {
+        let upload = prepare.await?;
+        fingerprint = upload.fingerprint();
+        Ok::<_, AgentError>(upload)
+    };
+    let response = receive_upload(headers, options, prepare).await?;
+    idempotency.including(&fingerprint).save(response).await
+}
+
+async fn receive_upload(
+    headers: &HeaderMap,
+    options: UploadOptions,
+    prepare: impl Future<Output = Result<PreparedUpload, AgentError>>,
+) -> Result<Response, AgentError> {
+    let tenant = request_tenant(headers)?;
+    let prepare = prepare_charged(headers, prepare);
+
+    let upload_id = match headers.get("x-upload-id").and_then(|h| h.to_str().ok()) {
+        Some(upload_id) => {
+            validate_upload_id(upload_id)
+                .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+            Some(upload_id.to_string())
+        }
+        None if options.run_async => Some(new_job_id()),
+        None => None,
+    };
+    let public_url = agent_public_url(headers);
+    let default_tags = default_tags(bearer_token(headers))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let Some(upload_id) = upload_id else {
+        let (upload, debit) = prepare.await?;
+        let stored = store_upload(upload, debit, &tenant, public_url, &default_tags).await?;
+        return Ok(Json(stored).into_response());
+    };
+
+    let bytes_expected = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    let tracker = progress::start(&upload_id, &tenant.name, bytes_expected)
+        .map_err(|e| api_error(StatusCode::CONFLICT, e.to_string()))?;
+    let abort = AbortOnDrop(tracker.clone());
+
+    let (upload, debit) = match progress::track(tracker.clone(), prepare).await {
+        Ok(prepared) => prepared,
+        Err(err) => {
+            tracker.fail(err.message());
+            return Err(err);
+        }
+    };
+
+    if !options.run_async {
+        let result = progress::track(
+            tracker.clone(),
+            store_upload(upload, debit, &tenant, public_url, &default_tags),
+        )
+        .await;
+        match &result {
+            Ok(response) => tracker.finish(&response.dataitem_id),
+            Err(err) => tracker.fail(err.message()),
+        }
+        return result.map(|response| Json(response).into_response());
+    }
+
+    // signing, storage and indexing run in the background, the body is already buffered
+    progress::set_queued(&tracker);
+    let origin = provenance::current();
+    let reservation = concurrency::held();
+    tokio::spawn(progress::track(tracker.clone(), async move {
+        let _abort = abort;
+        // the buffered body counts against the memory budget until it is stored
+        let _reservation = reservation;
+        // accepted before maintenance started, stored once it ends
+        maintenance::wait_until_off().await;
+        let _permit = progress::job_permit().await;
+        let store = store_upload(upload, debit, &tenant, public_url, &default_tags);
+        match provenance::scope(origin, store).await {
+            Ok(response) => tracker.finish(&response.dataitem_id),
+            Err(err) => tracker.fail(err.message()),
+        }
+    }));
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(JobAccepted {
+            success: true,
+            message: format!("upload accepted, poll /jobs/{upload_id} for the result"),
+            job_id: upload_id,
+        }),
+    )
+        .into_response())
+}
+
+/// Receive an upload with `prepare`, then debit it from the caller's credits.
+async fn prepare_charged(
+    headers: &HeaderMap,
+    prepare: impl Future<Output = Result<PreparedUpload, AgentError>>,
+) -> Result<(PreparedUpload, Option<Debit>), AgentError> {
+    let upload = prepare.await?;
+    let debit = charge_upload(headers, upload.data.len()).await?;
+    Ok((upload, debit))
+}
+
+async fn charge_upload(headers: &HeaderMap, bytes: usize) -> Result<Option<Debit>, AgentError> {
+    credits::debit(&CreditsConfig::load(), bearer_token(headers), bytes).await.map_err(|e| {
+        match e.is::<InsufficientCredits>() {
+            true => api_error(StatusCode::PAYMENT_REQUIRED, e.to_string()),
+            false => upstream_error(StatusCode::BAD_GATEWAY, "failed to debit upload credits", &e),
+        }
+    })
+}
+
+/// A received and validated upload, ready to be signed and stored.
+struct PreparedUpload {
+    data: Vec<u8>,
+    content_type: String,
+    extra_tags: Vec<UploadTag>,
+    is_signed: bool,
+}
+
+impl PreparedUpload {
+    /// Tells repeated uploads with the same `Idempotency-Key` apart by content and tags.
+    fn fingerprint(&self) -> String {
+        let tags = serde_json::to_string(&self.extra_tags).unwrap_or_default();
+        format!("{}\n{}\n{tags}", sha256_hex(&self.data), self.content_type)
+    }
+}
+
+/// Read the multipart form and run the content type policy and malware scan.
+async fn prepare_upload(
+    mut multipart: Multipart,
+    is_signed: bool,
+) -> Result<PreparedUpload, AgentError> {
+    let mut file_data: Option<Vec<u8>> = None;
+    let mut content_type: Option<String> = None;
+    let mut extra_tags: Vec<UploadTag> = Vec::new();
+    let mut file_name: Option<String> = None;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        let field_name = field.name().unwrap_or("");
+
+        match field_name {
+            "file" => {
+                content_type = field.content_type().map(|ct| ct.to_string());
+                // read chunk by chunk so tracked uploads report the bytes received so far
+                let mut data = Vec::new();
+                while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+                    progress::add_received(chunk.len());
+                    data.extend_from_slice(&chunk);
+                }
+                file_data = Some(data);
+            }
+            "content_type" if content_type.is_none() => {
+                content_type = Some(field.text().await.map_err(|_| {
+                    api_error(StatusCode::BAD_REQUEST, "failed to read content type")
+                })?);
+            }
+            "tags" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|_| api_error(StatusCode::BAD_REQUEST, "failed to read tags field"))?;
+
+                let parsed: Vec<UploadTag> = serde_json::from_str(&text).map_err(|_| {
+                    api_error(
+                        StatusCode::BAD_REQUEST,
+                        "invalid tags payload, expected JSON array of objects with key/value",
+                    )
+                })?;
+
+                extra_tags.extend(parsed);
+            }
+            "filename" => {
+                file_name = Some(field.text().await.map_err(|_| {
+                    api_error(StatusCode::BAD_REQUEST, "failed to read filename field")
+                })?);
+            }
+            "tag" => {
+                let text = field
+                    .text()
+                    .await
+                    .map_err(|_| api_error(StatusCode::BAD_REQUEST, "failed to read tag field"))?;
+                let tag = parse_tag_pair(&text)
+                    .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+                extra_tags.push(tag);
+            }
+            "tags.json.gz" => {
+                let gzipped = field.bytes().await.map_err(|_| {
+                    api_error(StatusCode::BAD_REQUEST, "failed to read tags.json.gz part")
+                })?;
+                let parsed = decode_gzip_tags(&gzipped)
+                    .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+                extra_tags.extend(parsed);
+            }
+            _ => {
+                // skip
+            }
+        }
+    }
+
+    let file_bytes =
+        file_data.ok_or_else(|| api_error(StatusCode::BAD_REQUEST, "no file data provided"))?;
+    if let Some(file_name) = file_name {
+        extra_tags.insert(0, upload_file_name_tag(&file_name)?);
+    }
+    validate_upload(file_bytes, content_type.as_deref(), extra_tags, is_signed, "/upload").await
+}
+
+/// Run the size limit, content type policy and malware scan on a received upload.
+async fn validate_upload(
+    file_bytes: Vec<u8>,
+    content_type: Option<&str>,
+    extra_tags: Vec<UploadTag>,
+    is_signed: bool,
+    route: &str,
+) -> Result<PreparedUpload, AgentError> {
+    if file_bytes.len() > OBJECT_SIZE_LIMIT {
+        return Err(object_too_large());
+    }
+
+    let content_type_str = resolve_content_type(content_type, &file_bytes);
+
+    let policy_check = if is_signed {
+        let (dataitem, signed_content_type) = reconstruct_dataitem_data(file_bytes.clone())
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("invalid dataitem: {e}")))?;
+        check_content_type_policy(&signed_content_type, &dataitem.data)
+    } else {
+        check_content_type_policy(&content_type_str, &file_bytes)
+    };
+    policy_check.map_err(|e| api_error(StatusCode::UNSUPPORTED_MEDIA_TYPE, e.to_string()))?;
+    scan_upload(route, &file_bytes).await?;
+
+    if is_signed && !extra_tags.is_empty() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "custom tags are not supported when uploading signed dataitems, the dataitems tags will be extracted and appied instead",
+        ));
+    }
+    check_tag_bytes(&extra_tags).map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    Ok(PreparedUpload { data: file_bytes, content_type: content_type_str, extra_tags, is_signed })
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/stage",
+    tag = "dataitems",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    params(
+        ("signed" = Option<bool>, Header, description = "file is an already signed ANS-104 dataitem"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = StageResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 413, body = ErrorResponse),
+        (status = 415, body = ErrorResponse, description = "content type rejected by the upload policy"),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "malware scanner unavailable")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_stage_upload(
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Json<StageResponse>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    let upload = prepare_upload(multipart, is_signed_upload(&headers)).await?;
+    let (staging_id, expires_at) = staging::stage(
+        &tenant,
+        upload.data,
+        &upload.content_type,
+        upload.extra_tags,
+        upload.is_signed,
+    )
+    .await
+    .map_err(|e| upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to stage upload", &e))?;
+    Ok(Json(StageResponse {
+        success: true,
+        message: format!("upload staged, publish it with POST /upload/commit/{staging_id}"),
+        staging_id,
+        expires_at: Some(expires_at.to_rfc3339()),
+    }))
+}
+
+fn staging_error(context: &str, err: anyhow::Error) -> AgentError {
+    match err.is::<StagingNotFound>() {
+        true => api_error(StatusCode::NOT_FOUND, err.to_string()),
+        false => upstream_error(StatusCode::INTERNAL_SERVER_ERROR, context, &err),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/commit/{staging_id}",
+    tag = "dataitems",
+    params(
+        ("staging_id" = String, Path, description = "staging ID returned by /upload/stage"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = UploadResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "unknown or expired staging ID, or staged with another key"),
+        (status = 409, body = ErrorResponse, description = "signed dataitem already stored"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "upload memory budget exhausted, see Retry-After")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_commit_upload(
+    headers: HeaderMap,
+    Path(staging_id): Path<String>,
+) -> Result<Response, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    // the commit request has no body, the staged one counts against the memory budget instead
+    let staged_bytes = staging::staged_size(&tenant, &staging_id)
+        .await
+        .map_err(|e| staging_error("failed to load staged upload", e))?;
+    let Some(_reservation) = concurrency::reserve_upload_bytes(staged_bytes) else {
+        return Ok(memory_budget_exceeded(staged_bytes));
+    };
+    let staged = staging::load(&tenant, &staging_id)
+        .await
+        .map_err(|e| staging_error("failed to load staged upload", e))?;
+    let default_tags = default_tags(bearer_token(&headers))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let debit = charge_upload(&headers, staged.data.len()).await?;
+    let upload = PreparedUpload {
+        data: staged.data,
+        content_type: staged.content_type,
+        extra_tags: staged.tags,
+        is_signed: staged.is_signed,
+    };
+    let public_url = agent_public_url(&headers);
+    let response = store_upload(upload, debit, &tenant, public_url, &default_tags).await?;
+    // published, a staged copy left behind is removed by the GC once expired
+    if let Err(err) = staging::discard(&tenant, &staging_id).await {
+        println!("STAGING: failed to remove committed {staging_id}: {err}");
+    }
+    Ok(Json(response).into_response())
+}
+
+#[utoipa::path(
+    delete,
+    path = "/upload/stage/{staging_id}",
+    tag = "dataitems",
+    params(
+        ("staging_id" = String, Path, description = "staging ID returned by /upload/stage"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = StageResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "unknown or expired staging ID, or staged with another key"),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_discard_upload(
+    headers: HeaderMap,
+    Path(staging_id): Path<String>,
+) -> Result<Json<StageResponse>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    staging::discard(&tenant, &staging_id)
+        .await
+        .map_err(|e| staging_error("failed to discard staged upload", e))?;
+    Ok(Json(StageResponse {
+        success: true,
+        staging_id,
+        expires_at: None,
+        message: "staged upload discarded".to_string(),
+    }))
+}
+
+/// Sign and store a prepared upload, refunding its `debit` when that fails.
+async fn store_upload(
+    upload: PreparedUpload,
+    debit: Option<Debit>,
+    tenant: &Tenant,
+    public_url: Option<String>,
+    default_tags: &[(String, String)],
+) -> Result<UploadResponse, AgentError> {
+    let extra_tag_pairs: Vec<(String, String)> =
+        upload.extra_tags.iter().map(|tag| (tag.key.clone(), tag.value.clone())).collect();
+
+    let result = if upload.is_signed {
+        store_signed_dataitem(upload.data, tenant).await
+    } else {
+        store_dataitem(upload.data, &upload.content_type, &extra_tag_pairs, default_tags, tenant)
+            .await
+    };
+    match &result {
+        Ok(_) => credits::settle(debit),
+        Err(_) => credits::refund(debit).await,
+    }
+
+    match result {
+        Ok(stored) => {
+            // the upload succeeded, an unsigned link is no reason to fail it
+            let token_url = public_url
+                .as_deref()
+                .and_then(|url| access_tokens::token_url(url, tenant, &stored.id));
+            let raw_presigned_url = match token_url {
+                Some(url) => Some(url),
+                None => presign_raw(&stored.id, tenant).await.ok(),
+            };
+            let receipt = receipts::issue(tenant, &stored.id, &stored.digest).await;
+            Ok(UploadResponse {
+                success: true,
+                custom_tags: upload.extra_tags,
+                tags: stored
+                    .tags
+                    .into_iter()
+                    .map(|(key, value)| UploadTag { key, value })
+                    .collect(),
+                agent_url: public_url.map(|url| format!("{url}/{}", stored.id)),
+                arweave_url: format!("{}/{}", arweave_gateway_url(), stored.id),
+                raw_presigned_url,
+                receipt,
+                dataitem_id: stored.id,
+                message: "file uploaded successfully".to_string(),
+            })
+        }
+        Err(e) => Err(store_error(e)),
+    }
+}
+
+fn store_error(err: anyhow::Error) -> AgentError {
+    if err.is::<DataitemExists>() {
+        api_error(StatusCode::CONFLICT, err.to_string())
+    } else if err.is::<Blocked>() {
+        api_error(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, err.to_string())
+    } else if err.is::<UploadAborted>() {
+        api_error(StatusCode::REQUEST_TIMEOUT, err.to_string())
+    } else {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to store file", &err)
+    }
+}
+
+/// `dry_run=true`: receive, validate and sign an upload like `store_upload` would, answering with
+/// its dataitem ID and tags without storing, indexing or charging it.
+async fn preview_upload(
+    headers: &HeaderMap,
+    prepare: impl Future<Output = Result<PreparedUpload, AgentError>>,
+) -> Result<UploadResponse, AgentError> {
+    let tenant = request_tenant(headers)?;
+    let default_tags = default_tags(bearer_token(headers))
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))?;
+    let upload = prepare.await?;
+    let extra_tag_pairs: Vec<(String, String)> =
+        upload.extra_tags.iter().map(|tag| (tag.key.clone(), tag.value.clone())).collect();
+
+    let preview = if upload.is_signed {
+        preview_signed_dataitem(upload.data, &tenant).await
+    } else {
+        preview_dataitem(upload.data, &upload.content_type, &extra_tag_pairs, &default_tags).await
+    }
+    .map_err(store_error)?;
+
+    Ok(UploadResponse {
+        success: true,
+        custom_tags: upload.extra_tags,
+        tags: preview.tags.into_iter().map(|(key, value)| UploadTag { key, value }).collect(),
+        agent_url: None,
+        arweave_url: format!("{}/{}", arweave_gateway_url(), preview.id),
+        raw_presigned_url: None,
+        receipt: None,
+        dataitem_id: preview.id,
+        message: "dry run, nothing was stored".to_string(),
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/upload/{upload_id}/progress",
+    tag = "dataitems",
+    params(
+        ("upload_id" = String, Path, description = "x-upload-id sent with the upload"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = UploadProgress),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "unknown or expired upload ID")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_upload_progress(
+    headers: HeaderMap,
+    Path(upload_id): Path<String>,
+) -> Result<Json<UploadProgress>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    upload_progress(&tenant.name, &upload_id)
+        .map(Json)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("no upload with id {upload_id}")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "job ID returned by POST /upload?async=true"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = UploadJob),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "unknown or expired job ID")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_upload_job(
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<UploadJob>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    upload_job(&tenant.name, &job_id)
+        .map(Json)
+        .ok_or_else(|| api_error(StatusCode::NOT_FOUND, format!("no job with id {job_id}")))
+}
+
+#[utoipa::path(
+    post,
+    path = "/upload/private",
+    tag = "dataitems",
+    request_body(content = UploadForm, content_type = "multipart/form-data"),
+    params(
+        ("x-bucket-name" = String, Header, description = "private LCP bucket name"),
+        ("x-dataitem-name" = Option<String>, Header, description = "registry name of the dataitem"),
+        ("x-folder-name" = Option<String>, Header, description = "folder (key prefix) inside the bucket"),
+        ("signed" = Option<bool>, Header, description = "file is an already signed ANS-104 dataitem")
+    ),
+    responses(
+        (status = 200, body = PrivateUploadResponse),
+        (status = 400, body = ErrorResponse, description = "missing or invalid bucket name"),
+        (status = 401, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "bucket not in PRIVATE_BUCKET_ALLOWLIST"),
+        (status = 413, body = ErrorResponse),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "a dependency is failing fast"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out"),
+        (status = 503, body = ErrorResponse, description = "malware scanner unavailable")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_private_file(
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Result<Json<PrivateUploadResponse>, AgentError> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let load_acc = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
+        api_error(
+            StatusCode::UNAUTHORIZED,
+            "invalid Authorization header format. Expected 'Bearer <token>'",
+        )
+    })?;
+
+    let bucket_name = headers
+        .get("bucket_name")
+        .or_else(|| headers.get("bucket-name"))
+        .or_else(|| headers.get("x-bucket-name"))
+        .or_else(|| headers.get("bucketname"))
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| api_error(StatusCode::BAD_REQUEST, "missing bucket_name header"))?;
+    check_private_bucket(bucket_name).map_err(|e| private_bucket_error("invalid bucket", e))?;
+
+    let dataitem_name = headers
+        .get("x-dataitem-name")
+        .or_else(|| headers.get("dataitem-name"))
+        .or_else(|| headers.get("dataitemname"))
+        .and_then(|h| h.to_str().ok())
+        .unwrap_or("");
+
+    let folder_name = headers.get("x-folder-name").and_then(|h| h.to_str().ok()).unwrap_or("");
+
+    let mut file_data: Option<Vec<u8>> = None;
+    let mut content_type: Option<String> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        let field_name = field.name().unwrap_or("");
+
+        match field_name {
+            "file" => {
+                content_type = field.content_type().map(|ct| ct.to_string());
+                file_data = Some(
+                    field
+                        .bytes()
+                        .await
+                        .map_err(|_| {
+                            api_error(StatusCode::BAD_REQUEST, "failed to read file data")
+                        })?
+                        .to_vec(),
+                );
+            }
+            "content_type" if content_type.is_none() => {
+                content_type = Some(field.text().await.map_err(|_| {
+                    api_error(StatusCode::BAD_REQUEST, "failed to read content type")
+                })?);
+            }
+            _ => {
+                // skip
+            }
+        }
+    }
+
+    let file_bytes =
+        file_data.ok_or_else(|| api_error(StatusCode::BAD_REQUEST, "no file data provided"))?;
+
+    if file_bytes.len() > OBJECT_SIZE_LIMIT {
+        return Err(object_too_large());
+    }
+
+    scan_upload("/upload/private", &file_bytes).await?;
+
+    let content_type_str = content_type.as_deref().unwrap_or("application/octet-stream");
+
+    let is_signed =
+        headers.get("signed").and_then(|h| h.to_str().ok()).map(|s| s == "true").unwrap_or(false);
+
+    let debit = charge_upload(&headers, file_bytes.len()).await?;
+
+    // private dataitems store
+    // supports signed (ANS-104 ready) and unsigned (raw dataitem's data) data ingress
+    match store_lcp_priv_bucket_dataitem(
+        file_bytes,
+        content_type_str,
+        bucket_name,
+        folder_name,
+        load_acc,
+        dataitem_name,
+        is_signed,
+    )
+    .await
+    {
+        Ok(dataitem_id) => {
+            credits::settle(debit);
+            Ok(Json(PrivateUploadResponse {
+                success: true,
+                dataitem_id,
+                dataitem_name: dataitem_name.to_string(),
+                folder_name: folder_name.to_string(),
+                is_signed,
+                message: "file uploaded to private bucket successfully".to_string(),
+            }))
+        }
+        Err(e) => {
+            credits::refund(debit).await;
+            Err(private_bucket_error("failed to store file", e))
+        }
+    }
+}
+
+fn private_bucket_error(context: &str, err: anyhow::Error) -> AgentError {
+    if err.is::<InvalidBucketName>() {
+        api_error(StatusCode::BAD_REQUEST, err.to_string())
+    } else if err.is::<BucketNotAllowed>() || err.is::<NotBucketOwner>() {
+        api_error(StatusCode::FORBIDDEN, err.to_string())
+    } else if err.is::<DirectUploadNotFound>() {
+        api_error(StatusCode::NOT_FOUND, err.to_string())
+    } else if err.is::<BucketExists>() {
+        api_error(StatusCode::CONFLICT, err.to_string())
+    } else {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, context, &err)
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/private/buckets",
+    tag = "dataitems",
+    request_body = CreateBucketRequest,
+    responses(
+        (status = 201, body = CreateBucketResponse),
+        (status = 400, body = ErrorResponse, description = "invalid bucket name"),
+        (status = 401, body = ErrorResponse, description = "missing or invalid load_acc key"),
+        (status = 403, body = ErrorResponse, description = "bucket not in PRIVATE_BUCKET_ALLOWLIST, or an operator key"),
+        (status = 409, body = ErrorResponse, description = "bucket name already taken"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "a dependency is failing fast"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_create_private_bucket(
+    headers: HeaderMap,
+    Json(request): Json<CreateBucketRequest>,
+) -> Result<(StatusCode, Json<CreateBucketResponse>), AgentError> {
+    let load_acc = bearer_token(&headers)
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+    // buckets are owned by the load_acc key they are tagged with
+    let is_server_key = get_env_var("SERVER_API_KEYS")
+        .is_ok_and(|keys| keys.split(',').any(|key| key.trim() == load_acc));
+    if is_server_key || !load_acc.starts_with("load_acc_") {
+        return Err(api_error(
+            StatusCode::FORBIDDEN,
+            "private buckets are created with a load_acc key",
+        ));
+    }
+    check_private_bucket(&request.bucket_name)
+        .map_err(|e| private_bucket_error("invalid bucket", e))?;
+    require_api_key(&headers).await?;
+
+    create_private_bucket(&request.bucket_name, load_acc)
+        .await
+        .map_err(|e| private_bucket_error("failed to create bucket", e))?;
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateBucketResponse {
+            success: true,
+            message: format!("bucket {} created", request.bucket_name),
+            bucket_name: request.bucket_name,
+        }),
+    ))
+}
+
+#[utoipa::path(
+    post,
+    path = "/private/{bucket}/presign-upload",
+    tag = "dataitems",
+    request_body = PresignUploadRequest,
+    params(("bucket" = String, Path, description = "private LCP bucket name")),
+    responses(
+        (status = 200, body = PresignUploadResponse),
+        (status = 400, body = ErrorResponse, description = "invalid bucket name"),
+        (status = 401, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "bucket not owned by the key or not in PRIVATE_BUCKET_ALLOWLIST"),
+        (status = 413, body = ErrorResponse, description = "size exceeds the upload size limit"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "a dependency is failing fast"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_presign_private_upload(
+    headers: HeaderMap,
+    Path(bucket_name): Path<String>,
+    Json(request): Json<PresignUploadRequest>,
+) -> Result<Json<PresignUploadResponse>, AgentError> {
+    let load_acc = bearer_token(&headers)
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+    check_private_bucket(&bucket_name).map_err(|e| private_bucket_error("invalid bucket", e))?;
+    let content_type =
+        request.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    if request.size > OBJECT_SIZE_LIMIT as u64 {
+        return Err(object_too_large());
+    }
+
+    let upload = direct_upload::presign(
+        &bucket_name,
+        load_acc,
+        &content_type,
+        request.size,
+        &request.folder_name,
+        &request.dataitem_name,
+        request.signed,
+    )
+    .await
+    .map_err(|e| private_bucket_error("failed to presign upload", e))?;
+    let complete_url = format!(
+        "{}/private/{bucket_name}/presign-upload/{}/complete",
+        agent_public_url(&headers).unwrap_or_default(),
+        upload.upload_id
+    );
+    Ok(Json(PresignUploadResponse {
+        success: true,
+        message: format!("PUT the file to upload_url, then POST {complete_url}"),
+        upload_id: upload.upload_id,
+        upload_url: upload.url,
+        method: "PUT".to_string(),
+        headers: BTreeMap::from([
+            ("Content-Type".to_string(), content_type),
+            ("Content-Length".to_string(), request.size.to_string()),
+        ]),
+        expires_at: upload.expires_at.to_rfc3339(),
+        complete_url,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/private/{bucket}/presign-upload/{upload_id}/complete",
+    tag = "dataitems",
+    params(
+        ("bucket" = String, Path, description = "private LCP bucket name"),
+        ("upload_id" = String, Path, description = "upload ID returned by /presign-upload")
+    ),
+    responses(
+        (status = 200, body = PrivateUploadResponse),
+        (status = 400, body = ErrorResponse, description = "invalid bucket name"),
+        (status = 401, body = ErrorResponse),
+        (status = 403, body = ErrorResponse, description = "bucket not in PRIVATE_BUCKET_ALLOWLIST"),
+        (status = 404, body = ErrorResponse, description = "unknown or expired upload ID, presigned with another key, or not uploaded yet"),
+        (status = 413, body = ErrorResponse),
+        (status = 422, body = ErrorResponse, description = "rejected by the malware scan"),
+        (status = 500, body = ErrorResponse),
+        (status = 503, body = ErrorResponse, description = "malware scanner unavailable, or upload memory budget exhausted, see Retry-After")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_complete_private_upload(
+    headers: HeaderMap,
+    Path((bucket_name, upload_id)): Path<(String, String)>,
+) -> Result<Response, AgentError> {
+    let load_acc = bearer_token(&headers)
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+    check_private_bucket(&bucket_name).map_err(|e| private_bucket_error("invalid bucket", e))?;
+    // the body never went through the agent, it is checked like `POST /upload/private` ones now,
+    // its size before it is loaded
+    let uploaded_bytes = direct_upload::uploaded_size(&bucket_name, &upload_id)
+        .await
+        .map_err(|e| private_bucket_error("failed to load upload", e))?;
+    if uploaded_bytes > OBJECT_SIZE_LIMIT as u64 {
+        return Err(reject_direct_upload(&bucket_name, &upload_id, object_too_large()).await);
+    }
+    // the completion has no body, the uploaded one counts against the memory budget instead
+    let Some(_reservation) = concurrency::reserve_upload_bytes(uploaded_bytes) else {
+        return Ok(memory_budget_exceeded(uploaded_bytes));
+    };
+    let upload = direct_upload::load(&bucket_name, &upload_id)
+        .await
+        .map_err(|e| private_bucket_error("failed to load upload", e))?;
+    if let Err(rejection) = scan_upload("/private/{bucket}/presign-upload", &upload.data).await {
+        return Err(reject_direct_upload(&bucket_name, &upload_id, rejection).await);
+    }
+
+    let debit = charge_upload(&headers, upload.data.len()).await?;
+    match store_lcp_priv_bucket_dataitem(
+        upload.data,
+        &upload.content_type,
+        &bucket_name,
+        &upload.folder_name,
+        load_acc,
+        &upload.dataitem_name,
+        upload.is_signed,
+    )
+    .await
+    {
+        Ok(dataitem_id) => {
+            credits::settle(debit);
+            if let Err(err) = direct_upload::remove(&bucket_name, &upload_id).await {
+                println!("DIRECT UPLOAD: failed to remove completed {upload_id}: {err}");
+            }
+            Ok(Json(PrivateUploadResponse {
+                success: true,
+                dataitem_id,
+                dataitem_name: upload.dataitem_name,
+                folder_name: upload.folder_name,
+                is_signed: upload.is_signed,
+                message: "file uploaded to private bucket successfully".to_string(),
+            })
+            .into_response())
+        }
+        Err(e) => {
+            credits::refund(debit).await;
+            Err(private_bucket_error("failed to store file", e))
+        }
+    }
+}
+
+fn object_too_large() -> AgentError {
+    api_error(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!("file size exceeds limit - {OBJECT_SIZE_LIMIT} bytes"),
+    )
+}
+
+/// Remove a direct upload `rejection` refused, so it isn't left in the bucket.
+async fn reject_direct_upload(
+    bucket_name: &str,
+    upload_id: &str,
+    rejection: AgentError,
+) -> AgentError {
+    if let Err(err) = direct_upload::remove(bucket_name, upload_id).await {
+        println!("DIRECT UPLOAD: failed to remove rejected {upload_id}: {err}");
+    }
+    rejection
+}
+
+fn deletion_info(deletion: Deletion) -> DeletionInfo {
+    let purge_after = (deletion.state == DELETION_DELETED).then(|| {
+        let retention = chrono::Duration::from_std(trash::retention()).unwrap_or_default();
+        (deletion.deleted_at + retention).to_rfc3339()
+    });
+    DeletionInfo {
+        dataitem_id: deletion.dataitem_id,
+        state: deletion.state,
+        deleted_at: deletion.deleted_at.to_rfc3339(),
+        purge_after,
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/{id}",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = DeletionInfo, description = "hidden until restored or purged"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_delete_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<DeletionInfo>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let presence =
+        dataitems_presence(&tenant, std::slice::from_ref(&dataitem_id)).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check dataitem", &e)
+        })?;
+    if presence.first().is_none_or(|&(raw, ans104)| !raw && !ans104) {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("dataitem {dataitem_id} not found")));
+    }
+
+    let deletion = trash::soft_delete(&tenant, &dataitem_id).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to delete dataitem", &e)
+    })?;
+    audit::record(
+        "dataitem_delete",
+        json!({ "tenant": tenant.name, "dataitem_id": dataitem_id, "state": deletion.state }),
+    )
+    .await;
+    Ok(Json(deletion_info(deletion)))
+}
+
+#[utoipa::path(
+    post,
+    path = "/report/{id}",
+    tag = "dataitems",
+    request_body = AbuseReportRequest,
+    params(
+        ("id" = String, Path, description = "reported dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 201, body = AbuseReportAccepted, description = "report queued for review"),
+        (status = 400, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "dataitem not stored"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    )
+)]
+pub async fn handle_report_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+    Json(payload): Json<AbuseReportRequest>,
+) -> Result<(StatusCode, Json<AbuseReportAccepted>), AgentError> {
+    let tenant = request_tenant(&headers)?;
+    let presence =
+        dataitems_presence(&tenant, std::slice::from_ref(&dataitem_id)).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to check dataitem", &e)
+        })?;
+    if presence.first().is_none_or(|&(raw, ans104)| !raw && !ans104) {
+        return Err(api_error(StatusCode::NOT_FOUND, format!("dataitem {dataitem_id} not found")));
+    }
+
+    match reports::submit(&tenant, &dataitem_id, payload).await {
+        Ok(report) => Ok((
+            StatusCode::CREATED,
+            Json(AbuseReportAccepted { success: true, report_id: report.id }),
+        )),
+        Err(e) if e.is::<InvalidReport>() => Err(api_error(StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) => {
+            Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to save the report", &e))
+        }
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/{id}/restore",
+    tag = "dataitems",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = DeletionInfo),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "dataitem was never deleted"),
+        (status = 409, body = ErrorResponse, description = "dataitem is already restored or purged"),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_restore_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<DeletionInfo>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let deletion = match trash::restore(&tenant, &dataitem_id).await {
+        Ok(deletion) => deletion,
+        Err(e) => {
+            return Err(match e.downcast_ref::<NotRestorable>() {
+                Some(NotRestorable { state: None, .. }) => {
+                    api_error(StatusCode::NOT_FOUND, e.to_string())
+                }
+                Some(_) => api_error(StatusCode::CONFLICT, e.to_string()),
+                None => upstream_error(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "failed to restore dataitem",
+                    &e,
+                ),
+            });
+        }
+    };
+    audit::record("dataitem_restore", json!({ "tenant": tenant.name, "dataitem_id": dataitem_id }))
+        .await;
+    Ok(Json(deletion_info(deletion)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/post/estimate/{id}",
+    tag = "arweave",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = PostEstimate),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 502, body = ErrorResponse, description = "the bundler payment service failed"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_post_estimate(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<PostEstimate>, AgentError> {
+    require_server_key(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let dataitem = get_dataitem(&dataitem_id, &tenant)
+        .await
+        .map_err(|e| upstream_error(StatusCode::NOT_FOUND, "failed to fetch dataitem", &e))?;
+    let estimate = bundler::estimate_post(&dataitem_id, &dataitem)
+        .await
+        .map_err(|e| upstream_error(StatusCode::BAD_GATEWAY, "failed to estimate the post", &e))?;
+    Ok(Json(estimate))
+}
+
+#[utoipa::path(
+    get,
+    path = "/post/{id}/status",
+    tag = "arweave",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, body = PostStatusResponse),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id"),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse, description = "dataitem was never posted"),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_post_status(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+) -> Result<Json<PostStatusResponse>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+    let history = post_status_history(&tenant.name, &dataitem_id).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the post status", &e)
+    })?;
+    let Some(latest) = history.last() else {
+        return Err(api_error(
+            StatusCode::NOT_FOUND,
+            format!("dataitem {dataitem_id} was never posted to arweave"),
+        ));
+    };
+    Ok(Json(PostStatusResponse {
+        status: latest.status.clone(),
+        dataitem_id,
+        history: history
+            .into_iter()
+            .map(|change| PostStatusEntry {
+                status: change.status,
+                detail: change.detail,
+                at: change.at.to_rfc3339(),
+            })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/bundler/balance",
+    tag = "arweave",
+    responses(
+        (status = 200, body = BundlerBalance, description = "balance of the agent's signer"),
+        (status = 401, body = ErrorResponse),
+        (status = 502, body = ErrorResponse, description = "the bundler payment service failed"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_bundler_balance(
+    headers: HeaderMap,
+) -> Result<Json<BundlerBalance>, AgentError> {
+    require_server_key(&headers)?;
+    let balance = bundler::agent_balance().await.map_err(|e| {
+        upstream_error(StatusCode::BAD_GATEWAY, "failed to fetch the bundler balance", &e)
+    })?;
+    Ok(Json(balance))
+}
+
+#[utoipa::path(
+    get,
+    path = "/credits",
+    tag = "dataitems",
+    responses(
+        (status = 200, body = CreditsResponse, description = "upload credits of the caller's API key"),
+        (status = 401, body = ErrorResponse),
+        (status = 502, body = ErrorResponse, description = "the credits API failed"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_credits(headers: HeaderMap) -> Result<Json<CreditsResponse>, AgentError> {
+    require_api_key(&headers).await?;
+    let api_key = bearer_token(&headers).unwrap_or_default();
+    let config = CreditsConfig::load();
+    let credits_per_byte = config.per_byte.to_string();
+    if !config.is_metered(api_key) {
+        return Ok(Json(CreditsResponse { metered: false, balance: None, credits_per_byte }));
+    }
+    let balance = credits::balance(&config, api_key).await.map_err(|e| {
+        upstream_error(StatusCode::BAD_GATEWAY, "failed to fetch the credits balance", &e)
+    })?;
+    Ok(Json(CreditsResponse {
+        metered: true,
+        balance: Some(balance.to_string()),
+        credits_per_byte,
+    }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/post/{id}",
+    tag = "arweave",
+    params(
+        ("id" = String, Path, description = "dataitem id"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace"),
+        ("Idempotency-Key" = Option<String>, Header, description = "repeated posts with the same key get the original response")
+    ),
+    params(PostDataitemParams),
+    responses(
+        (status = 200, body = PostDataitemResponse),
+        (status = 202, body = JobAccepted, description = "async=true, post_at or delay_seconds: queued as a post_dataitem job"),
+        (status = 400, body = ErrorResponse, description = "malformed dataitem id or schedule"),
+        (status = 401, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "a post with the same Idempotency-Key is in progress"),
+        (status = 410, body = ErrorResponse, description = "dataitem was deleted"),
+        (status = 422, body = ErrorResponse, description = "Idempotency-Key used for another request"),
+        (status = 500, body = ErrorResponse),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_post_dataitem(
+    headers: HeaderMap,
+    DataitemId(dataitem_id): DataitemId,
+    Query(params): Query<PostDataitemParams>,
+) -> Result<Response, AgentError> {
+    require_server_key(&headers)?;
+
+    let tenant = request_tenant(&headers)?;
+    let schedule = post_schedule(&params)?;
+    let run_async = params.run_async.to_string();
+    let run_at = params.post_at.clone().unwrap_or_default();
+    let delay = params.delay_seconds.map(|secs| secs.to_string()).unwrap_or_default();
+    let route = format!("/post/{dataitem_id}");
+    let idempotency = IdempotencyKey::from_headers(
+        &headers,
+        &route,
+        &[&tenant.name, &run_async, &run_at, &delay],
+    )?;
+    idempotency::once(idempotency, post_or_queue(tenant, dataitem_id, params, schedule)).await
+}
+
+/// When a `post_at` or `delay_seconds` post is due, at most `MAX_POST_DELAY_DAYS` ahead.
+fn post_schedule(params: &PostDataitemParams) -> Result<Option<DateTime<Utc>>, AgentError> {
+    let max_delay = chrono::Duration::days(MAX_POST_DELAY_DAYS);
+    let too_late = || {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            format!("posts can be scheduled at most {MAX_POST_DELAY_DAYS} days ahead"),
+        )
+    };
+    let run_at = match (&params.post_at, params.delay_seconds) {
+        (None, None) => return Ok(None),
+        (Some(_), Some(_)) => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                "post_at and delay_seconds are mutually exclusive",
+            ));
+        }
+        (Some(post_at), None) => DateTime::parse_from_rfc3339(post_at)
+            .map_err(|e| {
+                api_error(StatusCode::BAD_REQUEST, format!("invalid post_at {post_at:?}: {e}"))
+            })?
+            .with_timezone(&Utc),
+        (None, Some(secs)) => {
+            if secs > max_delay.num_seconds() as u64 {
+                return Err(too_late());
+            }
+            Utc::now() + chrono::Duration::seconds(secs as i64)
+        }
+    };
+    if run_at > Utc::now() + max_delay {
+        return Err(too_late());
+    }
+    Ok(Some(run_at))
+}
+
+async fn post_or_queue(
+    tenant: Tenant,
+    dataitem_id: String,
+    params: PostDataitemParams,
+    schedule: Option<DateTime<Utc>>,
+) -> Result<Response, AgentError> {
+    blocklist::check_id(&dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &dataitem_id).await.map_err(deleted_error)?;
+
+    if params.run_async || schedule.is_some() {
+        // a time in the past runs with the next poll
+        let run_at = schedule.unwrap_or_else(Utc::now);
+        let payload = json!({ "dataitem_id": dataitem_id });
+        let job_id =
+            jobs::enqueue_at(POST_DATAITEM_JOB, &tenant.name, payload, run_at).await.map_err(
+                |e| upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to queue post", &e),
+            )?;
+        let message = match schedule {
+            Some(run_at) => format!(
+                "post of {dataitem_id} scheduled for {} as job {job_id}",
+                run_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            ),
+            None => format!("post of {dataitem_id} queued as job {job_id}"),
+        };
+        return Ok((StatusCode::ACCEPTED, Json(JobAccepted { success: true, message, job_id }))
+            .into_response());
+    }
+
+    match post_dataitem(dataitem_id.clone(), &tenant).await {
+        Ok(response) => Ok(Json(PostDataitemResponse {
+            success: true,
+            dataitem_id,
+            bundler_response: response,
+            message: "dataitem posted to arweave successfully".to_string(),
+        })
+        .into_response()),
+        Err(e) if e.is::<Blocked>() => {
+            Err(api_error(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, e.to_string()))
+        }
+        Err(e) => {
+            Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to post dataitem", &e))
+        }
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/registry/{bucket_name}",
+    tag = "private",
+    params(("bucket_name" = String, Path, description = "private LCP bucket name")),
+    responses(
+        (status = 200, body = BucketRegistryResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_get_bucket_registry(
+    headers: HeaderMap,
+    Path(bucket_name): Path<String>,
+) -> Result<Json<BucketRegistryResponse>, AgentError> {
+    let auth_header = headers
+        .get("authorization")
+        .and_then(|h| h.to_str().ok())
+        .ok_or_else(|| api_error(StatusCode::UNAUTHORIZED, "missing Authorization header"))?;
+
+    let token = auth_header.strip_prefix("Bearer ").ok_or_else(|| {
+        api_error(StatusCode::UNAUTHORIZED, "invalid Authorization header format")
+    })?;
+
+    let aws_secret = get_env_var("REGISTRY_SECRET_KEY")
+        .map_err(|_| api_error(StatusCode::INTERNAL_SERVER_ERROR, "server configuration error"))?;
+
+    if token != aws_secret {
+        return Err(api_error(StatusCode::UNAUTHORIZED, "invalid API key"));
+    }
+
+    match get_bucket_registry(&bucket_name) {
+        Ok(registry_entries) => Ok(Json(BucketRegistryResponse {
+            success: true,
+            bucket_name,
+            entries: registry_entries,
+        })),
+        Err(e) => Err(api_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            format!("failed to get registry: {}", e),
+        )),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/openapi.json",
+    tag = "agent",
+    responses((status = 200, description = "OpenAPI 3.1 document of the agent API"))
+)]
+pub async fn handle_openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(crate::core::openapi::api_doc())
+}
+
+#[utoipa::path(
+    put,
+    path = "/s3/{bucket}/{key}",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "facade bucket"),
+        ("key" = String, Path, description = "object key, registered as the dataitem name"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    request_body(content = Vec<u8>, content_type = "application/octet-stream"),
+    responses(
+        (status = 200, description = "object stored as an agent signed dataitem, ETag is its id"),
+        (status = 403, description = "S3 XML error: AccessDenied, SignatureDoesNotMatch"),
+        (status = 422, description = "S3 XML error: rejected by the malware scan")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_s3_put_object(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Path((bucket, key)): Path<(String, String)>,
+    body: Bytes,
+) -> Result<Response, S3Error> {
+    s3_facade::authorize(&method, &uri, &headers, &body).await?;
+    let tenant = request_tenant(&headers)?;
+    let data = s3_facade::decode_payload(&headers, body)?;
+
+    let declared = headers.get(header::CONTENT_TYPE).and_then(|h| h.to_str().ok());
+    let content_type = resolve_content_type(declared, &data);
+    check_content_type_policy(&content_type, &data).map_err(|e| {
+        S3Error::new(StatusCode::UNSUPPORTED_MEDIA_TYPE, "InvalidRequest", e.to_string())
+    })?;
+    scan_upload("/s3", &data).await?;
+
+    let size = data.len() as u64;
+    let mut tags =
+        vec![("S3-Bucket".to_string(), bucket.clone()), ("S3-Key".to_string(), key.clone())];
+    tags.extend(s3_facade::user_metadata_tags(&headers));
+    let default_tags =
+        default_tags(bearer_token(&headers)).map_err(|e| S3Error::internal(e.to_string()))?;
+    let debit = charge_upload(&headers, data.len()).await?;
+    let stored = store_dataitem(data, &content_type, &tags, &default_tags, &tenant).await;
+    match &stored {
+        Ok(_) => credits::settle(debit),
+        Err(_) => credits::refund(debit).await,
+    }
+    let dataitem_id = stored.map(|stored| stored.id).map_err(|e| match e.is::<Blocked>() {
+        true => {
+            S3Error::new(StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS, "AccessDenied", e.to_string())
+        }
+        false => S3Error::internal(format!("failed to store object: {e}")),
+    })?;
+
+    replace_named_entry(
+        &s3_facade::facade_registry(&tenant, &bucket),
+        RegistryEntry {
+            dataitem_id: dataitem_id.clone(),
+            dataitem_name: key,
+            size: Some(size),
+            last_modified: Some(Utc::now()),
+        },
+    )
+    .map_err(|e| S3Error::internal(format!("failed to register object: {e}")))?;
+
+    Ok(([(header::ETAG, quoted_etag(&dataitem_id)), (X_DATAITEM_ID, dataitem_id)]).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/s3/{bucket}/{key}",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "facade bucket"),
+        ("key" = String, Path, description = "object key"),
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "raw body of the dataitem registered under the key"),
+        (status = 403, description = "S3 XML error: AccessDenied, SignatureDoesNotMatch"),
+        (status = 404, description = "S3 XML error: NoSuchKey")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_s3_get_object(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Path((bucket, key)): Path<(String, String)>,
+) -> Result<Response, S3Error> {
+    s3_facade::authorize(&method, &uri, &headers, &[]).await?;
+    let tenant = request_tenant(&headers)?;
+
+    let entry = find_named_entry(&s3_facade::facade_registry(&tenant, &bucket), &key)
+        .map_err(|e| S3Error::internal(format!("failed to read registry: {e}")))?
+        .ok_or_else(|| S3Error::no_such_key(&key))?;
+    blocklist::check_id(&entry.dataitem_id).await.map_err(blocked_error)?;
+    trash::check_not_deleted(&tenant, &entry.dataitem_id)
+        .await
+        .map_err(|_| S3Error::no_such_key(&key))?;
+    let object = get_dataitem_raw(&entry.dataitem_id, &tenant)
+        .await
+        .map_err(|_| S3Error::no_such_key(&key))?;
+    blocklist::check_content(&object.data).await.map_err(blocked_error)?;
+
+    let content_type =
+        object.content_type.unwrap_or_else(|| "application/octet-stream".to_string());
+    let mut response = (
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::ETAG, quoted_etag(&entry.dataitem_id)),
+            (X_DATAITEM_ID, entry.dataitem_id),
+        ],
+        object.data,
+    )
+        .into_response();
+    if let Some(last_modified) = entry.last_modified {
+        let http_date = last_modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        if let Ok(value) = HeaderValue::from_str(&http_date) {
+            response.headers_mut().insert(header::LAST_MODIFIED, value);
+        }
+    }
+    Ok(response)
+}
+
+#[utoipa::path(
+    get,
+    path = "/s3/{bucket}",
+    tag = "s3",
+    params(
+        ("bucket" = String, Path, description = "facade bucket"),
+        ListObjectsParams,
+        ("x-tenant" = Option<String>, Header, description = "tenant namespace")
+    ),
+    responses(
+        (status = 200, description = "ListObjectsV2 XML result"),
+        (status = 403, description = "S3 XML error: AccessDenied, SignatureDoesNotMatch")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_s3_list_objects(
+    method: Method,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+    Path(bucket): Path<String>,
+    Query(params): Query<ListObjectsParams>,
+) -> Result<Response, S3Error> {
+    s3_facade::authorize(&method, &uri, &headers, &[]).await?;
+    let tenant = request_tenant(&headers)?;
+
+    let entries = get_bucket_registry(&s3_facade::facade_registry(&tenant, &bucket))
+        .map_err(|e| S3Error::internal(format!("failed to read registry: {e}")))?;
+    let xml = s3_facade::list_objects_xml(
+        &bucket,
+        entries,
+        &params.prefix,
+        params.delimiter.as_deref(),
+        params.max_keys.unwrap_or(s3_facade::MAX_LIST_KEYS),
+        params.continuation_token.as_deref(),
+        params.start_after.as_deref(),
+    )?;
+    Ok(([(header::CONTENT_TYPE, "application/xml")], xml).into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/gc",
+    tag = "admin",
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = GcReport, description = "dry-run report, nothing is deleted or repaired"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_gc_report(headers: HeaderMap) -> Result<Json<GcReport>, AgentError> {
+    require_admin(&headers)?;
+    let tenant = request_tenant(&headers)?;
+
+    collect_garbage(&tenant, true).await.map(Json).map_err(|e| {
+        api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("failed to scan for garbage: {e}"))
+    })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/index/compaction",
+    tag = "admin",
+    responses(
+        (status = 200, body = IndexCompactionReport, description = "dry-run report, nothing is merged"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_index_compaction_report(
+    headers: HeaderMap,
+) -> Result<Json<IndexCompactionReport>, AgentError> {
+    require_admin(&headers)?;
+    compact_tag_index(true).await.map(Json).map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the tag index stats", &e)
+    })
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/index/compaction",
+    tag = "admin",
+    responses(
+        (status = 200, body = IndexCompactionReport, description = "the tag index was merged"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_compact_index(
+    headers: HeaderMap,
+) -> Result<Json<IndexCompactionReport>, AgentError> {
+    require_admin(&headers)?;
+    let report = compact_tag_index(false).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to compact the tag index", &e)
+    })?;
+    audit::record(
+        "index_compaction",
+        json!({ "rows_before": report.rows_before, "removed": report.removed }),
+    )
+    .await;
+    Ok(Json(report))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/request-log",
+    tag = "admin",
+    responses(
+        (status = 200, body = RequestLogSettings, description = "the request log sampling in effect"),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_request_log_settings(
+    headers: HeaderMap,
+) -> Result<Json<RequestLogSettings>, AgentError> {
+    require_admin(&headers)?;
+    Ok(Json(request_log::settings()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/request-log",
+    tag = "admin",
+    request_body = RequestLogUpdate,
+    responses(
+        (status = 200, body = RequestLogSettings, description = "the sampling now in effect, until restart"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_update_request_log(
+    headers: HeaderMap,
+    Json(payload): Json<RequestLogUpdate>,
+) -> Result<Json<RequestLogSettings>, AgentError> {
+    require_admin(&headers)?;
+    let settings = request_log::update(payload)
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    audit::record(
+        "request_log_update",
+        json!({ "sample_rate": settings.sample_rate, "paths": settings.paths }),
+    )
+    .await;
+    Ok(Json(settings))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/maintenance",
+    tag = "admin",
+    responses(
+        (status = 200, body = MaintenanceSettings),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_maintenance(
+    headers: HeaderMap,
+) -> Result<Json<MaintenanceSettings>, AgentError> {
+    require_admin(&headers)?;
+    Ok(Json(maintenance::settings()))
+}
+
+#[utoipa::path(
+    put,
+    path = "/admin/maintenance",
+    tag = "admin",
+    request_body = MaintenanceUpdate,
+    responses(
+        (status = 200, body = MaintenanceSettings, description = "the mode now in effect, until restart"),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_update_maintenance(
+    headers: HeaderMap,
+    Json(payload): Json<MaintenanceUpdate>,
+) -> Result<Json<MaintenanceSettings>, AgentError> {
+    require_admin(&headers)?;
+    let settings = maintenance::update(payload);
+    audit::record(
+        "maintenance_update",
+        json!({ "enabled": settings.enabled, "message": settings.message }),
+    )
+    .await;
+    Ok(Json(settings))
+}
+
+#[utoipa::path(
+    get,
+    path = "/export/index",
+    tag = "admin",
+    params(ExportParams, ("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, description = "tag index rows, streamed as NDJSON or CSV (with a header row)"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_export_index(
+    headers: HeaderMap,
+    Query(params): Query<ExportParams>,
+) -> Result<Response, AgentError> {
+    require_admin(&headers)?;
+    let tenant = request_tenant(&headers)?;
+
+    let format = match params.format.as_deref() {
+        None => ExportFormat::Ndjson,
+        Some(value) => ExportFormat::parse(value).ok_or_else(|| {
+            api_error(StatusCode::BAD_REQUEST, "format must be one of: ndjson, csv")
+        })?,
+    };
+    if let (Some(from), Some(to)) = (params.from, params.to) {
+        if from >= to {
+            return Err(api_error(StatusCode::BAD_REQUEST, "from must be before to"));
+        }
+    }
+
+    let stream = export_index(&tenant.name, format, params.from, params.to).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to export index", &e)
+    })?;
+
+    audit::record(
+        "index_export",
+        serde_json::json!({
+            "tenant": tenant.name,
+            "format": format.extension(),
+            "from": params.from,
+            "to": params.to,
+        }),
+    )
+    .await;
+
+    let disposition = format!("attachment; filename=\"dataitem_tags.{}\"", format.extension());
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/sync/dataitems",
+    tag = "dataitems",
+    params(SyncParams, ("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, description = "dataitems in indexing order, streamed as NDJSON rows of id, tags, content_type, size, sha256, blake3, created_at and cursor"),
+        (status = 400, body = ErrorResponse, description = "malformed cursor"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_sync_dataitems(
+    headers: HeaderMap,
+    Query(params): Query<SyncParams>,
+) -> Result<Response, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+
+    let since = params
+        .since
+        .as_deref()
+        .map(decode_sync_cursor)
+        .transpose()
+        .map_err(|e| api_error(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let limit = params.limit.unwrap_or(DEFAULT_SYNC_LIMIT);
+    let stream = sync_dataitems(&tenant.name, since.as_ref(), limit).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the catalogue", &e)
+    })?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], Body::from_stream(stream))
+        .into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/import",
+    tag = "dataitems",
+    request_body(
+        content = Vec<u8>,
+        description = "an ANS-104 bundle, or with `Content-Type: application/x-ndjson` one base64 encoded signed dataitem per line",
+        content_type = "application/octet-stream"
+    ),
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = ImportResponse, description = "per-item report, failed items do not abort the import"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 413, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_import(
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<ImportResponse>, AgentError> {
+    require_api_key(&headers).await?;
+    let tenant = request_tenant(&headers)?;
+
+    let is_ndjson = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/x-ndjson") || ct.starts_with("application/jsonl"))
+        .unwrap_or(false);
+
+    let items: Vec<Result<Vec<u8>, String>> = if is_ndjson {
+        body.split(|b| *b == b'\n')
+            .map(|line| line.trim_ascii())
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                general_purpose::STANDARD
+                    .decode(line)
+                    .or_else(|_| general_purpose::URL_SAFE_NO_PAD.decode(line))
+                    .map_err(|e| format!("invalid base64 line: {e}"))
+            })
+            .collect()
+    } else {
+        unpack_bundle(&body)
+            .map_err(|e| api_error(StatusCode::BAD_REQUEST, format!("invalid bundle: {e}")))?
+            .into_iter()
+            .map(Ok)
+            .collect()
+    };
+
+    if items.is_empty() {
+        return Err(api_error(StatusCode::BAD_REQUEST, "no dataitems to import"));
+    }
+
+    let mut reports = Vec::with_capacity(items.len());
+    for (index, item) in items.into_iter().enumerate() {
+        let result = match item {
+            Ok(data) => import_dataitem(data, &tenant).await,
+            Err(err) => Err(err),
+        };
+        reports.push(match result {
+            Ok(dataitem_id) => ImportItemReport {
+                index,
+                dataitem_id: Some(dataitem_id),
+                success: true,
+                error: None,
+            },
+            Err(error) => {
+                ImportItemReport { index, dataitem_id: None, success: false, error: Some(error) }
+            }
+        });
+    }
+
+    let imported = reports.iter().filter(|r| r.success).count();
+    let failed = reports.len() - imported;
+    println!("IMPORT: tenant={:?} imported={imported} failed={failed}", tenant.name);
+
+    Ok(Json(ImportResponse { success: failed == 0, imported, failed, items: reports }))
+}
+
+/// Store one imported dataitem under the same policy and scan checks as `/upload`.
+async fn import_dataitem(data: Vec<u8>, tenant: &Tenant) -> Result<String, String> {
+    let (dataitem, content_type) =
+        reconstruct_dataitem_data(data.clone()).map_err(|e| format!("invalid dataitem: {e}"))?;
+    check_content_type_policy(&content_type, &dataitem.data).map_err(|e| e.to_string())?;
+    scan_upload("/import", &data).await.map_err(|err| err.message().to_string())?;
+    store_signed_dataitem(data, tenant)
+        .await
+        .map(|stored| stored.id)
+        .map_err(|e| format!("failed to store dataitem: {e}"))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/replication/status",
+    tag = "admin",
+    responses(
+        (status = 200, body = ReplicationStatus),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_replication_status(
+    headers: HeaderMap,
+) -> Result<Json<ReplicationStatus>, AgentError> {
+    require_admin(&headers)?;
+    Ok(Json(replication_status().await))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/selftest",
+    tag = "admin",
+    responses(
+        (status = 200, body = SelfTestReport, description = "every check passed or was skipped"),
+        (status = 401, body = ErrorResponse),
+        (status = 503, body = SelfTestReport, description = "a check failed")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_selftest(headers: HeaderMap) -> Result<Response, AgentError> {
+    require_admin(&headers)?;
+    let report = run_self_test().await;
+    let status = if report.ok { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    Ok((status, Json(report)).into_response())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reload",
+    tag = "admin",
+    responses(
+        (status = 200, body = ReloadReport, description = "the configuration was reloaded"),
+        (status = 401, body = ErrorResponse),
+        (status = 422, body = ReloadReport, description = "a derived setting is invalid"),
+        (status = 500, body = ErrorResponse, description = "`.env` can't be parsed, nothing changed")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_reload(headers: HeaderMap) -> Result<Response, AgentError> {
+    require_admin(&headers)?;
+    let report = reload_config()
+        .map_err(|e| api_error(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:#}")))?;
+    let status = if report.ok { StatusCode::OK } else { StatusCode::UNPROCESSABLE_ENTITY };
+    Ok((status, Json(report)).into_response())
+}
+
+fn job_info(job: JobRecord) -> JobInfo {
+    JobInfo {
+        payload: serde_json::from_str(&job.payload).unwrap_or(serde_json::Value::Null),
+        last_error: Some(job.last_error).filter(|e| !e.is_empty()),
+        run_at: job.run_at.to_rfc3339(),
+        created_at: job.created_at.to_rfc3339(),
+        updated_at: job.updated_at.to_rfc3339(),
+        id: job.id,
+        kind: job.kind,
+        tenant: job.tenant,
+        state: job.state,
+        attempts: job.attempts,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    tag = "admin",
+    params(JobsParams),
+    responses(
+        (status = 200, body = JobsResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_list_jobs(
+    headers: HeaderMap,
+    Query(params): Query<JobsParams>,
+) -> Result<Json<JobsResponse>, AgentError> {
+    require_admin(&headers)?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let jobs =
+        list_jobs(params.state.as_deref(), params.kind.as_deref(), limit).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to list jobs", &e)
+        })?;
+    Ok(Json(JobsResponse { jobs: jobs.into_iter().map(job_info).collect() }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/jobs/{id}/retry",
+    tag = "admin",
+    params(("id" = String, Path, description = "dead-lettered job id")),
+    responses(
+        (status = 200, body = JobInfo),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "job is not dead-lettered")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_retry_job(
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<JobInfo>, AgentError> {
+    require_admin(&headers)?;
+    match jobs::retry_job(&job_id).await {
+        Ok(Some(job)) => {
+            audit::record("job_retry", json!({ "job_id": job_id, "kind": job.kind })).await;
+            Ok(Json(job_info(job)))
+        }
+        Ok(None) => Err(api_error(StatusCode::NOT_FOUND, format!("no job with id {job_id}"))),
+        Err(e) if e.is::<JobNotDead>() => Err(api_error(StatusCode::CONFLICT, e.to_string())),
+        Err(e) => Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to retry job", &e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/migrations/prefix",
+    tag = "admin",
+    request_body = PrefixMigrationRequest,
+    params(("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 202, body = PrefixMigrationStatus, description = "migration queued as a job"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "a migration of the tenant is unfinished"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled"),
+        (status = 504, body = ErrorResponse, description = "a dependency timed out")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_start_prefix_migration(
+    headers: HeaderMap,
+    Json(payload): Json<PrefixMigrationRequest>,
+) -> Result<(StatusCode, Json<PrefixMigrationStatus>), AgentError> {
+    require_admin(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let (job_id, migration) = match migration::start(&tenant, payload).await {
+        Ok(started) => started,
+        Err(e) if e.is::<InvalidMigration>() => {
+            return Err(api_error(StatusCode::BAD_REQUEST, e.to_string()));
+        }
+        Err(e) if e.is::<MigrationInProgress>() => {
+            return Err(api_error(StatusCode::CONFLICT, e.to_string()));
+        }
+        Err(e) => {
+            return Err(upstream_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "failed to queue the migration",
+                &e,
+            ));
+        }
+    };
+    audit::record(
+        "prefix_migration",
+        json!({
+            "job_id": job_id,
+            "tenant": tenant.name,
+            "from_dir_name": migration.from_dir_name,
+            "from_raw_dir_name": migration.from_raw_dir_name,
+            "delete_source": migration.delete_source,
+        }),
+    )
+    .await;
+    let status = PrefixMigrationStatus {
+        job_id,
+        tenant: tenant.name,
+        state: "pending".to_string(),
+        attempts: 0,
+        last_error: None,
+        updated_at: Utc::now().to_rfc3339(),
+        migration,
+    };
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/migrations/prefix/{id}",
+    tag = "admin",
+    params(("id" = String, Path, description = "migration job id")),
+    responses(
+        (status = 200, body = PrefixMigrationStatus),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_prefix_migration(
+    headers: HeaderMap,
+    Path(job_id): Path<String>,
+) -> Result<Json<PrefixMigrationStatus>, AgentError> {
+    require_admin(&headers)?;
+    match migration::status(&job_id).await {
+        Ok(Some(status)) => Ok(Json(status)),
+        Ok(None) => {
+            Err(api_error(StatusCode::NOT_FOUND, format!("no prefix migration with id {job_id}")))
+        }
+        Err(e) => Err(upstream_error(
             StatusCode::INTERNAL_SERVER_ERROR,
-            Json(json!({"error": format!("failed to get registry: {}", e)})),
+            "failed to read the migration",
+            &e,
         )),
     }
 }
+
+fn abuse_report_info(report: AbuseReportRecord) -> AbuseReportInfo {
+    AbuseReportInfo {
+        created_at: report.created_at.to_rfc3339(),
+        updated_at: report.updated_at.to_rfc3339(),
+        id: report.id,
+        tenant: report.tenant,
+        dataitem_id: report.dataitem_id,
+        reason: report.reason,
+        details: report.details,
+        reporter_name: report.reporter_name,
+        reporter_email: report.reporter_email,
+        source_ip: report.source_ip,
+        state: report.state,
+        resolution_note: report.resolution_note,
+        resolved_by: report.resolved_by,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/reports",
+    tag = "admin",
+    params(AbuseReportsParams),
+    responses(
+        (status = 200, body = AbuseReportsResponse, description = "newest reports first"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_list_reports(
+    headers: HeaderMap,
+    Query(params): Query<AbuseReportsParams>,
+) -> Result<Json<AbuseReportsResponse>, AgentError> {
+    require_admin(&headers)?;
+    let limit = params.limit.unwrap_or(50).clamp(1, 500);
+    let state = match params.state.as_deref() {
+        None => Some(REPORT_OPEN),
+        Some("all") => None,
+        Some(state) => Some(state),
+    };
+    let reports =
+        list_abuse_reports(state, params.tenant.as_deref(), None, limit).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to list reports", &e)
+        })?;
+    Ok(Json(AbuseReportsResponse { reports: reports.into_iter().map(abuse_report_info).collect() }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/reports/{id}/resolve",
+    tag = "admin",
+    request_body = ResolveReportRequest,
+    params(("id" = String, Path, description = "report id")),
+    responses(
+        (status = 200, body = AbuseReportInfo, description = "the resolved report"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 404, body = ErrorResponse),
+        (status = 409, body = ErrorResponse, description = "report already resolved"),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_resolve_report(
+    headers: HeaderMap,
+    Path(report_id): Path<String>,
+    Json(payload): Json<ResolveReportRequest>,
+) -> Result<Json<AbuseReportInfo>, AgentError> {
+    require_admin(&headers)?;
+    match reports::resolve(&report_id, &payload.action, payload.note).await {
+        Ok(Some(report)) => {
+            audit::record(
+                "abuse_report_resolved",
+                json!({
+                    "report_id": report.id,
+                    "tenant": report.tenant,
+                    "dataitem_id": report.dataitem_id,
+                    "state": report.state,
+                }),
+            )
+            .await;
+            Ok(Json(abuse_report_info(report)))
+        }
+        Ok(None) => Err(api_error(StatusCode::NOT_FOUND, format!("no report with id {report_id}"))),
+        Err(e) if e.is::<InvalidReport>() => Err(api_error(StatusCode::BAD_REQUEST, e.to_string())),
+        Err(e) if e.is::<ReportResolved>() => Err(api_error(StatusCode::CONFLICT, e.to_string())),
+        Err(e) => {
+            Err(upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to resolve report", &e))
+        }
+    }
+}
+
+/// `24h` or `7d` style analytics period, at most a year.
+fn parse_period(period: &str) -> Option<chrono::Duration> {
+    let (amount, unit) = period.split_at(period.len().checked_sub(1)?);
+    let amount: i64 = amount.parse().ok().filter(|amount| *amount > 0)?;
+    let duration = match unit {
+        "h" => chrono::Duration::hours(amount),
+        "d" => chrono::Duration::days(amount),
+        _ => return None,
+    };
+    (duration <= chrono::Duration::days(365)).then_some(duration)
+}
+
+fn tag_usage(usage: TagUsage, with_value: bool) -> TagUsageEntry {
+    TagUsageEntry {
+        key: usage.key,
+        value: with_value.then_some(usage.value),
+        dataitems: usage.dataitems,
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/analytics/top",
+    tag = "admin",
+    params(AnalyticsParams, ("x-tenant" = Option<String>, Header, description = "tenant namespace")),
+    responses(
+        (status = 200, body = TopAnalytics),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse),
+        (status = 501, body = ErrorResponse, description = "indexing is disabled")
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_analytics_top(
+    headers: HeaderMap,
+    Query(params): Query<AnalyticsParams>,
+) -> Result<Json<TopAnalytics>, AgentError> {
+    require_admin(&headers)?;
+    let tenant = request_tenant(&headers)?;
+    let period = params.period.unwrap_or_else(|| "7d".to_string());
+    let duration = parse_period(&period).ok_or_else(|| {
+        api_error(
+            StatusCode::BAD_REQUEST,
+            "invalid period, expected hours or days such as 24h or 7d, at most 365d",
+        )
+    })?;
+    let limit = params.limit.unwrap_or(10).clamp(1, 100);
+    let since = Utc::now() - duration;
+
+    let mut excluded = TagPolicy::from_env().reserved;
+    excluded.sort();
+    excluded.dedup();
+    let (dataitems, tag_keys, tag_values) = tokio::try_join!(
+        top_dataitems(&tenant.name, since, limit),
+        top_tags(&tenant.name, since, &excluded, false, limit),
+        top_tags(&tenant.name, since, &excluded, true, limit),
+    )
+    .map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to compute analytics", &e)
+    })?;
+
+    Ok(Json(TopAnalytics {
+        period,
+        since: since.to_rfc3339(),
+        dataitems: dataitems
+            .into_iter()
+            .map(|(dataitem_id, hits)| TopDataitem { dataitem_id, hits })
+            .collect(),
+        tag_keys: tag_keys.into_iter().map(|usage| tag_usage(usage, false)).collect(),
+        tag_values: tag_values.into_iter().map(|usage| tag_usage(usage, true)).collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/provenance",
+    tag = "admin",
+    params(ProvenanceParams, ("x-tenant" = Option<String>, Header, description = "restrict to one tenant")),
+    responses(
+        (status = 200, body = ProvenanceResponse),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_provenance(
+    headers: HeaderMap,
+    Query(params): Query<ProvenanceParams>,
+) -> Result<Json<ProvenanceResponse>, AgentError> {
+    require_admin(&headers)?;
+    if params.dataitem_id.is_none() && params.principal.is_none() && params.source_ip.is_none() {
+        return Err(api_error(
+            StatusCode::BAD_REQUEST,
+            "one of dataitem_id, principal or source_ip is required",
+        ));
+    }
+    let tenant = match headers.contains_key("x-tenant") {
+        true => Some(request_tenant(&headers)?.name),
+        false => None,
+    };
+    let limit = params.limit.unwrap_or(100).clamp(1, 1000);
+
+    let records = query_provenance(
+        tenant.as_deref(),
+        params.dataitem_id.as_deref(),
+        params.principal.as_deref(),
+        params.source_ip.as_deref(),
+        limit,
+    )
+    .await
+    .map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to query provenance", &e)
+    })?;
+
+    let items = records
+        .into_iter()
+        .map(|record| UploadProvenance {
+            tenant: record.tenant,
+            dataitem_id: record.dataitem_id,
+            content_type: record.content_type,
+            principal: record.principal,
+            source_ip: record.source_ip,
+            user_agent: record.user_agent,
+            indexed_at: record.created_at.to_rfc3339(),
+        })
+        .collect();
+    Ok(Json(ProvenanceResponse { items }))
+}
+
+fn blocklist_entry(entry: BlockEntry) -> BlocklistEntry {
+    BlocklistEntry {
+        kind: entry.kind,
+        value: entry.value,
+        reason: entry.reason,
+        active: entry.active,
+        updated_at: entry.updated_at.to_rfc3339(),
+    }
+}
+
+/// Normalize a blocklist value, lowercasing hashes, or reject it.
+fn blocklist_value(kind: &str, value: &str) -> Result<String, AgentError> {
+    let value = value.trim();
+    let valid = match kind {
+        BLOCK_BY_ID => is_arweave_id(value),
+        BLOCK_BY_HASH => value.len() == 64 && value.bytes().all(|b| b.is_ascii_hexdigit()),
+        _ => {
+            return Err(api_error(
+                StatusCode::BAD_REQUEST,
+                format!("unknown blocklist kind {kind:?}, expected id or sha256"),
+            ));
+        }
+    };
+    if !valid {
+        return Err(api_error(StatusCode::BAD_REQUEST, format!("invalid {kind} {value:?}")));
+    }
+    Ok(if kind == BLOCK_BY_HASH { value.to_ascii_lowercase() } else { value.to_string() })
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/blocklist",
+    tag = "admin",
+    responses(
+        (status = 200, body = BlocklistResponse, description = "active entries, newest first"),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_list_blocklist(
+    headers: HeaderMap,
+) -> Result<Json<BlocklistResponse>, AgentError> {
+    require_admin(&headers)?;
+    let entries = active_block_entries().await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to read the blocklist", &e)
+    })?;
+    Ok(Json(BlocklistResponse { entries: entries.into_iter().map(blocklist_entry).collect() }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/blocklist",
+    tag = "admin",
+    request_body = BlockRequest,
+    responses(
+        (status = 200, body = BlocklistEntry),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_block(
+    headers: HeaderMap,
+    Json(payload): Json<BlockRequest>,
+) -> Result<Json<BlocklistEntry>, AgentError> {
+    require_admin(&headers)?;
+    let value = blocklist_value(&payload.kind, &payload.value)?;
+    let reason = payload.reason.unwrap_or_default();
+    let entry =
+        blocklist::set_blocked(&payload.kind, &value, &reason, true).await.map_err(|e| {
+            upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to update the blocklist", &e)
+        })?;
+    audit::record(
+        "blocklist_add",
+        json!({ "kind": entry.kind, "value": entry.value, "reason": reason }),
+    )
+    .await;
+    Ok(Json(blocklist_entry(entry)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/blocklist/{kind}/{value}",
+    tag = "admin",
+    params(
+        ("kind" = String, Path, description = "`id` or `sha256`"),
+        ("value" = String, Path, description = "blocked dataitem ID or content hash")
+    ),
+    responses(
+        (status = 200, body = BlocklistEntry, description = "the lifted entry"),
+        (status = 400, body = ErrorResponse),
+        (status = 401, body = ErrorResponse),
+        (status = 500, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_unblock(
+    headers: HeaderMap,
+    Path((kind, value)): Path<(String, String)>,
+) -> Result<Json<BlocklistEntry>, AgentError> {
+    require_admin(&headers)?;
+    let value = blocklist_value(&kind, &value)?;
+    let entry = blocklist::set_blocked(&kind, &value, "", false).await.map_err(|e| {
+        upstream_error(StatusCode::INTERNAL_SERVER_ERROR, "failed to update the blocklist", &e)
+    })?;
+    audit::record("blocklist_remove", json!({ "kind": entry.kind, "value": entry.value })).await;
+    Ok(Json(blocklist_entry(entry)))
+}
+
+#[utoipa::path(
+    delete,
+    path = "/admin/bucket-ownership",
+    tag = "admin",
+    params(OwnershipCacheParams),
+    responses(
+        (status = 200, body = OwnershipCacheInvalidated),
+        (status = 401, body = ErrorResponse)
+    ),
+    security(("bearer" = []))
+)]
+pub async fn handle_invalidate_bucket_ownership(
+    headers: HeaderMap,
+    Query(params): Query<OwnershipCacheParams>,
+) -> Result<Json<OwnershipCacheInvalidated>, AgentError> {
+    require_admin(&headers)?;
+    let invalidated = invalidate_bucket_ownership(params.bucket.as_deref());
+    audit::record("bucket_ownership_invalidate", json!({ "bucket": params.bucket })).await;
+    Ok(Json(OwnershipCacheInvalidated { invalidated }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/admin/schedule",
+    tag = "admin",
+    responses((status = 200, body = ScheduleResponse), (status = 401, body = ErrorResponse)),
+    security(("bearer" = []))
+)]
+pub async fn handle_schedule(headers: HeaderMap) -> Result<Json<ScheduleResponse>, AgentError> {
+    require_admin(&headers)?;
+    Ok(Json(ScheduleResponse { tasks: scheduled_tasks() }))
+}
@@ -0,0 +1,219 @@
+use crate::core::{
+    models::UploadTag,
+    progress::new_job_id,
+    provenance,
+    s3::{AgentConfig, list_all_objects},
+    storage::{StorageBackend, storage_backend},
+    tenant::Tenant,
+    utils::get_env_var,
+};
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_STAGING_DIR: &str = "staging";
+const DEFAULT_TTL_SECS: i64 = 24 * 60 * 60;
+const MANIFEST_SUFFIX: &str = ".json";
+
+/// What the commit needs besides the staged body, written next to it.
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    content_type: String,
+    tags: Vec<UploadTag>,
+    is_signed: bool,
+    principal: String,
+    staged_at: DateTime<Utc>,
+}
+
+/// A staged upload loaded back for its commit.
+pub(crate) struct StagedUpload {
+    pub data: Vec<u8>,
+    pub content_type: String,
+    pub tags: Vec<UploadTag>,
+    pub is_signed: bool,
+}
+
+/// A staging ID that is unknown, expired, or staged with another API key.
+#[derive(Debug)]
+pub(crate) struct StagingNotFound(pub String);
+
+impl std::fmt::Display for StagingNotFound {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "staged upload {} not found", self.0)
+    }
+}
+
+impl std::error::Error for StagingNotFound {}
+
+/// Staged uploads are kept under `STAGING_DIR_NAME` (default `staging`) of the tenant's bucket,
+/// apart from the published prefixes.
+fn staging_dir() -> String {
+    get_env_var("STAGING_DIR_NAME")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_STAGING_DIR.to_string())
+}
+
+/// Uncommitted uploads expire after `STAGING_TTL_SECS` (default a day).
+fn ttl() -> chrono::Duration {
+    let secs = get_env_var("STAGING_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    chrono::Duration::seconds(secs.unwrap_or(DEFAULT_TTL_SECS))
+}
+
+fn keys(staging_id: &str) -> (String, String) {
+    let body = format!("{}/{staging_id}", staging_dir());
+    let manifest = format!("{body}{MANIFEST_SUFFIX}");
+    (body, manifest)
+}
+
+// staging IDs are generated by `new_job_id`, anything else would address other objects
+fn is_staging_id(staging_id: &str) -> bool {
+    staging_id.len() == 32 && staging_id.bytes().all(|byte| byte.is_ascii_hexdigit())
+}
+
+/// Hold a validated upload in the staging prefix until committed, returning its staging ID and
+/// expiry.
+pub(crate) async fn stage(
+    tenant: &Tenant,
+    data: Vec<u8>,
+    content_type: &str,
+    tags: Vec<UploadTag>,
+    is_signed: bool,
+) -> Result<(String, DateTime<Utc>), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    let staging_id = new_job_id();
+    let (key_body, key_manifest) = keys(&staging_id);
+    let manifest = Manifest {
+        content_type: content_type.to_string(),
+        tags,
+        is_signed,
+        principal: provenance::current().principal,
+        staged_at: Utc::now(),
+    };
+
+    // the manifest goes first so the GC can expire a body whose staging was interrupted
+    let manifest_json = serde_json::to_vec(&manifest)?;
+    storage.put(bucket, &key_manifest, manifest_json, "application/json", None).await?;
+    if let Err(err) = storage.put(bucket, &key_body, data, content_type, None).await {
+        if let Err(rollback_err) = storage.delete(bucket, &key_manifest).await {
+            println!("STAGING: rollback of {key_manifest} failed: {rollback_err}");
+        }
+        return Err(err);
+    }
+    Ok((staging_id, manifest.staged_at + ttl()))
+}
+
+async fn manifest(
+    storage: &dyn StorageBackend,
+    bucket: &str,
+    staging_id: &str,
+) -> Result<Manifest, Error> {
+    let not_found = || StagingNotFound(staging_id.to_string());
+    if !is_staging_id(staging_id) {
+        return Err(not_found().into());
+    }
+    let (_, key_manifest) = keys(staging_id);
+    if !storage.exists(bucket, &key_manifest).await? {
+        return Err(not_found().into());
+    }
+    let manifest: Manifest = serde_json::from_slice(&storage.get(bucket, &key_manifest).await?)?;
+    let expired = manifest.staged_at + ttl() < Utc::now();
+    if expired || manifest.principal != provenance::current().principal {
+        return Err(not_found().into());
+    }
+    Ok(manifest)
+}
+
+/// Size of the body staged by the current request's API key, to reserve memory for before it is
+/// loaded, failing with `StagingNotFound`.
+pub(crate) async fn staged_size(tenant: &Tenant, staging_id: &str) -> Result<u64, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    manifest(storage.as_ref(), bucket, staging_id).await?;
+    let (key_body, _) = keys(staging_id);
+    let size = storage.size(bucket, &key_body).await?;
+    size.ok_or_else(|| StagingNotFound(staging_id.to_string()).into())
+}
+
+/// The upload staged by the current request's API key, failing with `StagingNotFound`.
+pub(crate) async fn load(tenant: &Tenant, staging_id: &str) -> Result<StagedUpload, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    let manifest = manifest(storage.as_ref(), bucket, staging_id).await?;
+    let (key_body, _) = keys(staging_id);
+    if !storage.exists(bucket, &key_body).await? {
+        return Err(StagingNotFound(staging_id.to_string()).into());
+    }
+    Ok(StagedUpload {
+        data: storage.get(bucket, &key_body).await?,
+        content_type: manifest.content_type,
+        tags: manifest.tags,
+        is_signed: manifest.is_signed,
+    })
+}
+
+async fn remove(storage: &dyn StorageBackend, bucket: &str, staging_id: &str) -> Result<(), Error> {
+    let (key_body, key_manifest) = keys(staging_id);
+    storage.delete(bucket, &key_body).await?;
+    storage.delete(bucket, &key_manifest).await
+}
+
+/// Drop an upload staged by the current request's API key, failing with `StagingNotFound`.
+pub(crate) async fn discard(tenant: &Tenant, staging_id: &str) -> Result<(), Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let storage = storage_backend().await?;
+    manifest(storage.as_ref(), bucket, staging_id).await?;
+    remove(storage.as_ref(), bucket, staging_id).await
+}
+
+/// Remove the tenant's uploads staged longer than `STAGING_TTL_SECS` ago and never committed,
+/// returning their staging IDs. With `dry_run` they are only listed.
+pub(crate) async fn purge_expired_stages(
+    storage: &dyn StorageBackend,
+    tenant: &Tenant,
+    dry_run: bool,
+    errors: &mut Vec<String>,
+) -> Result<Vec<String>, Error> {
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let prefix = format!("{}/", staging_dir());
+    let staged_before = Utc::now() - ttl();
+
+    let mut expired = Vec::new();
+    for object in list_all_objects(storage, bucket, &prefix).await? {
+        let Some(staging_id) = object
+            .key
+            .strip_prefix(&prefix)
+            .and_then(|name| name.strip_suffix(MANIFEST_SUFFIX))
+            .filter(|id| is_staging_id(id))
+        else {
+            continue;
+        };
+        let manifest = match storage.get(bucket, &object.key).await {
+            Ok(json) => serde_json::from_slice::<Manifest>(&json).map_err(Error::from),
+            Err(err) => Err(err),
+        };
+        match manifest {
+            Ok(manifest) if manifest.staged_at < staged_before => {}
+            Ok(_) => continue,
+            Err(err) => {
+                errors.push(format!("read {}: {err}", object.key));
+                continue;
+            }
+        }
+        if dry_run {
+            expired.push(staging_id.to_string());
+            continue;
+        }
+        match remove(storage, bucket, staging_id).await {
+            Ok(()) => expired.push(staging_id.to_string()),
+            Err(err) => errors.push(format!("expire staged {staging_id}: {err}")),
+        }
+    }
+    Ok(expired)
+}
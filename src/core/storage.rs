@@ -0,0 +1,244 @@
+use crate::core::{
+    chunked::with_chunking, failover::with_read_failover, replication::with_replication,
+    s3::S3Backend, utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use async_trait::async_trait;
+use std::{
+    path::{Component, Path, PathBuf},
+    time::Duration,
+};
+
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ObjectBody {
+    pub data: Vec<u8>,
+    pub content_type: Option<String>,
+    /// `Content-Encoding` the object was stored with, see `put_encoded`
+    pub content_encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ListPage {
+    pub objects: Vec<StoredObject>,
+    pub next_continuation_token: Option<String>,
+}
+
+/// Object storage operations the agent pipeline relies on.
+#[async_trait]
+pub trait StorageBackend: Send + Sync {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        content_type: &str,
+        tagging: Option<&str>,
+    ) -> Result<(), Error>;
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error>;
+
+    /// Like `get`, plus the stored content type when the backend keeps one.
+    async fn get_object(&self, bucket: &str, key: &str) -> Result<ObjectBody, Error> {
+        Ok(ObjectBody {
+            data: self.get(bucket, key).await?,
+            content_type: None,
+            content_encoding: None,
+        })
+    }
+
+    /// Whether `put_encoded` is supported, recording the encoding for `get_object`.
+    fn keeps_content_encoding(&self) -> bool {
+        false
+    }
+
+    /// Like `put`, `body` being encoded with `content_encoding`.
+    async fn put_encoded(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _body: Vec<u8>,
+        _content_type: &str,
+        content_encoding: &str,
+    ) -> Result<(), Error> {
+        Err(anyhow!("the storage backend can't record a {content_encoding} content encoding"))
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error>;
+
+    /// Size of the body `get` returns for `key`, `None` when there is no object, read without
+    /// loading it.
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        let page = self.list(bucket, key, None).await?;
+        Ok(page.objects.into_iter().find(|object| object.key == key).map(|object| object.size))
+    }
+
+    async fn presign(&self, bucket: &str, key: &str, expires_in: Duration)
+    -> Result<String, Error>;
+
+    /// URL a client can `PUT` a body of `content_type` and exactly `content_length` bytes to at
+    /// `key` until it expires.
+    async fn presign_put(
+        &self,
+        _bucket: &str,
+        _key: &str,
+        _content_type: &str,
+        _content_length: u64,
+        _expires_in: Duration,
+    ) -> Result<String, Error> {
+        Err(anyhow!("the storage backend can't presign uploads"))
+    }
+
+    /// List the objects directly under `prefix` (`/` delimited), one page at a time.
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        continuation_token: Option<String>,
+    ) -> Result<ListPage, Error>;
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error>;
+}
+
+/// Select the backend from `STORAGE_BACKEND` (`s3` by default, `fs` for local development),
+/// reading from the `READ_REPLICA_S3_*` endpoints when it fails, mirrored to the
+/// `REPLICA_S3_*` endpoint when one is configured and splitting bodies above
+/// `CHUNKED_STORAGE_THRESHOLD_BYTES` into chunks.
+pub(crate) async fn storage_backend() -> Result<Box<dyn StorageBackend>, Error> {
+    let primary = primary_backend().await?;
+    Ok(with_chunking(with_replication(with_read_failover(primary).await).await))
+}
+
+/// The `STORAGE_BACKEND` alone, without read failover, replication or chunking.
+pub(crate) async fn primary_backend() -> Result<Box<dyn StorageBackend>, Error> {
+    Ok(match get_env_var("STORAGE_BACKEND").unwrap_or_default().to_lowercase().as_str() {
+        "" | "s3" => Box::new(S3Backend::new().await?),
+        "fs" => Box::new(FsBackend::load()?),
+        other => return Err(anyhow!("unsupported STORAGE_BACKEND: {other}")),
+    })
+}
+
+/// Local filesystem backend storing objects at `{STORAGE_FS_ROOT}/{bucket}/{key}`.
+pub struct FsBackend {
+    root: PathBuf,
+}
+
+impl FsBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        FsBackend { root: root.into() }
+    }
+
+    fn load() -> Result<Self, Error> {
+        let root = get_env_var("STORAGE_FS_ROOT").unwrap_or_else(|_| "./data".to_string());
+        Ok(FsBackend::new(root))
+    }
+
+    fn object_path(&self, bucket: &str, key: &str) -> Result<PathBuf, Error> {
+        let relative = Path::new(bucket).join(key);
+        // keys must stay inside the bucket directory
+        if relative.components().any(|c| !matches!(c, Component::Normal(_))) {
+            return Err(anyhow!("invalid object key: {key}"));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+#[async_trait]
+impl StorageBackend for FsBackend {
+    async fn put(
+        &self,
+        bucket: &str,
+        key: &str,
+        body: Vec<u8>,
+        _content_type: &str,
+        _tagging: Option<&str>,
+    ) -> Result<(), Error> {
+        let path = self.object_path(bucket, key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, body).await?;
+        Ok(())
+    }
+
+    async fn get(&self, bucket: &str, key: &str) -> Result<Vec<u8>, Error> {
+        Ok(tokio::fs::read(self.object_path(bucket, key)?).await?)
+    }
+
+    async fn exists(&self, bucket: &str, key: &str) -> Result<bool, Error> {
+        Ok(tokio::fs::try_exists(self.object_path(bucket, key)?).await?)
+    }
+
+    async fn size(&self, bucket: &str, key: &str) -> Result<Option<u64>, Error> {
+        match tokio::fs::metadata(self.object_path(bucket, key)?).await {
+            Ok(metadata) => Ok(Some(metadata.len())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn presign(
+        &self,
+        bucket: &str,
+        key: &str,
+        _expires_in: Duration,
+    ) -> Result<String, Error> {
+        let path = std::path::absolute(self.object_path(bucket, key)?)?;
+        Ok(format!("file://{}", path.display()))
+    }
+
+    async fn list(
+        &self,
+        bucket: &str,
+        prefix: &str,
+        _continuation_token: Option<String>,
+    ) -> Result<ListPage, Error> {
+        let bucket_dir = self.object_path(bucket, "")?;
+        let mut objects = Vec::new();
+        let mut pending = vec![bucket_dir.clone()];
+
+        while let Some(dir) = pending.pop() {
+            let mut entries = match tokio::fs::read_dir(&dir).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            while let Some(entry) = entries.next_entry().await? {
+                let metadata = entry.metadata().await?;
+                if metadata.is_dir() {
+                    pending.push(entry.path());
+                    continue;
+                }
+                let key = entry
+                    .path()
+                    .strip_prefix(&bucket_dir)?
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                // mimic the S3 `/` delimiter: only keys directly under the prefix
+                let direct_child =
+                    key.strip_prefix(prefix).map(|rest| !rest.contains('/')).unwrap_or(false);
+                if direct_child {
+                    objects.push(StoredObject { key, size: metadata.len() });
+                }
+            }
+        }
+
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(ListPage { objects, next_continuation_token: None })
+    }
+
+    async fn delete(&self, bucket: &str, key: &str) -> Result<(), Error> {
+        match tokio::fs::remove_file(self.object_path(bucket, key)?).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
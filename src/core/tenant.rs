@@ -0,0 +1,87 @@
+use crate::core::utils::get_env_var;
+use anyhow::{Error, anyhow};
+use headers::HeaderMap;
+use serde::Deserialize;
+
+/// Storage and index namespace of an application served by the agent.
+///
+/// Tenants are configured through the `TENANTS` env var as a JSON array, e.g.
+/// `[{"name":"app1","bucket":"app1-bucket","dir_name":"dataitems","raw_dir_name":"raw",
+/// "api_keys":["key1"]}]`. Unset buckets/prefixes fall back to the agent defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Tenant {
+    /// empty for the default (untenanted) namespace
+    pub name: String,
+    #[serde(default)]
+    pub bucket: Option<String>,
+    #[serde(default)]
+    pub dir_name: Option<String>,
+    #[serde(default)]
+    pub raw_dir_name: Option<String>,
+    /// previous `dir_name`s still searched by reads, newest first
+    #[serde(default)]
+    pub legacy_dir_names: Option<Vec<String>>,
+    #[serde(default)]
+    pub legacy_raw_dir_names: Option<Vec<String>>,
+    /// keys routed to this tenant, a tenant without keys is selectable via `x-tenant`
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+}
+
+impl Tenant {
+    pub fn is_default(&self) -> bool {
+        self.name.is_empty()
+    }
+}
+
+pub(crate) fn load_tenants() -> Result<Vec<Tenant>, Error> {
+    match get_env_var("TENANTS") {
+        Ok(raw) if !raw.trim().is_empty() => {
+            let tenants: Vec<Tenant> = serde_json::from_str(&raw)
+                .map_err(|err| anyhow!("invalid TENANTS config: {err}"))?;
+            if tenants.iter().any(|tenant| tenant.is_default()) {
+                return Err(anyhow!("invalid TENANTS config: tenant name must not be empty"));
+            }
+            Ok(tenants)
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// The default tenant followed by every configured one.
+pub(crate) fn all_tenants() -> Result<Vec<Tenant>, Error> {
+    let mut tenants = vec![Tenant::default()];
+    tenants.extend(load_tenants()?);
+    Ok(tenants)
+}
+
+/// The configured tenant called `name`, the default tenant when it is empty.
+pub fn tenant_by_name(name: &str) -> Result<Tenant, Error> {
+    all_tenants()?
+        .into_iter()
+        .find(|tenant| tenant.name == name)
+        .ok_or_else(|| anyhow!("unknown tenant {name:?}"))
+}
+
+/// Resolve the request tenant: an API key bound to a tenant always wins, otherwise the
+/// `x-tenant` header may select a tenant that has no keys bound to it.
+pub(crate) fn resolve_tenant(headers: &HeaderMap, token: Option<&str>) -> Result<Tenant, Error> {
+    let tenants = load_tenants()?;
+
+    let bound = token.and_then(|token| {
+        tenants.iter().find(|tenant| tenant.api_keys.iter().any(|key| key == token))
+    });
+    if let Some(tenant) = bound {
+        return Ok(tenant.clone());
+    }
+
+    let Some(requested) = headers.get("x-tenant").and_then(|h| h.to_str().ok()) else {
+        return Ok(Tenant::default());
+    };
+
+    match tenants.into_iter().find(|tenant| tenant.name == requested) {
+        Some(tenant) if tenant.api_keys.is_empty() => Ok(tenant),
+        Some(_) => Err(anyhow!("tenant {requested} requires one of its bound API keys")),
+        None => Err(anyhow!("unknown tenant: {requested}")),
+    }
+}
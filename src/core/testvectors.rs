@@ -0,0 +1,320 @@
+use crate::core::utils::sha256_hex;
+use base64::{Engine as _, engine::general_purpose::URL_SAFE_NO_PAD};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use utoipa::ToSchema;
+
+static TEST_VECTORS: Lazy<Vec<TestVector>> =
+    Lazy::new(|| VECTORS.iter().map(build_test_vector).collect());
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct TestVectorTag {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Clone, ToSchema)]
+pub struct TestVector {
+    pub name: String,
+    pub content_type: String,
+    /// base64url (no padding) of the raw data
+    pub data: String,
+    pub input_tags: Vec<TestVectorTag>,
+    /// tags as they appear in the signed dataitem, injected tags included
+    pub tags: Vec<TestVectorTag>,
+    /// base64url (no padding) of the signer's RSA modulus
+    pub owner: String,
+    pub owner_address: String,
+    pub dataitem_id: String,
+    /// base64url (no padding) of the RSA-PSS signature
+    pub signature: String,
+    /// base64url (no padding) of the ANS-104 serialized dataitem
+    pub dataitem: String,
+    pub data_sha256: String,
+    pub dataitem_sha256: String,
+    pub dataitem_size: usize,
+}
+
+struct FixedVector {
+    name: &'static str,
+    content_type: &'static str,
+    data: &'static [u8],
+    input_tags: &'static [(&'static str, &'static str)],
+    tags: &'static [(&'static str, &'static str)],
+    dataitem_id: &'static str,
+    signature: &'static str,
+    dataitem: &'static str,
+    dataitem_sha256: &'static str,
+    dataitem_size: usize,
+}
+
+/// Public modulus of the throwaway key the vectors were signed with. The
+/// private half is not kept anywhere, the key only exists to pin the bytes.
+const SIGNER_OWNER: &str = concat!(
+    "uRU6V1oEg5vpDJurZgkRQqZ9BqGhTwFcIQH-xiS7amNx931dqHvwNe0JSz8KJiPZt8hedCH1ibZZ67gFifMzzAYH",
+    "Z2pA2pvqrsrnBualxYYrM45Rpudec2PEaUji8eG9PX1oJi9sqPs7IByevcFXBCBDmHtzDK5VfIcVb1sCkk_l5HbU",
+    "0lGd4sl7wMNkq7y4UtIyPRxtjXOffFhQ9eolaTCev1km6v8WVHHJXUtV3GIUrL1ZYEszpfzRO2unPg6O3bjVOP7r",
+    "wUOtFVa1YDi8uteyAqjQ33ZU9HtNbu_Lfbk3UiovrOMNTOJUAu231cOl1JZ9HXwoYHIgKZCWemp8QQlNdSA8KrGA",
+    "SfhdcQa7ZL4ZrqLisQ67RsIZvFxdKjdXXOLRCpg7Pxe2W_g8XFnyQNz_OytzW0uNhUqyRk04jYHTbrEaWIUoKrDt",
+    "W3V5OPZAHT2x-Z2gCBIBnmJ8ZynMzIB5i2EgQihEC8siDmb723D1Okm3O5YFXsXkGt6mdQNSGI0lJmcjNGzUNo2H",
+    "2JS_LQ-qTBQvhDmCtus5N6SFaEmjaPqLCVzJ2RlVgiHKuV-LFbXVRIKMKLvHTtG653PCi2XPRjL2s3iGYbohMT3P",
+    "37NnTIiyTBTDF1SJmSj-l-3ISnygMiLCGCpjyUPcgEeCdv6ICclPC8KvriA_2-AKIDU",
+);
+const SIGNER_ADDRESS: &str = "cceLpK18WrXnYnrFnIWWBVpGBn9Ui_brEFYdTieilGA";
+
+const VECTORS: [FixedVector; 4] = [
+    FixedVector {
+        name: "plain-text",
+        content_type: "text/plain",
+        data: b"hello world",
+        input_tags: &[],
+        tags: &[
+            ("Content-Type", "text/plain"),
+            ("Storage-Provider", "Load-S3"),
+            ("Agent-Version", "agent@0.7.0"),
+            ("SHA-256", "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"),
+        ],
+        dataitem_id: "ZSobWuj5BXs9qPoIUdOzSRH5SE7UPFz-0i-a2nxr2sk",
+        signature: concat!(
+            "MQ8EaHef8puKnWeUpAVyQy-zT28xcKfO6c8pXyivLtGAft34nOoZJMAzeLdyfWJfBIFTHjPYGRegPLjrofMYwngp",
+            "j3FrgEX2Rkuh5dYM07wizX7wVa8o8xbWox-lQgJmw7E9AWjzq54N7B3bGNCu_B_SEhNkrrGgvERVPPjItY4THeYl",
+            "B0i7m3ndhw_M5Ff8xKsMUyBOg0s6yQDerlSRfSmuMC9Gt4ifAn-q6rdvAjYq0ozHT8kI9EZ8cws5WyzIVbL5ynue",
+            "yaPQw-ruktTCgKMoOzICxVAzcf-rzuu5Pc147nSMOdsIP843zs866gU6eB6V5CQI3nUxVjd-_aoVLHv7OWJjku6e",
+            "Ia2X_91vKilKH-IKVzY0MXBRDnwGQLbJJPF20XUKDRh2sGT_O-dux44zsAOEQgHxUQ24q8QHsb8u08dMiiT5OrDd",
+            "rLsMyO9Ofem718ydpHNmfGsACjEQ2MRzkO0Ygw23tqpFNTURAnM-y490q0JY_7E7foYOtexhSUXqD05uaIAWGfzU",
+            "u9oDfE6Qcovtvt8ULC-pklYVcdGQBKOWHC1mSMi0htd_Yg3a9ZU7hkf90wwfP-J2ILdULOTgt6wGl71hbhIYY9DB",
+            "oVqTh-vBmDZLKmRK0q1kLcZ0gUmyO0qcpsaehRWPaWjiiMUt37xno-h0PYf1U0md8f0",
+        ),
+        dataitem: concat!(
+            "AQAxDwRod5_ym4qdZ5SkBXJDL7NPbzFwp87pzylfKK8u0YB-3fic6hkkwDN4t3J9Yl8EgVMeM9gZF6A8uOuh8xjC",
+            "eCmPcWuARfZGS6Hl1gzTvCLNfvBVryjzFtajH6VCAmbDsT0BaPOrng3sHdsY0K78H9ISE2SusaC8RFU8-Mi1jhMd",
+            "5iUHSLubed2HD8zkV_zEqwxTIE6DSzrJAN6uVJF9Ka4wL0a3iJ8Cf6rqt28CNirSjMdPyQj0RnxzCzlbLMhVsvnK",
+            "e57Jo9DD6u6S1MKAoyg7MgLFUDNx_6vO67k9zXjudIw52wg_zjfOzzrqBTp4HpXkJAjedTFWN379qhUse_s5YmOS",
+            "7p4hrZf_3W8qKUof4gpXNjQxcFEOfAZAtskk8XbRdQoNGHawZP87527HjjOwA4RCAfFRDbirxAexvy7Tx0yKJPk6",
+            "sN2suwzI70596bvXzJ2kc2Z8awAKMRDYxHOQ7RiDDbe2qkU1NRECcz7Lj3SrQlj_sTt-hg617GFJReoPTm5ogBYZ",
+            "_NS72gN8TpByi-2-3xQsL6mSVhVx0ZAEo5YcLWZIyLSG139iDdr1lTuGR_3TDB8_4nYgt1Qs5OC3rAaXvWFuEhhj",
+            "0MGhWpOH68GYNksqZErSrWQtxnSBSbI7Spymxp6FFY9paOKIxS3fvGej6HQ9h_VTSZ3x_bkVOldaBIOb6Qybq2YJ",
+            "EUKmfQahoU8BXCEB_sYku2pjcfd9Xah78DXtCUs_CiYj2bfIXnQh9Ym2Weu4BYnzM8wGB2dqQNqb6q7K5wbmpcWG",
+            "KzOOUabnXnNjxGlI4vHhvT19aCYvbKj7OyAcnr3BVwQgQ5h7cwyuVXyHFW9bApJP5eR21NJRneLJe8DDZKu8uFLS",
+            "Mj0cbY1zn3xYUPXqJWkwnr9ZJur_FlRxyV1LVdxiFKy9WWBLM6X80Ttrpz4Ojt241Tj-68FDrRVWtWA4vLrXsgKo",
+            "0N92VPR7TW7vy325N1IqL6zjDUziVALtt9XDpdSWfR18KGByICmQlnpqfEEJTXUgPCqxgEn4XXEGu2S-Ga6i4rEO",
+            "u0bCGbxcXSo3V1zi0QqYOz8Xtlv4PFxZ8kDc_zsrc1tLjYVKskZNOI2B026xGliFKCqw7Vt1eTj2QB09sfmdoAgS",
+            "AZ5ifGcpzMyAeYthIEIoRAvLIg5m-9tw9TpJtzuWBV7F5BrepnUDUhiNJSZnIzRs1DaNh9iUvy0PqkwUL4Q5grbr",
+            "OTekhWhJo2j6iwlcydkZVYIhyrlfixW11USCjCi7x07Ruudzwotlz0Yy9rN4hmG6ITE9z9-zZ0yIskwUwxdUiZko",
+            "_pftyEp8oDIiwhgqY8lD3IBHgnb-iAnJTwvCr64gP9vgCiA1AAAEAAAAAAAAAJcAAAAAAAAACBhDb250ZW50LVR5",
+            "cGUUdGV4dC9wbGFpbiBTdG9yYWdlLVByb3ZpZGVyDkxvYWQtUzMaQWdlbnQtVmVyc2lvbhZhZ2VudEAwLjcuMA5T",
+            "SEEtMjU2gAFiOTRkMjdiOTkzNGQzZTA4YTUyZTUyZDdkYTdkYWJmYWM0ODRlZmUzN2E1MzgwZWU5MDg4ZjdhY2Uy",
+            "ZWZjZGU5AGhlbGxvIHdvcmxk",
+        ),
+        dataitem_sha256: "2c5529f84daf54d3d433268076c41ed68d04a23ab3fbae6c12c8233edeaf1648",
+        dataitem_size: 1206,
+    },
+    FixedVector {
+        name: "custom-tags",
+        content_type: "application/json",
+        data: br#"{"hello":"world"}"#,
+        input_tags: &[("App-Name", "load-s3-agent"), ("tag1", "tag1")],
+        tags: &[
+            ("Content-Type", "application/json"),
+            ("Storage-Provider", "Load-S3"),
+            ("Agent-Version", "agent@0.7.0"),
+            ("SHA-256", "93a23971a914e5eacbf0a8d25154cda309c3c1c72fbb9914d47c60f3cb681588"),
+            ("App-Name", "load-s3-agent"),
+            ("tag1", "tag1"),
+        ],
+        dataitem_id: "JyBAxvaZkzr84ckjOVyJg0zd0wsmf_oUD29KNzNF4EY",
+        signature: concat!(
+            "Z3zrvmoEjHvQOjhgAGnXn5SzfQciQliTpK82EJ15lJPp-LVbF4GTl3HNEBOioc1hPHVuhhysW0xK-sIGbJvOz7zq",
+            "cyGBrGrncI6hH0Hu7xXQonHVNEIPrwJ1snS3zNEESaKBm299I4hAL5hCwZrRs3FdWEF_QyyRoJLyiltAauJH5s0o",
+            "gCnaNyuFdtrEg6DSHO78gGH3lhDJWre3gDKbpSFHGKxGqW8D0SqtmVSouA4Hxw5uKDA6AKB8W2IS1QQ9Pt7QMaxq",
+            "lz0MlGVw7eVqQnA7akB50m2ASIVAGVcthKaQ2q5Li7wfZW6eG-A3Xh_gVq_jjk4j6a8CucAbkGffrC2-27asuruJ",
+            "jxaLw2uUn73lb8dFBPO7MOg5sF5aNDrNyxj6QUWP319GYggMe8Jm4y8s2r9OgHsHY1eS-u2J2ys-0GSqPGchZLEL",
+            "o8MBxXbnzbAYUkkBd1fmYKhVrXzQ0rKWr0aCXLJs-j_-8BoDSb-WYjP5wGBkKLz8Wc_yf2WDkzd2OV_AWQMHED_G",
+            "jvKdPG7YvzC9zvo5kLwMfvSvD8mAhu7rt2xx_VevTkw0I7jpzCvir6bU9KktbfCx-RV6Z-inWKQSQ8kEqwk0yqBB",
+            "PHuLP8wbPhbmtfbHV-L061Sibm9EkJNzcPfQLovVsZ7NGgstQOCJaL6nBxUoNIvuykI",
+        ),
+        dataitem: concat!(
+            "AQBnfOu-agSMe9A6OGAAadeflLN9ByJCWJOkrzYQnXmUk-n4tVsXgZOXcc0QE6KhzWE8dW6GHKxbTEr6wgZsm87P",
+            "vOpzIYGsaudwjqEfQe7vFdCicdU0Qg-vAnWydLfM0QRJooGbb30jiEAvmELBmtGzcV1YQX9DLJGgkvKKW0Bq4kfm",
+            "zSiAKdo3K4V22sSDoNIc7vyAYfeWEMlat7eAMpulIUcYrEapbwPRKq2ZVKi4DgfHDm4oMDoAoHxbYhLVBD0-3tAx",
+            "rGqXPQyUZXDt5WpCcDtqQHnSbYBIhUAZVy2EppDarkuLvB9lbp4b4DdeH-BWr-OOTiPprwK5wBuQZ9-sLb7btqy6",
+            "u4mPFovDa5SfveVvx0UE87sw6DmwXlo0Os3LGPpBRY_fX0ZiCAx7wmbjLyzav06AewdjV5L67YnbKz7QZKo8ZyFk",
+            "sQujwwHFdufNsBhSSQF3V-ZgqFWtfNDSspavRoJcsmz6P_7wGgNJv5ZiM_nAYGQovPxZz_J_ZYOTN3Y5X8BZAwcQ",
+            "P8aO8p08bti_ML3O-jmQvAx-9K8PyYCG7uu3bHH9V69OTDQjuOnMK-KvptT0qS1t8LH5FXpn6KdYpBJDyQSrCTTK",
+            "oEE8e4s_zBs-Fua19sdX4vTrVKJub0SQk3Nw99Aui9Wxns0aCy1A4IlovqcHFSg0i-7KQrkVOldaBIOb6Qybq2YJ",
+            "EUKmfQahoU8BXCEB_sYku2pjcfd9Xah78DXtCUs_CiYj2bfIXnQh9Ym2Weu4BYnzM8wGB2dqQNqb6q7K5wbmpcWG",
+            "KzOOUabnXnNjxGlI4vHhvT19aCYvbKj7OyAcnr3BVwQgQ5h7cwyuVXyHFW9bApJP5eR21NJRneLJe8DDZKu8uFLS",
+            "Mj0cbY1zn3xYUPXqJWkwnr9ZJur_FlRxyV1LVdxiFKy9WWBLM6X80Ttrpz4Ojt241Tj-68FDrRVWtWA4vLrXsgKo",
+            "0N92VPR7TW7vy325N1IqL6zjDUziVALtt9XDpdSWfR18KGByICmQlnpqfEEJTXUgPCqxgEn4XXEGu2S-Ga6i4rEO",
+            "u0bCGbxcXSo3V1zi0QqYOz8Xtlv4PFxZ8kDc_zsrc1tLjYVKskZNOI2B026xGliFKCqw7Vt1eTj2QB09sfmdoAgS",
+            "AZ5ifGcpzMyAeYthIEIoRAvLIg5m-9tw9TpJtzuWBV7F5BrepnUDUhiNJSZnIzRs1DaNh9iUvy0PqkwUL4Q5grbr",
+            "OTekhWhJo2j6iwlcydkZVYIhyrlfixW11USCjCi7x07Ruudzwotlz0Yy9rN4hmG6ITE9z9-zZ0yIskwUwxdUiZko",
+            "_pftyEp8oDIiwhgqY8lD3IBHgnb-iAnJTwvCr64gP9vgCiA1AAAGAAAAAAAAAL4AAAAAAAAADBhDb250ZW50LVR5",
+            "cGUgYXBwbGljYXRpb24vanNvbiBTdG9yYWdlLVByb3ZpZGVyDkxvYWQtUzMaQWdlbnQtVmVyc2lvbhZhZ2VudEAw",
+            "LjcuMA5TSEEtMjU2gAE5M2EyMzk3MWE5MTRlNWVhY2JmMGE4ZDI1MTU0Y2RhMzA5YzNjMWM3MmZiYjk5MTRkNDdj",
+            "NjBmM2NiNjgxNTg4EEFwcC1OYW1lGmxvYWQtczMtYWdlbnQIdGFnMQh0YWcxAHsiaGVsbG8iOiJ3b3JsZCJ9",
+        ),
+        dataitem_sha256: "d19be77e1a583edb879b1eaff53dde4deb7412a7460feb1cc237a844be245e65",
+        dataitem_size: 1251,
+    },
+    FixedVector {
+        name: "content-type-tag-override",
+        content_type: "application/octet-stream",
+        data: b"<html></html>",
+        input_tags: &[("Content-Type", "text/html")],
+        tags: &[
+            ("Storage-Provider", "Load-S3"),
+            ("Agent-Version", "agent@0.7.0"),
+            ("SHA-256", "b633a587c652d02386c4f16f8c6f6aab7352d97f16367c3c40576214372dd628"),
+            ("Content-Type", "text/html"),
+        ],
+        dataitem_id: "eJMMKTp85cwH2JcavgA6xyMwa2oKEZCSD9DjCFJn17I",
+        signature: concat!(
+            "oTpaCBmnFnoWVhdV22UB_boJLxu_drHDQ7Q8pwwRO_HoYZYj7jW4ok6iZi6KHsSs_33IMKSh28ZFinvfWg1HSrV8",
+            "gykO6kgpDxALUPonAVw9Rv1axVsmcjdntHGFjZ9pgTEfhEoRDwTj-0Wx_n6Nn6jSgb7X0w8QJ2cSIhLWvFQohwqp",
+            "v6IREGdVTdtKpGZKWZU7A0Pg7XgAWkqDrneal_Hi7A-I8bEcPpLOfz-66a6y5CIWqMkaU37q1wdVd1tl70Ooc0ID",
+            "vD0z8yJHvVZjqN5EME9JB0g8Lsk2YyM5GPRXdn1v3PJci57ZKNKEl4XEnxdfDGj0-jiEmXMXVIlxoVO3kh6_epds",
+            "IoNyQeGU5cCcbAPMCXEalqf0Y0ObPe3o9PiBbDxBuFQ5f3CplPBeFOBQvOB4K_Cuv8sYfYnS62KvWBO7JJkCtDwZ",
+            "0gvI0hGjTG6RTHGZAfd4dkdRpDqt98WnDR4uItiLS9JaRa1hzP7VPgIL9Zl0HGIXVdBUqqRSvFdaTa4Diismpy4Y",
+            "MA2d_esu420Ht0rWSmP2-TAKFMMiLN7Swaej3wUTPZG-nPkX5Y2DWgT9tS-GYlmvVua51DBEQloX5sJZkgNwrhqy",
+            "AOp--xxsU6EWmQEprZBToxSBxV1lRgV-iQCgaqVTboP00j5_anIJUPPbfaVI9FGNbXA",
+        ),
+        dataitem: concat!(
+            "AQChOloIGacWehZWF1XbZQH9ugkvG792scNDtDynDBE78ehhliPuNbiiTqJmLooexKz_fcgwpKHbxkWKe99aDUdK",
+            "tXyDKQ7qSCkPEAtQ-icBXD1G_VrFWyZyN2e0cYWNn2mBMR-EShEPBOP7RbH-fo2fqNKBvtfTDxAnZxIiEta8VCiH",
+            "Cqm_ohEQZ1VN20qkZkpZlTsDQ-DteABaSoOud5qX8eLsD4jxsRw-ks5_P7rprrLkIhaoyRpTfurXB1V3W2XvQ6hz",
+            "QgO8PTPzIke9VmOo3kQwT0kHSDwuyTZjIzkY9Fd2fW_c8lyLntko0oSXhcSfF18MaPT6OISZcxdUiXGhU7eSHr96",
+            "l2wig3JB4ZTlwJxsA8wJcRqWp_RjQ5s97ej0-IFsPEG4VDl_cKmU8F4U4FC84Hgr8K6_yxh9idLrYq9YE7skmQK0",
+            "PBnSC8jSEaNMbpFMcZkB93h2R1GkOq33xacNHi4i2ItL0lpFrWHM_tU-Agv1mXQcYhdV0FSqpFK8V1pNrgOKKyan",
+            "LhgwDZ396y7jbQe3StZKY_b5MAoUwyIs3tLBp6PfBRM9kb6c-RfljYNaBP21L4ZiWa9W5rnUMERCWhfmwlmSA3Cu",
+            "GrIA6n77HGxToRaZASmtkFOjFIHFXWVGBX6JAKBqpVNug_TSPn9qcglQ89t9pUj0UY1tcLkVOldaBIOb6Qybq2YJ",
+            "EUKmfQahoU8BXCEB_sYku2pjcfd9Xah78DXtCUs_CiYj2bfIXnQh9Ym2Weu4BYnzM8wGB2dqQNqb6q7K5wbmpcWG",
+            "KzOOUabnXnNjxGlI4vHhvT19aCYvbKj7OyAcnr3BVwQgQ5h7cwyuVXyHFW9bApJP5eR21NJRneLJe8DDZKu8uFLS",
+            "Mj0cbY1zn3xYUPXqJWkwnr9ZJur_FlRxyV1LVdxiFKy9WWBLM6X80Ttrpz4Ojt241Tj-68FDrRVWtWA4vLrXsgKo",
+            "0N92VPR7TW7vy325N1IqL6zjDUziVALtt9XDpdSWfR18KGByICmQlnpqfEEJTXUgPCqxgEn4XXEGu2S-Ga6i4rEO",
+            "u0bCGbxcXSo3V1zi0QqYOz8Xtlv4PFxZ8kDc_zsrc1tLjYVKskZNOI2B026xGliFKCqw7Vt1eTj2QB09sfmdoAgS",
+            "AZ5ifGcpzMyAeYthIEIoRAvLIg5m-9tw9TpJtzuWBV7F5BrepnUDUhiNJSZnIzRs1DaNh9iUvy0PqkwUL4Q5grbr",
+            "OTekhWhJo2j6iwlcydkZVYIhyrlfixW11USCjCi7x07Ruudzwotlz0Yy9rN4hmG6ITE9z9-zZ0yIskwUwxdUiZko",
+            "_pftyEp8oDIiwhgqY8lD3IBHgnb-iAnJTwvCr64gP9vgCiA1AAAEAAAAAAAAAJYAAAAAAAAACCBTdG9yYWdlLVBy",
+            "b3ZpZGVyDkxvYWQtUzMaQWdlbnQtVmVyc2lvbhZhZ2VudEAwLjcuMA5TSEEtMjU2gAFiNjMzYTU4N2M2NTJkMDIz",
+            "ODZjNGYxNmY4YzZmNmFhYjczNTJkOTdmMTYzNjdjM2M0MDU3NjIxNDM3MmRkNjI4GENvbnRlbnQtVHlwZRJ0ZXh0",
+            "L2h0bWwAPGh0bWw-PC9odG1sPg",
+        ),
+        dataitem_sha256: "a335a79b9bd48a5f7f181c2f9bf3d8e927d231f74c955b7c82ddac099070c257",
+        dataitem_size: 1207,
+    },
+    FixedVector {
+        name: "reserved-tags-dropped",
+        content_type: "application/octet-stream",
+        data: &[0, 1, 2, 3, 255],
+        input_tags: &[("Storage-Provider", "spoofed"), ("Agent-Version", "spoofed")],
+        tags: &[
+            ("Content-Type", "application/octet-stream"),
+            ("Storage-Provider", "Load-S3"),
+            ("Agent-Version", "agent@0.7.0"),
+            ("SHA-256", "ff5d8507b6a72bee2debce2c0054798deaccdc5d8a1b945b6280ce8aa9cba52e"),
+        ],
+        dataitem_id: "wTDswZnLeSz63Nz5u6p-1COcFyhwfOYsE5JJkzaK5dM",
+        signature: concat!(
+            "NUrJrKS_EL7hRnQUdTFvLCbNQBnxsHUCnSCpUMgE-PlorrjI2JDETHRNg6DR9YGywFJIYUhijcw0l90F-mMWCfeG",
+            "QLAWD-L4WE3oGklmZomhieNtppkMTmyo9AohaZBc0GuadBRjUHU-6yXDoEnj3u75kZYBv9CnYOhqc2mRotidKy4D",
+            "ryprGn9qQEj1AIXwx963qV2G0OQPTWBeodvViVarlsGNOPRF-bV6VFetjnUKXKgLeSoweO5-wzvng7zLlvFTp_Iq",
+            "_x9cXVSULeMNfnrtKHDORC2m5KFlLAwFJL1T1CLQZ--1xmpT9BTwy5-tC2aHl4F7qPh6Cc_gKZ0sS9oe089hrkt7",
+            "dumt9sx3ph3WWdi4_OalF2PgRIdOQOX9R2GtXQPf0LG3qpklboGnWjtfbpTr6rp6LxhQhVHlXj79pNRUKKq2RaV8",
+            "4mvwUnsY19tyJpOld3iED0X6NWgaG6jDVhu-7MwGfzFBAnhU8Hq52m8P3OoR91qGrv4vD2UQoSc9KIT1TwEgPJKU",
+            "wubPm3XyLc_oB81bjHf30TeoGbF54mtYcACRg2CgrFey1qEsWpIMl3woaOXKiQf-M_sn7yOMqt6xZYb9CLOuuuhk",
+            "ly3OYPAfvco_XnVxBS9A5TbmlbJPBNDSizyHpZr_p4gXhn0EoZH8vQK-5NkMc40y8ow",
+        ),
+        dataitem: concat!(
+            "AQA1SsmspL8QvuFGdBR1MW8sJs1AGfGwdQKdIKlQyAT4-WiuuMjYkMRMdE2DoNH1gbLAUkhhSGKNzDSX3QX6YxYJ",
+            "94ZAsBYP4vhYTegaSWZmiaGJ422mmQxObKj0CiFpkFzQa5p0FGNQdT7rJcOgSePe7vmRlgG_0Kdg6GpzaZGi2J0r",
+            "LgOvKmsaf2pASPUAhfDH3repXYbQ5A9NYF6h29WJVquWwY049EX5tXpUV62OdQpcqAt5KjB47n7DO-eDvMuW8VOn",
+            "8ir_H1xdVJQt4w1-eu0ocM5ELabkoWUsDAUkvVPUItBn77XGalP0FPDLn60LZoeXgXuo-HoJz-ApnSxL2h7Tz2Gu",
+            "S3t26a32zHemHdZZ2Lj85qUXY-BEh05A5f1HYa1dA9_QsbeqmSVugadaO19ulOvqunovGFCFUeVePv2k1FQoqrZF",
+            "pXzia_BSexjX23Imk6V3eIQPRfo1aBobqMNWG77szAZ_MUECeFTwernabw_c6hH3Woau_i8PZRChJz0ohPVPASA8",
+            "kpTC5s-bdfItz-gHzVuMd_fRN6gZsXnia1hwAJGDYKCsV7LWoSxakgyXfCho5cqJB_4z-yfvI4yq3rFlhv0Is666",
+            "6GSXLc5g8B-9yj9edXEFL0DlNuaVsk8E0NKLPIelmv-niBeGfQShkfy9Ar7k2QxzjTLyjLkVOldaBIOb6Qybq2YJ",
+            "EUKmfQahoU8BXCEB_sYku2pjcfd9Xah78DXtCUs_CiYj2bfIXnQh9Ym2Weu4BYnzM8wGB2dqQNqb6q7K5wbmpcWG",
+            "KzOOUabnXnNjxGlI4vHhvT19aCYvbKj7OyAcnr3BVwQgQ5h7cwyuVXyHFW9bApJP5eR21NJRneLJe8DDZKu8uFLS",
+            "Mj0cbY1zn3xYUPXqJWkwnr9ZJur_FlRxyV1LVdxiFKy9WWBLM6X80Ttrpz4Ojt241Tj-68FDrRVWtWA4vLrXsgKo",
+            "0N92VPR7TW7vy325N1IqL6zjDUziVALtt9XDpdSWfR18KGByICmQlnpqfEEJTXUgPCqxgEn4XXEGu2S-Ga6i4rEO",
+            "u0bCGbxcXSo3V1zi0QqYOz8Xtlv4PFxZ8kDc_zsrc1tLjYVKskZNOI2B026xGliFKCqw7Vt1eTj2QB09sfmdoAgS",
+            "AZ5ifGcpzMyAeYthIEIoRAvLIg5m-9tw9TpJtzuWBV7F5BrepnUDUhiNJSZnIzRs1DaNh9iUvy0PqkwUL4Q5grbr",
+            "OTekhWhJo2j6iwlcydkZVYIhyrlfixW11USCjCi7x07Ruudzwotlz0Yy9rN4hmG6ITE9z9-zZ0yIskwUwxdUiZko",
+            "_pftyEp8oDIiwhgqY8lD3IBHgnb-iAnJTwvCr64gP9vgCiA1AAAEAAAAAAAAAKUAAAAAAAAACBhDb250ZW50LVR5",
+            "cGUwYXBwbGljYXRpb24vb2N0ZXQtc3RyZWFtIFN0b3JhZ2UtUHJvdmlkZXIOTG9hZC1TMxpBZ2VudC1WZXJzaW9u",
+            "FmFnZW50QDAuNy4wDlNIQS0yNTaAAWZmNWQ4NTA3YjZhNzJiZWUyZGViY2UyYzAwNTQ3OThkZWFjY2RjNWQ4YTFi",
+            "OTQ1YjYyODBjZThhYTljYmE1MmUAAAECA_8",
+        ),
+        dataitem_sha256: "7c72fb062882aa4c1d460cec7eb0d7d8a2dd0fdcefcb4435547d9da1a122ca90",
+        dataitem_size: 1214,
+    },
+];
+
+/// Canonical dataitems as the agent's ANS-104 pipeline builds them from the
+/// fixed inputs, with agent 0.7.0's default tags. RSA-PSS signatures are
+/// salted, so the dataitems were signed once offline and are shipped as is:
+/// every agent serves byte-identical vectors.
+pub(crate) fn get_test_vectors() -> &'static [TestVector] {
+    &TEST_VECTORS
+}
+
+fn to_tags(tags: &[(&str, &str)]) -> Vec<TestVectorTag> {
+    tags.iter().map(|(k, v)| TestVectorTag { name: k.to_string(), value: v.to_string() }).collect()
+}
+
+fn build_test_vector(vector: &FixedVector) -> TestVector {
+    TestVector {
+        name: vector.name.to_string(),
+        content_type: vector.content_type.to_string(),
+        data: URL_SAFE_NO_PAD.encode(vector.data),
+        input_tags: to_tags(vector.input_tags),
+        tags: to_tags(vector.tags),
+        owner: SIGNER_OWNER.to_string(),
+        owner_address: SIGNER_ADDRESS.to_string(),
+        dataitem_id: vector.dataitem_id.to_string(),
+        signature: vector.signature.to_string(),
+        dataitem: vector.dataitem.to_string(),
+        data_sha256: sha256_hex(vector.data),
+        dataitem_sha256: vector.dataitem_sha256.to_string(),
+        dataitem_size: vector.dataitem_size,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn decode(value: &str) -> Vec<u8> {
+        URL_SAFE_NO_PAD.decode(value).unwrap()
+    }
+
+    #[test]
+    fn vectors_are_self_consistent() {
+        let owner = decode(SIGNER_OWNER);
+        assert_eq!(owner.len(), 512);
+        assert_eq!(URL_SAFE_NO_PAD.encode(Sha256::digest(&owner)), SIGNER_ADDRESS);
+
+        for vector in get_test_vectors() {
+            let dataitem = decode(&vector.dataitem);
+            let signature = decode(&vector.signature);
+            let data = decode(&vector.data);
+
+            assert_eq!(dataitem.len(), vector.dataitem_size, "{}", vector.name);
+            assert_eq!(sha256_hex(&dataitem), vector.dataitem_sha256, "{}", vector.name);
+            assert_eq!(&dataitem[..2], &[1, 0], "{}", vector.name);
+            assert_eq!(&dataitem[2..514], signature.as_slice(), "{}", vector.name);
+            assert_eq!(&dataitem[514..1026], owner.as_slice(), "{}", vector.name);
+            assert_eq!(
+                URL_SAFE_NO_PAD.encode(Sha256::digest(&signature)),
+                vector.dataitem_id,
+                "{}",
+                vector.name
+            );
+            assert!(dataitem.ends_with(&data), "{}", vector.name);
+            let sha_tag = vector.tags.iter().find(|tag| tag.name == "SHA-256").unwrap();
+            assert_eq!(sha_tag.value, vector.data_sha256, "{}", vector.name);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use crate::core::utils::get_env_var;
+use anyhow::{Error, anyhow};
+use axum_server::tls_rustls::RustlsConfig;
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+/// TLS settings for serving HTTPS directly, from the PEM files at `TLS_CERT_PATH` and
+/// `TLS_KEY_PATH`, or `None` when neither is set.
+///
+/// The files are checked every `TLS_RELOAD_INTERVAL_SECS` (default 60, 0 disables) and
+/// reloaded in place when they change, so renewed certificates apply without a restart.
+pub async fn tls_config() -> Result<Option<RustlsConfig>, Error> {
+    let (cert, key) = match (get_env_var("TLS_CERT_PATH"), get_env_var("TLS_KEY_PATH")) {
+        (Ok(cert), Ok(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+        (Err(_), Err(_)) => return Ok(None),
+        _ => return Err(anyhow!("TLS_CERT_PATH and TLS_KEY_PATH must be set together")),
+    };
+    let config = RustlsConfig::from_pem_file(&cert, &key)
+        .await
+        .map_err(|err| anyhow!("failed to load the TLS certificate: {err}"))?;
+    spawn_reload_task(config.clone(), cert, key);
+    Ok(Some(config))
+}
+
+fn spawn_reload_task(config: RustlsConfig, cert: PathBuf, key: PathBuf) {
+    let Some(interval) = get_env_var("TLS_RELOAD_INTERVAL_SECS")
+        .ok()
+        .map_or(Some(60), |v| v.parse::<u64>().ok())
+        .filter(|secs| *secs > 0)
+    else {
+        return;
+    };
+
+    tokio::spawn(async move {
+        let period = Duration::from_secs(interval);
+        let mut ticker = tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+        let mut loaded = modified(&cert, &key);
+        loop {
+            ticker.tick().await;
+            let current = modified(&cert, &key);
+            if current == loaded {
+                continue;
+            }
+            // a failed reload (e.g. a half-written renewal) keeps the previous certificate
+            // and is retried on the next tick
+            match config.reload_from_pem_file(&cert, &key).await {
+                Ok(()) => {
+                    println!("TLS: reloaded certificate from {}", cert.display());
+                    loaded = current;
+                }
+                Err(err) => println!("TLS RELOAD FAILED: {err}"),
+            }
+        }
+    });
+}
+
+fn modified(cert: &Path, key: &Path) -> Option<(SystemTime, SystemTime)> {
+    let mtime = |path: &Path| std::fs::metadata(path).and_then(|meta| meta.modified()).ok();
+    Some((mtime(cert)?, mtime(key)?))
+}
@@ -0,0 +1,183 @@
+use crate::core::{
+    metadata::{
+        DELETION_DELETED, DELETION_PURGED, DELETION_RESTORED, Deletion, expired_deletions,
+        get_deletion, hidden_dataitems, indexing_enabled, save_deletion,
+    },
+    refreshed::Refreshed,
+    s3::AgentConfig,
+    storage::StorageBackend,
+    tenant::Tenant,
+    utils::get_env_var,
+};
+use anyhow::Error;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use std::{collections::HashSet, time::Duration};
+
+const DEFAULT_RETENTION_SECS: u64 = 30 * 86_400;
+const PURGE_BATCH: usize = 1000;
+
+// `tenant/dataitem_id` of every soft-deleted or purged dataitem
+static HIDDEN: Lazy<Refreshed<HashSet<String>>> = Lazy::new(|| Refreshed::new("TRASH"));
+
+/// A soft-deleted dataitem, hidden from every route until restored.
+#[derive(Debug)]
+pub(crate) struct Deleted(pub String);
+
+impl std::fmt::Display for Deleted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "dataitem {} was deleted", self.0)
+    }
+}
+
+impl std::error::Error for Deleted {}
+
+/// A restore of a dataitem that isn't soft-deleted, `state` is `None` when it never was.
+#[derive(Debug)]
+pub(crate) struct NotRestorable {
+    pub id: String,
+    pub state: Option<String>,
+}
+
+impl std::fmt::Display for NotRestorable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.state {
+            Some(state) => write!(f, "dataitem {} is {state}, only deleted ones restore", self.id),
+            None => write!(f, "dataitem {} was never deleted", self.id),
+        }
+    }
+}
+
+impl std::error::Error for NotRestorable {}
+
+fn hidden_key(tenant: &str, dataitem_id: &str) -> String {
+    format!("{tenant}/{dataitem_id}")
+}
+
+/// Deletions are re-read from ClickHouse every `SOFT_DELETE_REFRESH_SECS` (default 30).
+fn refresh_interval() -> Duration {
+    let secs = get_env_var("SOFT_DELETE_REFRESH_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(30))
+}
+
+/// How long soft-deleted objects are kept before the GC purges them,
+/// `SOFT_DELETE_RETENTION_SECS` (default 30 days).
+pub(crate) fn retention() -> Duration {
+    let secs = get_env_var("SOFT_DELETE_RETENTION_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(DEFAULT_RETENTION_SECS))
+}
+
+/// Fail with `Deleted` when the tenant's dataitem is soft-deleted or purged.
+pub(crate) async fn check_not_deleted(tenant: &Tenant, dataitem_id: &str) -> Result<(), Deleted> {
+    let hidden = HIDDEN
+        .get(refresh_interval(), async {
+            if !indexing_enabled() {
+                return Ok(HashSet::new());
+            }
+            let deletions = hidden_dataitems().await?;
+            Ok(deletions.iter().map(|d| hidden_key(&d.tenant, &d.dataitem_id)).collect())
+        })
+        .await;
+    match hidden.contains(&hidden_key(&tenant.name, dataitem_id)) {
+        true => Err(Deleted(dataitem_id.to_string())),
+        false => Ok(()),
+    }
+}
+
+async fn set_state(deletion: Deletion, hidden: bool) -> Result<Deletion, Error> {
+    save_deletion(&deletion).await?;
+    let key = hidden_key(&deletion.tenant, &deletion.dataitem_id);
+    HIDDEN.update(|set| {
+        match hidden {
+            true => set.insert(key),
+            false => set.remove(&key),
+        };
+    });
+    Ok(deletion)
+}
+
+/// Soft-delete a dataitem, applied on this agent right away and on the others at their next
+/// reload. Deleting it again keeps the original deletion time.
+pub(crate) async fn soft_delete(tenant: &Tenant, dataitem_id: &str) -> Result<Deletion, Error> {
+    let now = Utc::now();
+    if let Some(existing) = get_deletion(&tenant.name, dataitem_id).await? {
+        if existing.state != DELETION_RESTORED {
+            return Ok(existing);
+        }
+    }
+    let deletion = Deletion {
+        tenant: tenant.name.clone(),
+        dataitem_id: dataitem_id.to_string(),
+        state: DELETION_DELETED.to_string(),
+        deleted_at: now,
+        updated_at: now,
+    };
+    set_state(deletion, true).await
+}
+
+/// Undo a soft-delete, failing with `NotRestorable` once purged or when not deleted.
+pub(crate) async fn restore(tenant: &Tenant, dataitem_id: &str) -> Result<Deletion, Error> {
+    let deletion = match get_deletion(&tenant.name, dataitem_id).await? {
+        Some(deletion) if deletion.state == DELETION_DELETED => deletion,
+        other => {
+            return Err(NotRestorable {
+                id: dataitem_id.to_string(),
+                state: other.map(|deletion| deletion.state),
+            }
+            .into());
+        }
+    };
+    let restored =
+        Deletion { state: DELETION_RESTORED.to_string(), updated_at: Utc::now(), ..deletion };
+    set_state(restored, false).await
+}
+
+/// Permanently remove the objects of the tenant's dataitems deleted longer than the retention
+/// ago, returning their IDs. With `dry_run` they are only listed.
+pub(crate) async fn purge_expired(
+    storage: &dyn StorageBackend,
+    tenant: &Tenant,
+    dry_run: bool,
+    errors: &mut Vec<String>,
+) -> Result<Vec<String>, Error> {
+    if !indexing_enabled() {
+        return Ok(Vec::new());
+    }
+    let retention = chrono::Duration::from_std(retention()).unwrap_or(chrono::Duration::MAX);
+    let deleted_before = Utc::now().checked_sub_signed(retention).unwrap_or_default();
+    let expired = expired_deletions(&tenant.name, deleted_before, PURGE_BATCH).await?;
+    if dry_run {
+        return Ok(expired.into_iter().map(|deletion| deletion.dataitem_id).collect());
+    }
+
+    let agent_config = AgentConfig::for_tenant(tenant);
+    let bucket = &agent_config.s3_bucket_name;
+    let mut purged = Vec::with_capacity(expired.len());
+    for deletion in expired {
+        let dataitem_id = &deletion.dataitem_id;
+        // the signed dataitem goes first, a raw body left behind is then an orphan for the GC
+        let keys = [
+            format!("{}/{dataitem_id}.ans104", agent_config.s3_dir_name),
+            format!("{}/{dataitem_id}", agent_config.s3_raw_dir_name),
+        ];
+        let mut failed = false;
+        for key in &keys {
+            if let Err(err) = storage.delete(bucket, key).await {
+                errors.push(format!("purge {key}: {err}"));
+                failed = true;
+                break;
+            }
+        }
+        if failed {
+            continue;
+        }
+        let id = dataitem_id.clone();
+        let purged_state =
+            Deletion { state: DELETION_PURGED.to_string(), updated_at: Utc::now(), ..deletion };
+        match save_deletion(&purged_state).await {
+            Ok(()) => purged.push(id),
+            Err(err) => errors.push(format!("purge {id}: {err}")),
+        }
+    }
+    Ok(purged)
+}
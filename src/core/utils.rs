@@ -1,11 +1,21 @@
-use anyhow::Error;
+use anyhow::{Error, anyhow};
 use dotenvy::dotenv;
-use std::env;
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    env,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
 
-use reqwest::{
-    Client,
-    header::{CONTENT_TYPE, HeaderMap, HeaderValue},
+use crate::core::{
+    http::{http_client, send_with_retry},
+    resilience::{CircuitBreaker, with_timeout},
 };
+use reqwest::header::{CONTENT_TYPE, HeaderMap, HeaderValue};
+use sha2::{Digest, Sha256};
 
 pub(crate) const STORAGE_PROVIDER_NAME: &str = "Load-S3";
 pub(crate) const DATAITEMS_ADDRESS: &str = "2BBwe2pSXn_Tp-q_mHry0Obp88dc7L-eDIWx0_BUfD0";
@@ -18,22 +28,127 @@ pub const INTERNAL_AUTH_SERVER: &str = "https://k8s.load-auth-service.load.netwo
 pub const SERVER_PORT: &str = "1247";
 pub const HYPERBEAM_NODE_URL: &str = "https://s3-node-1.load.network";
 
+static AUTH_BREAKER: Lazy<CircuitBreaker> = Lazy::new(|| CircuitBreaker::from_env("auth", "AUTH"));
+// sha256 of verified load_acc keys and when they were verified
+static AUTH_CACHE: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(Default::default);
+
+/// Where the server listens, from `BIND_ADDR`.
+#[derive(Debug, Clone)]
+pub enum BindAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for BindAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindAddr::Tcp(addr) => write!(f, "{addr}"),
+            BindAddr::Unix(path) => write!(f, "unix:{}", path.display()),
+        }
+    }
+}
+
+/// `BIND_ADDR` accepts an interface (`127.0.0.1`, port from `SERVER_PORT`), a socket address
+/// (`[::1]:8080`) or a Unix domain socket (`unix:/run/load-s3-agent.sock`). Defaults to all
+/// interfaces on `SERVER_PORT`. TLS is only served over TCP, a Unix domain socket with
+/// `TLS_CERT_PATH` or `TLS_KEY_PATH` set is refused.
+pub fn bind_addr() -> Result<BindAddr, Error> {
+    let port = get_env_var("SERVER_PORT").unwrap_or_else(|_| SERVER_PORT.to_string());
+    let port: u16 = port.parse().map_err(|_| anyhow!("invalid SERVER_PORT: {port}"))?;
+    let raw = get_env_var("BIND_ADDR").unwrap_or_default();
+    let raw = raw.trim();
+    if raw.is_empty() {
+        return Ok(BindAddr::Tcp(SocketAddr::from(([0, 0, 0, 0], port))));
+    }
+    if let Some(path) = raw.strip_prefix("unix:") {
+        if get_env_var("TLS_CERT_PATH").is_ok() || get_env_var("TLS_KEY_PATH").is_ok() {
+            return Err(anyhow!("TLS is not supported on a Unix domain socket"));
+        }
+        return Ok(BindAddr::Unix(PathBuf::from(path)));
+    }
+    if let Ok(addr) = raw.parse::<SocketAddr>() {
+        return Ok(BindAddr::Tcp(addr));
+    }
+    let ip: IpAddr = raw
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse()
+        .map_err(|_| anyhow!("invalid BIND_ADDR: {raw}"))?;
+    Ok(BindAddr::Tcp(SocketAddr::new(ip, port)))
+}
+
 pub(crate) fn get_env_var(key: &str) -> Result<String, Error> {
     dotenv().ok();
     Ok(env::var(key)?)
 }
 
-pub(crate) async fn is_valid_api_key(load_acc_token: &str) -> Result<bool, reqwest::Error> {
-    let url = format!("{INTERNAL_AUTH_SERVER}/internal/verify/{load_acc_token}");
+/// Auth service verifying `load_acc` keys, `INTERNAL_AUTH_SERVER` or the Load deployment.
+pub(crate) fn internal_auth_server() -> String {
+    env_url("INTERNAL_AUTH_SERVER", INTERNAL_AUTH_SERVER)
+}
+
+/// HyperBEAM node advertised to clients, `HYPERBEAM_NODE_URL` or the Load deployment.
+pub(crate) fn hyperbeam_node_url() -> String {
+    env_url("HYPERBEAM_NODE_URL", HYPERBEAM_NODE_URL)
+}
+
+fn env_url(key: &str, default: &str) -> String {
+    let url = get_env_var(key).ok().filter(|url| !url.trim().is_empty());
+    url.as_deref().unwrap_or(default).trim().trim_end_matches('/').to_string()
+}
+
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    Sha256::digest(data).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Verified load_acc keys are trusted for `AUTH_CACHE_TTL_SECS` (default 0, no caching).
+fn auth_cache_ttl() -> Duration {
+    let secs = get_env_var("AUTH_CACHE_TTL_SECS").ok().and_then(|v| v.parse().ok());
+    Duration::from_secs(secs.unwrap_or(0))
+}
+
+/// Drop expired load_acc verifications, returning how many were dropped.
+pub(crate) fn purge_auth_cache() -> usize {
+    let ttl = auth_cache_ttl();
+    let mut cache = AUTH_CACHE.lock().unwrap();
+    let before = cache.len();
+    cache.retain(|_, verified_at| verified_at.elapsed() < ttl);
+    before - cache.len()
+}
+
+/// Forget every load_acc verification.
+pub(crate) fn clear_auth_cache() {
+    AUTH_CACHE.lock().unwrap().clear();
+}
+
+pub(crate) async fn is_valid_api_key(load_acc_token: &str) -> Result<bool, Error> {
+    let ttl = auth_cache_ttl();
+    let cache_key = sha256_hex(load_acc_token.as_bytes());
+    let cached = AUTH_CACHE.lock().unwrap().get(&cache_key).is_some_and(|at| at.elapsed() < ttl);
+    if cached {
+        return Ok(true);
+    }
+
+    let url = format!("{}/internal/verify/{load_acc_token}", internal_auth_server());
     let server_auth = get_env_var("AUTH_SERVER_KEY").unwrap();
 
     let mut headers = HeaderMap::new();
     headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
     headers.insert("X-Load-Auth-Token", HeaderValue::from_str(&server_auth).unwrap());
 
-    let client = Client::new();
-    let response = client.get(&url).headers(headers).send().await?;
-
-    let result: serde_json::Value = response.json().await?;
-    Ok(result["is_active"] == true)
+    AUTH_BREAKER.check()?;
+    let result = with_timeout("auth", 10, async {
+        let response = send_with_retry("auth", http_client()?.get(&url).headers(headers)).await?;
+        let result: serde_json::Value = response.json().await?;
+        Ok::<_, Error>(result["is_active"] == true)
+    })
+    .await;
+    match result {
+        Ok(_) => AUTH_BREAKER.record_success(),
+        Err(_) => AUTH_BREAKER.record_failure(),
+    }
+    if matches!(result, Ok(true)) && !ttl.is_zero() {
+        AUTH_CACHE.lock().unwrap().insert(cache_key, Instant::now());
+    }
+    result
 }
@@ -0,0 +1,66 @@
+use crate::core::{
+    http::{http_client, send_with_retry},
+    resilience::with_timeout,
+    utils::get_env_var,
+};
+use anyhow::{Error, anyhow};
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+
+/// Deliver an event to every `WEBHOOK_URLS` (comma separated) endpoint in the background.
+///
+/// The body is `{"event": ..., "timestamp": ..., ...fields}`. With `WEBHOOK_SECRET` set it is
+/// signed in an `X-Webhook-Signature: sha256=<hex hmac>` header. Delivery failures are logged
+/// after the `HTTP_RETRY_*` retries, never surfaced to the caller.
+pub(crate) fn notify(event: &str, fields: Value) {
+    let urls: Vec<String> = get_env_var("WEBHOOK_URLS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|url| url.trim().to_string())
+        .filter(|url| !url.is_empty())
+        .collect();
+    if urls.is_empty() {
+        return;
+    }
+
+    let mut body = json!({
+        "event": event,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    if let (Some(body), Value::Object(fields)) = (body.as_object_mut(), fields) {
+        body.extend(fields);
+    }
+    let body = body.to_string();
+    let event = event.to_string();
+    tokio::spawn(async move {
+        for url in urls {
+            if let Err(err) = deliver(&url, &body).await {
+                println!("WEBHOOK: {event} to {url} failed: {err}");
+            }
+        }
+    });
+}
+
+async fn deliver(url: &str, body: &str) -> Result<(), Error> {
+    with_timeout("webhook", 10, async {
+        let mut request = http_client()?
+            .post(url)
+            .header("content-type", "application/json")
+            .body(body.to_string());
+        if let Ok(secret) = get_env_var("WEBHOOK_SECRET") {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+                .expect("hmac accepts keys of any size");
+            mac.update(body.as_bytes());
+            let signature: String =
+                mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect();
+            request = request.header("x-webhook-signature", format!("sha256={signature}"));
+        }
+        let response = send_with_retry("webhook", request).await?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(anyhow!("endpoint returned {}", response.status())),
+        }
+    })
+    .await
+}
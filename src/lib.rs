@@ -0,0 +1,9 @@
+//! `load-s3-agent` storage pipeline as a library.
+//!
+//! The [`Agent`] handle exposes the same store, retrieve, query and post operations the HTTP
+//! server uses, so other Rust services can embed the pipeline directly. Configuration is read
+//! from the environment (or a `.env` file), exactly like the server binary.
+
+pub mod core;
+
+pub use crate::core::agent::Agent;
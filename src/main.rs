@@ -1,45 +1,182 @@
-use crate::core::server::{
-    OBJECT_SIZE_LIMIT, SERVER_PORT, handle_get_bucket_registry, handle_post_dataitem,
-    handle_private_file, handle_query_tags, handle_route, handle_storage_stats, serve_dataitem,
-    upload_file,
-};
-use axum::{
-    Router,
-    extract::DefaultBodyLimit,
-    routing::{get, post},
-};
+use anyhow::{Context, Error, anyhow};
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
 use dotenvy::dotenv;
-use tower_http::{cors::CorsLayer, limit::RequestBodyLimitLayer};
+use futures::StreamExt;
+use load_s3_agent::{
+    Agent,
+    core::{
+        metadata::ExportFormat,
+        server::{
+            BindAddr, bind_addr, install_cors_policy, self_test, spawn_background_tasks, tls_config,
+        },
+        tenant::tenant_by_name,
+    },
+};
+use serde::Serialize;
+use serde_json::json;
+use std::{net::SocketAddr, os::unix::fs::FileTypeExt, pin::pin};
+use tokio::io::AsyncWriteExt;
+
+/// Load S3 agent: the HTTP server, and maintenance commands run against the same configuration
+/// without going through its authenticated routes.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Verify the configuration against the agent's dependencies, print the report and exit
+    #[arg(long)]
+    check: bool,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Args)]
+struct TenantArg {
+    /// Tenant to operate on, the default tenant when omitted
+    #[arg(long, default_value = "")]
+    tenant: String,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Run the HTTP server (the default)
+    Serve,
+    /// Index the stored dataitems missing from the ClickHouse tag index
+    Reindex {
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Delete orphaned raw bodies, repair missing ones and purge expired deletions
+    Gc {
+        /// Only report what would be done
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Print the dataitems count and total size of the tenant's bucket
+    Stats {
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Post a stored dataitem to Arweave through the bundler
+    Post {
+        /// Dataitem ID
+        id: String,
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+    /// Stream the tag index rows to stdout
+    ExportIndex {
+        /// ndjson or csv
+        #[arg(long, default_value = "ndjson")]
+        format: String,
+        /// Only rows indexed at or after this RFC 3339 timestamp
+        #[arg(long)]
+        from: Option<DateTime<Utc>>,
+        /// Only rows indexed before this RFC 3339 timestamp
+        #[arg(long)]
+        to: Option<DateTime<Utc>>,
+        #[command(flatten)]
+        tenant: TenantArg,
+    },
+}
+
+fn agent_for(tenant: &TenantArg) -> Result<Agent, Error> {
+    Ok(Agent::for_tenant(tenant_by_name(&tenant.tenant)?))
+}
 
-mod core;
+fn print_json(value: &impl Serialize) -> Result<(), Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Run a maintenance command, its report printed as JSON on stdout.
+async fn run(command: Command) -> Result<(), Error> {
+    match command {
+        Command::Serve => serve().await,
+        Command::Reindex { tenant } => print_json(&agent_for(&tenant)?.reindex().await?),
+        Command::Gc { dry_run, tenant } => print_json(&agent_for(&tenant)?.gc(dry_run).await?),
+        Command::Stats { tenant } => {
+            let (count, size) = agent_for(&tenant)?.stats().await?;
+            print_json(&json!({ "total_dataitems_count": count, "total_dataitems_size": size }))
+        }
+        Command::Post { id, tenant } => print_json(&agent_for(&tenant)?.post(&id).await?),
+        Command::ExportIndex { format, from, to, tenant } => {
+            let format = ExportFormat::parse(&format)
+                .ok_or_else(|| anyhow!("format must be one of: ndjson, csv"))?;
+            let mut rows = pin!(agent_for(&tenant)?.export_index(format, from, to).await?);
+            let mut stdout = tokio::io::stdout();
+            while let Some(chunk) = rows.next().await {
+                stdout.write_all(&chunk?).await?;
+            }
+            Ok(stdout.flush().await?)
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
     // Load environment variables from a .env file if present
     dotenv().ok();
+    let cli = Cli::parse();
+
+    if cli.check {
+        let report = self_test().await;
+        println!("{}", serde_json::to_string_pretty(&report).unwrap());
+        std::process::exit(if report.ok { 0 } else { 1 });
+    }
+
+    if let Err(err) = run(cli.command.unwrap_or(Command::Serve)).await {
+        eprintln!("error: {err:#}");
+        std::process::exit(1);
+    }
+}
+
+async fn serve() -> Result<(), Error> {
+    install_cors_policy()?;
+    // BIND_ADDR if set, otherwise all interfaces on SERVER_PORT, checked before anything starts
+    let bind = bind_addr()?;
+    let tls = tls_config().await?;
+
+    spawn_background_tasks();
+
+    let router = Agent::new().router();
 
-    let cors = CorsLayer::new()
-        .allow_origin(tower_http::cors::Any)
-        .allow_methods(tower_http::cors::Any)
-        .allow_headers(tower_http::cors::Any);
-
-    let router = Router::new()
-        .route("/", get(handle_route))
-        .route("/stats", get(handle_storage_stats))
-        .route("/upload", post(upload_file))
-        .route("/upload/private", post(handle_private_file))
-        .route("/tags/query", post(handle_query_tags))
-        .route("/post/{id}", post(handle_post_dataitem))
-        .route("/registry/{bucket_name}", get(handle_get_bucket_registry))
-        .route("/{id}", get(serve_dataitem))
-        .layer(DefaultBodyLimit::max(OBJECT_SIZE_LIMIT))
-        .layer(RequestBodyLimitLayer::new(OBJECT_SIZE_LIMIT))
-        .layer(cors);
-
-    // Use SERVER_PORT from env if set, otherwise default to the constant
-    let port = std::env::var("SERVER_PORT").unwrap_or_else(|_| SERVER_PORT.to_string());
-
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{port}")).await.unwrap();
-    println!("Server running on PORT: {port}");
-    axum::serve(listener, router).await.unwrap();
+    match (bind, tls) {
+        (BindAddr::Tcp(addr), Some(tls)) => {
+            println!("Server running on {addr} (TLS)");
+            axum_server::bind_rustls(addr, tls)
+                .serve(router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .context("server failed")?;
+        }
+        (BindAddr::Tcp(addr), None) => {
+            let listener = tokio::net::TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("failed to bind {addr}"))?;
+            println!("Server running on {addr}");
+            axum::serve(listener, router.into_make_service_with_connect_info::<SocketAddr>())
+                .await
+                .context("server failed")?;
+        }
+        (BindAddr::Unix(path), None) => {
+            // a socket left behind by a previous run would fail the bind
+            let stale =
+                std::fs::symlink_metadata(&path).is_ok_and(|meta| meta.file_type().is_socket());
+            if stale {
+                std::fs::remove_file(&path).with_context(|| {
+                    format!("failed to remove the stale socket {}", path.display())
+                })?;
+            }
+            let listener = tokio::net::UnixListener::bind(&path)
+                .with_context(|| format!("failed to bind unix:{}", path.display()))?;
+            println!("Server running on unix:{}", path.display());
+            axum::serve(listener, router).await.context("server failed")?;
+        }
+        (BindAddr::Unix(_), Some(_)) => {
+            return Err(anyhow!("TLS is not supported on a Unix domain socket"));
+        }
+    }
+    Ok(())
 }